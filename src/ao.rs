@@ -0,0 +1,227 @@
+use crate::{
+    bvh::Bvh,
+    camera::Camera,
+    deferred::{DEPTH, NORMAL, world_position},
+    graph::{BufferKind, RenderGraph},
+    rng::pixel_hash,
+    rt::{Intersect, Ray},
+    shade::sample_lambert,
+};
+
+use std::{
+    f32::consts::PI,
+    sync::Arc,
+};
+
+use rayon::prelude::*;
+use vek::*;
+
+/// Name of the scalar buffer the ambient occlusion passes write, from 0 fully occluded to 1
+/// unoccluded.
+pub const AO: &str = "ao";
+
+/// Configuration of how ambient occlusion is sampled.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AoOptions {
+    /// Number of directions sampled per pixel. Defaults to 16.
+    pub samples: u32,
+    /// World-space distance within which geometry occludes. Defaults to 1.
+    pub radius: f32,
+    /// Offset from the surface, to avoid surfaces occluding themselves. Defaults to 0.01.
+    pub bias: f32,
+}
+
+impl Default for AoOptions {
+    fn default() -> Self {
+        AoOptions {
+            samples: 16,
+            radius: 1.0,
+            bias: 0.01,
+        }
+    }
+}
+
+/// Golden angle, in radians, which spreads consecutive samples evenly around a circle.
+const GOLDEN_ANGLE: f32 = 2.399_963_2;
+
+/// Cosine-weighted direction in the hemisphere around a normalized normal, for sample `i`
+/// of `n`, rotated about the normal by `rotation` radians.
+///
+/// The samples follow a Fibonacci spiral, which covers the hemisphere evenly, and rotating
+/// them differently per pixel turns banding into noise.
+pub fn hemisphere_sample(normal: Vec3<f32>, i: u32, n: u32, rotation: f32) -> Vec3<f32> {
+    let u = (i as f32 + 0.5) / n as f32;
+    let phi = i as f32 * GOLDEN_ANGLE + rotation;
+    let (dir, _) = sample_lambert(normal, Vec2::new(u, (phi / (2.0 * PI)).rem_euclid(1.0)));
+    dir
+}
+
+/// Rotation in radians for a pixel's samples, hashed from its coordinates.
+fn pixel_rotation(x: usize, y: usize) -> f32 {
+    // the top 24 bits fit exactly in an f32
+    let h = pixel_hash(x as u32, y as u32, 0, 0) >> 40;
+    h as f32 / (1 << 24) as f32 * 2.0 * PI
+}
+
+/// Ambient occlusion at a surface point, by tracing rays into the hemisphere around its
+/// normal against shapes in a BVH, from 0 fully occluded to 1 unoccluded.
+pub fn ray_traced<T>(
+    bvh: &Bvh,
+    shapes: &[T],
+    point: Vec3<f32>,
+    normal: Vec3<f32>,
+    rotation: f32,
+    options: &AoOptions,
+) -> f32
+    where
+        T: Intersect {
+
+    if options.samples == 0 {
+        return 1.0;
+    }
+    let normal = normal.normalized();
+    let origin = point + normal * options.bias;
+    let occluded = (0..options.samples)
+        .filter(|&i| {
+            let dir = hemisphere_sample(normal, i, options.samples, rotation);
+            bvh.any_hit(shapes, &Ray::new(origin, dir), 0.0, options.radius)
+        })
+        .count();
+    1.0 - occluded as f32 / options.samples as f32
+}
+
+/// Add a screen-space ambient occlusion pass to a render graph, which estimates occlusion
+/// from the G-buffer's depths and normals, and writes it to the `AO` buffer, which this
+/// declares.
+///
+/// Each sample point in the hemisphere is projected onto the screen, and counts as occluded
+/// if the depth buffer there is nearer, by geometry within the radius. This only sees
+/// geometry which is on screen and not itself hidden, but is cheap and needs no scene.
+pub fn add_ssao_pass<'a>(
+    graph: &'a mut RenderGraph,
+    camera: &Camera,
+    options: AoOptions,
+) -> &'a mut RenderGraph {
+    let vp = camera.view_projection();
+    let inv_vp = vp.inverted();
+
+    graph.buffer(AO, BufferKind::Scalar);
+    graph.pass("ssao", &[DEPTH, NORMAL], &[AO], move |gbuffer, out| {
+        let (x_size, y_size) = (gbuffer.x_size(), gbuffer.y_size());
+        let depth = gbuffer.depth(DEPTH);
+        let normal = gbuffer.normal(NORMAL);
+
+        // screen position and depth of a world-space point
+        let project = |p: Vec3<f32>| {
+            let clip = vp * Vec4::from_point(p);
+            let ndc = clip.xyz() / clip.w;
+            let x = ((ndc.x + 1.0) / 2.0 * x_size as f32).floor();
+            let y = ((1.0 - ndc.y) / 2.0 * y_size as f32).floor();
+            if clip.w <= 0.0 || x < 0.0 || y < 0.0 || x >= x_size as f32 || y >= y_size as f32 {
+                None
+            } else {
+                Some((Vec2::new(x as usize, y as usize), (ndc.z + 1.0) / 2.0))
+            }
+        };
+
+        // parallel iter over rows
+        out.scalar_mut(AO)
+            .par_chunks_mut(x_size.max(1))
+            .enumerate()
+            .for_each(|(y, row)| for (x, ao) in row.iter_mut().enumerate() {
+                let i = y * x_size + x;
+                *ao = 1.0;
+                if !depth[i].is_finite() || options.samples == 0 {
+                    continue;
+                }
+
+                let point = world_position(inv_vp, Vec2::new(x, y), depth[i], x_size, y_size);
+                let n = normal[i].normalized();
+                let rotation = pixel_rotation(x, y);
+                let mut occluded = 0;
+                for s in 0..options.samples {
+                    // spread sample distances out, weighted towards the surface
+                    let scale = (s as f32 + 1.0) / options.samples as f32;
+                    let dir = hemisphere_sample(n, s, options.samples, rotation);
+                    let sample = point + n * options.bias + dir * options.radius * scale;
+
+                    if let Some((xy, sample_depth)) = project(sample) {
+                        let j = xy.y * x_size + xy.x;
+                        if depth[j] >= sample_depth {
+                            continue;
+                        }
+                        // only count occluders within the radius, so distant foreground
+                        // objects don't darken the background behind them
+                        let occluder = world_position(inv_vp, xy, depth[j], x_size, y_size);
+                        if occluder.distance(point) <= options.radius {
+                            occluded += 1;
+                        }
+                    }
+                }
+                *ao = 1.0 - occluded as f32 / options.samples as f32;
+            });
+    })
+}
+
+/// Add a ray-traced ambient occlusion pass to a render graph, which traces rays from each
+/// G-buffer pixel against a scene in a BVH, and writes the result to the `AO` buffer, which
+/// this declares.
+///
+/// The scene should be the same geometry that was rasterized into the G-buffer, in world
+/// space. This is slower than `add_ssao_pass`, but sees geometry off screen.
+pub fn add_ray_traced_ao_pass<'a, T>(
+    graph: &'a mut RenderGraph,
+    camera: &Camera,
+    bvh: Arc<Bvh>,
+    shapes: Arc<Vec<T>>,
+    options: AoOptions,
+) -> &'a mut RenderGraph
+    where
+        T: Intersect + Send + Sync + 'static {
+
+    let inv_vp = camera.view_projection().inverted();
+
+    graph.buffer(AO, BufferKind::Scalar);
+    graph.pass("ray traced ao", &[DEPTH, NORMAL], &[AO], move |gbuffer, out| {
+        let (x_size, y_size) = (gbuffer.x_size(), gbuffer.y_size());
+        let depth = gbuffer.depth(DEPTH);
+        let normal = gbuffer.normal(NORMAL);
+
+        // parallel iter over rows
+        out.scalar_mut(AO)
+            .par_chunks_mut(x_size.max(1))
+            .enumerate()
+            .for_each(|(y, row)| for (x, ao) in row.iter_mut().enumerate() {
+                let i = y * x_size + x;
+                *ao =
+                    if depth[i].is_finite() {
+                        let point = world_position(
+                            inv_vp, Vec2::new(x, y), depth[i], x_size, y_size,
+                        );
+                        ray_traced(&bvh, &shapes, point, normal[i], pixel_rotation(x, y), &options)
+                    } else {
+                        1.0
+                    };
+            });
+    })
+}
+
+/// Add a pass to a render graph which multiplies the RGB of a color buffer by the `AO`
+/// buffer.
+///
+/// Add this after the passes which write the color buffer, such as the deferred lighting
+/// pass, since writers of a buffer run in the order they were added.
+pub fn add_apply_ao_pass<'a>(graph: &'a mut RenderGraph, color: &str) -> &'a mut RenderGraph {
+    let target = color.to_owned();
+    graph.pass("apply ao", &[AO], &[color], move |ao_buffer, out| {
+        let ao = ao_buffer.scalar(AO);
+        out.color_mut(&target)
+            .par_iter_mut()
+            .zip(ao.par_iter())
+            .for_each(|(color, &ao)| {
+                color.r *= ao;
+                color.g *= ao;
+                color.b *= ao;
+            });
+    })
+}
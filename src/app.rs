@@ -0,0 +1,84 @@
+use crate::{
+    DrawContext,
+    PaintCmd,
+    Window,
+    canvas::Canvas,
+    input::InputEvent,
+};
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Rate at which `run_app` updates and draws.
+const FPS: f64 = 60.0;
+
+/// Interactive program, which handles input and is updated and drawn every frame.
+///
+/// All methods are called on the drawing thread, in the order `init`, then each frame,
+/// `event` for every input event received, `update`, and `draw`.
+pub trait App {
+    /// Called once before the first frame.
+    fn init(&mut self, ctx: &DrawContext) {
+        let _ = ctx;
+    }
+
+    /// Handle a user input event.
+    fn event(&mut self, event: InputEvent) {
+        let _ = event;
+    }
+
+    /// Advance the state by `dt` seconds.
+    fn update(&mut self, dt: f32) {
+        let _ = dt;
+    }
+
+    /// Draw the current state.
+    ///
+    /// The canvas keeps its contents between frames, and is resized and cleared when the
+    /// window is.
+    fn draw(&mut self, canvas: &mut Canvas);
+}
+
+/// Open a window of the given size, and run an app in it until the window closes.
+pub fn run_app<A>(x_size: usize, y_size: usize, mut app: A)
+    where
+        A: App + Send + 'static {
+
+    let frame_time = Duration::from_secs_f64(1.0 / FPS);
+
+    Window::build()
+        .size(x_size, y_size)
+        .double_buffered(true)
+        .spawn(move |ctx| {
+            let mut canvas = Canvas::new(x_size, y_size);
+            app.init(&ctx);
+
+            let mut last = Instant::now();
+            while !ctx.shutdown.is_shutdown() {
+                let start = Instant::now();
+
+                for event in ctx.input.try_iter() {
+                    if let InputEvent::Resize { x_size, y_size } = event {
+                        canvas = Canvas::new(x_size, y_size);
+                    }
+                    app.event(event);
+                }
+
+                app.update(start.duration_since(last).as_secs_f32());
+                last = start;
+
+                app.draw(&mut canvas);
+                ctx.wait_for_capacity();
+                canvas.flush_to(&ctx.paint);
+                ctx.push(PaintCmd::EndFrame);
+
+                // sleep off the rest of the frame
+                let elapsed = start.elapsed();
+                if elapsed < frame_time {
+                    thread::sleep(frame_time - elapsed);
+                }
+            }
+        });
+}
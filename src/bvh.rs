@@ -0,0 +1,348 @@
+use crate::rt::{Aabb, Hit, Intersect, Ray};
+
+use rayon::prelude::*;
+use vek::*;
+
+/// Number of buckets centroids are sorted into when evaluating splits.
+const NUM_BINS: usize = 12;
+
+/// Nodes with at most this many primitives may become leaves.
+const MAX_LEAF_SIZE: usize = 4;
+
+/// Subtrees with at least this many primitives are built in parallel.
+const PARALLEL_THRESHOLD: usize = 4096;
+
+/// Cost of traversing a node, relative to the cost of intersecting a primitive.
+const TRAVERSAL_COST: f32 = 1.0;
+
+/// Bounding volume hierarchy over a slice of shapes.
+///
+/// The hierarchy only stores indices, so the same slice of shapes must be passed to
+/// `closest_hit` and `any_hit` as was passed to `build`.
+#[derive(Clone, Debug)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    /// Shape indices, in leaf order.
+    indices: Vec<usize>,
+}
+
+/// Node, stored depth-first, so that an inner node's left child directly follows it.
+#[derive(Copy, Clone, Debug)]
+struct Node {
+    bounds: Aabb,
+    kind: NodeKind,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum NodeKind {
+    Leaf {
+        start: usize,
+        count: usize,
+    },
+    Inner {
+        right: usize,
+    },
+}
+
+/// Primitive being sorted into the hierarchy.
+#[derive(Copy, Clone)]
+struct Prim {
+    index: usize,
+    bounds: Aabb,
+    centroid: Vec3<f32>,
+}
+
+/// Node of the hierarchy during construction, before it is flattened.
+enum BuildNode {
+    Leaf {
+        bounds: Aabb,
+        start: usize,
+        count: usize,
+    },
+    Inner {
+        bounds: Aabb,
+        left: Box<BuildNode>,
+        right: Box<BuildNode>,
+    },
+}
+
+impl Bvh {
+    /// Build a hierarchy over `shapes`, splitting nodes by the surface area heuristic.
+    ///
+    /// Large subtrees are built in parallel.
+    pub fn build<T>(shapes: &[T]) -> Self
+        where
+            T: Intersect + Sync {
+
+        let mut prims: Vec<Prim> = shapes
+            .par_iter()
+            .enumerate()
+            .map(|(index, shape)| {
+                let bounds = shape.bounds();
+                Prim { index, bounds, centroid: bounds.center() }
+            })
+            .collect();
+
+        let mut nodes = Vec::new();
+        if !prims.is_empty() {
+            let root = build_node(&mut prims, 0);
+            flatten(root, &mut nodes);
+        }
+
+        Bvh {
+            nodes,
+            indices: prims.iter().map(|prim| prim.index).collect(),
+        }
+    }
+
+    /// Bounding box of all the shapes.
+    pub fn bounds(&self) -> Aabb {
+        self.nodes.first()
+            .map(|root| root.bounds)
+            .unwrap_or_else(Aabb::empty)
+    }
+
+    /// Find the nearest intersection of `ray` with any of `shapes`, returning the index of
+    /// the shape hit.
+    pub fn closest_hit<T>(
+        &self,
+        shapes: &[T],
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<(usize, Hit)>
+        where
+            T: Intersect {
+
+        let mut closest = None;
+        let mut t_max = t_max;
+        self.traverse(ray, t_min, &mut t_max, |index, t_max| {
+            if let Some(hit) = shapes[index].intersect(ray, t_min, *t_max) {
+                *t_max = hit.t;
+                closest = Some((index, hit));
+            }
+            false
+        });
+        closest
+    }
+
+    /// Whether `ray` intersects any of `shapes` within `[t_min, t_max]`.
+    ///
+    /// This stops at the first intersection found, so is cheaper than `closest_hit`, which
+    /// makes it suited to shadow rays.
+    pub fn any_hit<T>(
+        &self,
+        shapes: &[T],
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+    ) -> bool
+        where
+            T: Intersect {
+
+        let mut t_max = t_max;
+        self.traverse(ray, t_min, &mut t_max, |index, t_max| {
+            shapes[index].intersect(ray, t_min, *t_max).is_some()
+        })
+    }
+
+    /// Visit the shapes in leaves whose bounds `ray` intersects, nearest nodes first.
+    ///
+    /// The visitor may shrink `t_max`, and returns whether to stop, in which case this
+    /// returns true.
+    fn traverse<F>(&self, ray: &Ray, t_min: f32, t_max: &mut f32, mut visit: F) -> bool
+        where
+            F: FnMut(usize, &mut f32) -> bool {
+
+        let root = match self.nodes.first() {
+            Some(root) => root,
+            None => return false,
+        };
+        let root_enter = match root.bounds.intersect_range(ray, t_min, *t_max) {
+            Some((enter, _)) => enter,
+            None => return false,
+        };
+
+        // stack of nodes to visit, with the distances at which the ray enters them
+        let mut stack: Vec<(usize, f32)> = Vec::with_capacity(64);
+        stack.push((0, root_enter));
+
+        while let Some((i, enter)) = stack.pop() {
+            // a closer hit may have been found since this node was pushed
+            if enter > *t_max {
+                continue;
+            }
+
+            match self.nodes[i].kind {
+                NodeKind::Leaf { start, count } => {
+                    for &index in &self.indices[start..start + count] {
+                        if visit(index, t_max) {
+                            return true;
+                        }
+                    }
+                },
+                NodeKind::Inner { right } => {
+                    let left = i + 1;
+                    let enter_child = |child: usize| self.nodes[child].bounds
+                        .intersect_range(ray, t_min, *t_max)
+                        .map(|(enter, _)| enter);
+
+                    // push the farther child first, so the nearer one is visited first
+                    match (enter_child(left), enter_child(right)) {
+                        (Some(l), Some(r)) => if l <= r {
+                            stack.push((right, r));
+                            stack.push((left, l));
+                        } else {
+                            stack.push((left, l));
+                            stack.push((right, r));
+                        },
+                        (Some(l), None) => stack.push((left, l)),
+                        (None, Some(r)) => stack.push((right, r)),
+                        (None, None) => (),
+                    }
+                },
+            }
+        }
+        false
+    }
+}
+
+/// Recursively build the subtree over `prims`, which start at `offset` in the full slice.
+///
+/// `prims` is reordered such that each leaf's primitives are contiguous.
+fn build_node(prims: &mut [Prim], offset: usize) -> BuildNode {
+    let bounds = prims.iter()
+        .fold(Aabb::empty(), |bounds, prim| bounds.union(prim.bounds));
+    let leaf = BuildNode::Leaf {
+        bounds,
+        start: offset,
+        count: prims.len(),
+    };
+    if prims.len() == 1 {
+        return leaf;
+    }
+
+    // split along the axis with the greatest spread of centroids
+    let centroid_bounds = prims.iter()
+        .fold(Aabb::empty(), |bounds, prim| bounds.grow(prim.centroid));
+    let extent = centroid_bounds.extent();
+    let axis =
+        if extent.x >= extent.y && extent.x >= extent.z { 0 }
+        else if extent.y >= extent.z { 1 }
+        else { 2 };
+
+    let mid = match sah_split(prims, bounds, &centroid_bounds, axis) {
+        Some(mid) => mid,
+        None if prims.len() <= MAX_LEAF_SIZE => return leaf,
+        None => {
+            // no useful split by the heuristic, such as when centroids coincide,
+            // so just split in half
+            prims.sort_unstable_by(|a, b| a.centroid[axis]
+                .partial_cmp(&b.centroid[axis])
+                .unwrap_or(std::cmp::Ordering::Equal));
+            prims.len() / 2
+        },
+    };
+
+    let (left, right) = prims.split_at_mut(mid);
+    let (left, right) =
+        if left.len() + right.len() >= PARALLEL_THRESHOLD {
+            rayon::join(
+                || build_node(left, offset),
+                || build_node(right, offset + mid),
+            )
+        } else {
+            (build_node(left, offset), build_node(right, offset + mid))
+        };
+
+    BuildNode::Inner {
+        bounds,
+        left: Box::new(left),
+        right: Box::new(right),
+    }
+}
+
+/// Find the cheapest split of `prims` along `axis` by the surface area heuristic, and
+/// partition them by it, returning the index of the first primitive of the right side.
+///
+/// Returns `None` without reordering if making a leaf is no more expensive than the best
+/// split, or no split separates the primitives.
+fn sah_split(
+    prims: &mut [Prim],
+    bounds: Aabb,
+    centroid_bounds: &Aabb,
+    axis: usize,
+) -> Option<usize> {
+    let min = centroid_bounds.min[axis];
+    let extent = centroid_bounds.max[axis] - min;
+    if extent <= 0.0 {
+        return None;
+    }
+    let bin_of = |prim: &Prim| {
+        let bin = ((prim.centroid[axis] - min) / extent * NUM_BINS as f32) as usize;
+        bin.min(NUM_BINS - 1)
+    };
+
+    // sort primitives into bins
+    let mut bins = [(Aabb::empty(), 0usize); NUM_BINS];
+    for prim in prims.iter() {
+        let bin = &mut bins[bin_of(prim)];
+        bin.0 = bin.0.union(prim.bounds);
+        bin.1 += 1;
+    }
+
+    // evaluate splitting between each pair of adjacent bins
+    let area = bounds.surface_area();
+    let mut best: Option<(f32, usize)> = None;
+    for split in 1..NUM_BINS {
+        let sum = |bins: &[(Aabb, usize)]| bins.iter()
+            .fold((Aabb::empty(), 0), |(b, n), &(bin_b, bin_n)| (b.union(bin_b), n + bin_n));
+        let (left_bounds, left_count) = sum(&bins[..split]);
+        let (right_bounds, right_count) = sum(&bins[split..]);
+        if left_count == 0 || right_count == 0 {
+            continue;
+        }
+
+        let cost = TRAVERSAL_COST
+            + (left_bounds.surface_area() * left_count as f32
+                + right_bounds.surface_area() * right_count as f32) / area;
+        if best.map(|(best_cost, _)| cost < best_cost).unwrap_or(true) {
+            best = Some((cost, split));
+        }
+    }
+
+    let (cost, split) = best?;
+    if prims.len() <= MAX_LEAF_SIZE && prims.len() as f32 <= cost {
+        return None;
+    }
+
+    // partition in place
+    let mut mid = 0;
+    for i in 0..prims.len() {
+        if bin_of(&prims[i]) < split {
+            prims.swap(i, mid);
+            mid += 1;
+        }
+    }
+    Some(mid)
+}
+
+/// Append a subtree to `nodes` in depth-first order.
+fn flatten(node: BuildNode, nodes: &mut Vec<Node>) {
+    match node {
+        BuildNode::Leaf { bounds, start, count } => nodes.push(Node {
+            bounds,
+            kind: NodeKind::Leaf { start, count },
+        }),
+        BuildNode::Inner { bounds, left, right } => {
+            let i = nodes.len();
+            nodes.push(Node {
+                bounds,
+                kind: NodeKind::Inner { right: 0 },
+            });
+            flatten(*left, nodes);
+            nodes[i].kind = NodeKind::Inner { right: nodes.len() };
+            flatten(*right, nodes);
+        },
+    }
+}
@@ -0,0 +1,392 @@
+use crate::{
+    rt::Ray,
+    input::{InputEvent, Key, MouseButton},
+};
+
+use vek::*;
+
+/// Camera projection.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    /// Perspective projection.
+    Perspective {
+        /// Vertical field of view, in radians.
+        fov_y: f32,
+        /// Width divided by height.
+        aspect: f32,
+        near: f32,
+        far: f32,
+    },
+    /// Orthographic projection.
+    Orthographic {
+        /// Height of the view volume, in world units.
+        height: f32,
+        /// Width divided by height.
+        aspect: f32,
+        near: f32,
+        far: f32,
+    },
+}
+
+/// Camera with a position, orientation, and projection.
+///
+/// Matrices are right-handed and map depth to `[-1, 1]`, following OpenGL conventions, which
+/// is what `pipeline::Pipeline` expects.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Camera {
+    pub eye: Vec3<f32>,
+    pub target: Vec3<f32>,
+    pub up: Vec3<f32>,
+    pub projection: Projection,
+}
+
+impl Camera {
+    /// Construct a perspective camera at the origin, looking down negative z.
+    ///
+    /// `fov_y` is the vertical field of view, in radians.
+    pub fn perspective(fov_y: f32, aspect: f32, near: f32, far: f32) -> Self {
+        Camera {
+            eye: Vec3::zero(),
+            target: -Vec3::unit_z(),
+            up: Vec3::unit_y(),
+            projection: Projection::Perspective { fov_y, aspect, near, far },
+        }
+    }
+
+    /// Construct an orthographic camera at the origin, looking down negative z.
+    ///
+    /// `height` is the height of the view volume, in world units.
+    pub fn orthographic(height: f32, aspect: f32, near: f32, far: f32) -> Self {
+        Camera {
+            eye: Vec3::zero(),
+            target: -Vec3::unit_z(),
+            up: Vec3::unit_y(),
+            projection: Projection::Orthographic { height, aspect, near, far },
+        }
+    }
+
+    /// Move the camera to `eye`, looking towards `target`.
+    pub fn look_at(mut self, eye: Vec3<f32>, target: Vec3<f32>, up: Vec3<f32>) -> Self {
+        self.eye = eye;
+        self.target = target;
+        self.up = up;
+        self
+    }
+
+    /// World-to-view matrix.
+    pub fn view(&self) -> Mat4<f32> {
+        Mat4::look_at_rh(self.eye, self.target, self.up)
+    }
+
+    /// View-to-clip matrix.
+    pub fn projection(&self) -> Mat4<f32> {
+        match self.projection {
+            Projection::Perspective { fov_y, aspect, near, far } =>
+                Mat4::perspective_rh_no(fov_y, aspect, near, far),
+            Projection::Orthographic { height, aspect, near, far } => {
+                let half_y = height / 2.0;
+                let half_x = half_y * aspect;
+                Mat4::orthographic_rh_no(FrustumPlanes {
+                    left: -half_x,
+                    right: half_x,
+                    bottom: -half_y,
+                    top: half_y,
+                    near,
+                    far,
+                })
+            },
+        }
+    }
+
+    /// World-to-clip matrix.
+    pub fn view_projection(&self) -> Mat4<f32> {
+        self.projection() * self.view()
+    }
+
+    /// Compute the world-space ray through a point on the screen, with its origin on the near
+    /// plane and a normalized direction.
+    ///
+    /// `xy` is in pixel coordinates, with y pointing down, on a screen of the given size.
+    /// Pass pixel coordinates plus 0.5 for rays through pixel centers.
+    pub fn screen_to_ray(
+        &self,
+        xy: Vec2<f32>,
+        x_size: usize,
+        y_size: usize,
+    ) -> Ray {
+        let ndc = Vec2::new(
+            xy.x / x_size as f32 * 2.0 - 1.0,
+            1.0 - xy.y / y_size as f32 * 2.0,
+        );
+
+        // unproject points on the near and far planes
+        let inv = self.view_projection().inverted();
+        let unproject = |z: f32| {
+            let p = inv * Vec4::new(ndc.x, ndc.y, z, 1.0);
+            p.xyz() / p.w
+        };
+        let near = unproject(-1.0);
+        let far = unproject(1.0);
+
+        Ray::new(near, (far - near).normalized())
+    }
+}
+
+/// Furthest the controllers pitch up or down, just short of straight up or down, where the
+/// view matrix would degenerate.
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+/// Unit direction facing the given yaw and pitch, in radians.
+///
+/// Zero yaw and pitch face negative z, positive yaw turns right, and positive pitch looks up.
+fn facing(yaw: f32, pitch: f32) -> Vec3<f32> {
+    Vec3::new(yaw.sin() * pitch.cos(), pitch.sin(), -yaw.cos() * pitch.cos())
+}
+
+/// Camera control which orbits around a target point, driven by the input event stream.
+///
+/// Dragging with the left mouse button orbits, dragging with the right or middle button pans
+/// the target, and scrolling zooms in and out. Pass every input event to `input`, then
+/// `apply` the controller to a camera each frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OrbitController {
+    /// Point orbited around, and looked at.
+    pub target: Vec3<f32>,
+    /// Distance from the target to the eye.
+    pub distance: f32,
+    /// Angle around the vertical axis, in radians.
+    pub yaw: f32,
+    /// Angle above the horizontal plane which the eye looks down from, in radians.
+    pub pitch: f32,
+    /// Radians to orbit per pixel dragged. Defaults to 0.01.
+    pub rotate_speed: f32,
+    /// Fraction of the distance to pan per pixel dragged. Defaults to 0.002.
+    pub pan_speed: f32,
+    /// Factor to zoom in by per line scrolled. Defaults to 1.1.
+    pub zoom_speed: f32,
+    /// Range which zooming keeps the distance within, where the minimum must not exceed
+    /// the maximum. Defaults to 0.01 to 10000.
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// Last cursor position.
+    cursor: Option<Vec2<f32>>,
+    /// Whether the left mouse button is held.
+    rotating: bool,
+    /// Whether the right or middle mouse button is held.
+    panning: bool,
+}
+
+impl OrbitController {
+    /// Construct a controller orbiting a target from the given distance, with the eye
+    /// looking down negative z.
+    pub fn new(target: Vec3<f32>, distance: f32) -> Self {
+        OrbitController {
+            target,
+            distance,
+            yaw: 0.0,
+            pitch: 0.0,
+            rotate_speed: 0.01,
+            pan_speed: 0.002,
+            zoom_speed: 1.1,
+            min_distance: 0.01,
+            max_distance: 10_000.0,
+            cursor: None,
+            rotating: false,
+            panning: false,
+        }
+    }
+
+    /// Observe an input event, returning whether the view changed.
+    pub fn input(&mut self, event: &InputEvent) -> bool {
+        match *event {
+            InputEvent::MouseMove { pos } => {
+                let delta = self.cursor.map(|cursor| pos - cursor).unwrap_or_default();
+                self.cursor = Some(pos);
+                if self.rotating {
+                    // the view follows the drag, as if grabbing the scene
+                    self.yaw += delta.x * self.rotate_speed;
+                    self.pitch = (self.pitch + delta.y * self.rotate_speed)
+                        .clamp(-MAX_PITCH, MAX_PITCH);
+                    true
+                } else if self.panning {
+                    let forward = self.forward();
+                    let right = forward.cross(Vec3::unit_y()).normalized();
+                    let up = right.cross(forward);
+                    let scale = self.distance * self.pan_speed;
+                    self.target += (up * delta.y - right * delta.x) * scale;
+                    true
+                } else {
+                    false
+                }
+            },
+            InputEvent::MousePress { button, .. } => {
+                self.set_held(button, true);
+                false
+            },
+            InputEvent::MouseRelease { button, .. } => {
+                self.set_held(button, false);
+                false
+            },
+            InputEvent::Scroll { delta } => {
+                self.distance = (self.distance / self.zoom_speed.powf(delta.y))
+                    .clamp(self.min_distance, self.max_distance);
+                true
+            },
+            _ => false,
+        }
+    }
+
+    fn set_held(&mut self, button: MouseButton, held: bool) {
+        match button {
+            MouseButton::Left => self.rotating = held,
+            MouseButton::Right | MouseButton::Middle => self.panning = held,
+            MouseButton::Other(_) => (),
+        }
+    }
+
+    /// Unit direction from the eye to the target.
+    pub fn forward(&self) -> Vec3<f32> {
+        // the eye is above the target when pitched up, so it looks down
+        facing(self.yaw, -self.pitch)
+    }
+
+    /// Position of the eye.
+    pub fn eye(&self) -> Vec3<f32> {
+        self.target - self.forward() * self.distance
+    }
+
+    /// World-to-view matrix.
+    pub fn view(&self) -> Mat4<f32> {
+        Mat4::look_at_rh(self.eye(), self.target, Vec3::unit_y())
+    }
+
+    /// Move a camera to the controller's eye, looking at its target.
+    pub fn apply(&self, camera: Camera) -> Camera {
+        camera.look_at(self.eye(), self.target, Vec3::unit_y())
+    }
+}
+
+/// First-person camera control which flies freely, driven by the input event stream.
+///
+/// W, A, S, and D move forwards, left, backwards, and right, Space and left shift move up
+/// and down, and dragging with the right mouse button looks around, or moving the mouse at
+/// all if `mouse_look` is set, such as when the cursor is grabbed. Looking follows the raw
+/// `InputEvent::MouseMotion`, so it isn't stopped by the edges of the window. Pass every
+/// input event to `input`, call `update` once per frame with the time elapsed, then `apply`
+/// the controller to a camera.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FlyController {
+    /// Position of the eye.
+    pub position: Vec3<f32>,
+    /// Angle around the vertical axis, in radians, turning right when positive.
+    pub yaw: f32,
+    /// Angle above the horizontal plane, in radians, looking up when positive.
+    pub pitch: f32,
+    /// World units to move per second. Defaults to 1.
+    pub move_speed: f32,
+    /// Radians to turn per unit of raw mouse motion, which is roughly a pixel. Defaults to
+    /// 0.003.
+    pub look_speed: f32,
+    /// Whether to look around whenever the mouse moves, rather than only while dragging
+    /// with the right mouse button. Defaults to false.
+    pub mouse_look: bool,
+    /// Whether the right mouse button is held.
+    looking: bool,
+    /// Held movement keys, as forward, backward, left, right, up, and down.
+    held: [bool; 6],
+}
+
+impl FlyController {
+    /// Construct a controller at a position, looking down negative z.
+    pub fn new(position: Vec3<f32>) -> Self {
+        FlyController {
+            position,
+            yaw: 0.0,
+            pitch: 0.0,
+            move_speed: 1.0,
+            look_speed: 0.003,
+            mouse_look: false,
+            looking: false,
+            held: [false; 6],
+        }
+    }
+
+    /// Observe an input event, returning whether the view changed.
+    ///
+    /// Movement keys only change the view once `update` is called.
+    pub fn input(&mut self, event: &InputEvent) -> bool {
+        match *event {
+            InputEvent::MouseMotion { delta } if self.looking || self.mouse_look => {
+                self.yaw += delta.x * self.look_speed;
+                self.pitch = (self.pitch - delta.y * self.look_speed)
+                    .clamp(-MAX_PITCH, MAX_PITCH);
+                true
+            },
+            InputEvent::MousePress { button: MouseButton::Right, .. } => {
+                self.looking = true;
+                false
+            },
+            InputEvent::MouseRelease { button: MouseButton::Right, .. } => {
+                self.looking = false;
+                false
+            },
+            InputEvent::KeyPress { key: Some(key), .. } => {
+                self.set_held(key, true);
+                false
+            },
+            InputEvent::KeyRelease { key: Some(key), .. } => {
+                self.set_held(key, false);
+                false
+            },
+            _ => false,
+        }
+    }
+
+    fn set_held(&mut self, key: Key, held: bool) {
+        let i = match key {
+            Key::W => 0,
+            Key::S => 1,
+            Key::A => 2,
+            Key::D => 3,
+            Key::Space => 4,
+            Key::LShift => 5,
+            _ => return,
+        };
+        self.held[i] = held;
+    }
+
+    /// Move by the held keys over the given number of seconds, returning whether the view
+    /// changed.
+    pub fn update(&mut self, dt: f32) -> bool {
+        let [forward, backward, left, right, up, down] = self.held;
+        let axis = |pos: bool, neg: bool| pos as i32 as f32 - neg as i32 as f32;
+
+        // move horizontally regardless of pitch, like walking
+        let flat_forward = facing(self.yaw, 0.0);
+        let flat_right = flat_forward.cross(Vec3::unit_y());
+        let direction = flat_forward * axis(forward, backward)
+            + flat_right * axis(right, left)
+            + Vec3::unit_y() * axis(up, down);
+
+        if direction == Vec3::zero() {
+            return false;
+        }
+        self.position += direction.normalized() * self.move_speed * dt;
+        true
+    }
+
+    /// Unit direction the eye looks in.
+    pub fn forward(&self) -> Vec3<f32> {
+        facing(self.yaw, self.pitch)
+    }
+
+    /// World-to-view matrix.
+    pub fn view(&self) -> Mat4<f32> {
+        Mat4::look_at_rh(self.position, self.position + self.forward(), Vec3::unit_y())
+    }
+
+    /// Move a camera to the controller's position, looking in its direction.
+    pub fn apply(&self, camera: Camera) -> Camera {
+        camera.look_at(self.position, self.position + self.forward(), Vec3::unit_y())
+    }
+}
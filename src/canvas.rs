@@ -0,0 +1,140 @@
+use crate::{
+    BlendMode,
+    PaintBatch,
+    PaintCmd,
+    SegQueue,
+};
+
+use image::RgbaImage;
+use vek::*;
+
+/// Offscreen buffer of RGBA pixels, which can be drawn to on the CPU and then presented
+/// to a window with a single paint instruction.
+///
+/// Coordinates are signed, and accesses outside of the canvas are discarded, so drawing
+/// code can be clipped for free.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Canvas {
+    x_size: usize,
+    y_size: usize,
+    /// Row-major RGBA pixels.
+    pixels: Vec<[u8; 4]>,
+}
+
+impl Canvas {
+    /// Construct a canvas of the given size, cleared to transparent black.
+    pub fn new(x_size: usize, y_size: usize) -> Self {
+        Canvas {
+            x_size,
+            y_size,
+            pixels: vec![[0x00, 0x00, 0x00, 0x00]; x_size * y_size],
+        }
+    }
+
+    /// Copy an image into a new canvas.
+    pub fn from_image(image: &RgbaImage) -> Self {
+        Canvas {
+            x_size: image.width() as usize,
+            y_size: image.height() as usize,
+            pixels: image.pixels().map(|pixel| pixel.0).collect(),
+        }
+    }
+
+    pub fn x_size(&self) -> usize {
+        self.x_size
+    }
+
+    pub fn y_size(&self) -> usize {
+        self.y_size
+    }
+
+    /// Row-major RGBA pixels.
+    pub fn pixels(&self) -> &[[u8; 4]] {
+        &self.pixels
+    }
+
+    /// Row-major RGBA pixels, mutably.
+    pub fn pixels_mut(&mut self) -> &mut [[u8; 4]] {
+        &mut self.pixels
+    }
+
+    /// Index of a pixel, or `None` if it's outside of the canvas.
+    fn index(&self, xy: Vec2<i32>) -> Option<usize> {
+        if xy.x >= 0 && xy.y >= 0 && (xy.x as usize) < self.x_size
+            && (xy.y as usize) < self.y_size {
+            Some(xy.y as usize * self.x_size + xy.x as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Set a pixel, discarding it if it's outside of the canvas.
+    pub fn put_pixel(&mut self, xy: Vec2<i32>, color: Rgba<u8>) {
+        if let Some(i) = self.index(xy) {
+            self.pixels[i] = [color.r, color.g, color.b, color.a];
+        }
+    }
+
+    /// Combine a color with a pixel, discarding it if it's outside of the canvas.
+    pub fn blend_pixel(&mut self, xy: Vec2<i32>, color: Rgba<u8>, blend: BlendMode) {
+        if let Some(i) = self.index(xy) {
+            self.pixels[i] = blend.blend([color.r, color.g, color.b, color.a], self.pixels[i]);
+        }
+    }
+
+    /// Get a pixel, or `None` if it's outside of the canvas.
+    pub fn get_pixel(&self, xy: Vec2<i32>) -> Option<Rgba<u8>> {
+        self.index(xy).map(|i| {
+            let [r, g, b, a] = self.pixels[i];
+            Rgba::new(r, g, b, a)
+        })
+    }
+
+    /// Set every pixel to a color.
+    pub fn fill(&mut self, color: Rgba<u8>) {
+        let rgba = [color.r, color.g, color.b, color.a];
+        for pixel in &mut self.pixels {
+            *pixel = rgba;
+        }
+    }
+
+    /// Draw another canvas with its top-left corner at `pos`, combining pixels with the
+    /// given blend mode.
+    ///
+    /// Parts of the source which fall outside of this canvas are discarded.
+    pub fn blit(&mut self, src: &Canvas, pos: Vec2<i32>, blend: BlendMode) {
+        // clip the source to this canvas
+        let x_start = (-pos.x).max(0) as usize;
+        let y_start = (-pos.y).max(0) as usize;
+        let x_end = (self.x_size as i64 - pos.x as i64).max(0).min(src.x_size as i64) as usize;
+        let y_end = (self.y_size as i64 - pos.y as i64).max(0).min(src.y_size as i64) as usize;
+
+        for y in y_start..y_end {
+            for x in x_start..x_end {
+                let dst = (y as i32 + pos.y) as usize * self.x_size + (x as i32 + pos.x) as usize;
+                self.pixels[dst] = blend.blend(src.pixels[y * src.x_size + x], self.pixels[dst]);
+            }
+        }
+    }
+
+    /// Copy the pixels into an image.
+    pub fn to_image(&self) -> RgbaImage {
+        let mut buf: Vec<u8> = Vec::with_capacity(self.pixels.len() * 4);
+        for rgba in &self.pixels {
+            buf.extend_from_slice(rgba);
+        }
+        RgbaImage::from_raw(self.x_size as u32, self.y_size as u32, buf)
+            .expect("image buffer size mismatch")
+    }
+
+    /// Copy the pixels into a batch, with its top-left corner at the given position.
+    pub fn to_batch(&self, x: usize, y: usize) -> PaintBatch {
+        PaintBatch::new(x, y, self.x_size, self.y_size, self.pixels.clone())
+    }
+
+    /// Present the whole canvas by pushing it to the paint queue as a single batch, at the
+    /// top-left of the window.
+    pub fn flush_to(&self, queue: &SegQueue<PaintCmd>) {
+        queue.push(self.to_batch(0, 0).into());
+    }
+}
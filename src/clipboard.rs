@@ -0,0 +1,41 @@
+use std::{
+    borrow::Cow,
+    thread,
+};
+
+use arboard::{Clipboard, ImageData};
+use image::RgbaImage;
+
+/// Copy an image to the system clipboard, in a background thread, logging any failure.
+///
+/// On Linux, the clipboard is owned by the program which set it, so the background thread
+/// keeps serving the image until something else is copied.
+pub fn copy_image(image: RgbaImage) {
+    thread::spawn(move || {
+        let copied = Clipboard::new().and_then(|mut clipboard| {
+            let data = ImageData {
+                width: image.width() as usize,
+                height: image.height() as usize,
+                bytes: Cow::Borrowed(&image),
+            };
+            set_image(&mut clipboard, data)
+        });
+        match copied {
+            Ok(()) => info!("copied {}x{} image to clipboard", image.width(), image.height()),
+            Err(e) => error!("failed to copy image to clipboard: {}", e),
+        }
+    });
+}
+
+#[cfg(target_os = "linux")]
+fn set_image(clipboard: &mut Clipboard, data: ImageData) -> Result<(), arboard::Error> {
+    use arboard::SetExtLinux;
+
+    // block until another program takes the clipboard, so it doesn't go blank
+    clipboard.set().wait().image(data)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_image(clipboard: &mut Clipboard, data: ImageData) -> Result<(), arboard::Error> {
+    clipboard.set_image(data)
+}
@@ -0,0 +1,512 @@
+use vek::*;
+
+/// Gamma encode a linear color component in `[0, 1]` with the sRGB transfer function.
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Gamma decode an sRGB color component in `[0, 1]` to linear.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.040_45 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Convert a linear color to 8-bit sRGB, clamping components to `[0, 1]`.
+///
+/// Alpha is not gamma encoded.
+pub fn encode_srgb(color: Rgba<f32>) -> Rgba<u8> {
+    to_u8(Rgba::new(
+        linear_to_srgb(clamp(color.r)),
+        linear_to_srgb(clamp(color.g)),
+        linear_to_srgb(clamp(color.b)),
+        color.a,
+    ))
+}
+
+/// Convert an 8-bit sRGB color to linear.
+///
+/// Alpha is not gamma decoded.
+pub fn decode_srgb(color: Rgba<u8>) -> Rgba<f32> {
+    let color = to_f32(color);
+    Rgba::new(
+        srgb_to_linear(color.r),
+        srgb_to_linear(color.g),
+        srgb_to_linear(color.b),
+        color.a,
+    )
+}
+
+/// Convert a color to 8-bit without gamma encoding, clamping components to `[0, 1]`.
+pub fn to_u8(color: Rgba<f32>) -> Rgba<u8> {
+    color.map(|c| (clamp(c) * 255.0).round() as u8)
+}
+
+/// Convert an 8-bit color to `[0, 1]` without gamma decoding.
+pub fn to_f32(color: Rgba<u8>) -> Rgba<f32> {
+    color.map(|c| c as f32 / 255.0)
+}
+
+/// Operator which maps unbounded linear HDR colors into `[0, 1]`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum ToneMap {
+    /// Clamp components to `[0, 1]`, which clips bright colors.
+    #[default]
+    Clamp,
+    /// Reinhard operator, `c / (1 + c)`.
+    Reinhard,
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve.
+    Aces,
+}
+
+impl ToneMap {
+    /// Map a linear HDR color into `[0, 1]`.
+    pub fn map(self, color: Rgb<f32>) -> Rgb<f32> {
+        // negative components aren't meaningful, and would break the curves
+        let color = color.map(|c| c.max(0.0));
+        let mapped = match self {
+            ToneMap::Clamp => color,
+            ToneMap::Reinhard => color.map(|x| x / (1.0 + x)),
+            ToneMap::Aces => color.map(|x| {
+                (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+            }),
+        };
+        mapped.map(clamp)
+    }
+}
+
+/// Dithering used when quantizing colors to a palette.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Dither {
+    /// Map each pixel to its nearest palette color, which bands smooth gradients.
+    None,
+    /// Offset each pixel by a 4x4 Bayer matrix threshold before mapping it, giving a regular
+    /// crosshatch pattern.
+    Bayer,
+    /// Diffuse each pixel's quantization error onto its unvisited neighbors, giving an
+    /// irregular pattern which preserves detail better.
+    FloydSteinberg,
+}
+
+/// 4x4 Bayer matrix, of thresholds in `0..16`.
+const BAYER_4X4: [[u8; 4]; 4] = [
+    [0, 8, 2, 10],
+    [12, 4, 14, 6],
+    [3, 11, 1, 9],
+    [15, 7, 13, 5],
+];
+
+/// Quantization of colors to a fixed palette, with dithering.
+///
+/// Alpha is left unchanged.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quantize {
+    pub palette: Vec<Rgb<u8>>,
+    pub dither: Dither,
+}
+
+impl Quantize {
+    pub fn new(palette: impl Into<Vec<Rgb<u8>>>, dither: Dither) -> Self {
+        Quantize {
+            palette: palette.into(),
+            dither,
+        }
+    }
+
+    /// The palette color nearest to a color, or `None` if the palette is empty.
+    pub fn nearest(&self, color: Rgb<f32>) -> Option<Rgb<u8>> {
+        self.palette.iter()
+            .map(|&entry| {
+                let dist = (entry.map(|c| c as f32) - color).map(|c| c * c).sum();
+                (entry, dist)
+            })
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(entry, _)| entry)
+    }
+
+    /// Quantize row-major RGBA pixels in place.
+    ///
+    /// Does nothing if the palette is empty.
+    pub fn apply(&self, pixels: &mut [[u8; 4]], x_size: usize, y_size: usize) {
+        if self.palette.is_empty() {
+            return;
+        }
+        let rgb = |[r, g, b, _]: [u8; 4]| Rgb::new(r, g, b).map(|c| c as f32);
+        let set = |pixel: &mut [u8; 4], color: Rgb<u8>| {
+            *pixel = [color.r, color.g, color.b, pixel[3]];
+        };
+
+        match self.dither {
+            Dither::None => for pixel in pixels.iter_mut() {
+                let color = self.nearest(rgb(*pixel)).unwrap();
+                set(pixel, color);
+            },
+            Dither::Bayer => {
+                // threshold offsets are scaled to roughly the spacing between palette colors
+                let spread = 255.0 / (self.palette.len() as f32).cbrt();
+                for y in 0..y_size {
+                    for x in 0..x_size {
+                        let threshold = (BAYER_4X4[y % 4][x % 4] as f32 + 0.5) / 16.0 - 0.5;
+                        let pixel = &mut pixels[y * x_size + x];
+                        let color = self.nearest(rgb(*pixel) + threshold * spread).unwrap();
+                        set(pixel, color);
+                    }
+                }
+            },
+            Dither::FloydSteinberg => {
+                let mut error = vec![Rgb::<f32>::zero(); x_size * y_size];
+                for y in 0..y_size {
+                    for x in 0..x_size {
+                        let i = y * x_size + x;
+                        let wanted = rgb(pixels[i]) + error[i];
+                        let color = self.nearest(wanted).unwrap();
+                        set(&mut pixels[i], color);
+
+                        // push the error onto unvisited neighbors
+                        let err = wanted - color.map(|c| c as f32);
+                        if x + 1 < x_size {
+                            error[i + 1] += err * (7.0 / 16.0);
+                        }
+                        if y + 1 < y_size {
+                            if x > 0 {
+                                error[i + x_size - 1] += err * (3.0 / 16.0);
+                            }
+                            error[i + x_size] += err * (5.0 / 16.0);
+                            if x + 1 < x_size {
+                                error[i + x_size + 1] += err * (1.0 / 16.0);
+                            }
+                        }
+                    }
+                }
+            },
+        }
+    }
+}
+
+/// Convert an RGB color in `[0, 1]` to hue, saturation, and value.
+///
+/// Hue is in turns, in `[0, 1)`, and saturation and value in `[0, 1]`. The color space of
+/// the input is preserved, so this is usually done on gamma-encoded colors.
+pub fn rgb_to_hsv(color: Rgb<f32>) -> Vec3<f32> {
+    let max = color.r.max(color.g).max(color.b);
+    let min = color.r.min(color.g).min(color.b);
+    let chroma = max - min;
+    let s =
+        if max > 0.0 { chroma / max }
+        else { 0.0 };
+    Vec3::new(hue(color, max, chroma), s, max)
+}
+
+/// Convert hue, saturation, and value to an RGB color. See `rgb_to_hsv`.
+pub fn hsv_to_rgb(hsv: Vec3<f32>) -> Rgb<f32> {
+    let chroma = hsv.z * hsv.y;
+    from_hue(hsv.x, chroma, hsv.z - chroma)
+}
+
+/// Convert an RGB color in `[0, 1]` to hue, saturation, and lightness.
+///
+/// Hue is in turns, in `[0, 1)`, and saturation and lightness in `[0, 1]`.
+pub fn rgb_to_hsl(color: Rgb<f32>) -> Vec3<f32> {
+    let max = color.r.max(color.g).max(color.b);
+    let min = color.r.min(color.g).min(color.b);
+    let chroma = max - min;
+    let l = (max + min) / 2.0;
+    let s =
+        if l > 0.0 && l < 1.0 { chroma / (1.0 - (2.0 * l - 1.0).abs()) }
+        else { 0.0 };
+    Vec3::new(hue(color, max, chroma), s, l)
+}
+
+/// Convert hue, saturation, and lightness to an RGB color. See `rgb_to_hsl`.
+pub fn hsl_to_rgb(hsl: Vec3<f32>) -> Rgb<f32> {
+    let chroma = (1.0 - (2.0 * hsl.z - 1.0).abs()) * hsl.y;
+    from_hue(hsl.x, chroma, hsl.z - chroma / 2.0)
+}
+
+/// Hue in turns of an RGB color, given its largest component and chroma.
+fn hue(color: Rgb<f32>, max: f32, chroma: f32) -> f32 {
+    if chroma <= 0.0 {
+        return 0.0;
+    }
+    let sixths =
+        if max == color.r { (color.g - color.b) / chroma }
+        else if max == color.g { (color.b - color.r) / chroma + 2.0 }
+        else { (color.r - color.g) / chroma + 4.0 };
+    (sixths / 6.0).rem_euclid(1.0)
+}
+
+/// RGB color of a hue in turns, with the given chroma, lifted by `min` in every component.
+fn from_hue(hue: f32, chroma: f32, min: f32) -> Rgb<f32> {
+    let sixths = hue.rem_euclid(1.0) * 6.0;
+    let x = chroma * (1.0 - (sixths % 2.0 - 1.0).abs());
+    let rgb = match sixths as u32 {
+        0 => Rgb::new(chroma, x, 0.0),
+        1 => Rgb::new(x, chroma, 0.0),
+        2 => Rgb::new(0.0, chroma, x),
+        3 => Rgb::new(0.0, x, chroma),
+        4 => Rgb::new(x, 0.0, chroma),
+        _ => Rgb::new(chroma, 0.0, x),
+    };
+    rgb + min
+}
+
+/// Convert a linear sRGB color to Björn Ottosson's OKLab, as lightness and two opponent
+/// axes.
+///
+/// OKLab is perceptually uniform, so equal distances look like roughly equal differences.
+pub fn linear_to_oklab(color: Rgb<f32>) -> Vec3<f32> {
+    let l = 0.412_221_47 * color.r + 0.536_332_55 * color.g + 0.051_445_995 * color.b;
+    let m = 0.211_903_5 * color.r + 0.680_699_5 * color.g + 0.107_396_96 * color.b;
+    let s = 0.088_302_46 * color.r + 0.281_718_85 * color.g + 0.629_978_7 * color.b;
+
+    let (l, m, s) = (l.cbrt(), m.cbrt(), s.cbrt());
+    Vec3::new(
+        0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+        1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+        0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+    )
+}
+
+/// Convert an OKLab color to linear sRGB. See `linear_to_oklab`.
+///
+/// Colors outside the sRGB gamut produce components outside `[0, 1]`.
+pub fn oklab_to_linear(lab: Vec3<f32>) -> Rgb<f32> {
+    let l = lab.x + 0.396_337_78 * lab.y + 0.215_803_76 * lab.z;
+    let m = lab.x - 0.105_561_346 * lab.y - 0.063_854_17 * lab.z;
+    let s = lab.x - 0.089_484_18 * lab.y - 1.291_485_5 * lab.z;
+
+    let (l, m, s) = (l * l * l, m * m * m, s * s * s);
+    Rgb::new(
+        4.076_741_7 * l - 3.307_711_6 * m + 0.230_969_94 * s,
+        -1.268_438 * l + 2.609_757_4 * m - 0.341_319_38 * s,
+        -0.004_196_086_3 * l - 0.703_418_6 * m + 1.707_614_7 * s,
+    )
+}
+
+/// Color space which a `Gradient` interpolates in.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum GradientSpace {
+    /// Interpolate gamma-encoded sRGB components directly, which is cheap, but darkens the
+    /// middle of gradients between saturated colors.
+    Srgb,
+    /// Interpolate linear RGB components, which mixes like light does.
+    Linear,
+    /// Interpolate hue, saturation, and value, taking the shorter way around the hue circle.
+    Hsv,
+    /// Interpolate in OKLab, which gives perceptually even steps.
+    #[default]
+    Oklab,
+}
+
+/// Color gradient through any number of stops.
+///
+/// Stops are 8-bit sRGB colors at positions along the gradient, usually in `[0, 1]`. Alpha
+/// is always interpolated linearly.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gradient {
+    /// Stops, sorted by position.
+    stops: Vec<(f32, Rgba<u8>)>,
+    space: GradientSpace,
+}
+
+impl Gradient {
+    /// Construct a gradient with no stops, interpolating in the given space.
+    pub fn new(space: GradientSpace) -> Self {
+        Gradient {
+            stops: Vec::new(),
+            space,
+        }
+    }
+
+    /// Construct a gradient evenly spaced over `[0, 1]` through the given colors.
+    pub fn even(space: GradientSpace, colors: &[Rgba<u8>]) -> Self {
+        let mut gradient = Gradient::new(space);
+        let last = colors.len().saturating_sub(1).max(1) as f32;
+        for (i, &color) in colors.iter().enumerate() {
+            gradient = gradient.stop(i as f32 / last, color);
+        }
+        gradient
+    }
+
+    /// Add a stop. Stops may be added in any order.
+    pub fn stop(mut self, position: f32, color: Rgba<u8>) -> Self {
+        let i = self.stops.iter()
+            .position(|&(p, _)| p > position)
+            .unwrap_or(self.stops.len());
+        self.stops.insert(i, (position, color));
+        self
+    }
+
+    pub fn stops(&self) -> &[(f32, Rgba<u8>)] {
+        &self.stops
+    }
+
+    pub fn space(&self) -> GradientSpace {
+        self.space
+    }
+
+    /// Color at a position along the gradient.
+    ///
+    /// Positions before the first stop or after the last take its color, and a gradient
+    /// with no stops is transparent black.
+    pub fn sample(&self, t: f32) -> Rgba<u8> {
+        let (first, last) = match (self.stops.first(), self.stops.last()) {
+            (Some(&first), Some(&last)) => (first, last),
+            _ => return Rgba::zero(),
+        };
+        if t.is_nan() || t <= first.0 {
+            return first.1;
+        }
+        if t >= last.0 {
+            return last.1;
+        }
+
+        let i = self.stops.iter().position(|&(p, _)| p > t).unwrap();
+        let (p0, c0) = self.stops[i - 1];
+        let (p1, c1) = self.stops[i];
+        let s = (t - p0) / (p1 - p0);
+        self.lerp(c0, c1, s)
+    }
+
+    fn lerp(&self, a: Rgba<u8>, b: Rgba<u8>, s: f32) -> Rgba<u8> {
+        let alpha = to_f32(a).a * (1.0 - s) + to_f32(b).a * s;
+        let encoded = |rgb: Rgb<f32>| to_u8(Rgba::new(rgb.r, rgb.g, rgb.b, alpha));
+        let linear = |rgb: Rgb<f32>| encode_srgb(Rgba::new(rgb.r, rgb.g, rgb.b, alpha));
+        let (ea, eb) = (Rgb::from(to_f32(a)), Rgb::from(to_f32(b)));
+        let (la, lb) = (Rgb::from(decode_srgb(a)), Rgb::from(decode_srgb(b)));
+
+        match self.space {
+            GradientSpace::Srgb => encoded(Rgb::lerp(ea, eb, s)),
+            GradientSpace::Linear => linear(Rgb::lerp(la, lb, s)),
+            GradientSpace::Hsv => {
+                let (ha, hb) = (rgb_to_hsv(ea), rgb_to_hsv(eb));
+                // go the shorter way around the hue circle
+                let mut dh = hb.x - ha.x;
+                if dh > 0.5 { dh -= 1.0 }
+                else if dh < -0.5 { dh += 1.0 }
+                let hsv = Vec3::lerp(ha, hb, s);
+                encoded(hsv_to_rgb(Vec3::new(ha.x + dh * s, hsv.y, hsv.z)))
+            },
+            GradientSpace::Oklab => {
+                let lab = Vec3::lerp(linear_to_oklab(la), linear_to_oklab(lb), s);
+                linear(oklab_to_linear(lab))
+            },
+        }
+    }
+}
+
+/// Colormap for mapping scalar values in `[0, 1]` to colors, for data visualization.
+///
+/// The presets are tabulated at a handful of points and interpolated between them, which
+/// stays within a few levels of the reference maps.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Colormap {
+    gradient: Gradient,
+}
+
+/// Viridis, tabulated at every tenth, from matplotlib.
+const VIRIDIS: [u32; 11] = [
+    0x440154, 0x482475, 0x414487, 0x355f8d, 0x2a788e, 0x21918c,
+    0x22a884, 0x44bf70, 0x7ad151, 0xbddf26, 0xfde725,
+];
+
+/// Magma, tabulated at every tenth, from matplotlib.
+const MAGMA: [u32; 11] = [
+    0x000004, 0x140e36, 0x3b0f70, 0x641a80, 0x8c2981, 0xb73779,
+    0xde4968, 0xf7705c, 0xfe9f6d, 0xfecf92, 0xfcfdbf,
+];
+
+impl Colormap {
+    /// Construct a colormap from any gradient over `[0, 1]`.
+    pub fn new(gradient: Gradient) -> Self {
+        Colormap { gradient }
+    }
+
+    /// Perceptually uniform blue-green-yellow map, which stays readable in grayscale and
+    /// to colorblind viewers.
+    pub fn viridis() -> Self {
+        Colormap::from_table(&VIRIDIS)
+    }
+
+    /// Perceptually uniform black-purple-cream map.
+    pub fn magma() -> Self {
+        Colormap::from_table(&MAGMA)
+    }
+
+    /// Google's rainbow-like map, from dark blue through green to dark red, for
+    /// distinguishing fine detail rather than reading off values.
+    pub fn turbo() -> Self {
+        // Anton Mikhailov's polynomial fit, tabulated finely enough to interpolate
+        const STEPS: usize = 32;
+        let colors = (0..=STEPS)
+            .map(|i| {
+                let t = i as f32 / STEPS as f32;
+                let poly = |c: [f32; 6]| {
+                    c[0] + t * (c[1] + t * (c[2] + t * (c[3] + t * (c[4] + t * c[5]))))
+                };
+                to_u8(Rgba::new(
+                    poly([0.135_721_38, 4.615_392_6, -42.660_324, 132.131_08, -152.942_4,
+                        59.286_38]),
+                    poly([0.091_402_61, 2.194_188_4, 4.842_966_6, -14.185_033, 4.277_298_6,
+                        2.829_566]),
+                    poly([0.106_673_3, 12.641_946, -60.582_05, 110.362_77, -89.903_11,
+                        27.348_25]),
+                    1.0,
+                ))
+            })
+            .collect::<Vec<_>>();
+        Colormap::new(Gradient::even(GradientSpace::Srgb, &colors))
+    }
+
+    /// Black to white, evenly in sRGB.
+    pub fn grayscale() -> Self {
+        Colormap::new(Gradient::even(GradientSpace::Srgb, &[
+            Rgba::new(0x00, 0x00, 0x00, 0xFF),
+            Rgba::new(0xFF, 0xFF, 0xFF, 0xFF),
+        ]))
+    }
+
+    fn from_table(table: &[u32]) -> Self {
+        let colors = table.iter()
+            .map(|&hex| Rgba::new((hex >> 16) as u8, (hex >> 8) as u8, hex as u8, 0xFF))
+            .collect::<Vec<_>>();
+        Colormap::new(Gradient::even(GradientSpace::Srgb, &colors))
+    }
+
+    /// The same map running the other way.
+    pub fn reversed(&self) -> Self {
+        let mut gradient = Gradient::new(self.gradient.space());
+        for &(position, color) in self.gradient.stops() {
+            gradient = gradient.stop(1.0 - position, color);
+        }
+        Colormap::new(gradient)
+    }
+
+    pub fn gradient(&self) -> &Gradient {
+        &self.gradient
+    }
+
+    /// Color of a value, which is clamped to `[0, 1]`.
+    pub fn sample(&self, t: f32) -> Rgba<u8> {
+        self.gradient.sample(t)
+    }
+}
+
+/// Table for gamma encoding linear 8-bit components to sRGB.
+pub(crate) fn srgb_encode_table() -> [u8; 256] {
+    let mut table = [0; 256];
+    for (i, out) in table.iter_mut().enumerate() {
+        *out = (linear_to_srgb(i as f32 / 255.0) * 255.0).round() as u8;
+    }
+    table
+}
+
+fn clamp(c: f32) -> f32 {
+    c.clamp(0.0, 1.0)
+}
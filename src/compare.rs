@@ -0,0 +1,159 @@
+use crate::{
+    PaintBatch,
+    open_window,
+    canvas::Canvas,
+    frag::fragment_to_image,
+    input::{InputEvent, MouseButton},
+    text::{rasterize_text, text_size},
+};
+
+use image::RgbaImage;
+use vek::*;
+
+/// Side-by-side comparison of two equally sized images, split by a vertical divider which
+/// the user drags with the mouse.
+///
+/// Pixels left of the divider show image A, and those right of it show image B, labeled in
+/// the top corners. This is useful for comparing two versions of a renderer, or filtered
+/// and unfiltered output. `compare` opens a window which displays one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Comparison {
+    a: Canvas,
+    b: Canvas,
+    /// Column of the divider, which is the first column of image B.
+    split: usize,
+    /// Last cursor x coordinate.
+    cursor: f32,
+    /// Whether the left mouse button is held, dragging the divider.
+    dragging: bool,
+    /// Color of the divider and labels. Defaults to opaque white.
+    pub divider: Rgba<u8>,
+}
+
+impl Comparison {
+    /// Compare two images, with the divider in the middle.
+    ///
+    /// Panics if the images differ in size.
+    pub fn new(a: Canvas, b: Canvas) -> Self {
+        assert!(
+            a.x_size() == b.x_size() && a.y_size() == b.y_size(),
+            "compared images differ in size",
+        );
+        Comparison {
+            split: a.x_size() / 2,
+            a,
+            b,
+            cursor: 0.0,
+            dragging: false,
+            divider: Rgba::white(),
+        }
+    }
+
+    pub fn x_size(&self) -> usize {
+        self.a.x_size()
+    }
+
+    pub fn y_size(&self) -> usize {
+        self.a.y_size()
+    }
+
+    /// Column of the divider.
+    pub fn split(&self) -> usize {
+        self.split
+    }
+
+    /// Move the divider to a column, clamped to the images.
+    pub fn set_split(&mut self, split: usize) {
+        self.split = split.min(self.x_size());
+    }
+
+    /// Observe an input event, returning whether the divider moved.
+    ///
+    /// Pressing the left mouse button jumps the divider to the cursor, and dragging moves it.
+    pub fn input(&mut self, event: &InputEvent) -> bool {
+        match *event {
+            InputEvent::MouseMove { pos } => {
+                self.cursor = pos.x;
+                if !self.dragging {
+                    return false;
+                }
+            },
+            InputEvent::MousePress { button: MouseButton::Left, .. } => {
+                self.dragging = true;
+            },
+            InputEvent::MouseRelease { button: MouseButton::Left, .. } => {
+                self.dragging = false;
+                return false;
+            },
+            _ => return false,
+        }
+        let prev = self.split;
+        self.set_split(self.cursor.max(0.0).round() as usize);
+        self.split != prev
+    }
+
+    /// Compose the two images, the divider, and the labels.
+    pub fn compose(&self) -> Canvas {
+        let mut out = self.b.clone();
+        let x_size = self.x_size();
+        if x_size > 0 {
+            let rows = out.pixels_mut().chunks_mut(x_size).zip(self.a.pixels().chunks(x_size));
+            for (out_row, a_row) in rows {
+                out_row[..self.split].copy_from_slice(&a_row[..self.split]);
+            }
+        }
+
+        for y in 0..self.y_size() as i32 {
+            out.put_pixel(Vec2::new(self.split as i32, y), self.divider);
+        }
+
+        // label each side, inside its top corner
+        let divider = self.divider;
+        let b_pos = Vec2::new(self.x_size() as i32 - text_size("B", 1).x as i32 - 4, 4);
+        rasterize_text(Vec2::new(4, 4), "A", 1, &mut |xy| out.put_pixel(xy, divider));
+        rasterize_text(b_pos, "B", 1, &mut |xy| out.put_pixel(xy, divider));
+        out
+    }
+
+    /// Compose the comparison into a batch covering the canvas.
+    pub fn to_batch(&self) -> PaintBatch {
+        self.compose().to_batch(0, 0)
+    }
+}
+
+/// Open a window comparing two canvases, split by a divider which the user drags with the
+/// mouse.
+///
+/// Panics if the canvases differ in size. See `Comparison`.
+pub fn compare(a: Canvas, b: Canvas) {
+    let mut comparison = Comparison::new(a, b);
+    open_window(comparison.x_size(), comparison.y_size(), move |ctx| {
+        ctx.push(comparison.to_batch());
+        for event in ctx.input.iter() {
+            if comparison.input(&event) {
+                ctx.push(comparison.to_batch());
+            }
+        }
+    });
+}
+
+/// Open a window comparing two images.
+///
+/// Panics if the images differ in size. See `Comparison`.
+pub fn compare_images(a: &RgbaImage, b: &RgbaImage) {
+    compare(Canvas::from_image(a), Canvas::from_image(b));
+}
+
+/// Render two fragment functions, and open a window comparing them.
+///
+/// This uses rayon for parallelism.
+pub fn fragment_compare<A, B>(x_size: usize, y_size: usize, fragment_a: A, fragment_b: B)
+    where
+        A: Fn(Vec2<i32>) -> Rgba<u8> + Sync,
+        B: Fn(Vec2<i32>) -> Rgba<u8> + Sync {
+
+    compare_images(
+        &fragment_to_image(x_size, y_size, fragment_a),
+        &fragment_to_image(x_size, y_size, fragment_b),
+    );
+}
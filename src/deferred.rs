@@ -0,0 +1,146 @@
+use crate::{
+    camera::Camera,
+    graph::{BufferKind, Buffers, RenderGraph},
+    shade::{Light, Material, blinn_phong},
+};
+
+use rayon::prelude::*;
+use vek::*;
+
+/// Name of the G-buffer's color buffer of linear surface albedos.
+pub const ALBEDO: &str = "albedo";
+
+/// Name of the G-buffer's normal buffer of world-space surface normals.
+pub const NORMAL: &str = "normal";
+
+/// Name of the G-buffer's depth buffer, of depths in `[0, 1]` as output by
+/// `pipeline::Pipeline`, and infinity where nothing was drawn.
+pub const DEPTH: &str = "depth";
+
+/// Name of the G-buffer's ID buffer of indices into the lighting pass's materials.
+pub const MATERIAL: &str = "material";
+
+/// Name of the color buffer the lighting pass writes.
+pub const LIT: &str = "lit";
+
+/// Surface attributes of one pixel of a G-buffer.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GSample {
+    /// Linear surface color, which replaces the material's diffuse and ambient colors.
+    pub albedo: Rgba<f32>,
+    /// World-space surface normal, which needn't be normalized.
+    pub normal: Vec3<f32>,
+    /// Depth in `[0, 1]`, such as `pipeline::Fragment::depth`.
+    pub depth: f32,
+    /// Index into the lighting pass's materials.
+    pub material: u32,
+}
+
+/// Declare the standard G-buffer layout on a render graph, along with the lit color buffer,
+/// which it presents.
+pub fn declare_gbuffer(graph: &mut RenderGraph) -> &mut RenderGraph {
+    graph
+        .buffer(ALBEDO, BufferKind::Color)
+        .buffer(NORMAL, BufferKind::Normal)
+        .buffer(DEPTH, BufferKind::Depth)
+        .buffer(MATERIAL, BufferKind::Id)
+        .buffer(LIT, BufferKind::Color)
+        .present_buffer(LIT)
+}
+
+/// Write a sample into the G-buffer at a pixel, if it's nearer than what's there already.
+///
+/// For use in a geometry pass which writes every G-buffer buffer. Returns whether the sample
+/// was written, and does nothing outside the buffers.
+pub fn write_gbuffer(buffers: &mut Buffers, xy: Vec2<i32>, sample: GSample) -> bool {
+    let (x_size, y_size) = (buffers.x_size(), buffers.y_size());
+    if xy.x < 0 || xy.y < 0 || xy.x as usize >= x_size || xy.y as usize >= y_size {
+        return false;
+    }
+    let i = xy.y as usize * x_size + xy.x as usize;
+
+    let depth = buffers.depth_mut(DEPTH);
+    if sample.depth >= depth[i] {
+        return false;
+    }
+    depth[i] = sample.depth;
+    buffers.color_mut(ALBEDO)[i] = sample.albedo;
+    buffers.normal_mut(NORMAL)[i] = sample.normal;
+    buffers.id_mut(MATERIAL)[i] = sample.material;
+    true
+}
+
+/// Reconstruct the world-space position of a pixel's center from its depth in `[0, 1]`,
+/// given the inverse of the world-to-clip matrix it was rendered with.
+pub fn world_position(
+    inv_view_projection: Mat4<f32>,
+    xy: Vec2<usize>,
+    depth: f32,
+    x_size: usize,
+    y_size: usize,
+) -> Vec3<f32> {
+    let ndc = Vec4::new(
+        (xy.x as f32 + 0.5) / x_size as f32 * 2.0 - 1.0,
+        1.0 - (xy.y as f32 + 0.5) / y_size as f32 * 2.0,
+        depth * 2.0 - 1.0,
+        1.0,
+    );
+    let p = inv_view_projection * ndc;
+    p.xyz() / p.w
+}
+
+/// Add a deferred lighting pass to a render graph, which shades every covered pixel of the
+/// G-buffer with Blinn-Phong lighting from every light, in parallel over rows.
+///
+/// The camera must be the one the geometry was rendered with, to reconstruct positions.
+/// Material IDs index into `materials`, with the default material for IDs out of range, and
+/// the albedo replaces each material's diffuse and ambient colors. Pixels where nothing was
+/// drawn stay transparent.
+pub fn add_lighting_pass<'a>(
+    graph: &'a mut RenderGraph,
+    camera: &Camera,
+    lights: Vec<Light>,
+    ambient: Rgb<f32>,
+    materials: Vec<Material>,
+) -> &'a mut RenderGraph {
+    let inv_vp = camera.view_projection().inverted();
+    let eye = camera.eye;
+
+    graph.pass(
+        "deferred lighting",
+        &[ALBEDO, NORMAL, DEPTH, MATERIAL],
+        &[LIT],
+        move |gbuffer, out| {
+            let (x_size, y_size) = (gbuffer.x_size(), gbuffer.y_size());
+            let albedo = gbuffer.color(ALBEDO);
+            let normal = gbuffer.normal(NORMAL);
+            let depth = gbuffer.depth(DEPTH);
+            let material = gbuffer.id(MATERIAL);
+
+            // parallel iter over rows
+            out.color_mut(LIT)
+                .par_chunks_mut(x_size.max(1))
+                .enumerate()
+                .for_each(|(y, row)| for (x, lit) in row.iter_mut().enumerate() {
+                    let i = y * x_size + x;
+                    if !depth[i].is_finite() {
+                        continue;
+                    }
+
+                    let point = world_position(
+                        inv_vp, Vec2::new(x, y), depth[i], x_size, y_size,
+                    );
+                    let surface = Material {
+                        ambient: Rgb::from(albedo[i]),
+                        diffuse: Rgb::from(albedo[i]),
+                        ..materials.get(material[i] as usize).copied().unwrap_or_default()
+                    };
+                    let color = blinn_phong(
+                        &surface, &lights, ambient,
+                        point, normal[i], eye - point,
+                    );
+                    *lit = Rgba::from_translucent(color, albedo[i].a);
+                });
+        },
+    )
+}
@@ -0,0 +1,894 @@
+use crate::{
+    BlendMode,
+    Paint,
+    PaintBatch,
+    PaintCmd,
+    SegQueue,
+    camera::Camera,
+    canvas::Canvas,
+    mesh::Mesh,
+    paint::push_paint,
+    raster::rasterize_triangle,
+    texture::Filter,
+    transform::Transform2,
+};
+
+use std::collections::HashSet;
+
+use image::RgbaImage;
+use vek::*;
+
+/// Fractional part of a number, always in `[0, 1)`.
+fn fract(n: f32) -> f32 {
+    n - n.floor()
+}
+
+/// Walk the pixels of a line between two pixels, inclusive, with Bresenham's algorithm.
+pub fn bresenham(
+    a: Vec2<i32>,
+    b: Vec2<i32>,
+    plot: &mut impl FnMut(Vec2<i32>),
+) {
+    let dx = (b.x - a.x).abs();
+    let dy = -(b.y - a.y).abs();
+    let sx = if a.x < b.x { 1 } else { -1 };
+    let sy = if a.y < b.y { 1 } else { -1 };
+
+    let mut err = dx + dy;
+    let mut p = a;
+    loop {
+        plot(p);
+        if p == b {
+            break;
+        }
+
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            p.x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            p.y += sy;
+        }
+    }
+}
+
+/// Clip a line segment to a rectangle, with the Liang-Barsky algorithm.
+pub(crate) fn clip_segment(
+    a: Vec2<f32>,
+    b: Vec2<f32>,
+    bounds: Aabr<f32>,
+) -> Option<(Vec2<f32>, Vec2<f32>)> {
+    if !(a.x.is_finite() && a.y.is_finite() && b.x.is_finite() && b.y.is_finite()) {
+        return None;
+    }
+    let d = b - a;
+    let mut t0 = 0.0f32;
+    let mut t1 = 1.0f32;
+    for &(p, q) in &[
+        (-d.x, a.x - bounds.min.x),
+        (d.x, bounds.max.x - a.x),
+        (-d.y, a.y - bounds.min.y),
+        (d.y, bounds.max.y - a.y),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let r = q / p;
+            if p < 0.0 { t0 = t0.max(r) }
+            else { t1 = t1.min(r) }
+        }
+    }
+    if t0 > t1 {
+        return None;
+    }
+    Some((a + d * t0, a + d * t1))
+}
+
+/// Walk the pixels of an anti-aliased line with Xiaolin Wu's algorithm.
+///
+/// The callback is passed each pixel along with its coverage, in `[0, 1]`.
+pub fn wu(
+    a: Vec2<f32>,
+    b: Vec2<f32>,
+    plot: &mut impl FnMut(Vec2<i32>, f32),
+) {
+    // iterate along the major axis, left to right
+    let steep = (b.y - a.y).abs() > (b.x - a.x).abs();
+    let (a, b) =
+        if steep { (Vec2::new(a.y, a.x), Vec2::new(b.y, b.x)) }
+        else { (a, b) };
+    let (a, b) = if a.x > b.x { (b, a) } else { (a, b) };
+
+    let mut plot = |x: i32, y: i32, coverage: f32| {
+        if steep {
+            plot(Vec2::new(y, x), coverage);
+        } else {
+            plot(Vec2::new(x, y), coverage);
+        }
+    };
+
+    let d = b - a;
+    let gradient = if d.x == 0.0 { 1.0 } else { d.y / d.x };
+
+    // first endpoint
+    let x_end = a.x.round();
+    let y_end = a.y + gradient * (x_end - a.x);
+    let x_gap = 1.0 - fract(a.x + 0.5);
+    let x_pixel_1 = x_end as i32;
+    let y_pixel_1 = y_end.floor() as i32;
+    plot(x_pixel_1, y_pixel_1, (1.0 - fract(y_end)) * x_gap);
+    plot(x_pixel_1, y_pixel_1 + 1, fract(y_end) * x_gap);
+    let mut y_inter = y_end + gradient;
+
+    // second endpoint
+    let x_end = b.x.round();
+    let y_end = b.y + gradient * (x_end - b.x);
+    let x_gap = fract(b.x + 0.5);
+    let x_pixel_2 = x_end as i32;
+    let y_pixel_2 = y_end.floor() as i32;
+    plot(x_pixel_2, y_pixel_2, (1.0 - fract(y_end)) * x_gap);
+    plot(x_pixel_2, y_pixel_2 + 1, fract(y_end) * x_gap);
+
+    // main loop
+    for x in x_pixel_1 + 1..x_pixel_2 {
+        let y = y_inter.floor() as i32;
+        plot(x, y, 1.0 - fract(y_inter));
+        plot(x, y + 1, fract(y_inter));
+        y_inter += gradient;
+    }
+}
+
+/// Walk the pixels of a line with the given thickness, by rasterizing it as a rectangle.
+pub fn thick(
+    a: Vec2<f32>,
+    b: Vec2<f32>,
+    thickness: f32,
+    plot: &mut impl FnMut(Vec2<i32>),
+) {
+    let d = b - a;
+    let len = d.magnitude();
+    if len == 0.0 {
+        return;
+    }
+
+    // offset perpendicular to the line, by half the thickness
+    let offset = Vec2::new(-d.y, d.x) / len * (thickness / 2.0);
+    let corners = [a + offset, b + offset, b - offset, a - offset];
+
+    rasterize_triangle(corners[0], corners[1], corners[2], &mut |xy, _| plot(xy));
+    rasterize_triangle(corners[0], corners[2], corners[3], &mut |xy, _| plot(xy));
+}
+
+/// Walk the horizontal spans of pixels inside a polygon, with the even-odd rule.
+///
+/// The polygon is implicitly closed, and may be concave or self-intersecting. A pixel is
+/// inside if its center is. The callback is passed each span's row, along with its start
+/// column and exclusive end column.
+pub fn scanline_polygon(
+    points: &[Vec2<f32>],
+    span: &mut impl FnMut(i32, i32, i32),
+) {
+    if points.len() < 3 {
+        return;
+    }
+
+    let y_min = points.iter().map(|p| p.y).fold(f32::INFINITY, f32::min);
+    let y_max = points.iter().map(|p| p.y).fold(f32::NEG_INFINITY, f32::max);
+
+    // rows whose centers are within the polygon's vertical extent
+    let row_start = (y_min - 0.5).ceil() as i32;
+    let row_end = (y_max - 0.5).ceil() as i32;
+
+    let mut crossings: Vec<f32> = Vec::new();
+    for y in row_start..row_end {
+        let center_y = y as f32 + 0.5;
+
+        // x coordinates where the row's center line crosses an edge
+        // (edges are half-open, so a vertex shared by two edges is only crossed once)
+        crossings.clear();
+        for (i, &a) in points.iter().enumerate() {
+            let b = points[(i + 1) % points.len()];
+            if (a.y <= center_y) != (b.y <= center_y) {
+                crossings.push(a.x + (center_y - a.y) / (b.y - a.y) * (b.x - a.x));
+            }
+        }
+        crossings.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+        // fill between alternating pairs of crossings
+        for pair in crossings.chunks_exact(2) {
+            let x_start = (pair[0] - 0.5).ceil() as i32;
+            let x_end = (pair[1] - 0.5).ceil() as i32;
+            if x_start < x_end {
+                span(y, x_start, x_end);
+            }
+        }
+    }
+}
+
+/// Walk the pixels of a circle's outline with the midpoint circle algorithm.
+///
+/// The sign of the radius is ignored.
+pub fn midpoint_circle(
+    center: Vec2<i32>,
+    radius: i32,
+    plot: &mut impl FnMut(Vec2<i32>),
+) {
+    let radius = radius.saturating_abs();
+    let mut x = radius;
+    let mut y = 0;
+    let mut err = 1 - radius;
+    while x >= y {
+        // eight-way symmetry
+        for &(dx, dy) in &[
+            (x, y), (y, x), (-y, x), (-x, y),
+            (-x, -y), (-y, -x), (y, -x), (x, -y),
+        ] {
+            plot(center + Vec2::new(dx, dy));
+        }
+
+        y += 1;
+        if err < 0 {
+            err += 2 * y + 1;
+        } else {
+            x -= 1;
+            err += 2 * (y - x) + 1;
+        }
+    }
+}
+
+/// Walk the pixels of an axis-aligned ellipse's outline with the midpoint ellipse algorithm.
+///
+/// The signs of the radii are ignored.
+pub fn midpoint_ellipse(
+    center: Vec2<i32>,
+    radii: Vec2<i32>,
+    plot: &mut impl FnMut(Vec2<i32>),
+) {
+    let radii = radii.map(|n| n.saturating_abs());
+    let rx2 = (radii.x as f64).powi(2);
+    let ry2 = (radii.y as f64).powi(2);
+
+    let mut plot4 = |x: i32, y: i32| {
+        plot(center + Vec2::new(x, y));
+        plot(center + Vec2::new(-x, y));
+        plot(center + Vec2::new(x, -y));
+        plot(center + Vec2::new(-x, -y));
+    };
+
+    let mut x = 0;
+    let mut y = radii.y;
+    let mut dx = 0.0;
+    let mut dy = 2.0 * rx2 * y as f64;
+
+    // region where the slope is shallower than -1, stepping in x
+    let mut d1 = ry2 - rx2 * radii.y as f64 + 0.25 * rx2;
+    while dx < dy {
+        plot4(x, y);
+        x += 1;
+        dx += 2.0 * ry2;
+        if d1 < 0.0 {
+            d1 += dx + ry2;
+        } else {
+            y -= 1;
+            dy -= 2.0 * rx2;
+            d1 += dx - dy + ry2;
+        }
+    }
+
+    // region where the slope is steeper than -1, stepping in y
+    let mut d2 = ry2 * (x as f64 + 0.5).powi(2) + rx2 * (y as f64 - 1.0).powi(2) - rx2 * ry2;
+    while y >= 0 {
+        plot4(x, y);
+        y -= 1;
+        dy -= 2.0 * rx2;
+        if d2 > 0.0 {
+            d2 += rx2 - dy;
+        } else {
+            x += 1;
+            dx += 2.0 * ry2;
+            d2 += dx - dy + rx2;
+        }
+    }
+}
+
+/// Walk the horizontal spans of pixels inside an axis-aligned ellipse.
+///
+/// The callback is passed each span's row, along with its start column and exclusive end
+/// column. The signs of the radii are ignored.
+pub fn scanline_ellipse(
+    center: Vec2<i32>,
+    radii: Vec2<i32>,
+    span: &mut impl FnMut(i32, i32, i32),
+) {
+    let radii = radii.map(|n| n.saturating_abs());
+    for dy in -radii.y..=radii.y {
+        let t =
+            if radii.y > 0 { dy as f32 / radii.y as f32 }
+            else { 0.0 };
+        let half_width = (radii.x as f32 * (1.0 - t * t).max(0.0).sqrt()).round() as i32;
+        span(center.y + dy, center.x - half_width, center.x + half_width + 1);
+    }
+}
+
+/// Walk the pixels of an anti-aliased axis-aligned ellipse, either its one-pixel-wide
+/// outline or its filled interior.
+///
+/// The callback is passed each pixel along with its coverage, in `[0, 1]`.
+pub fn ellipse_aa(
+    center: Vec2<f32>,
+    radii: Vec2<f32>,
+    filled: bool,
+    plot: &mut impl FnMut(Vec2<i32>, f32),
+) {
+    if radii.x <= 0.0 || radii.y <= 0.0 {
+        return;
+    }
+
+    let min = (center - radii - 1.0).map(|n| n.floor() as i32);
+    let max = (center + radii + 1.0).map(|n| n.ceil() as i32);
+    for y in min.y..=max.y {
+        for x in min.x..=max.x {
+            let d = Vec2::new(x as f32 + 0.5, y as f32 + 0.5) - center;
+
+            // approximate signed distance to the ellipse, by the implicit function divided by
+            // the magnitude of its gradient
+            let f = (d / radii).magnitude_squared() - 1.0;
+            let grad = (d * 2.0 / (radii * radii)).magnitude();
+            let dist = if grad > 0.0 { f / grad } else { -radii.x.min(radii.y) };
+
+            let coverage =
+                if filled { 0.5 - dist }
+                else { 1.0 - dist.abs() };
+            let coverage = coverage.clamp(0.0, 1.0);
+            if coverage > 0.0 {
+                plot(Vec2::new(x, y), coverage);
+            }
+        }
+    }
+}
+
+/// Walk the pixels of a circular arc, with the midpoint circle algorithm.
+///
+/// Angles are in radians, measured from the positive x axis towards the positive y axis, and
+/// the arc sweeps from `start` to `end`.
+pub fn arc(
+    center: Vec2<i32>,
+    radius: i32,
+    start: f32,
+    end: f32,
+    plot: &mut impl FnMut(Vec2<i32>),
+) {
+    let tau = 2.0 * std::f32::consts::PI;
+    let sweep = end - start;
+    midpoint_circle(center, radius, &mut |xy| {
+        let d = xy - center;
+        let angle = (d.y as f32).atan2(d.x as f32);
+        if sweep >= tau || (angle - start).rem_euclid(tau) <= sweep {
+            plot(xy);
+        }
+    });
+}
+
+/// Walk the edges of a mesh's triangles, projected onto the screen.
+///
+/// Positions are transformed to clip space by `mvp`, and mapped to a screen of the given
+/// size, with y pointing down. Each edge shared by several triangles is only walked once,
+/// and edges are clipped against the near plane and the screen. If `cull_backfaces` is set,
+/// edges are skipped unless they border a triangle which is front-facing, meaning wound
+/// counter-clockwise in normalized device coordinates, following OpenGL conventions.
+pub fn wireframe(
+    mesh: &Mesh,
+    mvp: Mat4<f32>,
+    x_size: usize,
+    y_size: usize,
+    cull_backfaces: bool,
+    line: &mut impl FnMut(Vec2<f32>, Vec2<f32>),
+) {
+    let clip: Vec<Vec4<f32>> = mesh.positions.iter()
+        .map(|&p| mvp * Vec4::from_point(p))
+        .collect();
+    let to_screen = |p: Vec4<f32>| Vec2::new(
+        (p.x / p.w + 1.0) / 2.0 * x_size as f32,
+        (1.0 - p.y / p.w) / 2.0 * y_size as f32,
+    );
+
+    let bounds = Aabr {
+        min: Vec2::zero(),
+        max: Vec2::new(x_size as f32, y_size as f32),
+    };
+
+    let mut edges: HashSet<(usize, usize)> = HashSet::new();
+    for tri in mesh.indices.chunks_exact(3) {
+        let verts = [clip[tri[0]], clip[tri[1]], clip[tri[2]]];
+
+        // the winding can only be judged if the whole triangle is in front of the camera
+        if cull_backfaces && verts.iter().all(|v| v.w > 0.0) {
+            let [a, b, c] = [to_screen(verts[0]), to_screen(verts[1]), to_screen(verts[2])];
+            // counter-clockwise with y up is clockwise with y down
+            let area = (b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y);
+            if area >= 0.0 {
+                continue;
+            }
+        }
+
+        for i in 0..3 {
+            let (a, b) = (tri[i], tri[(i + 1) % 3]);
+            edges.insert((a.min(b), a.max(b)));
+        }
+    }
+
+    for (a, b) in edges {
+        let (mut a, mut b) = (clip[a], clip[b]);
+
+        // clip against the near plane, where z = -w
+        let da = a.z + a.w;
+        let db = b.z + b.w;
+        if da < 0.0 && db < 0.0 {
+            continue;
+        }
+        if da < 0.0 {
+            a = Vec4::lerp(a, b, da / (da - db));
+        } else if db < 0.0 {
+            b = Vec4::lerp(b, a, db / (db - da));
+        }
+
+        // clip to the screen, since vertices near the camera project far outside of it
+        if let Some((a, b)) = clip_segment(to_screen(a), to_screen(b), bounds) {
+            line(a, b);
+        }
+    }
+}
+
+/// Configuration of how an image is drawn by `blit_image`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BlitOptions {
+    /// Factor to scale the image by in each dimension. Defaults to 1.
+    pub scale: Vec2<f32>,
+    /// Filter used when scaling. Defaults to `Filter::Nearest`, which keeps pixel art sharp.
+    pub filter: Filter,
+    /// Whether to mirror the image horizontally. Defaults to false.
+    pub flip_x: bool,
+    /// Whether to mirror the image vertically. Defaults to false.
+    pub flip_y: bool,
+    /// How the image is combined with the canvas. Defaults to `BlendMode::AlphaOver`.
+    pub blend: BlendMode,
+}
+
+impl Default for BlitOptions {
+    fn default() -> Self {
+        BlitOptions {
+            scale: Vec2::one(),
+            filter: Filter::Nearest,
+            flip_x: false,
+            flip_y: false,
+            blend: BlendMode::AlphaOver,
+        }
+    }
+}
+
+/// Size of an image once drawn with the given options.
+pub fn blit_size(image: &RgbaImage, options: &BlitOptions) -> Vec2<usize> {
+    let size = Vec2::new(image.width() as f32, image.height() as f32) * options.scale;
+    size.map(|n| n.max(0.0).round() as usize)
+}
+
+/// Walk the pixels of a scaled and flipped image, with its top-left corner at `pos`.
+///
+/// The callback is passed each pixel along with its color, which should be combined with
+/// the canvas by the options' blend mode.
+pub fn blit_pixels(
+    image: &RgbaImage,
+    pos: Vec2<i32>,
+    options: &BlitOptions,
+    plot: &mut impl FnMut(Vec2<i32>, [u8; 4]),
+) {
+    let size = blit_size(image, options);
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let color = blit_sample(image, size, pixel_center(x, y), options);
+            plot(pos + Vec2::new(x as i32, y as i32), color);
+        }
+    }
+}
+
+fn pixel_center(x: usize, y: usize) -> Vec2<f32> {
+    Vec2::new(x as f32 + 0.5, y as f32 + 0.5)
+}
+
+/// Sample the color of a scaled and flipped image, of the given drawn size, at a position
+/// within it.
+fn blit_sample(
+    image: &RgbaImage,
+    size: Vec2<usize>,
+    pos: Vec2<f32>,
+    options: &BlitOptions,
+) -> [u8; 4] {
+    let src_size = Vec2::new(image.width() as i32, image.height() as i32);
+    let size = size.map(|n| n as f32);
+    let pos = Vec2::new(
+        if options.flip_x { size.x - pos.x } else { pos.x },
+        if options.flip_y { size.y - pos.y } else { pos.y },
+    );
+
+    // position in source pixels
+    let src = pos * src_size.map(|n| n as f32) / size;
+    let texel = |xy: Vec2<i32>| {
+        let xy = Vec2::<i32>::partial_max(Vec2::<i32>::partial_min(xy, src_size - 1), Vec2::zero());
+        image.get_pixel(xy.x as u32, xy.y as u32).0
+    };
+
+    match options.filter {
+        Filter::Nearest => texel(src.map(|n| n.floor() as i32)),
+        Filter::Bilinear => {
+            // offset so that texel centers are at integer coordinates
+            let src = src - 0.5;
+            let base = src.map(|n| n.floor());
+            let t = src - base;
+            let base = base.map(|n| n as i32);
+
+            // interpolate premultiplied colors, so transparent texels don't darken edges
+            let premul = |xy: Vec2<i32>| {
+                let [r, g, b, a] = texel(xy);
+                let a = a as f32 / 255.0;
+                Rgba::new(r as f32 * a, g as f32 * a, b as f32 * a, a)
+            };
+            let c = Rgba::lerp(
+                Rgba::lerp(premul(base), premul(base + Vec2::new(1, 0)), t.x),
+                Rgba::lerp(premul(base + Vec2::new(0, 1)), premul(base + Vec2::new(1, 1)), t.x),
+                t.y,
+            );
+
+            let rgb =
+                if c.a > 0.0 { c.rgb() / c.a }
+                else { Rgb::zero() };
+            let out = Rgba::from_translucent(rgb, c.a * 255.0)
+                .map(|n| n.clamp(0.0, 255.0).round() as u8);
+            [out.r, out.g, out.b, out.a]
+        },
+    }
+}
+
+/// Draw an image onto the paint queue, with its top-left corner at `pos`.
+///
+/// The visible part of the image is pushed as a single `PaintBatch`, and parts at negative
+/// coordinates are discarded.
+pub fn blit_image(
+    queue: &SegQueue<PaintCmd>,
+    image: &RgbaImage,
+    pos: Vec2<i32>,
+    options: &BlitOptions,
+) {
+    let size = blit_size(image, options);
+
+    // clip to non-negative coordinates
+    let start = pos.map(|n| (-n).max(0) as usize);
+    if start.x >= size.x || start.y >= size.y {
+        return;
+    }
+
+    let mut rgba = Vec::with_capacity((size.x - start.x) * (size.y - start.y));
+    for y in start.y..size.y {
+        for x in start.x..size.x {
+            rgba.push(blit_sample(image, size, pixel_center(x, y), options));
+        }
+    }
+
+    let batch = PaintBatch::new(
+        (pos.x + start.x as i32) as usize,
+        (pos.y + start.y as i32) as usize,
+        size.x - start.x,
+        size.y - start.y,
+        rgba,
+    );
+    queue.push(batch.with_blend(options.blend).into());
+}
+
+/// Draw an image onto a canvas, with its top-left corner at `pos`.
+///
+/// Parts of the image which fall outside of the canvas are discarded.
+pub fn blit_image_to_canvas(
+    canvas: &mut Canvas,
+    image: &RgbaImage,
+    pos: Vec2<i32>,
+    options: &BlitOptions,
+) {
+    blit_pixels(image, pos, options, &mut |xy, [r, g, b, a]| {
+        canvas.blend_pixel(xy, Rgba::new(r, g, b, a), options.blend)
+    });
+}
+
+/// Draw an image onto the paint queue, mapped from its drawn size by a transform, such as
+/// the current transform of a `TransformStack`.
+///
+/// The image's top-left corner is at the local origin. Each covered pixel is painted
+/// individually, with the options' blend mode.
+pub fn blit_image_transformed(
+    queue: &SegQueue<PaintCmd>,
+    transform: &Transform2,
+    image: &RgbaImage,
+    options: &BlitOptions,
+) {
+    let inverse = match transform.inverse() {
+        Some(inverse) => inverse,
+        // collapsed to nothing
+        None => return,
+    };
+    let size = blit_size(image, options);
+    let local_size = size.map(|n| n as f32);
+
+    // bounds of the transformed corners
+    let corners = [
+        Vec2::zero(),
+        Vec2::new(local_size.x, 0.0),
+        Vec2::new(0.0, local_size.y),
+        local_size,
+    ];
+    let (min, max) = corners.iter()
+        .map(|&corner| transform.apply(corner))
+        .fold(
+            (Vec2::broadcast(f32::INFINITY), Vec2::broadcast(f32::NEG_INFINITY)),
+            |(min, max), p| (Vec2::partial_min(min, p), Vec2::partial_max(max, p)),
+        );
+    let min = min.map(|n| (n.floor() as i32).max(0));
+    let max = max.map(|n| n.ceil() as i32);
+
+    for y in min.y..max.y {
+        for x in min.x..max.x {
+            // sample at the pixel center, in local space
+            let local = inverse.apply(pixel_center(x as usize, y as usize));
+            let inside = local.x >= 0.0 && local.y >= 0.0
+                && local.x < local_size.x && local.y < local_size.y;
+            if !inside {
+                continue;
+            }
+
+            let [r, g, b, a] = blit_sample(image, size, local, options);
+            let paint = Paint { x: x as usize, y: y as usize, r, g, b, a };
+            queue.push(PaintCmd::Blend(paint, options.blend));
+        }
+    }
+}
+
+/// Draw a one-pixel-wide line onto the paint queue, with Bresenham's algorithm.
+pub fn draw_line(
+    queue: &SegQueue<PaintCmd>,
+    a: Vec2<i32>,
+    b: Vec2<i32>,
+    color: Rgba<u8>,
+) {
+    bresenham(a, b, &mut |xy| push_paint(queue, xy, color));
+}
+
+/// Draw an anti-aliased line onto the paint queue, with Xiaolin Wu's algorithm.
+///
+/// Pixels are alpha blended over the canvas, with alpha scaled by their coverage.
+pub fn draw_line_aa(
+    queue: &SegQueue<PaintCmd>,
+    a: Vec2<f32>,
+    b: Vec2<f32>,
+    color: Rgba<u8>,
+) {
+    wu(a, b, &mut |xy, coverage| push_coverage(queue, xy, coverage, color));
+}
+
+/// Draw a line with the given thickness onto the paint queue.
+pub fn draw_thick_line(
+    queue: &SegQueue<PaintCmd>,
+    a: Vec2<f32>,
+    b: Vec2<f32>,
+    thickness: f32,
+    color: Rgba<u8>,
+) {
+    thick(a, b, thickness, &mut |xy| push_paint(queue, xy, color));
+}
+
+/// Push each span as a single `PaintCmd::FillRect`, discarding parts at negative coordinates,
+/// and empty spans.
+fn push_span(queue: &SegQueue<PaintCmd>, y: i32, x_start: i32, x_end: i32, color: Rgba<u8>) {
+    if y >= 0 && x_end > 0 && x_end > x_start {
+        let x_start = x_start.max(0) as usize;
+        queue.push(PaintCmd::FillRect {
+            rect: Rect::new(x_start, y as usize, x_end as usize - x_start, 1),
+            color,
+        });
+    }
+}
+
+/// Push a pixel blended over the canvas, with its alpha scaled by coverage, so that
+/// overlapping and adjacent edges composite rather than overwrite each other.
+fn push_coverage(queue: &SegQueue<PaintCmd>, xy: Vec2<i32>, coverage: f32, color: Rgba<u8>) {
+    let alpha = (color.a as f32 * coverage).round() as u8;
+    if alpha > 0 {
+        if let Some(paint) = Paint::at(xy, Rgba { a: alpha, ..color }) {
+            queue.push(PaintCmd::Blend(paint, BlendMode::AlphaOver));
+        }
+    }
+}
+
+/// Fill a polygon onto the paint queue, with the even-odd rule.
+///
+/// The polygon is implicitly closed, and may be concave or self-intersecting. Each span is
+/// painted as a single `PaintCmd::FillRect`.
+pub fn fill_polygon(
+    queue: &SegQueue<PaintCmd>,
+    points: &[Vec2<f32>],
+    color: Rgba<u8>,
+) {
+    scanline_polygon(points, &mut |y, x_start, x_end| {
+        push_span(queue, y, x_start, x_end, color)
+    });
+}
+
+/// Draw a circle's one-pixel-wide outline onto the paint queue.
+pub fn draw_circle(
+    queue: &SegQueue<PaintCmd>,
+    center: Vec2<i32>,
+    radius: i32,
+    color: Rgba<u8>,
+) {
+    midpoint_circle(center, radius, &mut |xy| push_paint(queue, xy, color));
+}
+
+/// Fill a circle onto the paint queue.
+pub fn fill_circle(
+    queue: &SegQueue<PaintCmd>,
+    center: Vec2<i32>,
+    radius: i32,
+    color: Rgba<u8>,
+) {
+    fill_ellipse(queue, center, Vec2::broadcast(radius), color);
+}
+
+/// Draw an anti-aliased circle outline onto the paint queue.
+///
+/// Pixels are alpha blended over the canvas, with alpha scaled by their coverage.
+pub fn draw_circle_aa(
+    queue: &SegQueue<PaintCmd>,
+    center: Vec2<f32>,
+    radius: f32,
+    color: Rgba<u8>,
+) {
+    draw_ellipse_aa(queue, center, Vec2::broadcast(radius), color);
+}
+
+/// Fill an anti-aliased circle onto the paint queue.
+///
+/// Pixels are alpha blended over the canvas, with alpha scaled by their coverage.
+pub fn fill_circle_aa(
+    queue: &SegQueue<PaintCmd>,
+    center: Vec2<f32>,
+    radius: f32,
+    color: Rgba<u8>,
+) {
+    fill_ellipse_aa(queue, center, Vec2::broadcast(radius), color);
+}
+
+/// Draw an axis-aligned ellipse's one-pixel-wide outline onto the paint queue.
+pub fn draw_ellipse(
+    queue: &SegQueue<PaintCmd>,
+    center: Vec2<i32>,
+    radii: Vec2<i32>,
+    color: Rgba<u8>,
+) {
+    midpoint_ellipse(center, radii, &mut |xy| push_paint(queue, xy, color));
+}
+
+/// Fill an axis-aligned ellipse onto the paint queue.
+///
+/// Each row is painted as a single `PaintCmd::FillRect`.
+pub fn fill_ellipse(
+    queue: &SegQueue<PaintCmd>,
+    center: Vec2<i32>,
+    radii: Vec2<i32>,
+    color: Rgba<u8>,
+) {
+    scanline_ellipse(center, radii, &mut |y, x_start, x_end| {
+        push_span(queue, y, x_start, x_end, color)
+    });
+}
+
+/// Draw an anti-aliased axis-aligned ellipse outline onto the paint queue.
+///
+/// Pixels are alpha blended over the canvas, with alpha scaled by their coverage.
+pub fn draw_ellipse_aa(
+    queue: &SegQueue<PaintCmd>,
+    center: Vec2<f32>,
+    radii: Vec2<f32>,
+    color: Rgba<u8>,
+) {
+    ellipse_aa(center, radii, false, &mut |xy, coverage| {
+        push_coverage(queue, xy, coverage, color)
+    });
+}
+
+/// Fill an anti-aliased axis-aligned ellipse onto the paint queue.
+///
+/// Pixels are alpha blended over the canvas, with alpha scaled by their coverage.
+pub fn fill_ellipse_aa(
+    queue: &SegQueue<PaintCmd>,
+    center: Vec2<f32>,
+    radii: Vec2<f32>,
+    color: Rgba<u8>,
+) {
+    ellipse_aa(center, radii, true, &mut |xy, coverage| {
+        push_coverage(queue, xy, coverage, color)
+    });
+}
+
+/// Draw a one-pixel-wide circular arc onto the paint queue.
+///
+/// Angles are in radians, measured from the positive x axis towards the positive y axis, and
+/// the arc sweeps from `start` to `end`.
+pub fn draw_arc(
+    queue: &SegQueue<PaintCmd>,
+    center: Vec2<i32>,
+    radius: i32,
+    start: f32,
+    end: f32,
+    color: Rgba<u8>,
+) {
+    arc(center, radius, start, end, &mut |xy| push_paint(queue, xy, color));
+}
+
+/// Draw an anti-aliased line onto the paint queue, with its endpoints mapped by a transform.
+pub fn draw_line_transformed(
+    queue: &SegQueue<PaintCmd>,
+    transform: &Transform2,
+    a: Vec2<f32>,
+    b: Vec2<f32>,
+    color: Rgba<u8>,
+) {
+    draw_line_aa(queue, transform.apply(a), transform.apply(b), color);
+}
+
+/// Draw a thick line onto the paint queue, with its endpoints mapped by a transform.
+///
+/// The thickness is not transformed.
+pub fn draw_thick_line_transformed(
+    queue: &SegQueue<PaintCmd>,
+    transform: &Transform2,
+    a: Vec2<f32>,
+    b: Vec2<f32>,
+    thickness: f32,
+    color: Rgba<u8>,
+) {
+    draw_thick_line(queue, transform.apply(a), transform.apply(b), thickness, color);
+}
+
+/// Fill a polygon onto the paint queue, with its vertices mapped by a transform.
+pub fn fill_polygon_transformed(
+    queue: &SegQueue<PaintCmd>,
+    transform: &Transform2,
+    points: &[Vec2<f32>],
+    color: Rgba<u8>,
+) {
+    let points: Vec<Vec2<f32>> = points.iter()
+        .map(|&point| transform.apply(point))
+        .collect();
+    fill_polygon(queue, &points, color);
+}
+
+/// Draw the edges of a mesh's triangles onto the paint queue, as one-pixel-wide lines, as
+/// seen by a camera.
+///
+/// This is a quick way to check 3D geometry. See `wireframe`.
+pub fn draw_wireframe(
+    queue: &SegQueue<PaintCmd>,
+    mesh: &Mesh,
+    camera: &Camera,
+    x_size: usize,
+    y_size: usize,
+    color: Rgba<u8>,
+    cull_backfaces: bool,
+) {
+    let mvp = camera.view_projection();
+    wireframe(mesh, mvp, x_size, y_size, cull_backfaces, &mut |a, b| {
+        let a = a.map(|n| n.floor() as i32);
+        let b = b.map(|n| n.floor() as i32);
+        draw_line(queue, a, b, color);
+    });
+}
@@ -0,0 +1,305 @@
+use crate::canvas::Canvas;
+
+use rayon::prelude::*;
+
+/// Pixel with components in `[0, 255]`, as floats.
+type PixelF = [f32; 4];
+
+fn to_f32(pixels: &[[u8; 4]]) -> Vec<PixelF> {
+    pixels.iter()
+        .map(|p| [p[0] as f32, p[1] as f32, p[2] as f32, p[3] as f32])
+        .collect()
+}
+
+fn store(canvas: &mut Canvas, pixels: &[PixelF]) {
+    canvas.pixels_mut()
+        .par_iter_mut()
+        .zip(pixels.par_iter())
+        .for_each(|(out, p)| {
+            for c in 0..4 {
+                out[c] = p[c].clamp(0.0, 255.0).round() as u8;
+            }
+        });
+}
+
+/// Convolve pixels with a symmetric kernel horizontally and then vertically, clamping
+/// coordinates to the edges, in parallel over rows.
+///
+/// `kernel` has odd length, with its center at the middle.
+fn convolve_separable(
+    pixels: &[PixelF],
+    x_size: usize,
+    y_size: usize,
+    kernel: &[f32],
+) -> Vec<PixelF> {
+    let r = (kernel.len() / 2) as i64;
+    let mut tmp = vec![[0.0; 4]; pixels.len()];
+    let mut out = vec![[0.0; 4]; pixels.len()];
+    if x_size == 0 || y_size == 0 {
+        return out;
+    }
+
+    // horizontal pass
+    tmp.par_chunks_mut(x_size)
+        .enumerate()
+        .for_each(|(y, row)| for (x, px) in row.iter_mut().enumerate() {
+            let mut sum = [0.0; 4];
+            for (k, &w) in kernel.iter().enumerate() {
+                let sx = (x as i64 + k as i64 - r).max(0).min(x_size as i64 - 1) as usize;
+                let p = pixels[y * x_size + sx];
+                for c in 0..4 {
+                    sum[c] += p[c] * w;
+                }
+            }
+            *px = sum;
+        });
+
+    // vertical pass
+    out.par_chunks_mut(x_size)
+        .enumerate()
+        .for_each(|(y, row)| for (x, px) in row.iter_mut().enumerate() {
+            let mut sum = [0.0; 4];
+            for (k, &w) in kernel.iter().enumerate() {
+                let sy = (y as i64 + k as i64 - r).max(0).min(y_size as i64 - 1) as usize;
+                let p = tmp[sy * x_size + x];
+                for c in 0..4 {
+                    sum[c] += p[c] * w;
+                }
+            }
+            *px = sum;
+        });
+    out
+}
+
+/// Normalized Gaussian kernel with the given standard deviation, out to three deviations.
+fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    let r = (sigma * 3.0).ceil().max(0.0) as i64;
+    let mut kernel: Vec<f32> = (-r..=r)
+        .map(|i| (-(i * i) as f32 / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let sum: f32 = kernel.iter().sum();
+    for w in &mut kernel {
+        *w /= sum;
+    }
+    kernel
+}
+
+fn gaussian(pixels: &[PixelF], x_size: usize, y_size: usize, sigma: f32) -> Vec<PixelF> {
+    if sigma > 0.0 {
+        convolve_separable(pixels, x_size, y_size, &gaussian_kernel(sigma))
+    } else {
+        pixels.to_vec()
+    }
+}
+
+/// Luminance of a pixel, by Rec. 709 weights, in `[0, 255]`.
+fn luma(p: PixelF) -> f32 {
+    p[0] * 0.2126 + p[1] * 0.7152 + p[2] * 0.0722
+}
+
+/// Blur a canvas with a Gaussian of the given standard deviation, in pixels.
+///
+/// The blur is separable, so it costs linearly in the deviation rather than quadratically.
+/// Pixels past the edges repeat the edge pixels. Like the other filters, this operates on
+/// the canvas' 8-bit values directly, and every channel including alpha, in parallel with
+/// rayon.
+pub fn gaussian_blur(canvas: &mut Canvas, sigma: f32) {
+    let (x_size, y_size) = (canvas.x_size(), canvas.y_size());
+    let blurred = gaussian(&to_f32(canvas.pixels()), x_size, y_size, sigma);
+    store(canvas, &blurred);
+}
+
+/// Blur a canvas by averaging each pixel with those up to `radius` pixels away in each
+/// axis.
+pub fn box_blur(canvas: &mut Canvas, radius: usize) {
+    let (x_size, y_size) = (canvas.x_size(), canvas.y_size());
+    let kernel = vec![1.0 / (radius * 2 + 1) as f32; radius * 2 + 1];
+    let blurred = convolve_separable(&to_f32(canvas.pixels()), x_size, y_size, &kernel);
+    store(canvas, &blurred);
+}
+
+/// Sharpen a canvas with an unsharp mask, adding back `amount` times the difference from a
+/// Gaussian blur of the given standard deviation. Alpha is unchanged.
+pub fn sharpen(canvas: &mut Canvas, sigma: f32, amount: f32) {
+    let (x_size, y_size) = (canvas.x_size(), canvas.y_size());
+    let pixels = to_f32(canvas.pixels());
+    let blurred = gaussian(&pixels, x_size, y_size, sigma);
+    let sharpened: Vec<PixelF> = pixels.par_iter()
+        .zip(blurred.par_iter())
+        .map(|(p, b)| [
+            p[0] + (p[0] - b[0]) * amount,
+            p[1] + (p[1] - b[1]) * amount,
+            p[2] + (p[2] - b[2]) * amount,
+            p[3],
+        ])
+        .collect();
+    store(canvas, &sharpened);
+}
+
+/// Replace a canvas with the magnitude of its luminance gradient, by the Sobel operator, as
+/// opaque grayscale, so edges are bright.
+pub fn sobel(canvas: &mut Canvas) {
+    let (x_size, y_size) = (canvas.x_size(), canvas.y_size());
+    let lumas: Vec<f32> = to_f32(canvas.pixels()).into_iter().map(luma).collect();
+    let at = |x: i64, y: i64| {
+        let x = x.max(0).min(x_size as i64 - 1) as usize;
+        let y = y.max(0).min(y_size as i64 - 1) as usize;
+        lumas[y * x_size + x]
+    };
+
+    let edges: Vec<PixelF> = (0..x_size * y_size)
+        .into_par_iter()
+        .map(|i| {
+            let (x, y) = ((i % x_size) as i64, (i / x_size) as i64);
+            let gx = at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+                - at(x - 1, y - 1) - 2.0 * at(x - 1, y) - at(x - 1, y + 1);
+            let gy = at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+                - at(x - 1, y - 1) - 2.0 * at(x, y - 1) - at(x + 1, y - 1);
+            // the largest possible magnitude is 4 * 255 * sqrt(2)
+            let magnitude = (gx * gx + gy * gy).sqrt() / 4.0;
+            [magnitude, magnitude, magnitude, 255.0]
+        })
+        .collect();
+    store(canvas, &edges);
+}
+
+/// Add a glow around the bright parts of a canvas.
+///
+/// Pixels brighter than `threshold`, in `[0, 1]` of luminance, are kept by how far they
+/// exceed it, blurred with a Gaussian of the given standard deviation, and added back
+/// scaled by `intensity`. Alpha is unchanged.
+pub fn bloom(canvas: &mut Canvas, threshold: f32, sigma: f32, intensity: f32) {
+    let (x_size, y_size) = (canvas.x_size(), canvas.y_size());
+    let pixels = to_f32(canvas.pixels());
+
+    // bright pass
+    let threshold = threshold.clamp(0.0, 1.0) * 255.0;
+    let bright: Vec<PixelF> = pixels.par_iter()
+        .map(|&p| {
+            let l = luma(p);
+            let weight =
+                if l > threshold && threshold < 255.0 { (l - threshold) / (255.0 - threshold) }
+                else { 0.0 };
+            [p[0] * weight, p[1] * weight, p[2] * weight, 0.0]
+        })
+        .collect();
+
+    let glow = gaussian(&bright, x_size, y_size, sigma);
+    let out: Vec<PixelF> = pixels.par_iter()
+        .zip(glow.par_iter())
+        .map(|(p, g)| [
+            p[0] + g[0] * intensity,
+            p[1] + g[1] * intensity,
+            p[2] + g[2] * intensity,
+            p[3],
+        ])
+        .collect();
+    store(canvas, &out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use vek::*;
+
+    const GREY: [u8; 4] = [90, 140, 200, 180];
+
+    fn filled(x_size: usize, y_size: usize, color: [u8; 4]) -> Canvas {
+        let mut canvas = Canvas::new(x_size, y_size);
+        canvas.fill(Rgba::from(color));
+        canvas
+    }
+
+    /// Canvas with its left half black and right half white.
+    fn step_edge(x_size: usize, y_size: usize) -> Canvas {
+        let mut canvas = filled(x_size, y_size, [0, 0, 0, 255]);
+        for y in 0..y_size {
+            for x in x_size / 2..x_size {
+                canvas.put_pixel(Vec2::new(x as i32, y as i32), Rgba::broadcast(255));
+            }
+        }
+        canvas
+    }
+
+    #[test]
+    fn gaussian_kernel_sums_to_one() {
+        for &sigma in &[0.3, 1.0, 2.5, 7.0] {
+            let kernel = gaussian_kernel(sigma);
+            assert_eq!(kernel.len() % 2, 1);
+            let sum: f32 = kernel.iter().sum();
+            assert!((sum - 1.0).abs() < 1e-5, "sigma {} sums to {}", sigma, sum);
+        }
+    }
+
+    #[test]
+    fn blurs_preserve_constant_image() {
+        let mut canvas = filled(13, 7, GREY);
+        gaussian_blur(&mut canvas, 2.0);
+        assert!(canvas.pixels().iter().all(|&p| p == GREY));
+
+        let mut canvas = filled(13, 7, GREY);
+        box_blur(&mut canvas, 3);
+        assert!(canvas.pixels().iter().all(|&p| p == GREY));
+    }
+
+    #[test]
+    fn sharpen_preserves_constant_image() {
+        let mut canvas = filled(9, 9, GREY);
+        sharpen(&mut canvas, 1.5, 2.0);
+        assert!(canvas.pixels().iter().all(|&p| p == GREY));
+    }
+
+    #[test]
+    fn sobel_is_zero_on_flat_input() {
+        let mut canvas = filled(8, 8, GREY);
+        sobel(&mut canvas);
+        assert!(canvas.pixels().iter().all(|&p| p == [0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn sobel_is_large_on_step_edge() {
+        let mut canvas = step_edge(8, 8);
+        sobel(&mut canvas);
+        for y in 0..8 {
+            let at = |x| canvas.get_pixel(Vec2::new(x, y)).unwrap();
+            // the columns either side of the edge
+            assert!(at(3).r >= 200, "{:?}", at(3));
+            assert!(at(4).r >= 200, "{:?}", at(4));
+            // far from the edge
+            assert_eq!(at(0).r, 0);
+            assert_eq!(at(7).r, 0);
+        }
+    }
+
+    #[test]
+    fn bloom_leaves_sub_threshold_pixels_unchanged() {
+        let mut canvas = filled(10, 10, GREY);
+        let before = canvas.pixels().to_vec();
+        bloom(&mut canvas, 0.9, 2.0, 1.0);
+        assert_eq!(canvas.pixels(), &before[..]);
+    }
+
+    #[test]
+    fn bloom_brightens_around_bright_pixels() {
+        let mut canvas = filled(11, 11, [0, 0, 0, 255]);
+        canvas.put_pixel(Vec2::new(5, 5), Rgba::broadcast(255));
+        bloom(&mut canvas, 0.5, 1.0, 4.0);
+        assert!(canvas.get_pixel(Vec2::new(6, 5)).unwrap().r > 0);
+        // alpha is unchanged
+        assert!(canvas.pixels().iter().all(|p| p[3] == 255));
+    }
+
+    #[test]
+    fn zero_size_canvases_dont_panic() {
+        for &(x_size, y_size) in &[(0, 0), (0, 5), (5, 0)] {
+            let mut canvas = Canvas::new(x_size, y_size);
+            gaussian_blur(&mut canvas, 1.0);
+            box_blur(&mut canvas, 2);
+            sharpen(&mut canvas, 1.0, 1.0);
+            sobel(&mut canvas);
+            bloom(&mut canvas, 0.5, 1.0, 1.0);
+            assert!(canvas.pixels().is_empty());
+        }
+    }
+}
@@ -1,8 +1,111 @@
+use crate::{
+    open_window,
+    Window,
+    DrawContext,
+    PaintBatch,
+    PaintCmd,
+    input::{InputEvent, Key, View},
+    params::Params,
+    simd::{F32x8, LANES},
+    tiles::{TileOrder, TileRenderer},
+    window::Navigator,
+};
 
-use crate::{open_window, Paint};
+use std::{
+    thread,
+    cell::RefCell,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
 
-use rayon::prelude::*;
+use crossbeam::channel::{self, Sender, Receiver};
+
+use rayon::{
+    prelude::*,
+    ThreadPool,
+    ThreadPoolBuilder,
+};
 use vek::*;
+use image::RgbaImage;
+
+/// Options for how fragments are scheduled.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct FragOptions {
+    /// Width and height of the square tiles which the canvas is partitioned into.
+    ///
+    /// Each tile is rendered by one rayon task into a local buffer, and painted as a single
+    /// batch. A size of 0 is treated as 1. Defaults to 32.
+    pub tile_size: usize,
+    /// Supersampling factor. Defaults to 1, which disables supersampling.
+    ///
+    /// With a factor of `n`, the fragment function is called at the centers of an `n` by
+    /// `n` grid of subpixels within each pixel, and the samples are filtered down to one
+    /// color per pixel. Only fragment functions of positions in canvas space, such as those
+    /// passed to `fragment_subpixel`, can be supersampled. Fragment functions of integer
+    /// pixel coordinates are called once per pixel.
+    pub ssaa: usize,
+    /// Filter used to combine supersamples. Defaults to `SsaaFilter::Box`.
+    pub ssaa_filter: SsaaFilter,
+    /// Number of threads to render tiles on. Defaults to `None`, which uses rayon's global
+    /// thread pool, with one thread per CPU. `Some(0)` means the same.
+    ///
+    /// With `Some(1)`, tiles are rendered sequentially on the drawing thread, which is
+    /// useful for debugging. With more, they're rendered on a dedicated pool of that many
+    /// threads, which is kept for reuse by later frames.
+    pub threads: Option<usize>,
+    /// Whether tiles are painted in row-major order. Defaults to false, which paints each
+    /// tile as soon as it's rendered.
+    ///
+    /// Ordering waits for every tile of a pass to finish before painting any of them, but
+    /// makes the sequence of paint instructions reproducible.
+    pub deterministic_order: bool,
+}
+
+impl Default for FragOptions {
+    fn default() -> Self {
+        FragOptions {
+            tile_size: 32,
+            ssaa: 1,
+            ssaa_filter: SsaaFilter::Box,
+            threads: None,
+            deterministic_order: false,
+        }
+    }
+}
+
+/// Filter for combining supersamples into pixels.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum SsaaFilter {
+    /// Average the samples within each pixel.
+    Box,
+    /// Weight samples by their distance from the pixel center, out to one pixel away, which
+    /// is smoother than a box filter but slightly blurrier.
+    Tent,
+}
+
+impl SsaaFilter {
+    /// Number of samples beyond a pixel's edge which the filter reads, at a supersampling
+    /// factor of `ssaa`.
+    fn margin(self, ssaa: usize) -> usize {
+        match self {
+            SsaaFilter::Box => 0,
+            SsaaFilter::Tent => ssaa.div_ceil(2),
+        }
+    }
+
+    /// Weight of a sample at the given distance from the pixel center, measured in samples,
+    /// at a supersampling factor of `ssaa`.
+    fn weight(self, dist: f32, ssaa: usize) -> f32 {
+        let ssaa = ssaa as f32;
+        match self {
+            SsaaFilter::Box => if dist < ssaa / 2.0 { 1.0 } else { 0.0 },
+            SsaaFilter::Tent => (1.0 - dist / ssaa).max(0.0),
+        }
+    }
+}
 
 /// Launch a window with the given function for computing a fragment color.
 ///
@@ -36,32 +139,1522 @@ pub fn fragment_stateful<S, F>(
         F: Send + Sync + 'static,
         F: Fn(Vec2<i32>, &S) -> Rgba<u8> {
 
+    // delegate
+    fragment_stateful_with(
+        x_size,
+        y_size,
+        FragOptions::default(),
+        state,
+        fragment,
+    )
+}
+
+/// Launch a window with the given function for computing a fragment color, with the given
+/// scheduling options. The fragment function will have read-access to some shared state.
+///
+/// This uses rayon for parallelism.
+pub fn fragment_stateful_with<S, F>(
+    x_size: usize,
+    y_size: usize,
+    options: FragOptions,
+    state: S,
+    fragment: F,
+)
+    where
+        S: Send + Sync + 'static,
+        F: Send + Sync + 'static,
+        F: Fn(Vec2<i32>, &S) -> Rgba<u8> {
+
     // open window, drawing thread
     open_window(
         x_size,
         y_size,
-        move |queue| {
-            // parallel iter over fragments
-            (0..x_size).into_par_iter()
-                .flat_map(|x| (0..y_size).into_par_iter()
-                    .map(move |y| (x, y)))
-                //.collect::<Vec<_>>().into_iter() // sequential for debug
-                .for_each(|(x, y)| {
+        move |ctx| render(
+            x_size,
+            y_size,
+            options,
+            &ctx,
+            |xy| fragment(xy, &state),
+        ),
+    );
+}
 
-                    // paint
-                    let color = fragment(
-                        Vec2::new(x as i32, y as i32),
-                        &state,
-                    );
-                    queue.push(Paint {
-                        x,
-                        y,
-                        r: color.r,
-                        g: color.g,
-                        b: color.b,
-                        a: color.a,
-                    });
+/// Launch a window with the given function for computing the fragment color at a position
+/// in canvas space, which is supersampled according to `options.ssaa`.
+///
+/// Pixel `(x, y)` covers positions from `(x, y)` to `(x + 1, y + 1)`, so without
+/// supersampling, the fragment function is called at `(x + 0.5, y + 0.5)`. Shaders which
+/// normalize positions by the canvas size work unchanged at any supersampling factor.
+///
+/// This uses rayon for parallelism.
+pub fn fragment_subpixel<F>(
+    x_size: usize,
+    y_size: usize,
+    options: FragOptions,
+    fragment: F,
+)
+    where
+        F: Fn(Vec2<f32>) -> Rgba<u8> + Send + Sync + 'static {
+
+    // open window, drawing thread
+    open_window(
+        x_size,
+        y_size,
+        move |ctx| render_samples(
+            x_size,
+            y_size,
+            Rect::new(0, 0, x_size, y_size),
+            options,
+            &ctx,
+            &fragment,
+            &|| false,
+        ),
+    );
+}
+
+/// Launch a window with the given function for computing a fragment color, rendering on
+/// the given rayon thread pool rather than the global one. The fragment function will have
+/// read-access to some shared state.
+///
+/// This lets a renderer embedded in a larger application keep its work off of the global
+/// pool, or pin its core count for benchmarking. If `options.threads` is set, it takes
+/// precedence over the pool.
+pub fn fragment_stateful_in<S, F>(
+    x_size: usize,
+    y_size: usize,
+    pool: Arc<ThreadPool>,
+    options: FragOptions,
+    state: S,
+    fragment: F,
+)
+    where
+        S: Send + Sync + 'static,
+        F: Send + Sync + 'static,
+        F: Fn(Vec2<i32>, &S) -> Rgba<u8> {
+
+    // open window, drawing thread
+    open_window(
+        x_size,
+        y_size,
+        move |ctx| pool.install(|| render(
+            x_size,
+            y_size,
+            options,
+            &ctx,
+            |xy| fragment(xy, &state),
+        )),
+    );
+}
+
+/// Launch a window with the given function for computing eight horizontally adjacent
+/// fragment colors at once.
+///
+/// The fragment function is passed the coordinates of eight pixels, in lanes from left to
+/// right, and returns their colors with components in `[0, 1]`, which are clamped and not
+/// gamma encoded. Arithmetic written against `F32x8` is vectorized by the compiler, which
+/// can be much faster for arithmetic-heavy fragment functions. Lanes past the right edge of
+/// the canvas are computed and discarded.
+///
+/// This uses rayon for parallelism.
+pub fn fragment_simd<F>(
+    x_size: usize,
+    y_size: usize,
+    fragment: F,
+)
+    where
+        F: Fn(Vec2<F32x8>) -> Rgba<F32x8> + Send + Sync + 'static {
+
+    // open window, drawing thread
+    open_window(
+        x_size,
+        y_size,
+        move |ctx| {
+            // parallel iter over rows
+            (0..y_size).into_par_iter().for_each(|y| {
+                if ctx.shutdown.is_shutdown() {
+                    return;
+                }
+                let rgba = simd_row(x_size, y, &fragment);
+                ctx.push(PaintBatch::new(0, y, x_size, 1, rgba));
+            });
+        },
+    );
+}
+
+/// Run the given eight-lane fragment function over an image, without opening a window. See
+/// `fragment_simd`.
+pub fn fragment_simd_to_image<F>(
+    x_size: usize,
+    y_size: usize,
+    fragment: F,
+) -> RgbaImage
+    where
+        F: Fn(Vec2<F32x8>) -> Rgba<F32x8> + Sync {
+
+    let mut buf: Vec<u8> = vec![0; x_size * y_size * 4];
+
+    // parallel iter over rows
+    buf.par_chunks_mut((x_size * 4).max(1))
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (texel, color) in row.chunks_mut(4).zip(simd_row(x_size, y, &fragment)) {
+                texel.copy_from_slice(&color);
+            }
+        });
+
+    RgbaImage::from_raw(x_size as u32, y_size as u32, buf)
+        .expect("image buffer size mismatch")
+}
+
+/// Compute a row of fragments eight at a time.
+fn simd_row<F>(x_size: usize, y: usize, fragment: &F) -> Vec<[u8; 4]>
+    where
+        F: Fn(Vec2<F32x8>) -> Rgba<F32x8> {
+
+    let mut rgba: Vec<[u8; 4]> = Vec::with_capacity(x_size + LANES);
+    for x in (0..x_size).step_by(LANES) {
+        let color = fragment(Vec2::new(F32x8::ramp(x as f32), F32x8::splat(y as f32)));
+        let to_u8 = |c: F32x8| c.clamp(0.0, 1.0).map(|n| (n * 255.0).round()).0;
+        let (r, g, b, a) = (to_u8(color.r), to_u8(color.g), to_u8(color.b), to_u8(color.a));
+        rgba.extend((0..LANES).map(|i| [r[i] as u8, g[i] as u8, b[i] as u8, a[i] as u8]));
+    }
+
+    // discard lanes past the edge
+    rgba.truncate(x_size);
+    rgba
+}
+
+/// How long `fragment_controlled` waits for re-render requests before checking for input.
+const CONTROL_POLL: Duration = Duration::from_millis(10);
+
+/// Handle for requesting that regions of a `fragment_controlled` canvas be re-rendered.
+///
+/// This can be cloned and sent to other threads, so the application can change the state
+/// its fragment function reads and then re-render only the affected region.
+#[derive(Clone, Debug)]
+pub struct RenderControl {
+    send: Sender<Rect<usize, usize>>,
+    recv: Receiver<Rect<usize, usize>>,
+}
+
+impl RenderControl {
+    pub fn new() -> Self {
+        let (send, recv) = channel::unbounded();
+        RenderControl { send, recv }
+    }
+
+    /// Request that the fragment function be re-run over a rectangle of the canvas.
+    ///
+    /// Parts of the rectangle outside of the canvas are ignored.
+    pub fn rerender_region(&self, rect: Rect<usize, usize>) {
+        let _ = self.send.send(rect);
+    }
+
+    /// Request that the fragment function be re-run over the whole canvas.
+    pub fn rerender_all(&self) {
+        self.rerender_region(Rect::new(0, 0, usize::MAX, usize::MAX));
+    }
+}
+
+impl Default for RenderControl {
+    fn default() -> Self {
+        RenderControl::new()
+    }
+}
+
+/// Launch a window which renders the given fragment function once, and then re-renders
+/// regions of the canvas on request.
+///
+/// Regions are requested through `control`, either by clones of it held elsewhere, or by the
+/// input handler, which is called with each user input event.
+///
+/// This uses rayon for parallelism.
+pub fn fragment_controlled<H, F>(
+    x_size: usize,
+    y_size: usize,
+    options: FragOptions,
+    control: RenderControl,
+    mut handle_input: H,
+    fragment: F,
+)
+    where
+        H: FnMut(InputEvent, &RenderControl) + Send + 'static,
+        F: Send + Sync + 'static,
+        F: Fn(Vec2<i32>) -> Rgba<u8> {
+
+    // open window, drawing thread
+    open_window(
+        x_size,
+        y_size,
+        move |ctx| {
+            let canvas = Rect::new(0, 0, x_size, y_size);
+            render_region(x_size, y_size, canvas, options, &ctx, &fragment);
+
+            while !ctx.shutdown.is_shutdown() {
+                for event in ctx.input.try_iter() {
+                    handle_input(event, &control);
+                }
+
+                // gather requests, skipping duplicates
+                let mut regions: Vec<Rect<usize, usize>> = Vec::new();
+                if let Ok(region) = control.recv.recv_timeout(CONTROL_POLL) {
+                    regions.push(region);
+                    for region in control.recv.try_iter() {
+                        if !regions.contains(&region) {
+                            regions.push(region);
+                        }
+                    }
+                }
+
+                for region in regions {
+                    render_region(x_size, y_size, region, options, &ctx, &fragment);
+                }
+            }
+        },
+    );
+}
+
+/// Handle for restarting a `fragment_restartable` render with new parameters.
+///
+/// This can be cloned and sent to other threads. Each restart bumps a generation counter,
+/// which the render in flight checks before each tile, so it's abandoned promptly.
+#[derive(Debug)]
+pub struct RenderRestart<P> {
+    generation: Arc<AtomicU64>,
+    send: Sender<P>,
+    recv: Receiver<P>,
+}
+
+impl<P> RenderRestart<P> {
+    pub fn new() -> Self {
+        let (send, recv) = channel::unbounded();
+        RenderRestart {
+            generation: Arc::new(AtomicU64::new(0)),
+            send,
+            recv,
+        }
+    }
+
+    /// Cancel the render in flight, and start rendering again with the given parameters.
+    ///
+    /// If several restarts arrive during one render, only the latest parameters are
+    /// rendered.
+    pub fn restart(&self, params: P) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        let _ = self.send.send(params);
+    }
+
+    /// Number of restarts so far.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+}
+
+impl<P> Clone for RenderRestart<P> {
+    fn clone(&self) -> Self {
+        RenderRestart {
+            generation: Arc::clone(&self.generation),
+            send: self.send.clone(),
+            recv: self.recv.clone(),
+        }
+    }
+}
+
+impl<P> Default for RenderRestart<P> {
+    fn default() -> Self {
+        RenderRestart::new()
+    }
+}
+
+/// Launch a window which renders the given fragment function with some parameters, and
+/// re-renders it from scratch whenever it's restarted with new parameters.
+///
+/// Restarts are requested through `restart`, either by clones of it held elsewhere, or by
+/// the input handler, which is called with each user input event. The input handler runs
+/// on its own thread, so it can restart a render while one is still in flight, which is then
+/// cancelled before its remaining tiles.
+///
+/// This uses rayon for parallelism.
+pub fn fragment_restartable<P, H, F>(
+    x_size: usize,
+    y_size: usize,
+    options: FragOptions,
+    params: P,
+    restart: RenderRestart<P>,
+    mut handle_input: H,
+    fragment: F,
+)
+    where
+        P: Send + Sync + 'static,
+        H: FnMut(InputEvent, &RenderRestart<P>) + Send + 'static,
+        F: Send + Sync + 'static,
+        F: Fn(Vec2<i32>, &P) -> Rgba<u8> {
+
+    // open window, drawing thread
+    open_window(
+        x_size,
+        y_size,
+        move |ctx| {
+            let ctx = &ctx;
+            let restart = &restart;
+            let canvas = Rect::new(0, 0, x_size, y_size);
+
+            let scoped = crossbeam::scope(|scope| {
+                // handle input concurrently, so it can cancel renders in flight
+                scope.spawn(move |_| while !ctx.shutdown.is_shutdown() {
+                    if let Ok(event) = ctx.input.recv_timeout(CONTROL_POLL) {
+                        handle_input(event, restart);
+                    }
                 });
+
+                let mut params = params;
+                let mut generation = restart.generation();
+                loop {
+                    render_region_cancellable(
+                        x_size,
+                        y_size,
+                        canvas,
+                        options,
+                        ctx,
+                        &|xy| fragment(xy, &params),
+                        &|| restart.generation() != generation,
+                    );
+
+                    // wait for a restart, and then skip to the latest one
+                    let next = loop {
+                        if ctx.shutdown.is_shutdown() {
+                            return;
+                        }
+                        if let Ok(next) = restart.recv.recv_timeout(CONTROL_POLL) {
+                            break next;
+                        }
+                    };
+                    // (drain before reading the generation, so a restart racing with this
+                    // leaves its parameters queued, rather than cancelling their render)
+                    params = restart.recv.try_iter().last().unwrap_or(next);
+                    generation = restart.generation();
+                }
+            });
+            if scoped.is_err() {
+                error!("restartable render thread panicked");
+            }
+        },
+    );
+}
+
+/// Region of a 2D world shown by `fragment_viewport`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Viewport {
+    /// World coordinates at the center of the canvas.
+    pub center: Vec2<f64>,
+    /// Size of a pixel, in world units.
+    pub scale: f64,
+}
+
+impl Viewport {
+    /// World coordinates of a canvas position, on a canvas of the given size.
+    ///
+    /// The world's y axis points up, unlike the canvas'.
+    pub fn to_world(&self, pos: Vec2<f64>, x_size: usize, y_size: usize) -> Vec2<f64> {
+        let from_center = pos - Vec2::new(x_size as f64, y_size as f64) / 2.0;
+        self.center + Vec2::new(from_center.x, -from_center.y) * self.scale
+    }
+
+    /// This viewport as seen through a zoomed and panned view of a canvas of the given size.
+    fn viewed(&self, view: View, x_size: usize, y_size: usize) -> Viewport {
+        let center = Vec2::new(x_size as f32, y_size as f32) / 2.0;
+        Viewport {
+            center: self.to_world(view.to_canvas(center).map(|n| n as f64), x_size, y_size),
+            scale: self.scale / view.zoom as f64,
         }
+    }
+}
+
+/// Launch a window which renders a 2D world through a viewport, which the user can zoom with
+/// the scroll wheel and pan by dragging with the left mouse button, with the same controls
+/// and zoom limits as `WindowBuilder::navigation`.
+///
+/// The fragment function is passed the world coordinates of each pixel's center, and the
+/// canvas is re-rendered whenever the viewport changes, so detail is never lost to
+/// magnification. A render still in flight when the viewport changes is cancelled, so the
+/// view keeps up with the mouse. This suits fractal explorers.
+///
+/// This uses rayon for parallelism.
+pub fn fragment_viewport<F>(
+    x_size: usize,
+    y_size: usize,
+    viewport: Viewport,
+    fragment: F,
+)
+    where
+        F: Send + Sync + 'static,
+        F: Fn(Vec2<f64>) -> Rgba<u8> {
+
+    // navigate the same way as windows with navigation, but re-render rather than scaling
+    let mut navigator = Navigator::default();
+
+    fragment_restartable(
+        x_size,
+        y_size,
+        FragOptions::default(),
+        viewport,
+        RenderRestart::new(),
+        move |event, restart| {
+            if let Some(view) = navigator.input(&event) {
+                restart.restart(viewport.viewed(view, x_size, y_size));
+            }
+        },
+        move |xy, viewport: &Viewport| {
+            let center = xy.map(|n| n as f64 + 0.5);
+            fragment(viewport.to_world(center, x_size, y_size))
+        },
+    );
+}
+
+/// Block sizes of successive passes of progressive rendering.
+const PROGRESSIVE_BLOCK_SIZES: [usize; 4] = [8, 4, 2, 1];
+
+/// Size of the square tiles which each pass of progressive rendering paints at a time, which
+/// is a multiple of every block size.
+const PROGRESSIVE_TILE_SIZE: usize = 64;
+
+/// Launch a window with the given function for computing a fragment color, rendering
+/// progressively.
+///
+/// The canvas is first rendered at a coarse resolution, by filling 8x8 blocks with the color
+/// of their top-left pixel, and then refined to 4x4, 2x2, and finally 1x1 blocks. Each pixel's
+/// color is only computed once. This gives immediate feedback for expensive fragment
+/// functions.
+///
+/// This uses rayon for parallelism.
+pub fn fragment_progressive<F>(
+    x_size: usize,
+    y_size: usize,
+    fragment: F,
+)
+    where
+        F: Send + Sync + 'static,
+        F: Fn(Vec2<i32>) -> Rgba<u8> {
+
+    // delegate
+    fragment_progressive_ordered(x_size, y_size, TileOrder::Scanline, fragment)
+}
+
+/// Launch a window with the given function for computing a fragment color, rendering
+/// progressively, with each pass starting tiles in the given order.
+///
+/// See `fragment_progressive`. Ordering tiles such as `TileOrder::Spiral` shows the most
+/// interesting part of the image first, when that's the center. `TileOrder::NearCursor` is
+/// the same as `TileOrder::CenterOut` here.
+///
+/// This uses rayon for parallelism.
+pub fn fragment_progressive_ordered<F>(
+    x_size: usize,
+    y_size: usize,
+    order: TileOrder,
+    fragment: F,
+)
+    where
+        F: Send + Sync + 'static,
+        F: Fn(Vec2<i32>) -> Rgba<u8> {
+
+    // open window, drawing thread
+    open_window(
+        x_size,
+        y_size,
+        move |ctx| {
+            // colors computed so far, which finer passes reuse
+            let mut samples: Vec<Option<Rgba<u8>>> = vec![None; x_size * y_size];
+
+            let mut tiles: Vec<Rect<usize, usize>> = Vec::new();
+            for y in (0..y_size).step_by(PROGRESSIVE_TILE_SIZE) {
+                for x in (0..x_size).step_by(PROGRESSIVE_TILE_SIZE) {
+                    let w = usize::min(PROGRESSIVE_TILE_SIZE, x_size - x);
+                    let h = usize::min(PROGRESSIVE_TILE_SIZE, y_size - y);
+                    tiles.push(Rect::new(x, y, w, h));
+                }
+            }
+            order.sort(&mut tiles, x_size, y_size);
+
+            for &block in &PROGRESSIVE_BLOCK_SIZES {
+                if ctx.shutdown.is_shutdown() {
+                    return;
+                }
+
+                // parallel iter over tiles, started in order
+                let new_samples: Vec<(usize, Rgba<u8>)> = tiles.iter()
+                    .par_bridge()
+                    .flat_map(|tile| {
+                        // stop computing new samples if the window closes
+                        if ctx.shutdown.is_shutdown() {
+                            return Vec::new();
+                        }
+
+                        let mut rgba: Vec<[u8; 4]> = vec![[0; 4]; tile.w * tile.h];
+                        let mut computed: Vec<(usize, Rgba<u8>)> = Vec::new();
+
+                        for y_start in (0..tile.h).step_by(block) {
+                            for x_start in (0..tile.w).step_by(block) {
+                                let y_end = usize::min(y_start + block, tile.h);
+                                let x_end = usize::min(x_start + block, tile.w);
+
+                                // sample the block's top-left pixel
+                                let (x, y) = (tile.x + x_start, tile.y + y_start);
+                                let i = y * x_size + x;
+                                let color = samples[i].unwrap_or_else(|| {
+                                    let color = fragment(Vec2::new(x as i32, y as i32));
+                                    computed.push((i, color));
+                                    color
+                                });
+
+                                // fill the block
+                                for y in y_start..y_end {
+                                    for x in x_start..x_end {
+                                        rgba[y * tile.w + x] = [color.r, color.g, color.b, color.a];
+                                    }
+                                }
+                            }
+                        }
+
+                        ctx.push(PaintBatch::new(
+                            tile.x, tile.y,
+                            tile.w, tile.h,
+                            rgba,
+                        ));
+                        computed
+                    })
+                    .collect();
+
+                for (i, color) in new_samples {
+                    samples[i] = Some(color);
+                }
+            }
+        },
     );
-}
\ No newline at end of file
+}
+
+/// Options for how `fragment_adaptive` refines the canvas.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct AdaptiveOptions {
+    /// Size of the blocks of the first, coarsest pass, rounded up to a power of two. Defaults
+    /// to 16.
+    pub coarse_block: usize,
+    /// Largest difference in any channel between a block's corners for it to count as
+    /// smooth, and not be refined. Defaults to 8.
+    pub threshold: u8,
+}
+
+impl Default for AdaptiveOptions {
+    fn default() -> Self {
+        AdaptiveOptions {
+            coarse_block: 16,
+            threshold: 8,
+        }
+    }
+}
+
+/// Launch a window with the given function for computing a fragment color, spending samples
+/// where the image changes rapidly.
+///
+/// Like `fragment_progressive`, the canvas is first filled with coarse blocks from one
+/// sample each, which are then halved in size each pass. But a block is only split if its
+/// color differs from the samples at its neighbors' corners by more than the threshold.
+/// Smooth blocks are instead filled by bilinearly interpolating between their corners, and
+/// never sampled further. This saves most of the work of fragment functions whose images
+/// are mostly smooth, such as fractals and path tracers, at the risk of missing details
+/// smaller than a block.
+///
+/// This uses rayon for parallelism.
+pub fn fragment_adaptive<F>(
+    x_size: usize,
+    y_size: usize,
+    options: AdaptiveOptions,
+    fragment: F,
+)
+    where
+        F: Send + Sync + 'static,
+        F: Fn(Vec2<i32>) -> Rgba<u8> {
+
+    let coarse = options.coarse_block.max(1).next_power_of_two();
+
+    // open window, drawing thread
+    open_window(
+        x_size,
+        y_size,
+        move |ctx| {
+            // colors computed so far, by pixel
+            let mut samples: Vec<Option<Rgba<u8>>> = vec![None; x_size * y_size];
+            // block corners which are smooth, and won't be refined
+            let mut settled: Vec<bool> = vec![false; x_size * y_size];
+
+            // first pass: fill each coarse block with its top-left sample
+            let new_samples: Vec<(usize, Rgba<u8>)> = (0..y_size.div_ceil(coarse))
+                .into_par_iter()
+                .flat_map(|block_y| {
+                    let y = block_y * coarse;
+                    let mut computed = Vec::new();
+                    for x in (0..x_size).step_by(coarse) {
+                        if ctx.shutdown.is_shutdown() {
+                            break;
+                        }
+                        let color = fragment(Vec2::new(x as i32, y as i32));
+                        computed.push((y * x_size + x, color));
+                        ctx.push(fill_block(
+                            (x, y), (x_size, y_size), coarse,
+                            |_, _| color,
+                        ));
+                    }
+                    computed
+                })
+                .collect();
+            for (i, color) in new_samples {
+                samples[i] = Some(color);
+            }
+
+            let mut parent = coarse;
+            while parent > 1 && !ctx.shutdown.is_shutdown() {
+                let child = parent / 2;
+                let samples_ref = &samples;
+                let settled_ref = &settled;
+
+                // samples computed in a row of parent blocks, and the blocks found smooth
+                type RowResult = (Vec<(usize, Rgba<u8>)>, Vec<usize>);
+
+                // parallel iter over rows of parent blocks
+                let results: Vec<RowResult> =
+                    (0..y_size.div_ceil(parent))
+                        .into_par_iter()
+                        .map(|block_y| {
+                            let y = block_y * parent;
+                            let mut computed = Vec::new();
+                            let mut smooth = Vec::new();
+                            for x in (0..x_size).step_by(parent) {
+                                if ctx.shutdown.is_shutdown() {
+                                    break;
+                                }
+                                let i = y * x_size + x;
+                                if settled_ref[i] {
+                                    continue;
+                                }
+
+                                // corner samples, reusing our own past the edges
+                                let own = match samples_ref[i] {
+                                    Some(color) => color,
+                                    None => continue,
+                                };
+                                let corner = |cx: usize, cy: usize| {
+                                    if cx < x_size && cy < y_size {
+                                        samples_ref[cy * x_size + cx].unwrap_or(own)
+                                    } else {
+                                        own
+                                    }
+                                };
+                                let corners = [
+                                    own,
+                                    corner(x + parent, y),
+                                    corner(x, y + parent),
+                                    corner(x + parent, y + parent),
+                                ];
+                                let diff = corners.iter()
+                                    .map(|c| channel_diff(*c, own))
+                                    .max()
+                                    .unwrap();
+
+                                if diff <= options.threshold {
+                                    // smooth, so interpolate between the corners
+                                    smooth.push(i);
+                                    let size = parent as f32;
+                                    let [c00, c10, c01, c11] = corners;
+                                    let (c00, c10, c01, c11) = (
+                                        c00.map(|n| n as f32), c10.map(|n| n as f32),
+                                        c01.map(|n| n as f32), c11.map(|n| n as f32),
+                                    );
+                                    ctx.push(fill_block(
+                                        (x, y), (x_size, y_size), parent,
+                                        |dx, dy| {
+                                            let t = Vec2::new(dx as f32, dy as f32) / size;
+                                            Rgba::lerp(
+                                                Rgba::lerp(c00, c10, t.x),
+                                                Rgba::lerp(c01, c11, t.x),
+                                                t.y,
+                                            ).map(|n| n.round() as u8)
+                                        },
+                                    ));
+                                    continue;
+                                }
+
+                                // detailed, so sample each child's top-left
+                                let mut quadrants = [own; 4];
+                                for (q, quadrant) in quadrants.iter_mut().enumerate() {
+                                    let cx = x + (q % 2) * child;
+                                    let cy = y + (q / 2) * child;
+                                    if cx >= x_size || cy >= y_size {
+                                        continue;
+                                    }
+                                    let ci = cy * x_size + cx;
+                                    *quadrant = samples_ref[ci].unwrap_or_else(|| {
+                                        let color = fragment(Vec2::new(cx as i32, cy as i32));
+                                        computed.push((ci, color));
+                                        color
+                                    });
+                                }
+                                ctx.push(fill_block(
+                                    (x, y), (x_size, y_size), parent,
+                                    |dx, dy| quadrants[dy / child * 2 + dx / child],
+                                ));
+                            }
+                            (computed, smooth)
+                        })
+                        .collect();
+
+                for (computed, smooth) in results {
+                    for (i, color) in computed {
+                        samples[i] = Some(color);
+                    }
+                    // settle the children of smooth blocks
+                    for i in smooth {
+                        let (x, y) = (i % x_size, i / x_size);
+                        for q in 0..4 {
+                            let cx = x + (q % 2) * child;
+                            let cy = y + (q / 2) * child;
+                            if cx < x_size && cy < y_size {
+                                settled[cy * x_size + cx] = true;
+                            }
+                        }
+                    }
+                }
+                parent = child;
+            }
+        },
+    );
+}
+
+/// Largest difference between two colors in any channel.
+fn channel_diff(a: Rgba<u8>, b: Rgba<u8>) -> u8 {
+    let d = (a.map(|n| n as i16) - b.map(|n| n as i16)).map(|n| n.unsigned_abs() as u8);
+    d.r.max(d.g).max(d.b).max(d.a)
+}
+
+/// Batch filling a square block of the canvas, clipped to the canvas, with a function of
+/// each pixel's offset within the block.
+fn fill_block(
+    (x, y): (usize, usize),
+    (x_size, y_size): (usize, usize),
+    block: usize,
+    color: impl Fn(usize, usize) -> Rgba<u8>,
+) -> PaintBatch {
+    let w = usize::min(block, x_size - x);
+    let h = usize::min(block, y_size - y);
+    let mut rgba: Vec<[u8; 4]> = Vec::with_capacity(w * h);
+    for dy in 0..h {
+        for dx in 0..w {
+            let c = color(dx, dy);
+            rgba.push([c.r, c.g, c.b, c.a]);
+        }
+    }
+    PaintBatch::new(x, y, w, h, rgba)
+}
+
+/// Launch a window which re-runs the given fragment function every frame, at up to the
+/// given frames per second.
+///
+/// The fragment function is passed the frame index, which starts at 0 and increments each
+/// frame, and each frame overwrites the entire canvas. The window is double-buffered, so
+/// only completed frames are displayed.
+///
+/// Space pauses and resumes the animation, and while it's paused, period steps forward by
+/// one frame.
+///
+/// If `fps` isn't positive, or is NaN, frames are rendered as fast as possible.
+///
+/// Every frame is rendered on the same persistent `TileRenderer`.
+pub fn animate<F>(
+    x_size: usize,
+    y_size: usize,
+    fps: f64,
+    fragment: F,
+)
+    where
+        F: Send + Sync + 'static,
+        F: Fn(u64, Vec2<i32>) -> Rgba<u8> {
+
+    // delegate
+    fragment_stateful_mut(
+        x_size,
+        y_size,
+        fps,
+        0,
+        |frame, _| *frame += 1,
+        move |xy, &frame| fragment(frame, xy),
+    )
+}
+
+/// Launch a window which re-runs the given fragment function every frame, at up to the
+/// given frames per second, passing it the time.
+///
+/// The fragment function is passed the animation time, in seconds since the first frame,
+/// and each frame overwrites the entire canvas. The window is double-buffered, so only
+/// completed frames are displayed.
+///
+/// Space pauses and resumes the animation, and while it's paused, period steps forward by
+/// one frame.
+///
+/// If `fps` isn't positive, or is NaN, frames are rendered as fast as possible.
+///
+/// Every frame is rendered on the same persistent `TileRenderer`.
+pub fn fragment_animated<F>(
+    x_size: usize,
+    y_size: usize,
+    fps: f64,
+    fragment: F,
+)
+    where
+        F: Send + Sync + 'static,
+        F: Fn(Vec2<i32>, f32) -> Rgba<u8> {
+
+    // delegate
+    fragment_animated_tiled(
+        x_size,
+        y_size,
+        fps,
+        TileOrder::Scanline,
+        fragment,
+    )
+}
+
+/// Launch a window which re-runs the given fragment function every frame, at up to the
+/// given frames per second, passing it the time, on a persistent `TileRenderer`.
+///
+/// This is like `fragment_animated`, but each frame's tiles are started in the given order.
+/// With `TileOrder::NearCursor`, tiles nearest the mouse cursor are rendered first.
+///
+/// Space pauses and resumes the animation, and while it's paused, period steps forward by
+/// one frame.
+///
+/// If `fps` isn't positive, or is NaN, frames are rendered as fast as possible.
+pub fn fragment_animated_tiled<F>(
+    x_size: usize,
+    y_size: usize,
+    fps: f64,
+    order: TileOrder,
+    fragment: F,
+)
+    where
+        F: Send + Sync + 'static,
+        F: Fn(Vec2<i32>, f32) -> Rgba<u8> {
+
+    let fragment = Arc::new(fragment);
+    let renderer = TileRenderer::new(0);
+    let tile_size = FragOptions::default().tile_size;
+    let mut cursor: Option<Vec2<f32>> = None;
+
+    frame_loop(
+        x_size,
+        y_size,
+        fps,
+        move |ctx, events, time| {
+            for event in events {
+                if let InputEvent::MouseMove { pos } = *event {
+                    cursor = Some(pos);
+                }
+            }
+            let order = match (order, cursor) {
+                (TileOrder::NearCursor, Some(pos)) => TileOrder::Near(pos),
+                (order, _) => order,
+            };
+
+            let fragment = Arc::clone(&fragment);
+            let frame = Arc::new(move |xy: Vec2<i32>| fragment(xy, time));
+            renderer.render(x_size, y_size, tile_size, order, ctx, &frame);
+        },
+    );
+}
+
+/// Launch a window which renders the given fragment function every frame, at up to the
+/// given frames per second, with state which evolves between frames.
+///
+/// Before each frame but the first, the update function is given mutable access to the
+/// state, along with the number of seconds since the previous frame. Then the fragment
+/// function renders the entire canvas with read-access to the state. The window is
+/// double-buffered, so only completed frames are displayed.
+///
+/// Space pauses and resumes the animation, and while it's paused, period steps forward by
+/// one frame.
+///
+/// If `fps` isn't positive, or is NaN, frames are rendered as fast as possible.
+///
+/// Every frame is rendered on the same persistent `TileRenderer`.
+pub fn fragment_stateful_mut<S, U, F>(
+    x_size: usize,
+    y_size: usize,
+    fps: f64,
+    state: S,
+    mut update: U,
+    fragment: F,
+)
+    where
+        S: Send + Sync + 'static,
+        U: FnMut(&mut S, f32) + Send + 'static,
+        F: Send + Sync + 'static,
+        F: Fn(Vec2<i32>, &S) -> Rgba<u8> {
+
+    let mut state = Arc::new(state);
+    let fragment = Arc::new(fragment);
+    let renderer = TileRenderer::new(0);
+    let tile_size = FragOptions::default().tile_size;
+
+    // previous frame's time, for computing elapsed time
+    let mut prev: Option<f32> = None;
+
+    frame_loop(
+        x_size,
+        y_size,
+        fps,
+        move |ctx, _, time| {
+            // update with the time since the previous frame
+            if let Some(prev) = prev {
+                // the renderer drops every tile's handle to the state before returning
+                let state = Arc::get_mut(&mut state)
+                    .expect("state still shared after rendering");
+                update(state, time - prev);
+            }
+            prev = Some(time);
+
+            let state = Arc::clone(&state);
+            let fragment = Arc::clone(&fragment);
+            let frame = Arc::new(move |xy: Vec2<i32>| fragment(xy, &state));
+            renderer.render(x_size, y_size, tile_size, TileOrder::Scanline, ctx, &frame);
+        },
+    );
+}
+
+/// Inputs to an interactive fragment function.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FragContext {
+    /// Coordinates of the fragment.
+    pub xy: Vec2<i32>,
+    /// Most recent mouse cursor position, in canvas pixel coordinates.
+    pub mouse: Vec2<f32>,
+    /// Animation time, in seconds since the first frame.
+    pub time: f32,
+    /// Frame index, which starts at 0 and increments each frame.
+    pub frame: u64,
+}
+
+/// Launch a window which re-runs the given fragment function every frame, at up to the
+/// given frames per second, with access to the mouse position and time.
+///
+/// The window is double-buffered, so only completed frames are displayed.
+///
+/// Space pauses and resumes the animation, and while it's paused, period steps forward by
+/// one frame.
+///
+/// If `fps` isn't positive, or is NaN, frames are rendered as fast as possible.
+///
+/// Every frame is rendered on the same persistent `TileRenderer`.
+pub fn fragment_interactive<F>(
+    x_size: usize,
+    y_size: usize,
+    fps: f64,
+    fragment: F,
+)
+    where
+        F: Send + Sync + 'static,
+        F: Fn(FragContext) -> Rgba<u8> {
+
+    let fragment = Arc::new(fragment);
+    let renderer = TileRenderer::new(0);
+    let tile_size = FragOptions::default().tile_size;
+    let mut mouse: Vec2<f32> = Vec2::zero();
+    let mut frame: u64 = 0;
+
+    frame_loop(
+        x_size,
+        y_size,
+        fps,
+        move |ctx, events, time| {
+            // catch up on input
+            for event in events {
+                if let InputEvent::MouseMove { pos } = *event {
+                    mouse = pos;
+                }
+            }
+
+            let fragment = Arc::clone(&fragment);
+            let frame_fragment = Arc::new(move |xy| fragment(FragContext {
+                xy,
+                mouse,
+                time,
+                frame,
+            }));
+            renderer.render(x_size, y_size, tile_size, TileOrder::Scanline, ctx, &frame_fragment);
+            frame += 1;
+        },
+    );
+}
+
+/// Open a double-buffered window, and call the given closure to render each frame, at up to
+/// the given frames per second.
+///
+/// The closure is passed the input events received since the previous frame, and the
+/// animation time in seconds. Space pauses and resumes the animation, and while paused,
+/// period steps forward by one frame. The time doesn't advance while paused.
+///
+/// If `fps` isn't positive, or is NaN, frames are rendered as fast as possible.
+pub(crate) fn frame_loop<R>(
+    x_size: usize,
+    y_size: usize,
+    fps: f64,
+    mut render_frame: R,
+)
+    where
+        R: FnMut(&DrawContext, &[InputEvent], f32) + Send + 'static {
+
+    // (a tiny positive fps would overflow the duration, so check the reciprocal)
+    let secs = 1.0 / fps;
+    let frame_time =
+        if secs > 0.0 && secs.is_finite() { Duration::from_secs_f64(secs) }
+        else { Duration::from_secs(0) };
+
+    // open window, drawing thread
+    Window::build()
+        .size(x_size, y_size)
+        .double_buffered(true)
+        .spawn(move |ctx| {
+            let mut playback = Playback::new(frame_time);
+            let mut events: Vec<InputEvent> = Vec::new();
+
+            while !ctx.shutdown.is_shutdown() {
+                let start = Instant::now();
+
+                // while paused, block until there's input to act on
+                if playback.is_stopped() {
+                    match ctx.input.recv() {
+                        Ok(event) => {
+                            playback.input(&event);
+                            events.push(event);
+                        },
+                        Err(_) => break,
+                    }
+                }
+                for event in ctx.input.try_iter() {
+                    playback.input(&event);
+                    events.push(event);
+                }
+
+                if let Some(time) = playback.advance(start) {
+                    render_frame(&ctx, &events, time);
+                    ctx.push(PaintCmd::EndFrame);
+                    events.clear();
+                }
+
+                // sleep off the rest of the frame
+                let elapsed = start.elapsed();
+                if elapsed < frame_time {
+                    thread::sleep(frame_time - elapsed);
+                }
+            }
+        });
+}
+
+/// Launch a window which re-runs the given fragment function every frame, at up to the
+/// given frames per second, passing it live-tweakable parameters and the time.
+///
+/// The parameters are adjusted with the keyboard, as described on `Params`, and unless
+/// disabled, their values are drawn over the top-left corner of the canvas. The window is
+/// double-buffered, so only completed frames are displayed.
+///
+/// Space pauses and resumes the animation, and while it's paused, period steps forward by
+/// one frame.
+///
+/// If `fps` isn't positive, or is NaN, frames are rendered as fast as possible.
+///
+/// Every frame is rendered on the same persistent `TileRenderer`.
+pub fn fragment_params<F>(
+    x_size: usize,
+    y_size: usize,
+    fps: f64,
+    params: Params,
+    fragment: F,
+)
+    where
+        F: Send + Sync + 'static,
+        F: Fn(Vec2<i32>, &Params, f32) -> Rgba<u8> {
+
+    let mut params = Arc::new(params);
+    let fragment = Arc::new(fragment);
+    let renderer = TileRenderer::new(0);
+    let tile_size = FragOptions::default().tile_size;
+
+    frame_loop(
+        x_size,
+        y_size,
+        fps,
+        move |ctx, events, time| {
+            // catch up on input
+            {
+                // the renderer drops every tile's handle to the params before returning
+                let params = Arc::get_mut(&mut params)
+                    .expect("params still shared after rendering");
+                for event in events {
+                    params.input(event);
+                }
+            }
+
+            let frame_params = Arc::clone(&params);
+            let fragment = Arc::clone(&fragment);
+            let frame = Arc::new(move |xy: Vec2<i32>| fragment(xy, &frame_params, time));
+            renderer.render(x_size, y_size, tile_size, TileOrder::Scanline, ctx, &frame);
+            if params.has_overlay() {
+                ctx.wait_for_capacity();
+                params.draw_overlay(&ctx.paint, Vec2::new(4, 4), Rgba::white());
+            }
+        },
+    );
+}
+
+/// Animation clock for `frame_loop`, which can be paused and stepped.
+struct Playback {
+    paused: bool,
+    /// Frames to step forward while paused.
+    steps: u32,
+    /// Animation time, in seconds.
+    time: f32,
+    /// When the previous frame was rendered, if playing.
+    prev: Option<Instant>,
+    /// Time to advance by when stepping.
+    step_time: f32,
+}
+
+impl Playback {
+    fn new(frame_time: Duration) -> Self {
+        let step_time = frame_time.as_secs_f32();
+        Playback {
+            paused: false,
+            steps: 0,
+            time: 0.0,
+            prev: None,
+            // uncapped animations step by a 60 fps frame
+            step_time: if step_time > 0.0 { step_time } else { 1.0 / 60.0 },
+        }
+    }
+
+    /// Handle the playback keybindings.
+    fn input(&mut self, event: &InputEvent) {
+        if let InputEvent::KeyPress { key: Some(key), .. } = *event {
+            match key {
+                Key::Space => {
+                    self.paused = !self.paused;
+                    self.steps = 0;
+                    self.prev = None;
+                },
+                Key::Period if self.paused => self.steps += 1,
+                _ => (),
+            }
+        }
+    }
+
+    /// Whether no frames will be rendered until more input arrives.
+    fn is_stopped(&self) -> bool {
+        self.paused && self.steps == 0
+    }
+
+    /// Advance the clock for a frame starting now, and get the frame's time, or `None` if
+    /// no frame should be rendered.
+    fn advance(&mut self, now: Instant) -> Option<f32> {
+        if self.paused {
+            if self.steps == 0 {
+                return None;
+            }
+            self.steps -= 1;
+            self.time += self.step_time;
+        } else {
+            if let Some(prev) = self.prev {
+                self.time += (now - prev).as_secs_f32();
+            }
+            self.prev = Some(now);
+        }
+        Some(self.time)
+    }
+}
+
+/// Run the given fragment function over an image, without opening a window.
+///
+/// This never touches OpenGL, so it works headlessly. This uses rayon for parallelism.
+pub fn fragment_to_image<F>(
+    x_size: usize,
+    y_size: usize,
+    fragment: F,
+) -> RgbaImage
+    where
+        F: Fn(Vec2<i32>) -> Rgba<u8> + Sync {
+
+    let mut buf: Vec<u8> = vec![0; x_size * y_size * 4];
+    if buf.is_empty() {
+        return RgbaImage::new(x_size as u32, y_size as u32);
+    }
+
+    // parallel iter over rows
+    buf.par_chunks_mut(x_size * 4)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (x, texel) in row.chunks_mut(4).enumerate() {
+                let color = fragment(Vec2::new(x as i32, y as i32));
+                texel.copy_from_slice(&[color.r, color.g, color.b, color.a]);
+            }
+        });
+
+    RgbaImage::from_raw(x_size as u32, y_size as u32, buf)
+        .expect("image buffer size mismatch")
+}
+
+/// Render every fragment of the canvas once, in parallel, painting each tile as a batch.
+///
+/// Remaining tiles are skipped if the window closes.
+pub(crate) fn render<F>(
+    x_size: usize,
+    y_size: usize,
+    options: FragOptions,
+    ctx: &DrawContext,
+    fragment: F,
+)
+    where
+        F: Fn(Vec2<i32>) -> Rgba<u8> + Sync {
+
+    let canvas = Rect::new(0, 0, x_size, y_size);
+    render_region(x_size, y_size, canvas, options, ctx, &fragment);
+}
+
+/// Render every fragment in a region of the canvas, in parallel, painting each tile as a
+/// batch.
+///
+/// The region is clipped to the canvas. Remaining tiles are skipped if the window closes.
+fn render_region<F>(
+    x_size: usize,
+    y_size: usize,
+    region: Rect<usize, usize>,
+    options: FragOptions,
+    ctx: &DrawContext,
+    fragment: &F,
+)
+    where
+        F: Fn(Vec2<i32>) -> Rgba<u8> + Sync {
+
+    render_region_cancellable(x_size, y_size, region, options, ctx, fragment, &|| false);
+}
+
+/// Render every fragment in a region of the canvas, like `render_region`, but also skip the
+/// remaining tiles once `cancelled` returns true, which is checked before each tile.
+fn render_region_cancellable<F, C>(
+    x_size: usize,
+    y_size: usize,
+    region: Rect<usize, usize>,
+    options: FragOptions,
+    ctx: &DrawContext,
+    fragment: &F,
+    cancelled: &C,
+)
+    where
+        F: Fn(Vec2<i32>) -> Rgba<u8> + Sync,
+        C: Fn() -> bool + Sync {
+
+    // functions of whole pixels can't be evaluated between them, so can't be supersampled
+    // (positions are never negative, so truncation rounds down)
+    let options = FragOptions { ssaa: 1, ..options };
+    let fragment = |pos: Vec2<f32>| fragment(pos.map(|n| n as i32));
+    render_samples(x_size, y_size, region, options, ctx, &fragment, cancelled);
+}
+
+/// Render every fragment in a region of the canvas, like `render_region_cancellable`, with
+/// a fragment function of positions in canvas space, which is supersampled if enabled.
+///
+/// Without supersampling, the fragment function is called at pixel centers.
+fn render_samples<F, C>(
+    x_size: usize,
+    y_size: usize,
+    region: Rect<usize, usize>,
+    options: FragOptions,
+    ctx: &DrawContext,
+    fragment: &F,
+    cancelled: &C,
+)
+    where
+        F: Fn(Vec2<f32>) -> Rgba<u8> + Sync,
+        C: Fn() -> bool + Sync {
+
+    // clip the region to the canvas
+    let x_min = usize::min(region.x, x_size);
+    let y_min = usize::min(region.y, y_size);
+    let x_max = usize::min(region.x.saturating_add(region.w), x_size);
+    let y_max = usize::min(region.y.saturating_add(region.h), y_size);
+
+    let tile_size = options.tile_size.max(1);
+    let x_tiles = (x_max - x_min).div_ceil(tile_size);
+    let y_tiles = (y_max - y_min).div_ceil(tile_size);
+
+    let render_tile = |tile: usize| -> Option<PaintBatch> {
+        if ctx.shutdown.is_shutdown() || cancelled() {
+            return None;
+        }
+
+        let x_start = x_min + (tile % x_tiles) * tile_size;
+        let y_start = y_min + (tile / x_tiles) * tile_size;
+        let x_end = usize::min(x_start + tile_size, x_max);
+        let y_end = usize::min(y_start + tile_size, y_max);
+
+        // paint the tile into a single batch
+        let rgba =
+            if options.ssaa > 1 {
+                supersample_tile(
+                    (x_start, y_start),
+                    (x_end, y_end),
+                    (x_size, y_size),
+                    options,
+                    fragment,
+                )
+            } else {
+                let mut rgba: Vec<[u8; 4]> = Vec::with_capacity(
+                    (x_end - x_start) * (y_end - y_start)
+                );
+                for y in y_start..y_end {
+                    for x in x_start..x_end {
+                        let color = fragment(Vec2::new(x as f32 + 0.5, y as f32 + 0.5));
+                        rgba.push([color.r, color.g, color.b, color.a]);
+                    }
+                }
+                rgba
+            };
+        Some(PaintBatch::new(
+            x_start, y_start,
+            x_end - x_start, y_end - y_start,
+            rgba,
+        ))
+    };
+    let tiles = 0..x_tiles * y_tiles;
+
+    if options.threads == Some(1) {
+        // sequentially, which is also in order
+        for batch in tiles.filter_map(render_tile) {
+            ctx.push(batch);
+        }
+        return;
+    }
+
+    // parallel iter over tiles
+    let render_all = || if options.deterministic_order {
+        let batches: Vec<Option<PaintBatch>> = tiles.into_par_iter()
+            .map(render_tile)
+            .collect();
+        for batch in batches.into_iter().flatten() {
+            ctx.push(batch);
+        }
+    } else {
+        tiles.into_par_iter()
+            .filter_map(render_tile)
+            .for_each(|batch| ctx.push(batch));
+    };
+    match options.threads {
+        Some(threads) if threads > 1 => with_pool(threads, render_all),
+        _ => render_all(),
+    }
+}
+
+thread_local! {
+    /// Thread pools built by `with_pool` on this thread, by number of threads.
+    static POOLS: RefCell<Vec<(usize, Arc<ThreadPool>)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Run a closure in a thread pool with the given number of threads, building it the first
+/// time it's needed on this thread.
+///
+/// Falls back to the global pool if the pool can't be built.
+fn with_pool<R, F>(threads: usize, f: F) -> R
+    where
+        F: FnOnce() -> R + Send,
+        R: Send {
+
+    let pool = POOLS.with(|pools| {
+        let mut pools = pools.borrow_mut();
+        if let Some((_, pool)) = pools.iter().find(|&&(n, _)| n == threads) {
+            return Some(pool.clone());
+        }
+        match ThreadPoolBuilder::new().num_threads(threads).build() {
+            Ok(pool) => {
+                let pool = Arc::new(pool);
+                pools.push((threads, pool.clone()));
+                Some(pool)
+            },
+            Err(e) => {
+                error!("failed to build thread pool of {} threads: {}", threads, e);
+                None
+            },
+        }
+    });
+    match pool {
+        Some(pool) => pool.install(f),
+        None => f(),
+    }
+}
+
+/// Render a tile of pixels from `start` to `end` by supersampling and filtering.
+///
+/// The fragment function is called at the center of each of the `ssaa * ssaa` subpixels of
+/// each pixel, in canvas space.
+fn supersample_tile<F>(
+    start: (usize, usize),
+    end: (usize, usize),
+    canvas_size: (usize, usize),
+    options: FragOptions,
+    fragment: &F,
+) -> Vec<[u8; 4]>
+    where
+        F: Fn(Vec2<f32>) -> Rgba<u8> {
+
+    let ssaa = options.ssaa;
+    let filter = options.ssaa_filter;
+    let margin = filter.margin(ssaa);
+
+    // range of samples which the tile's pixels read, clipped to the canvas
+    let sx_start = (start.0 * ssaa).saturating_sub(margin);
+    let sy_start = (start.1 * ssaa).saturating_sub(margin);
+    let sx_end = usize::min(end.0 * ssaa + margin, canvas_size.0 * ssaa);
+    let sy_end = usize::min(end.1 * ssaa + margin, canvas_size.1 * ssaa);
+    let sx_size = sx_end - sx_start;
+
+    let mut samples: Vec<Rgba<f32>> = Vec::with_capacity(sx_size * (sy_end - sy_start));
+    for sy in sy_start..sy_end {
+        for sx in sx_start..sx_end {
+            let pos = Vec2::new(sx as f32 + 0.5, sy as f32 + 0.5) / ssaa as f32;
+            samples.push(fragment(pos).map(|c| c as f32));
+        }
+    }
+
+    // range of samples which a pixel reads, clipped to the canvas
+    let footprint = |p: usize, s_end: usize|
+        (p * ssaa).saturating_sub(margin)..usize::min((p + 1) * ssaa + margin, s_end);
+
+    // filter samples down to pixels
+    // (normalizing by the total weight, which is smaller at the canvas edges)
+    let mut rgba: Vec<[u8; 4]> = Vec::with_capacity((end.0 - start.0) * (end.1 - start.1));
+    for y in start.1..end.1 {
+        for x in start.0..end.0 {
+            let center = Vec2::new(x as f32 + 0.5, y as f32 + 0.5) * ssaa as f32;
+
+            let mut sum = Rgba::<f32>::zero();
+            let mut total = 0.0;
+            for sy in footprint(y, sy_end) {
+                let wy = filter.weight((sy as f32 + 0.5 - center.y).abs(), ssaa);
+                for sx in footprint(x, sx_end) {
+                    let wx = filter.weight((sx as f32 + 0.5 - center.x).abs(), ssaa);
+                    let w = wx * wy;
+                    sum += samples[(sy - sy_start) * sx_size + (sx - sx_start)] * w;
+                    total += w;
+                }
+            }
+
+            let color = (sum / total).map(|c| c.round().clamp(0.0, 255.0) as u8);
+            rgba.push([color.r, color.g, color.b, color.a]);
+        }
+    }
+    rgba
+}
@@ -0,0 +1,315 @@
+use crate::{
+    camera::Camera,
+    mesh::Mesh,
+    shade::PbrMaterial,
+    texture::Texture,
+};
+
+use std::{
+    fmt,
+    error::Error,
+    path::Path,
+    collections::HashMap,
+};
+
+use ::gltf::{
+    buffer,
+    image::{self as gltf_image, Format},
+    mesh::Mode,
+    camera::Projection as GltfProjection,
+};
+use image::RgbaImage;
+use vek::*;
+
+/// Far plane distance, as a multiple of the near plane, for cameras with an infinite far
+/// plane, which `Camera` can't represent.
+const INFINITE_FAR: f32 = 10_000.0;
+
+/// Scene loaded from a glTF file.
+///
+/// Each glTF mesh primitive becomes one `GltfMesh`, in its own local space, and each node
+/// which references a mesh places its primitives in the world as `MeshInstance`s. The node
+/// hierarchy itself is kept in `nodes`.
+#[derive(Clone, Debug, Default)]
+pub struct GltfScene {
+    pub meshes: Vec<GltfMesh>,
+    /// Base color textures, indexed by `GltfMesh::base_color_texture`.
+    pub textures: Vec<Texture>,
+    pub instances: Vec<MeshInstance>,
+    /// Cameras placed by the scene's nodes, in world space, in node order.
+    pub cameras: Vec<Camera>,
+    /// The scene's nodes, each before its descendants.
+    pub nodes: Vec<GltfNode>,
+    /// Indices into `nodes` of the nodes without a parent.
+    pub roots: Vec<usize>,
+}
+
+/// Mesh with the material from a glTF file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfMesh {
+    pub mesh: Mesh,
+    /// Material, where the base color is the base color factor, which should be multiplied
+    /// by the base color texture, if any.
+    pub material: PbrMaterial,
+    /// Index into `GltfScene::textures` of the base color texture, in sRGB.
+    pub base_color_texture: Option<usize>,
+}
+
+/// Placement of a mesh in the world.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MeshInstance {
+    /// Index into `GltfScene::meshes`.
+    pub mesh: usize,
+    /// Model-to-world matrix, combining the transforms of the node and its ancestors.
+    pub transform: Mat4<f32>,
+}
+
+/// Node of a glTF scene, with a transform relative to its parent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfNode {
+    pub name: Option<String>,
+    /// Local-to-parent matrix.
+    pub transform: Mat4<f32>,
+    /// Indices into `GltfScene::meshes` of the primitives of the node's mesh.
+    pub meshes: Vec<usize>,
+    /// Camera, in the node's local space.
+    pub camera: Option<Camera>,
+    /// Indices into `GltfScene::nodes`.
+    pub children: Vec<usize>,
+}
+
+impl GltfScene {
+    /// Each instance's mesh transformed into world space, such as for building a BVH of
+    /// all the scene's triangles.
+    pub fn world_meshes(&self) -> Vec<Mesh> {
+        self.instances
+            .iter()
+            .map(|instance| self.meshes[instance.mesh].mesh.transformed(instance.transform))
+            .collect()
+    }
+}
+
+/// Error loading a glTF file.
+#[derive(Debug)]
+pub enum GltfError {
+    /// Failed to read or parse the file or its buffers and images.
+    Gltf(::gltf::Error),
+    /// The file uses a feature which isn't supported.
+    Unsupported(String),
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GltfError::Gltf(e) => write!(f, "failed to load gltf file: {}", e),
+            GltfError::Unsupported(msg) => write!(f, "unsupported gltf feature: {}", msg),
+        }
+    }
+}
+
+impl Error for GltfError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GltfError::Gltf(e) => Some(e),
+            GltfError::Unsupported(_) => None,
+        }
+    }
+}
+
+impl From<::gltf::Error> for GltfError {
+    fn from(e: ::gltf::Error) -> Self {
+        GltfError::Gltf(e)
+    }
+}
+
+/// Load a glTF 2.0 file, either `.gltf` with its external or embedded buffers, or binary
+/// `.glb`.
+///
+/// Loads the default scene, or the first if there is no default. Supports triangle mesh
+/// primitives with positions, normals, and first texture coordinates, node transforms,
+/// metallic-roughness materials with base color textures, and cameras. Primitives in other
+/// modes, skins, animations, morph targets, and other textures are ignored.
+pub fn load_gltf(path: impl AsRef<Path>) -> Result<GltfScene, GltfError> {
+    let (document, buffers, images) = ::gltf::import(path)?;
+
+    // only convert images used as base color textures, so other textures in formats which
+    // aren't supported don't fail the load
+    let mut texture_indices = HashMap::new();
+    let mut images: Vec<Option<gltf_image::Data>> = images.into_iter().map(Some).collect();
+    let mut textures = Vec::new();
+    for material in document.materials() {
+        let info = match material.pbr_metallic_roughness().base_color_texture() {
+            Some(info) => info,
+            None => continue,
+        };
+        let image = info.texture().source().index();
+        if let Some(data) = images[image].take() {
+            texture_indices.insert(image, textures.len());
+            textures.push(Texture::new(convert_image(image, data)?));
+        }
+    }
+
+    // primitives are flattened, so remember where each mesh's primitives start
+    let mut meshes = Vec::new();
+    let mut mesh_starts = Vec::new();
+    for mesh in document.meshes() {
+        mesh_starts.push(meshes.len());
+        for primitive in mesh.primitives() {
+            if primitive.mode() != Mode::Triangles {
+                continue;
+            }
+            meshes.push(convert_primitive(&primitive, &buffers, &texture_indices));
+        }
+    }
+    mesh_starts.push(meshes.len());
+
+    let mut scene = GltfScene {
+        meshes,
+        textures,
+        instances: Vec::new(),
+        cameras: Vec::new(),
+        nodes: Vec::new(),
+        roots: Vec::new(),
+    };
+    let root = document.default_scene().or_else(|| document.scenes().next());
+    if let Some(root) = root {
+        for node in root.nodes() {
+            let index = visit_node(&node, Mat4::identity(), &mesh_starts, &mut scene);
+            scene.roots.push(index);
+        }
+    }
+    Ok(scene)
+}
+
+/// Add a node and its descendants to the scene, given its parent's model-to-world matrix,
+/// returning the node's index.
+fn visit_node(
+    node: &::gltf::Node,
+    parent: Mat4<f32>,
+    mesh_starts: &[usize],
+    scene: &mut GltfScene,
+) -> usize {
+    let local = Mat4::from_col_arrays(node.transform().matrix());
+    let transform = parent * local;
+
+    let meshes = match node.mesh() {
+        Some(mesh) => (mesh_starts[mesh.index()]..mesh_starts[mesh.index() + 1]).collect(),
+        None => Vec::new(),
+    };
+    for &mesh in &meshes {
+        scene.instances.push(MeshInstance { mesh, transform });
+    }
+    let camera = node.camera();
+    if let Some(camera) = &camera {
+        scene.cameras.push(convert_camera(camera, transform));
+    }
+
+    let index = scene.nodes.len();
+    scene.nodes.push(GltfNode {
+        name: node.name().map(String::from),
+        transform: local,
+        meshes,
+        camera: camera.map(|camera| convert_camera(&camera, Mat4::identity())),
+        children: Vec::new(),
+    });
+    for child in node.children() {
+        let child = visit_node(&child, transform, mesh_starts, scene);
+        scene.nodes[index].children.push(child);
+    }
+    index
+}
+
+/// Convert a primitive, given the index into `GltfScene::textures` of each converted image.
+fn convert_primitive(
+    primitive: &::gltf::Primitive,
+    buffers: &[buffer::Data],
+    texture_indices: &HashMap<usize, usize>,
+) -> GltfMesh {
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+
+    let mut mesh = Mesh::default();
+    if let Some(positions) = reader.read_positions() {
+        mesh.positions = positions.map(Vec3::from).collect();
+    }
+    if let Some(normals) = reader.read_normals() {
+        mesh.normals = normals.map(Vec3::from).collect();
+    }
+    if let Some(uvs) = reader.read_tex_coords(0) {
+        // glTF's UV origin is already the top-left of the image
+        mesh.uvs = uvs.into_f32().map(Vec2::from).collect();
+    }
+    mesh.indices =
+        if let Some(indices) = reader.read_indices() {
+            indices.into_u32().map(|i| i as usize).collect()
+        } else {
+            (0..mesh.positions.len()).collect()
+        };
+    if mesh.normals.is_empty() && !mesh.positions.is_empty() {
+        mesh.compute_normals();
+    }
+
+    let material = primitive.material();
+    let pbr = material.pbr_metallic_roughness();
+    let base_color = Rgba::from(pbr.base_color_factor());
+    let base_color_texture = pbr
+        .base_color_texture()
+        .and_then(|info| texture_indices.get(&info.texture().source().index()).copied());
+
+    GltfMesh {
+        mesh,
+        material: PbrMaterial {
+            base_color: base_color.rgb(),
+            metallic: pbr.metallic_factor(),
+            roughness: pbr.roughness_factor(),
+            emission: Rgb::from(material.emissive_factor()),
+        },
+        base_color_texture,
+    }
+}
+
+fn convert_camera(camera: &::gltf::Camera, transform: Mat4<f32>) -> Camera {
+    let base = match camera.projection() {
+        GltfProjection::Perspective(p) => {
+            let near = p.znear();
+            let far = p.zfar().unwrap_or(near * INFINITE_FAR);
+            Camera::perspective(p.yfov(), p.aspect_ratio().unwrap_or(1.0), near, far)
+        },
+        GltfProjection::Orthographic(o) => {
+            let aspect = if o.ymag() != 0.0 { o.xmag() / o.ymag() } else { 1.0 };
+            Camera::orthographic(o.ymag() * 2.0, aspect, o.znear(), o.zfar())
+        },
+    };
+
+    // glTF cameras look down their local negative z, with y up
+    let eye = (transform * Vec4::from_point(Vec3::zero())).xyz();
+    let forward = (transform * Vec4::from_direction(-Vec3::unit_z())).xyz();
+    let up = (transform * Vec4::from_direction(Vec3::unit_y())).xyz();
+    base.look_at(eye, eye + forward, up)
+}
+
+/// Convert an 8-bit glTF image to RGBA.
+fn convert_image(index: usize, data: gltf_image::Data) -> Result<RgbaImage, GltfError> {
+    let channels = match data.format {
+        Format::R8 => 1,
+        Format::R8G8 => 2,
+        Format::R8G8B8 => 3,
+        Format::R8G8B8A8 => 4,
+        format => {
+            let msg = format!("image {} has {:?} pixels, only 8-bit is supported", index, format);
+            return Err(GltfError::Unsupported(msg));
+        },
+    };
+    let mut rgba = Vec::with_capacity(data.pixels.len() / channels * 4);
+    for texel in data.pixels.chunks_exact(channels) {
+        rgba.extend_from_slice(&match *texel {
+            [l] => [l, l, l, 255],
+            [l, a] => [l, l, l, a],
+            [r, g, b] => [r, g, b, 255],
+            [r, g, b, a] => [r, g, b, a],
+            _ => unreachable!(),
+        });
+    }
+    RgbaImage::from_raw(data.width, data.height, rgba)
+        .filter(|image| image.width() > 0 && image.height() > 0)
+        .ok_or_else(|| GltfError::Unsupported(format!("image {} is empty or truncated", index)))
+}
@@ -0,0 +1,493 @@
+use crate::{
+    PaintBatch,
+    PaintCmd,
+    SegQueue,
+    color::encode_srgb,
+};
+
+use std::{
+    fmt,
+    collections::HashMap,
+    error::Error,
+};
+
+use vek::*;
+
+/// Type of the pixels of a render graph buffer.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum BufferKind {
+    /// Linear colors, cleared to transparent black.
+    Color,
+    /// Depths, cleared to infinitely far away.
+    Depth,
+    /// Any other per-pixel scalar, such as ambient occlusion, cleared to 0.
+    Scalar,
+    /// Surface normals, cleared to zero vectors.
+    Normal,
+    /// Object or material IDs, cleared to 0.
+    Id,
+}
+
+/// Pixels of a render graph buffer, row-major.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BufferData {
+    Color(Vec<Rgba<f32>>),
+    Depth(Vec<f32>),
+    Scalar(Vec<f32>),
+    Normal(Vec<Vec3<f32>>),
+    Id(Vec<u32>),
+}
+
+impl BufferData {
+    /// A cleared buffer of the given kind and number of pixels.
+    pub fn new(kind: BufferKind, len: usize) -> Self {
+        match kind {
+            BufferKind::Color => BufferData::Color(vec![Rgba::zero(); len]),
+            BufferKind::Depth => BufferData::Depth(vec![f32::INFINITY; len]),
+            BufferKind::Scalar => BufferData::Scalar(vec![0.0; len]),
+            BufferKind::Normal => BufferData::Normal(vec![Vec3::zero(); len]),
+            BufferKind::Id => BufferData::Id(vec![0; len]),
+        }
+    }
+
+    pub fn kind(&self) -> BufferKind {
+        match self {
+            BufferData::Color(_) => BufferKind::Color,
+            BufferData::Depth(_) => BufferKind::Depth,
+            BufferData::Scalar(_) => BufferKind::Scalar,
+            BufferData::Normal(_) => BufferKind::Normal,
+            BufferData::Id(_) => BufferKind::Id,
+        }
+    }
+}
+
+/// Named buffers visible to a render graph pass.
+///
+/// The accessors panic if the buffer wasn't declared by the pass, or is of another kind.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Buffers {
+    x_size: usize,
+    y_size: usize,
+    buffers: HashMap<String, BufferData>,
+}
+
+/// Implement a pair of accessors for each kind of buffer.
+macro_rules! buffer_accessors {
+    ($( $variant:ident: $get:ident, $get_mut:ident -> $pixel:ty ),*$(,)?) => {$(
+        pub fn $get(&self, name: &str) -> &[$pixel] {
+            match self.buffers.get(name) {
+                Some(BufferData::$variant(pixels)) => pixels,
+                Some(other) => panic!(
+                    "buffer {:?} is {:?}, not {:?}",
+                    name, other.kind(), BufferKind::$variant,
+                ),
+                None => panic!("buffer {:?} is not available to this pass", name),
+            }
+        }
+
+        pub fn $get_mut(&mut self, name: &str) -> &mut [$pixel] {
+            match self.buffers.get_mut(name) {
+                Some(BufferData::$variant(pixels)) => pixels,
+                Some(other) => panic!(
+                    "buffer {:?} is {:?}, not {:?}",
+                    name, other.kind(), BufferKind::$variant,
+                ),
+                None => panic!("buffer {:?} is not available to this pass", name),
+            }
+        }
+    )*}
+}
+
+impl Buffers {
+    pub fn x_size(&self) -> usize {
+        self.x_size
+    }
+
+    pub fn y_size(&self) -> usize {
+        self.y_size
+    }
+
+    pub fn get(&self, name: &str) -> Option<&BufferData> {
+        self.buffers.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut BufferData> {
+        self.buffers.get_mut(name)
+    }
+
+    buffer_accessors! {
+        Color: color, color_mut -> Rgba<f32>,
+        Depth: depth, depth_mut -> f32,
+        Scalar: scalar, scalar_mut -> f32,
+        Normal: normal, normal_mut -> Vec3<f32>,
+        Id: id, id_mut -> u32,
+    }
+}
+
+/// Failure to order or run a render graph.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum GraphError {
+    /// A pass or the present step names a buffer which wasn't declared.
+    UnknownBuffer {
+        pass: String,
+        buffer: String,
+    },
+    /// The buffer presented isn't a color buffer.
+    NotColor(String),
+    /// The passes depend on each other in a cycle, so they can't be ordered.
+    Cycle(Vec<String>),
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GraphError::UnknownBuffer { pass, buffer } => write!(
+                f, "pass {:?} uses undeclared buffer {:?}",
+                pass, buffer,
+            ),
+            GraphError::NotColor(buffer) => write!(
+                f, "presented buffer {:?} is not a color buffer",
+                buffer,
+            ),
+            GraphError::Cycle(passes) => write!(
+                f, "passes depend on each other in a cycle: {}",
+                passes.join(", "),
+            ),
+        }
+    }
+}
+
+impl Error for GraphError {}
+
+/// Body of a render graph pass, given the buffers it reads and writes.
+type PassFn = Box<dyn FnMut(&Buffers, &mut Buffers) + Send>;
+
+/// A step of a render graph, with the buffers it reads and writes.
+struct Pass {
+    name: String,
+    reads: Vec<String>,
+    writes: Vec<String>,
+    run: PassFn,
+}
+
+/// Graph of CPU render passes over named per-pixel buffers, such as for deferred shading.
+///
+/// Passes declare which buffers they read and write, and are run in an order which
+/// respects that: writers of a buffer run in the order they were added, and a pass which
+/// reads a buffer sees the writes of the passes added before it, running after them and
+/// before any writers added after it. If no writer of a buffer was added before a pass which
+/// reads it, the pass runs after all of its writers instead. Each pass is given the buffers
+/// it reads, and mutable access to the buffers it writes, and can parallelize over pixels
+/// itself, such as with rayon. A final present step paints one color buffer to the window.
+pub struct RenderGraph {
+    x_size: usize,
+    y_size: usize,
+    kinds: Vec<(String, BufferKind)>,
+    passes: Vec<Pass>,
+    present: Option<String>,
+    buffers: Buffers,
+}
+
+impl RenderGraph {
+    /// Construct an empty graph, for buffers of the given size.
+    pub fn new(x_size: usize, y_size: usize) -> Self {
+        RenderGraph {
+            x_size,
+            y_size,
+            kinds: Vec::new(),
+            passes: Vec::new(),
+            present: None,
+            buffers: Buffers {
+                x_size,
+                y_size,
+                buffers: HashMap::new(),
+            },
+        }
+    }
+
+    pub fn x_size(&self) -> usize {
+        self.x_size
+    }
+
+    pub fn y_size(&self) -> usize {
+        self.y_size
+    }
+
+    /// Declare a buffer, which is cleared before each execution.
+    ///
+    /// Declaring an existing name replaces it.
+    pub fn buffer(&mut self, name: &str, kind: BufferKind) -> &mut Self {
+        self.kinds.retain(|(existing, _)| existing != name);
+        self.kinds.push((name.to_owned(), kind));
+        self
+    }
+
+    /// Add a pass, which reads and writes the named buffers.
+    ///
+    /// The pass is only given access to those buffers. A buffer it both reads and writes is
+    /// only among the buffers it writes, where it has the contents written before it.
+    pub fn pass<F>(&mut self, name: &str, reads: &[&str], writes: &[&str], run: F) -> &mut Self
+        where
+            F: FnMut(&Buffers, &mut Buffers) + Send + 'static {
+
+        self.passes.push(Pass {
+            name: name.to_owned(),
+            reads: reads.iter().map(|&s| s.to_owned()).collect(),
+            writes: writes.iter().map(|&s| s.to_owned()).collect(),
+            run: Box::new(run),
+        });
+        self
+    }
+
+    /// Set the color buffer which `present` paints to the window.
+    pub fn present_buffer(&mut self, name: &str) -> &mut Self {
+        self.present = Some(name.to_owned());
+        self
+    }
+
+    /// Names of the passes, in the order they run.
+    pub fn order(&self) -> Result<Vec<&str>, GraphError> {
+        Ok(self.schedule()?.into_iter().map(|i| self.passes[i].name.as_str()).collect())
+    }
+
+    /// Indices of the passes, in the order they run.
+    fn schedule(&self) -> Result<Vec<usize>, GraphError> {
+        for pass in &self.passes {
+            for buffer in pass.reads.iter().chain(&pass.writes) {
+                if !self.kinds.iter().any(|(name, _)| name == buffer) {
+                    return Err(GraphError::UnknownBuffer {
+                        pass: pass.name.clone(),
+                        buffer: buffer.clone(),
+                    });
+                }
+            }
+        }
+
+        // dependencies of each pass
+        let n = self.passes.len();
+        let mut deps: Vec<Vec<usize>> = vec![Vec::new(); n];
+        let writers = |buffer: &String| -> Vec<usize> {
+            (0..n).filter(|&j| self.passes[j].writes.contains(buffer)).collect()
+        };
+        for (i, pass) in self.passes.iter().enumerate() {
+            // writers of a buffer run in the order they were added
+            for buffer in &pass.writes {
+                deps[i].extend(writers(buffer).into_iter().filter(|&j| j < i));
+            }
+
+            for buffer in pass.reads.iter().filter(|buffer| !pass.writes.contains(buffer)) {
+                let (before, after): (Vec<usize>, Vec<usize>) = writers(buffer)
+                    .into_iter()
+                    .partition(|&j| j < i);
+                if before.is_empty() {
+                    // read the final contents
+                    deps[i].extend(after);
+                } else {
+                    // read the earlier writes, before later writers overwrite them
+                    deps[i].extend(before);
+                    for j in after {
+                        deps[j].push(i);
+                    }
+                }
+            }
+        }
+
+        // topological sort, preferring the order passes were added
+        let mut order = Vec::with_capacity(n);
+        let mut done = vec![false; n];
+        while order.len() < n {
+            let next = (0..n).find(|&i| !done[i] && deps[i].iter().all(|&j| done[j]));
+            match next {
+                Some(i) => {
+                    done[i] = true;
+                    order.push(i);
+                },
+                None => return Err(GraphError::Cycle(
+                    (0..n).filter(|&i| !done[i]).map(|i| self.passes[i].name.clone()).collect()
+                )),
+            }
+        }
+        Ok(order)
+    }
+
+    /// Clear the buffers, and run every pass in order.
+    pub fn execute(&mut self) -> Result<(), GraphError> {
+        let order = self.schedule()?;
+
+        let len = self.x_size * self.y_size;
+        self.buffers.buffers.clear();
+        for (name, kind) in &self.kinds {
+            self.buffers.buffers.insert(name.clone(), BufferData::new(*kind, len));
+        }
+
+        for i in order {
+            let pass = &mut self.passes[i];
+            let buffers = &mut self.buffers.buffers;
+
+            // lend the declared buffers out to the pass, so it can't use any others
+            let (x_size, y_size) = (self.x_size, self.y_size);
+            let mut lend = |names: &[String]| Buffers {
+                x_size,
+                y_size,
+                buffers: names.iter()
+                    .filter_map(|name| buffers.remove_entry(name))
+                    .collect(),
+            };
+            let mut writes = lend(&pass.writes);
+            let reads = lend(&pass.reads);
+            (pass.run)(&reads, &mut writes);
+            buffers.extend(reads.buffers);
+            buffers.extend(writes.buffers);
+        }
+        Ok(())
+    }
+
+    /// Buffers as of the last execution.
+    pub fn buffers(&self) -> &Buffers {
+        &self.buffers
+    }
+
+    /// Paint the presented color buffer to the paint queue as a single batch, gamma encoded
+    /// to sRGB.
+    ///
+    /// Does nothing if no buffer is presented.
+    pub fn present(&self, queue: &SegQueue<PaintCmd>) -> Result<(), GraphError> {
+        let name = match self.present {
+            Some(ref name) => name,
+            None => return Ok(()),
+        };
+        let pixels = match self.buffers.get(name) {
+            Some(BufferData::Color(pixels)) => pixels,
+            Some(_) => return Err(GraphError::NotColor(name.clone())),
+            None => return Err(GraphError::UnknownBuffer {
+                pass: "present".to_owned(),
+                buffer: name.clone(),
+            }),
+        };
+
+        let rgba: Vec<[u8; 4]> = pixels.iter()
+            .map(|&color| {
+                let color = encode_srgb(color);
+                [color.r, color.g, color.b, color.a]
+            })
+            .collect();
+        queue.push(PaintBatch::new(0, 0, self.x_size, self.y_size, rgba).into());
+        Ok(())
+    }
+
+    /// Run every pass, and then present the result.
+    pub fn run_frame(&mut self, queue: &SegQueue<PaintCmd>) -> Result<(), GraphError> {
+        self.execute()?;
+        self.present(queue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Graph of 1x1 buffers with the given passes, as (name, reads, writes), which do
+    /// nothing.
+    fn graph_of(passes: &[(&str, &[&str], &[&str])]) -> RenderGraph {
+        let mut graph = RenderGraph::new(1, 1);
+        graph
+            .buffer("color", BufferKind::Color)
+            .buffer("ao", BufferKind::Scalar)
+            .buffer("depth", BufferKind::Depth);
+        for &(name, reads, writes) in passes {
+            graph.pass(name, reads, writes, |_, _| ());
+        }
+        graph
+    }
+
+    #[test]
+    fn reader_sees_earlier_writers() {
+        // apply writes the color which ao reads, but after it
+        let graph = graph_of(&[
+            ("geom", &[], &["color"]),
+            ("ao", &["color"], &["ao"]),
+            ("apply", &["ao"], &["color"]),
+        ]);
+        assert_eq!(graph.order().unwrap(), ["geom", "ao", "apply"]);
+    }
+
+    #[test]
+    fn later_writer_waits_for_reader() {
+        // blur reads the geometry's color, so the overwrite must wait for it
+        let graph = graph_of(&[
+            ("geom", &[], &["color"]),
+            ("overwrite", &[], &["color"]),
+            ("blur", &["color"], &["ao"]),
+        ]);
+        assert_eq!(graph.order().unwrap(), ["geom", "overwrite", "blur"]);
+
+        let graph = graph_of(&[
+            ("geom", &[], &["color"]),
+            ("blur", &["color"], &["ao"]),
+            ("overwrite", &[], &["color"]),
+        ]);
+        assert_eq!(graph.order().unwrap(), ["geom", "blur", "overwrite"]);
+    }
+
+    #[test]
+    fn reader_added_first_runs_after_writers() {
+        let graph = graph_of(&[
+            ("shade", &["depth"], &["color"]),
+            ("geom", &[], &["depth"]),
+        ]);
+        assert_eq!(graph.order().unwrap(), ["geom", "shade"]);
+    }
+
+    #[test]
+    fn cycle_is_an_error() {
+        let graph = graph_of(&[
+            ("a", &["depth"], &["ao"]),
+            ("b", &["ao"], &["depth"]),
+        ]);
+        assert_eq!(
+            graph.order(),
+            Err(GraphError::Cycle(vec!["a".to_owned(), "b".to_owned()])),
+        );
+    }
+
+    #[test]
+    fn unknown_buffer_is_an_error() {
+        let graph = graph_of(&[("a", &["normal"], &[])]);
+        assert_eq!(
+            graph.order(),
+            Err(GraphError::UnknownBuffer {
+                pass: "a".to_owned(),
+                buffer: "normal".to_owned(),
+            }),
+        );
+    }
+
+    #[test]
+    fn passes_see_writes_in_order() {
+        let mut graph = RenderGraph::new(1, 1);
+        graph
+            .buffer("ao", BufferKind::Scalar)
+            .buffer("copy", BufferKind::Scalar)
+            .pass("set", &[], &["ao"], |_, out| out.scalar_mut("ao")[0] = 1.0)
+            .pass("copy", &["ao"], &["copy"], |input, out| {
+                out.scalar_mut("copy")[0] = input.scalar("ao")[0];
+            })
+            .pass("double", &[], &["ao"], |_, out| out.scalar_mut("ao")[0] *= 2.0);
+        graph.execute().unwrap();
+        assert_eq!(graph.buffers().scalar("ao"), [2.0]);
+        assert_eq!(graph.buffers().scalar("copy"), [1.0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "not available to this pass")]
+    fn undeclared_read_panics() {
+        let mut graph = RenderGraph::new(1, 1);
+        graph
+            .buffer("ao", BufferKind::Scalar)
+            .buffer("depth", BufferKind::Depth)
+            .pass("sneaky", &[], &["ao"], |input, _| {
+                input.depth("depth");
+            });
+        let _ = graph.execute();
+    }
+}
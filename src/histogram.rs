@@ -0,0 +1,190 @@
+use image::RgbaImage;
+use vek::*;
+
+/// Rec. 709 luminance of a color.
+pub fn luminance(color: Rgb<f32>) -> f32 {
+    color.r * 0.2126 + color.g * 0.7152 + color.b * 0.0722
+}
+
+/// Histogram of 8-bit pixel values, per channel and of luminance, with 256 bins each.
+///
+/// Transparent pixels are counted like any other. Values are counted as stored, so for a
+/// `linear` window, luminance is linear too.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Histogram {
+    pub r: [u32; 256],
+    pub g: [u32; 256],
+    pub b: [u32; 256],
+    pub luma: [u32; 256],
+    /// Number of pixels counted.
+    pub count: u32,
+}
+
+impl Histogram {
+    /// Count row-major RGBA pixels, such as those of a `Canvas`.
+    pub fn from_pixels(pixels: &[[u8; 4]]) -> Self {
+        let mut histogram = Histogram {
+            r: [0; 256],
+            g: [0; 256],
+            b: [0; 256],
+            luma: [0; 256],
+            count: pixels.len() as u32,
+        };
+        for &[r, g, b, _] in pixels {
+            histogram.r[r as usize] += 1;
+            histogram.g[g as usize] += 1;
+            histogram.b[b as usize] += 1;
+            let rgb = Rgb::new(r, g, b).map(|c| c as f32);
+            histogram.luma[luminance(rgb).round().min(255.0) as usize] += 1;
+        }
+        histogram
+    }
+
+    /// Count the pixels of an image, such as from `DrawContext::read_canvas`.
+    pub fn from_image(image: &RgbaImage) -> Self {
+        let pixels: Vec<[u8; 4]> = image.pixels().map(|pixel| pixel.0).collect();
+        Histogram::from_pixels(&pixels)
+    }
+
+    /// Mean luminance, in `[0, 1]`.
+    pub fn average_luminance(&self) -> f32 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        let sum: f32 = self.luma.iter()
+            .enumerate()
+            .map(|(i, &n)| i as f32 * n as f32)
+            .sum();
+        sum / self.count as f32 / 255.0
+    }
+
+    /// Luminance, in `[0, 1]`, which the given fraction of pixels are at or below, such as
+    /// 0.5 for the median, or 0.99 to find the near-brightest pixels.
+    pub fn percentile_luminance(&self, fraction: f32) -> f32 {
+        let threshold = (fraction.clamp(0.0, 1.0) * self.count as f32).ceil() as u32;
+        let mut seen = 0;
+        for (i, &n) in self.luma.iter().enumerate() {
+            seen += n;
+            if seen >= threshold.max(1) {
+                return i as f32 / 255.0;
+            }
+        }
+        1.0
+    }
+}
+
+/// Log-average luminance of linear HDR colors, the usual key for automatic exposure, such
+/// as for `ToneMap::Reinhard`.
+///
+/// Luminance is offset by a small delta before taking the log, so black pixels don't drag
+/// the average to zero.
+pub fn log_average_luminance(colors: &[Rgb<f32>]) -> f32 {
+    const DELTA: f32 = 1e-4;
+
+    if colors.is_empty() {
+        return 0.0;
+    }
+    let sum: f32 = colors.iter()
+        .map(|&color| (luminance(color).max(0.0) + DELTA).ln())
+        .sum();
+    (sum / colors.len() as f32).exp()
+}
+
+/// Luminance of linear HDR colors which the given fraction of them are at or below.
+pub fn percentile_luminance(colors: &[Rgb<f32>], fraction: f32) -> f32 {
+    if colors.is_empty() {
+        return 0.0;
+    }
+    let mut lumas: Vec<f32> = colors.iter().map(|&color| luminance(color)).collect();
+    lumas.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let i = (fraction.clamp(0.0, 1.0) * (lumas.len() - 1) as f32).round() as usize;
+    lumas[i]
+}
+
+/// Exposure multiplier which brings the log-average luminance of linear HDR colors to
+/// middle gray, for scaling them before a `ToneMap`.
+pub fn auto_exposure(colors: &[Rgb<f32>]) -> f32 {
+    const MIDDLE_GRAY: f32 = 0.18;
+
+    let average = log_average_luminance(colors);
+    if average > 0.0 { MIDDLE_GRAY / average } else { 1.0 }
+}
+
+/// Height of the histogram overlay, in pixels.
+const OVERLAY_Y_SIZE: usize = 64;
+
+/// Draw a histogram over the bottom-left corner of canvas pixels, with a bin per column,
+/// the color channels added together, and luminance as a gray outline.
+pub(crate) fn draw_histogram(
+    pixels: &mut [[u8; 4]],
+    x_size: usize,
+    y_size: usize,
+    histogram: &Histogram,
+) {
+    let pad = 2;
+    let x_end = usize::min(256 + pad * 2, x_size);
+    let y_start = y_size.saturating_sub(OVERLAY_Y_SIZE + pad * 2);
+
+    // darken a backdrop so the bins are legible over anything
+    for y in y_start..y_size {
+        for pixel in &mut pixels[y * x_size..y * x_size + x_end] {
+            let [r, g, b, _] = *pixel;
+            *pixel = [r / 4, g / 4, b / 4, 0xFF];
+        }
+    }
+
+    // scale to the tallest bin, ignoring the clipped extremes, which often dominate
+    let tallest = [&histogram.r, &histogram.g, &histogram.b, &histogram.luma].iter()
+        .flat_map(|bins| bins[1..255].iter())
+        .copied()
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let height = |n: u32| {
+        usize::min(n as usize * OVERLAY_Y_SIZE / tallest as usize, OVERLAY_Y_SIZE)
+    };
+
+    let bottom = y_size.saturating_sub(pad);
+    for i in 0..256 {
+        let x = pad + i;
+        if x >= x_end {
+            break;
+        }
+        let channels = [histogram.r[i], histogram.g[i], histogram.b[i]];
+        for (c, &n) in channels.iter().enumerate() {
+            for y in bottom.saturating_sub(height(n))..bottom {
+                pixels[y * x_size + x][c] = pixels[y * x_size + x][c].saturating_add(0xC0);
+            }
+        }
+        let luma_top = bottom.saturating_sub(height(histogram.luma[i]).max(1));
+        if luma_top < bottom {
+            pixels[luma_top * x_size + x] = [0xFF; 4];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlay_rises_from_the_bottom_left() {
+        let (x_size, y_size) = (300, 100);
+        let mut pixels = vec![[0x80, 0x80, 0x80, 0xFF]; x_size * y_size];
+        let histogram = Histogram::from_pixels(&pixels);
+        draw_histogram(&mut pixels, x_size, y_size, &histogram);
+
+        // row 0 is the top, so the backdrop only covers the bottom rows
+        assert_eq!(pixels[0], [0x80, 0x80, 0x80, 0xFF]);
+        assert_eq!(pixels[(y_size - 1) * x_size], [0x20, 0x20, 0x20, 0xFF]);
+        assert_eq!(pixels[(y_size - 1) * x_size + x_size - 1], [0x80, 0x80, 0x80, 0xFF]);
+
+        // the full bin is a bar from just above the bottom up to the overlay's height
+        let x = 2 + 0x80;
+        let bar = |y: usize| pixels[y * x_size + x];
+        assert_eq!(bar(y_size - 3), [0xE0, 0xE0, 0xE0, 0xFF]);
+        assert_eq!(bar(y_size - 2 - OVERLAY_Y_SIZE + 1), [0xE0, 0xE0, 0xE0, 0xFF]);
+        assert_eq!(bar(y_size - 2 - OVERLAY_Y_SIZE - 1), [0x20, 0x20, 0x20, 0xFF]);
+        assert_eq!(bar(y_size - 2), [0x20, 0x20, 0x20, 0xFF]);
+    }
+}
@@ -0,0 +1,191 @@
+use std::path::PathBuf;
+
+use vek::*;
+
+/// Number of pixels of pixel-precise scrolling which count as one line.
+pub(crate) const PIXELS_PER_LINE: f32 = 20.0;
+
+/// Keyboard key.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Key {
+    A, B, C, D, E, F, G, H, I, J, K, L, M,
+    N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    Escape,
+    Space,
+    Enter,
+    Tab,
+    Backspace,
+    Delete,
+    Insert,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Left,
+    Right,
+    Up,
+    Down,
+    LShift,
+    RShift,
+    LControl,
+    RControl,
+    LAlt,
+    RAlt,
+    /// Left command, windows, or super key.
+    LLogo,
+    /// Right command, windows, or super key.
+    RLogo,
+    Minus,
+    Equals,
+    Comma,
+    Period,
+    Slash,
+    Backslash,
+    Semicolon,
+    Apostrophe,
+    LBracket,
+    RBracket,
+    Grave,
+}
+
+/// Mouse button.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    Other(u16),
+}
+
+/// Modifier keys held during an input event.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    /// Command, windows, or super key.
+    pub logo: bool,
+}
+
+/// User input and window events forwarded from the window to the drawing thread.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InputEvent {
+    /// A keyboard key was pressed.
+    KeyPress {
+        /// The key, or `None` if it isn't one which `Key` represents.
+        key: Option<Key>,
+        /// Platform-specific scancode, or 0 if the backend doesn't provide one.
+        scancode: u32,
+        modifiers: Modifiers,
+    },
+    /// A keyboard key was released.
+    KeyRelease {
+        /// The key, or `None` if it isn't one which `Key` represents.
+        key: Option<Key>,
+        /// Platform-specific scancode, or 0 if the backend doesn't provide one.
+        scancode: u32,
+        modifiers: Modifiers,
+    },
+    /// The mouse cursor moved.
+    MouseMove {
+        /// New cursor position, in canvas pixel coordinates.
+        pos: Vec2<f32>,
+    },
+    /// The mouse moved, by raw device motion, which isn't accelerated or stopped by the
+    /// edges of the window or screen, such as for looking around with a grabbed cursor.
+    ///
+    /// This is only reported while the window is focused, alongside any `MouseMove`.
+    MouseMotion {
+        /// Distance moved, in device-specific units, which are roughly pixels.
+        delta: Vec2<f32>,
+    },
+    /// A mouse button was pressed.
+    MousePress {
+        button: MouseButton,
+        modifiers: Modifiers,
+    },
+    /// A mouse button was released.
+    MouseRelease {
+        button: MouseButton,
+        modifiers: Modifiers,
+    },
+    /// The mouse wheel or touchpad was scrolled.
+    Scroll {
+        /// Amount scrolled, in lines.
+        delta: Vec2<f32>,
+    },
+    /// The left mouse button was pressed over the canvas, in a window with picking enabled.
+    ///
+    /// This follows the corresponding `MousePress`.
+    Pick {
+        /// Canvas pixel under the cursor.
+        x: usize,
+        y: usize,
+        /// Color displayed at that pixel.
+        rgba: Rgba<u8>,
+    },
+    /// The object ID under the cursor changed, in a window with an ID buffer, because the
+    /// cursor moved or the IDs under it were painted.
+    ///
+    /// When caused by the cursor moving, this follows the corresponding `MouseMove`. When the
+    /// cursor leaves the canvas, this is reported with ID 0, at the last pixel it was over.
+    Hover {
+        /// Canvas pixel under the cursor.
+        x: usize,
+        y: usize,
+        /// Object ID at that pixel, which is 0 for no object.
+        id: u32,
+    },
+    /// The window was resized, and the canvas reallocated and cleared.
+    Resize {
+        x_size: usize,
+        y_size: usize,
+    },
+    /// A file was dragged and dropped onto the window, such as a model or image for the
+    /// drawing thread to load.
+    ///
+    /// If several files are dropped at once, there is one event for each.
+    DroppedFile {
+        path: PathBuf,
+    },
+    /// The user zoomed or panned the view of the canvas, in a window with navigation
+    /// enabled.
+    Navigate {
+        view: View,
+    },
+}
+
+/// Part of the canvas displayed by a window with navigation enabled.
+///
+/// A point displayed where canvas coordinate `d` would be without navigation shows canvas
+/// coordinate `offset + d / zoom`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct View {
+    /// Canvas coordinates displayed at the top-left corner.
+    pub offset: Vec2<f32>,
+    /// Magnification, greater than 1 when zoomed in.
+    pub zoom: f32,
+}
+
+impl Default for View {
+    fn default() -> Self {
+        View {
+            offset: Vec2::zero(),
+            zoom: 1.0,
+        }
+    }
+}
+
+impl View {
+    /// Map a displayed position to canvas coordinates.
+    pub fn to_canvas(&self, pos: Vec2<f32>) -> Vec2<f32> {
+        self.offset + pos / self.zoom
+    }
+
+    /// Map canvas coordinates to their displayed position.
+    pub fn from_canvas(&self, pos: Vec2<f32>) -> Vec2<f32> {
+        (pos - self.offset) * self.zoom
+    }
+}
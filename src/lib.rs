@@ -1,33 +1,195 @@
 
 #[macro_use]
-#[doc(hide)]
+#[doc(hidden)]
 pub extern crate log;
-#[doc(hide)]
+#[doc(hidden)]
 pub extern crate crossbeam;
-#[doc(hide)]
+#[cfg(feature = "gl")]
+#[doc(hidden)]
 pub extern crate glium;
-#[doc(hide)]
+#[doc(hidden)]
 pub extern crate image;
-#[doc(hide)]
+#[doc(hidden)]
 pub extern crate rand;
-#[doc(hide)]
+#[doc(hidden)]
 pub extern crate rayon;
-#[doc(hide)]
+#[doc(hidden)]
+pub extern crate gif;
+#[doc(hidden)]
 pub extern crate vek;
 
 /// Concurrent per-fragment painting.
 pub mod frag;
 
-/// Displaying pixels in an opengl window.
+/// Parameters which can be tweaked live with the keyboard.
+pub mod params;
+
+/// Eight-lane `f32` vectors, for SIMD fragment functions.
+pub mod simd;
+
+/// Persistent worker threads for rendering tiles in priority order.
+pub mod tiles;
+
+/// Triangle rasterization.
+pub mod raster;
+
+/// Programmable software rasterization pipeline.
+pub mod pipeline;
+
+/// Shadow mapping for the software pipeline.
+pub mod shadow;
+
+/// Render graphs of CPU passes over named per-pixel buffers.
+pub mod graph;
+
+/// G-buffers and deferred shading on render graphs.
+pub mod deferred;
+
+/// Ambient occlusion, screen-space or ray-traced.
+pub mod ao;
+
+/// Triangle meshes and OBJ loading.
+pub mod mesh;
+
+/// Camera matrices, screen-space unprojection, and interactive camera controllers.
+pub mod camera;
+
+/// Ray tracing primitives and intersection.
+pub mod rt;
+
+/// Bounding volume hierarchies for ray tracing.
+pub mod bvh;
+
+/// Blinn-Phong lighting and physically based BRDF sampling, for rasterizers and ray tracers.
+pub mod shade;
+
+/// Scene graphs of nodes with hierarchical transforms, and attached meshes, lights, and
+/// cameras.
+pub mod scene;
+
+/// Offscreen CPU-side pixel buffers.
+pub mod canvas;
+
+/// Post-processing filters on canvases, such as blurs and bloom.
+pub mod filter;
+
+/// Splitting the canvas into viewports with their own coordinates and paint queues.
+pub mod viewport;
+
+/// Side-by-side comparison of two images, with a draggable divider.
+pub mod compare;
+
+/// Event-driven interactive programs.
+pub mod app;
+
+/// Line drawing and other 2D primitives.
+pub mod draw;
+
+/// 2D affine transforms and transform stacks.
+pub mod transform;
+
+/// Bitmap font text rendering.
+pub mod text;
+
+/// Frame rate and throughput statistics.
+pub mod stats;
+
+/// Pixel histograms and luminance analysis, for debugging exposure.
+pub mod histogram;
+
+/// Software texture sampling.
+pub mod texture;
+
+/// Coherent noise for procedural textures.
+pub mod noise;
+
+/// Deterministic per-pixel random number streams, for reproducible stochastic renders.
+pub mod rng;
+
+/// Low-discrepancy and blue-noise sample patterns, for antialiasing and path tracing.
+pub mod sampling;
+
+/// Signed distance functions and sphere tracing.
+pub mod sdf;
+
+/// Color conversions, including sRGB gamma encoding, and gradients.
+pub mod color;
+
+/// Heatmaps of scalar data.
+pub mod viz;
+
+/// Charts of data series, with axes and ticks.
+pub mod plot;
+
+/// Double-buffered grid simulations, such as cellular automata.
+pub mod sim;
+
+/// Particle systems, splatted with additive blending.
+pub mod particles;
+
+/// Recording the canvas over time.
+pub mod record;
+
+/// Golden-image regression testing.
+pub mod testing;
+
+/// Watching asset files for changes, to reload them without restarting.
+pub mod watch;
+
+/// Async adapters for the paint queue and frame presentation.
+#[cfg(feature = "async")]
+pub mod sink;
+
+/// Copying images to the system clipboard.
+#[cfg(feature = "clipboard")]
+pub mod clipboard;
+
+/// Fragment functions scripted in a small expression language, and reloaded live.
+#[cfg(feature = "script")]
+pub mod script;
+
+/// Loading meshes, materials, and cameras from glTF scenes.
+#[cfg(feature = "import")]
+pub mod gltf;
+
+/// Displaying pixels in a window, with an OpenGL or softbuffer backend.
 mod window;
 
+/// Instructions sent through the paint queue.
+mod paint;
+
+/// User input forwarded from the window.
+pub mod input;
+
 // re-exports
 pub use crossbeam::queue::SegQueue;
 
-#[doc(transparent)]
+#[doc(inline)]
 pub use window::{
     open_window,
+    try_open_window,
+    Window,
+    WindowBuilder,
+    Backend,
+    Scaling,
+    PostEffect,
+    DrawContext,
+    WindowControl,
+    CursorIcon,
+    ShutdownToken,
+    PresentSignal,
+    WindowError,
+};
+
+#[doc(inline)]
+pub use paint::{
+    BlendMode,
     Paint,
+    PaintBatch,
+    PaintDepth,
+    PaintF,
+    PaintId,
+    PaintCmd,
 };
 
 /// Re-exports of useful crates.
@@ -0,0 +1,373 @@
+use crate::{
+    PaintCmd,
+    SegQueue,
+    camera::Camera,
+    pipeline::Pipeline,
+    rt::Triangle,
+};
+
+use std::{
+    fmt,
+    io,
+    fs,
+    error::Error,
+    path::Path,
+    collections::HashMap,
+};
+
+use vek::*;
+
+/// Indexed triangle mesh.
+///
+/// Every three indices form a triangle. `normals` and `uvs` are either empty, or have one
+/// entry per position.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Mesh {
+    pub positions: Vec<Vec3<f32>>,
+    pub normals: Vec<Vec3<f32>>,
+    pub uvs: Vec<Vec2<f32>>,
+    pub indices: Vec<usize>,
+}
+
+impl Mesh {
+    /// Number of triangles.
+    pub fn num_triangles(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// Replace the normals with smooth per-vertex normals, computed by averaging the
+    /// area-weighted normals of the adjacent triangles.
+    pub fn compute_normals(&mut self) {
+        let mut normals = vec![Vec3::zero(); self.positions.len()];
+        for tri in self.indices.chunks_exact(3) {
+            let p0 = self.positions[tri[0]];
+            let p1 = self.positions[tri[1]];
+            let p2 = self.positions[tri[2]];
+            let normal = (p1 - p0).cross(p2 - p0);
+            for &i in tri {
+                normals[i] += normal;
+            }
+        }
+        for normal in &mut normals {
+            let len = normal.magnitude();
+            *normal =
+                if len > 0.0 { *normal / len }
+                else { Vec3::unit_y() };
+        }
+        self.normals = normals;
+    }
+
+    /// Copy of the mesh with positions transformed by a matrix, and normals by its inverse
+    /// transpose, so they stay perpendicular to the surface.
+    pub fn transformed(&self, matrix: Mat4<f32>) -> Mesh {
+        let normal_matrix = matrix.inverted().transposed();
+        Mesh {
+            positions: self.positions
+                .iter()
+                .map(|&p| (matrix * Vec4::from_point(p)).xyz())
+                .collect(),
+            normals: self.normals
+                .iter()
+                .map(|&n| (normal_matrix * Vec4::from_direction(n)).xyz().normalized())
+                .collect(),
+            uvs: self.uvs.clone(),
+            indices: self.indices.clone(),
+        }
+    }
+
+    /// Triangles of the mesh, for ray tracing.
+    pub fn triangles(&self) -> Vec<Triangle> {
+        self.indices
+            .chunks_exact(3)
+            .map(|tri| Triangle::new(
+                self.positions[tri[0]],
+                self.positions[tri[1]],
+                self.positions[tri[2]],
+            ))
+            .collect()
+    }
+}
+
+/// Error loading an OBJ file.
+#[derive(Debug)]
+pub enum ObjError {
+    /// Failed to read the file.
+    Io(io::Error),
+    /// The file is malformed.
+    Parse {
+        /// 1-based line number.
+        line: usize,
+        msg: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io(e) => write!(f, "failed to read obj file: {}", e),
+            ObjError::Parse { line, msg } => write!(f, "obj parse error on line {}: {}", line, msg),
+        }
+    }
+}
+
+impl Error for ObjError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ObjError::Io(e) => Some(e),
+            ObjError::Parse { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for ObjError {
+    fn from(e: io::Error) -> Self {
+        ObjError::Io(e)
+    }
+}
+
+/// Load a Wavefront OBJ file.
+///
+/// See `parse_obj`.
+pub fn load_obj(path: impl AsRef<Path>) -> Result<Mesh, ObjError> {
+    parse_obj(&fs::read_to_string(path)?)
+}
+
+/// Parse the contents of a Wavefront OBJ file.
+///
+/// Supports vertex positions, normals, and texture coordinates, and faces, which are
+/// triangulated as fans. Each distinct combination of position, normal, and texture
+/// coordinate indices becomes one vertex of the mesh. Texture coordinates are flipped
+/// vertically, so that `(0, 0)` is the top-left of the texture image. Other statements are
+/// ignored.
+pub fn parse_obj(src: &str) -> Result<Mesh, ObjError> {
+    let mut obj_positions: Vec<Vec3<f32>> = Vec::new();
+    let mut obj_normals: Vec<Vec3<f32>> = Vec::new();
+    let mut obj_uvs: Vec<Vec2<f32>> = Vec::new();
+
+    let mut mesh = Mesh::default();
+    let mut has_normals = true;
+    let mut has_uvs = true;
+
+    // (position, uv, normal) index triple -> mesh vertex index
+    let mut vertex_indices: HashMap<(usize, Option<usize>, Option<usize>), usize> =
+        HashMap::new();
+
+    for (line_i, line) in src.lines().enumerate() {
+        let err = |msg: &str| ObjError::Parse {
+            line: line_i + 1,
+            msg: msg.into(),
+        };
+
+        // strip comments
+        let line = line.split('#').next().unwrap();
+        let mut tokens = line.split_whitespace();
+
+        let floats = |tokens: std::str::SplitWhitespace, n: usize| -> Result<Vec<f32>, ObjError> {
+            let parsed = tokens
+                .take(n)
+                .map(|token| token.parse::<f32>())
+                .collect::<Result<Vec<f32>, _>>()
+                .map_err(|_| err("invalid number"))?;
+            if parsed.len() < n {
+                return Err(err("too few components"));
+            }
+            Ok(parsed)
+        };
+
+        match tokens.next() {
+            Some("v") => {
+                let v = floats(tokens, 3)?;
+                obj_positions.push(Vec3::new(v[0], v[1], v[2]));
+            },
+            Some("vn") => {
+                let v = floats(tokens, 3)?;
+                obj_normals.push(Vec3::new(v[0], v[1], v[2]));
+            },
+            Some("vt") => {
+                let v = floats(tokens, 2)?;
+                obj_uvs.push(Vec2::new(v[0], 1.0 - v[1]));
+            },
+            Some("f") => {
+                // resolve each face vertex to a mesh vertex index
+                let mut face: Vec<usize> = Vec::new();
+                for token in tokens {
+                    let mut parts = token.split('/');
+
+                    // 1-based index, or negative index relative to the end
+                    let resolve =
+                        |part: Option<&str>, len: usize| -> Result<Option<usize>, ObjError> {
+                            match part {
+                                None | Some("") => Ok(None),
+                                Some(part) => {
+                                    let i: isize = part.parse()
+                                        .map_err(|_| err("invalid index"))?;
+                                    let resolved =
+                                        if i > 0 { i - 1 }
+                                        else { len as isize + i };
+                                    if resolved < 0 || resolved as usize >= len {
+                                        return Err(err("index out of range"));
+                                    }
+                                    Ok(Some(resolved as usize))
+                                },
+                            }
+                        };
+
+                    let pos = resolve(parts.next(), obj_positions.len())?
+                        .ok_or_else(|| err("face vertex missing position"))?;
+                    let uv = resolve(parts.next(), obj_uvs.len())?;
+                    let normal = resolve(parts.next(), obj_normals.len())?;
+
+                    has_uvs &= uv.is_some();
+                    has_normals &= normal.is_some();
+
+                    let index = *vertex_indices
+                        .entry((pos, uv, normal))
+                        .or_insert_with(|| {
+                            mesh.positions.push(obj_positions[pos]);
+                            mesh.uvs.push(uv.map(|i| obj_uvs[i]).unwrap_or(Vec2::zero()));
+                            mesh.normals.push(
+                                normal.map(|i| obj_normals[i]).unwrap_or(Vec3::zero()),
+                            );
+                            mesh.positions.len() - 1
+                        });
+                    face.push(index);
+                }
+
+                if face.len() < 3 {
+                    return Err(err("face has fewer than 3 vertices"));
+                }
+
+                // triangulate as a fan
+                for i in 1..face.len() - 1 {
+                    mesh.indices.extend_from_slice(&[face[0], face[i], face[i + 1]]);
+                }
+            },
+            _ => (),
+        }
+    }
+
+    // drop attributes which not every vertex had
+    if !has_uvs {
+        mesh.uvs.clear();
+    }
+    if !has_normals {
+        mesh.normals.clear();
+    }
+
+    Ok(mesh)
+}
+
+/// Render a mesh onto the paint queue through the software pipeline, with depth testing.
+///
+/// Positions are transformed to clip space by `mvp`. The fragment function is passed the
+/// interpolated normal and texture coordinates, which are zero if the mesh lacks them.
+pub fn render_mesh(
+    queue: &SegQueue<PaintCmd>,
+    mesh: &Mesh,
+    mvp: Mat4<f32>,
+    x_size: usize,
+    y_size: usize,
+    fragment: impl Fn(Vec3<f32>, Vec2<f32>) -> Rgba<u8>,
+) {
+    let pipeline = Pipeline::new(
+        x_size,
+        y_size,
+        |&i: &usize| {
+            let clip = mvp * Vec4::from_point(mesh.positions[i]);
+            let normal = mesh.normals.get(i).copied().unwrap_or(Vec3::zero());
+            let uv = mesh.uvs.get(i).copied().unwrap_or(Vec2::zero());
+            (clip, (normal, uv))
+        },
+        |(normal, uv): (Vec3<f32>, Vec2<f32>)| fragment(normal, uv),
+    );
+
+    // the vertex shader reads attributes by index
+    let vertices: Vec<usize> = (0..mesh.positions.len()).collect();
+    pipeline.draw_to_queue(queue, &vertices, &mesh.indices);
+}
+
+/// How `render_shaded` interpolates lighting across triangles.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Shading {
+    /// Light each triangle uniformly, by its face normal, for a faceted look.
+    Flat,
+    /// Light each vertex by its normal, and interpolate the lighting across triangles.
+    ///
+    /// If the mesh lacks normals, smooth normals are computed.
+    Gouraud,
+}
+
+/// Fraction of a surface's color which `render_shaded` shows even facing away from the
+/// light.
+const AMBIENT: f32 = 0.1;
+
+/// Render a mesh onto the paint queue in a single color, lit by a directional light, with
+/// depth testing.
+///
+/// Lighting is Lambertian, plus a little ambient light so unlit faces aren't pitch black.
+/// `light_dir` points from the surface towards the light, in the mesh's space.
+#[allow(clippy::too_many_arguments)]
+pub fn render_shaded(
+    queue: &SegQueue<PaintCmd>,
+    mesh: &Mesh,
+    camera: &Camera,
+    x_size: usize,
+    y_size: usize,
+    light_dir: Vec3<f32>,
+    color: Rgba<u8>,
+    shading: Shading,
+) {
+    let light_dir = light_dir.normalized();
+    let lambert = |normal: Vec3<f32>| {
+        let diffuse = normal.normalized().dot(light_dir).max(0.0);
+        AMBIENT + (1.0 - AMBIENT) * diffuse
+    };
+
+    // each vertex is a position and its light intensity
+    let mut vertices: Vec<(Vec3<f32>, f32)> = Vec::new();
+    let mut indices: Vec<usize> = Vec::new();
+    match shading {
+        Shading::Flat => {
+            // duplicate vertices per triangle, so each can have the face's intensity
+            for tri in mesh.indices.chunks_exact(3) {
+                let p0 = mesh.positions[tri[0]];
+                let p1 = mesh.positions[tri[1]];
+                let p2 = mesh.positions[tri[2]];
+                let intensity = lambert((p1 - p0).cross(p2 - p0));
+                for &p in &[p0, p1, p2] {
+                    indices.push(vertices.len());
+                    vertices.push((p, intensity));
+                }
+            }
+        },
+        Shading::Gouraud => {
+            let computed;
+            let normals =
+                if mesh.normals.is_empty() {
+                    let mut smooth = mesh.clone();
+                    smooth.compute_normals();
+                    computed = smooth.normals;
+                    &computed
+                } else {
+                    &mesh.normals
+                };
+            vertices.extend(mesh.positions.iter()
+                .zip(normals)
+                .map(|(&p, &normal)| (p, lambert(normal))));
+            indices.extend_from_slice(&mesh.indices);
+        },
+    }
+
+    let mvp = camera.view_projection();
+    let pipeline = Pipeline::new(
+        x_size,
+        y_size,
+        |&(p, intensity): &(Vec3<f32>, f32)| (mvp * Vec4::from_point(p), intensity),
+        |intensity: f32| {
+            let rgb = Rgb::new(color.r, color.g, color.b)
+                .map(|n| (n as f32 * intensity).min(255.0).round() as u8);
+            Rgba::from_translucent(rgb, color.a)
+        },
+    );
+    pipeline.draw_to_queue(queue, &vertices, &indices);
+}
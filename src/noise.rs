@@ -0,0 +1,311 @@
+use rand::{
+    Rng,
+    SeedableRng,
+    rngs::StdRng,
+    seq::SliceRandom,
+};
+use vek::*;
+
+/// Skewing factor from 2D space to the simplex grid.
+const F2: f32 = 0.366_025_4; // (sqrt(3) - 1) / 2
+
+/// Unskewing factor from the 2D simplex grid to space.
+const G2: f32 = 0.211_324_87; // (3 - sqrt(3)) / 6
+
+/// Skewing factor from 3D space to the simplex grid.
+const F3: f32 = 1.0 / 3.0;
+
+/// Unskewing factor from the 3D simplex grid to space.
+const G3: f32 = 1.0 / 6.0;
+
+/// Source of coherent gradient noise, in 2D and 3D.
+///
+/// Noise is deterministic for a given seed. Samples are roughly in `[-1, 1]`, and vary
+/// smoothly, with features about one unit apart.
+#[derive(Clone)]
+pub struct Noise {
+    /// Permutation of `0..256`, repeated twice to avoid wrapping indices.
+    perm: [u8; 512],
+}
+
+impl Noise {
+    /// Construct noise from a seed.
+    pub fn new(seed: u64) -> Self {
+        Noise::from_rng(&mut StdRng::seed_from_u64(seed))
+    }
+
+    /// Construct noise from a random number generator.
+    pub fn from_rng(rng: &mut impl Rng) -> Self {
+        let mut shuffled: Vec<u8> = (0..=255).collect();
+        shuffled.shuffle(rng);
+
+        let mut perm = [0; 512];
+        for (i, p) in perm.iter_mut().enumerate() {
+            *p = shuffled[i % 256];
+        }
+        Noise { perm }
+    }
+
+    fn hash(&self, i: i32) -> i32 {
+        self.perm[i as usize] as i32
+    }
+
+    /// Sample 2D Perlin noise.
+    pub fn perlin2(&self, p: Vec2<f32>) -> f32 {
+        let base = p.map(|n| n.floor());
+        let f = p - base;
+        let x = base.x as i32 & 255;
+        let y = base.y as i32 & 255;
+        let u = fade(f.x);
+        let v = fade(f.y);
+
+        // hash each corner of the cell
+        let a = self.hash(x) + y;
+        let b = self.hash(x + 1) + y;
+
+        lerp(
+            lerp(
+                grad2(self.hash(a), f.x, f.y),
+                grad2(self.hash(b), f.x - 1.0, f.y),
+                u,
+            ),
+            lerp(
+                grad2(self.hash(a + 1), f.x, f.y - 1.0),
+                grad2(self.hash(b + 1), f.x - 1.0, f.y - 1.0),
+                u,
+            ),
+            v,
+        )
+    }
+
+    /// Sample 3D Perlin noise.
+    pub fn perlin3(&self, p: Vec3<f32>) -> f32 {
+        let base = p.map(|n| n.floor());
+        let f = p - base;
+        let x = base.x as i32 & 255;
+        let y = base.y as i32 & 255;
+        let z = base.z as i32 & 255;
+        let u = fade(f.x);
+        let v = fade(f.y);
+        let w = fade(f.z);
+
+        // hash each corner of the cell
+        let a = self.hash(x) + y;
+        let aa = self.hash(a) + z;
+        let ab = self.hash(a + 1) + z;
+        let b = self.hash(x + 1) + y;
+        let ba = self.hash(b) + z;
+        let bb = self.hash(b + 1) + z;
+
+        lerp(
+            lerp(
+                lerp(
+                    grad3(self.hash(aa), f.x, f.y, f.z),
+                    grad3(self.hash(ba), f.x - 1.0, f.y, f.z),
+                    u,
+                ),
+                lerp(
+                    grad3(self.hash(ab), f.x, f.y - 1.0, f.z),
+                    grad3(self.hash(bb), f.x - 1.0, f.y - 1.0, f.z),
+                    u,
+                ),
+                v,
+            ),
+            lerp(
+                lerp(
+                    grad3(self.hash(aa + 1), f.x, f.y, f.z - 1.0),
+                    grad3(self.hash(ba + 1), f.x - 1.0, f.y, f.z - 1.0),
+                    u,
+                ),
+                lerp(
+                    grad3(self.hash(ab + 1), f.x, f.y - 1.0, f.z - 1.0),
+                    grad3(self.hash(bb + 1), f.x - 1.0, f.y - 1.0, f.z - 1.0),
+                    u,
+                ),
+                v,
+            ),
+            w,
+        )
+    }
+
+    /// Sample 2D simplex noise.
+    ///
+    /// This is cheaper than Perlin noise, and has fewer directional artifacts.
+    pub fn simplex2(&self, p: Vec2<f32>) -> f32 {
+        // skew to find the simplex cell
+        let s = (p.x + p.y) * F2;
+        let i = (p.x + s).floor();
+        let j = (p.y + s).floor();
+        let t = (i + j) * G2;
+        let x0 = p.x - (i - t);
+        let y0 = p.y - (j - t);
+
+        // which of the cell's two triangles the point is in
+        let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+
+        let x1 = x0 - i1 as f32 + G2;
+        let y1 = y0 - j1 as f32 + G2;
+        let x2 = x0 - 1.0 + 2.0 * G2;
+        let y2 = y0 - 1.0 + 2.0 * G2;
+
+        let ii = i as i32 & 255;
+        let jj = j as i32 & 255;
+        let g0 = self.hash(ii + self.hash(jj));
+        let g1 = self.hash(ii + i1 + self.hash(jj + j1));
+        let g2 = self.hash(ii + 1 + self.hash(jj + 1));
+
+        let corner = |g: i32, x: f32, y: f32| {
+            let t = 0.5 - x * x - y * y;
+            if t < 0.0 { 0.0 } else { t.powi(4) * grad2(g, x, y) }
+        };
+
+        // scale to roughly [-1, 1]
+        70.0 * (corner(g0, x0, y0) + corner(g1, x1, y1) + corner(g2, x2, y2))
+    }
+
+    /// Sample 3D simplex noise.
+    ///
+    /// This is cheaper than Perlin noise, and has fewer directional artifacts.
+    pub fn simplex3(&self, p: Vec3<f32>) -> f32 {
+        // skew to find the simplex cell
+        let s = (p.x + p.y + p.z) * F3;
+        let i = (p.x + s).floor();
+        let j = (p.y + s).floor();
+        let k = (p.z + s).floor();
+        let t = (i + j + k) * G3;
+        let x0 = p.x - (i - t);
+        let y0 = p.y - (j - t);
+        let z0 = p.z - (k - t);
+
+        // which of the cell's six tetrahedra the point is in
+        let (i1, j1, k1, i2, j2, k2) =
+            if x0 >= y0 && y0 >= z0 { (1, 0, 0, 1, 1, 0) }
+            else if x0 >= y0 && x0 >= z0 { (1, 0, 0, 1, 0, 1) }
+            else if x0 >= y0 { (0, 0, 1, 1, 0, 1) }
+            else if y0 < z0 { (0, 0, 1, 0, 1, 1) }
+            else if x0 < z0 { (0, 1, 0, 0, 1, 1) }
+            else { (0, 1, 0, 1, 1, 0) };
+
+        let x1 = x0 - i1 as f32 + G3;
+        let y1 = y0 - j1 as f32 + G3;
+        let z1 = z0 - k1 as f32 + G3;
+        let x2 = x0 - i2 as f32 + 2.0 * G3;
+        let y2 = y0 - j2 as f32 + 2.0 * G3;
+        let z2 = z0 - k2 as f32 + 2.0 * G3;
+        let x3 = x0 - 1.0 + 3.0 * G3;
+        let y3 = y0 - 1.0 + 3.0 * G3;
+        let z3 = z0 - 1.0 + 3.0 * G3;
+
+        let ii = i as i32 & 255;
+        let jj = j as i32 & 255;
+        let kk = k as i32 & 255;
+        let hash3 = |di: i32, dj: i32, dk: i32| {
+            self.hash(ii + di + self.hash(jj + dj + self.hash(kk + dk)))
+        };
+        let g0 = hash3(0, 0, 0);
+        let g1 = hash3(i1, j1, k1);
+        let g2 = hash3(i2, j2, k2);
+        let g3 = hash3(1, 1, 1);
+
+        let corner = |g: i32, x: f32, y: f32, z: f32| {
+            let t = 0.6 - x * x - y * y - z * z;
+            if t < 0.0 { 0.0 } else { t.powi(4) * grad3(g, x, y, z) }
+        };
+
+        // scale to roughly [-1, 1]
+        32.0 * (
+            corner(g0, x0, y0, z0)
+                + corner(g1, x1, y1, z1)
+                + corner(g2, x2, y2, z2)
+                + corner(g3, x3, y3, z3)
+        )
+    }
+}
+
+/// Fractal Brownian motion, which sums octaves of noise at increasing frequencies and
+/// decreasing amplitudes, for detail at many scales.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Fbm {
+    /// Number of octaves summed. Defaults to 5.
+    pub octaves: u32,
+    /// Frequency multiplier between octaves. Defaults to 2.
+    pub lacunarity: f32,
+    /// Amplitude multiplier between octaves. Defaults to 0.5.
+    pub gain: f32,
+}
+
+impl Default for Fbm {
+    fn default() -> Self {
+        Fbm {
+            octaves: 5,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+}
+
+impl Fbm {
+    /// Sum octaves of a 2D noise function, such as `|p| noise.simplex2(p)`.
+    ///
+    /// The sum is normalized by the total amplitude, so it stays in the noise's range.
+    pub fn sample2(&self, p: Vec2<f32>, noise: impl Fn(Vec2<f32>) -> f32) -> f32 {
+        self.sum(|frequency| noise(p * frequency))
+    }
+
+    /// Sum octaves of a 3D noise function, such as `|p| noise.simplex3(p)`.
+    ///
+    /// The sum is normalized by the total amplitude, so it stays in the noise's range.
+    pub fn sample3(&self, p: Vec3<f32>, noise: impl Fn(Vec3<f32>) -> f32) -> f32 {
+        self.sum(|frequency| noise(p * frequency))
+    }
+
+    fn sum(&self, octave: impl Fn(f32) -> f32) -> f32 {
+        let mut sum = 0.0;
+        let mut total = 0.0;
+        let mut frequency = 1.0;
+        let mut amplitude = 1.0;
+        for _ in 0..self.octaves {
+            sum += octave(frequency) * amplitude;
+            total += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.gain;
+        }
+
+        if total > 0.0 { sum / total } else { 0.0 }
+    }
+}
+
+/// Perlin's quintic smoothstep, `6t^5 - 15t^4 + 10t^3`.
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// Dot product of an offset with one of eight 2D gradients, chosen by a hash.
+fn grad2(hash: i32, x: f32, y: f32) -> f32 {
+    match hash & 7 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    }
+}
+
+/// Dot product of an offset with one of twelve 3D gradients, toward the edges of a cube,
+/// chosen by a hash.
+fn grad3(hash: i32, x: f32, y: f32, z: f32) -> f32 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v =
+        if h < 4 { y }
+        else if h == 12 || h == 14 { x }
+        else { z };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
@@ -0,0 +1,809 @@
+use std::{
+    thread,
+    ops::Range,
+    path::PathBuf,
+};
+
+use crate::color::{Quantize, ToneMap, srgb_encode_table, to_f32, to_u8};
+
+use crossbeam::queue::SegQueue;
+use image::RgbaImage;
+use vek::*;
+
+/// Instruction to paint a single pixel.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Paint {
+    pub x: usize,
+    pub y: usize,
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Paint {
+    /// Construct a paint from signed coordinates and a color, or `None` if the coordinates
+    /// are negative.
+    pub fn at(xy: Vec2<i32>, color: Rgba<u8>) -> Option<Self> {
+        if xy.x >= 0 && xy.y >= 0 {
+            Some(Paint {
+                x: xy.x as usize,
+                y: xy.y as usize,
+                r: color.r,
+                g: color.g,
+                b: color.b,
+                a: color.a,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// How a painted color is combined with the color already on the canvas.
+///
+/// Blending is computed on the CPU, with colors normalized to `[0, 1]`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum BlendMode {
+    /// Overwrite the canvas color.
+    #[default]
+    Replace,
+    /// Composite over the canvas color, weighted by the painted alpha.
+    AlphaOver,
+    /// Add to the canvas color, weighted by the painted alpha.
+    Additive,
+    /// Multiply the canvas color, weighted by the painted alpha.
+    Multiply,
+}
+
+impl BlendMode {
+    /// Combine a painted color with a canvas color.
+    pub fn blend(self, src: [u8; 4], dst: [u8; 4]) -> [u8; 4] {
+        if self == BlendMode::Replace {
+            return src;
+        }
+
+        let to_f32 = |c: [u8; 4]| Rgba::new(c[0], c[1], c[2], c[3])
+            .map(|n| n as f32 / 255.0);
+        let src = to_f32(src);
+        let dst = to_f32(dst);
+        let sa = src.a;
+
+        let out = match self {
+            BlendMode::Replace => unreachable!(),
+            BlendMode::AlphaOver => {
+                let a = sa + dst.a * (1.0 - sa);
+                let rgb =
+                    if a > 0.0 { (src.rgb() * sa + dst.rgb() * dst.a * (1.0 - sa)) / a }
+                    else { Rgb::zero() };
+                Rgba::from_translucent(rgb, a)
+            },
+            BlendMode::Additive => Rgba::from_translucent(
+                dst.rgb() + src.rgb() * sa,
+                dst.a + sa,
+            ),
+            BlendMode::Multiply => Rgba::from_translucent(
+                dst.rgb() * Rgb::lerp(Rgb::one(), src.rgb(), sa),
+                dst.a,
+            ),
+        };
+
+        let out = out.map(|n| (n.clamp(0.0, 1.0) * 255.0).round() as u8);
+        [out.r, out.g, out.b, out.a]
+    }
+}
+
+/// Instruction to paint a rectangular tile of pixels at once.
+///
+/// Submitting a whole tile in one allocation is much cheaper than pushing a `Paint` for
+/// each of its pixels. Parts of the tile which fall outside of the canvas are discarded.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct PaintBatch {
+    /// X coordinate of the tile's top-left pixel.
+    pub x: usize,
+    /// Y coordinate of the tile's top-left pixel.
+    pub y: usize,
+    /// Width of the tile.
+    pub x_size: usize,
+    /// Height of the tile.
+    pub y_size: usize,
+    /// Row-major RGBA pixels, of length `x_size * y_size`. Batches of any other length are
+    /// discarded by the window.
+    pub rgba: Box<[[u8; 4]]>,
+    /// How the pixels are combined with the canvas.
+    pub blend: BlendMode,
+}
+
+impl PaintBatch {
+    /// Construct a batch which replaces the canvas pixels, asserting that the pixel data
+    /// matches the tile size.
+    pub fn new(
+        x: usize,
+        y: usize,
+        x_size: usize,
+        y_size: usize,
+        rgba: impl Into<Box<[[u8; 4]]>>,
+    ) -> Self {
+        let rgba = rgba.into();
+        assert_eq!(
+            rgba.len(), x_size * y_size,
+            "paint batch pixel count does not match its size",
+        );
+        PaintBatch {
+            x,
+            y,
+            x_size,
+            y_size,
+            rgba,
+            blend: BlendMode::Replace,
+        }
+    }
+
+    /// Set the blend mode.
+    pub fn with_blend(mut self, blend: BlendMode) -> Self {
+        self.blend = blend;
+        self
+    }
+}
+
+/// Instruction to paint a single pixel, subject to a depth test.
+///
+/// The window keeps a depth buffer alongside the canvas. The paint is discarded if its depth
+/// is greater than the depth already stored for that pixel, and otherwise overwrites both.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PaintDepth {
+    pub x: usize,
+    pub y: usize,
+    pub rgba: [u8; 4],
+    pub depth: f32,
+}
+
+/// Instruction to write an object ID to a single pixel of the window's ID buffer.
+///
+/// IDs are written alongside colors, for identifying what was drawn at each pixel in debug
+/// renders, and are reported by `InputEvent::Hover` as the cursor moves. They're discarded
+/// unless the window was built with an `id_buffer`. ID 0 means no object.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct PaintId {
+    pub x: usize,
+    pub y: usize,
+    pub id: u32,
+}
+
+impl PaintId {
+    /// Construct a paint from signed coordinates and an ID, or `None` if the coordinates
+    /// are negative.
+    pub fn at(xy: Vec2<i32>, id: u32) -> Option<Self> {
+        if xy.x >= 0 && xy.y >= 0 {
+            Some(PaintId {
+                x: xy.x as usize,
+                y: xy.y as usize,
+                id,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Instruction to paint a single pixel with a linear HDR color.
+///
+/// Components may exceed 1, and are brought into range by the window's `ToneMap`, so the
+/// window should usually be configured as `linear` too.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PaintF {
+    pub x: usize,
+    pub y: usize,
+    pub rgba: Rgba<f32>,
+}
+
+impl PaintF {
+    /// Construct a paint from signed coordinates and a color, or `None` if the coordinates
+    /// are negative.
+    pub fn at(xy: Vec2<i32>, rgba: Rgba<f32>) -> Option<Self> {
+        if xy.x >= 0 && xy.y >= 0 {
+            Some(PaintF {
+                x: xy.x as usize,
+                y: xy.y as usize,
+                rgba,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Instruction sent to the window through the paint queue.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PaintCmd {
+    /// Paint a single pixel.
+    Paint(Paint),
+    /// Paint a single pixel with the given blend mode.
+    Blend(Paint, BlendMode),
+    /// Paint a tile of pixels.
+    Batch(PaintBatch),
+    /// Paint a single pixel, subject to a depth test.
+    Depth(PaintDepth),
+    /// Paint a single pixel with an HDR color, which is kept at full precision, and tone
+    /// mapped when presented.
+    Float(PaintF),
+    /// Write an object ID to a single pixel of the ID buffer.
+    Id(PaintId),
+    /// Fill every pixel of the canvas with a color, and reset the ID buffer to 0.
+    Clear(Rgba<u8>),
+    /// Fill a rectangle of the canvas with a color.
+    ///
+    /// Parts of the rectangle which fall outside of the canvas are discarded.
+    FillRect {
+        rect: Rect<usize, usize>,
+        color: Rgba<u8>,
+    },
+    /// Reset every pixel of the depth buffer to infinitely far away.
+    ClearDepth,
+    /// Restart accumulation, so the next sample painted to each pixel replaces it.
+    ///
+    /// Pixels keep their current color until they are painted again. If the window blends
+    /// frames, the next completed frame also replaces the blended frames. Has no effect
+    /// unless the window is accumulating or blending frames.
+    Reset,
+    /// Save the displayed pixels to a PNG file at the given path.
+    ///
+    /// The file is encoded and written in the background.
+    Capture(PathBuf),
+    /// Mark the end of a frame.
+    ///
+    /// If the window is double-buffered, it only displays pixels painted before the most
+    /// recent `EndFrame`, so partially painted frames are never shown.
+    EndFrame,
+}
+
+impl From<Paint> for PaintCmd {
+    fn from(paint: Paint) -> Self {
+        PaintCmd::Paint(paint)
+    }
+}
+
+impl From<PaintBatch> for PaintCmd {
+    fn from(batch: PaintBatch) -> Self {
+        PaintCmd::Batch(batch)
+    }
+}
+
+impl From<PaintDepth> for PaintCmd {
+    fn from(paint: PaintDepth) -> Self {
+        PaintCmd::Depth(paint)
+    }
+}
+
+impl From<PaintF> for PaintCmd {
+    fn from(paint: PaintF) -> Self {
+        PaintCmd::Float(paint)
+    }
+}
+
+impl From<PaintId> for PaintCmd {
+    fn from(paint: PaintId) -> Self {
+        PaintCmd::Id(paint)
+    }
+}
+
+/// Configuration of how a `PaintTarget` applies and presents paints.
+#[derive(Clone, Debug)]
+pub(crate) struct TargetOptions {
+    /// Whether to only display frames completed with `EndFrame`.
+    pub(crate) double_buffered: bool,
+    /// Whether to clear the canvas and depth buffer after each `EndFrame`.
+    pub(crate) clear_each_frame: bool,
+    /// Whether pixels are linear, and must be gamma encoded for images.
+    pub(crate) linear: bool,
+    /// Tone mapping for HDR paints.
+    pub(crate) tone_map: ToneMap,
+    /// Whether to average paints to each pixel, rather than overwriting it.
+    pub(crate) accumulate: bool,
+    /// Weight to blend each completed frame into the displayed pixels by, if blending.
+    pub(crate) frame_blend: Option<f32>,
+    /// Palette quantization applied to displayed pixels.
+    pub(crate) quantize: Option<Quantize>,
+    /// Whether to keep an object ID per pixel.
+    pub(crate) id_buffer: bool,
+}
+
+/// CPU-side canvas state, which paint instructions are applied to.
+pub(crate) struct PaintTarget {
+    x_size: usize,
+    y_size: usize,
+    /// Pixels which paints are applied to.
+    back: Vec<[u8; 4]>,
+    /// Pixels as of the last `EndFrame`, if double-buffered or blending frames.
+    front: Option<Vec<[u8; 4]>>,
+    /// Depth buffer for depth-tested paints.
+    depth: Vec<f32>,
+    /// Object ID of each pixel, if keeping an ID buffer.
+    ids: Option<Vec<u32>>,
+    /// HDR color of each pixel most recently painted with one, or `None` if no HDR colors
+    /// have been painted.
+    ///
+    /// These are tone mapped into `back` when presented, so the tone mapping can change
+    /// without repainting.
+    hdr: Option<Vec<Option<Rgba<f32>>>>,
+    /// Span of pixel indices whose HDR colors haven't been tone mapped into `back`.
+    unmapped: Option<Range<usize>>,
+    /// Sum and count of the samples painted to each pixel, if accumulating.
+    accum: Option<Vec<(Rgba<f32>, u32)>>,
+    /// Moving average of completed frames, if blending frames.
+    history: Option<Vec<Rgba<f32>>>,
+    /// Whether the next `EndFrame` replaces `history`, rather than blending into it.
+    history_reset: bool,
+    /// Quantized copy of the displayed pixels as of the last `take_dirty`, if quantizing.
+    quantized: Option<Vec<[u8; 4]>>,
+    /// Span of pixel indices in `back` changed since the last `EndFrame`, if double-buffered.
+    changed: Option<Range<usize>>,
+    /// Span of pixel indices in the displayed pixels changed since they were last taken.
+    ///
+    /// Many paints to the same pixels within a frame are coalesced in the pixel buffers, so
+    /// only this span needs to be uploaded, once.
+    dirty: Option<Range<usize>>,
+    options: TargetOptions,
+}
+
+impl PaintTarget {
+    /// Construct a cleared target of the given size.
+    pub(crate) fn new(x_size: usize, y_size: usize, options: TargetOptions) -> Self {
+        let len = x_size * y_size;
+        PaintTarget {
+            x_size,
+            y_size,
+            back: vec![[0x00, 0x00, 0x00, 0x00]; len],
+            front:
+                if options.double_buffered || options.frame_blend.is_some() {
+                    Some(vec![[0x00, 0x00, 0x00, 0x00]; len])
+                }
+                else { None },
+            depth: vec![f32::INFINITY; len],
+            ids:
+                if options.id_buffer { Some(vec![0; len]) }
+                else { None },
+            hdr: None,
+            unmapped: None,
+            accum:
+                if options.accumulate { Some(vec![(Rgba::zero(), 0); len]) }
+                else { None },
+            history: options.frame_blend.map(|_| vec![Rgba::zero(); len]),
+            history_reset: true,
+            quantized: options.quantize.as_ref().map(|_| vec![[0x00, 0x00, 0x00, 0x00]; len]),
+            changed: None,
+            dirty: None,
+            options,
+        }
+    }
+
+    /// Apply a paint instruction.
+    pub(crate) fn apply(&mut self, cmd: PaintCmd) {
+        let x_size = self.x_size;
+        let y_size = self.y_size;
+
+        match cmd {
+            PaintCmd::Paint(Paint {
+                                x,
+                                y,
+                                r,
+                                g,
+                                b,
+                                a,
+                            }) => {
+                // discard paints outside the canvas
+                // (such as those sent before a resize)
+                if x < x_size && y < y_size {
+                    let i: usize = y * x_size + x;
+
+                    let rgba = match self.accumulate(i, to_f32(Rgba::new(r, g, b, a))) {
+                        Some(average) => to_u8(average).into_array(),
+                        None => [r, g, b, a],
+                    };
+                    self.settle(i);
+                    self.back[i] = rgba;
+                    self.touch(i..i + 1);
+                }
+            },
+            PaintCmd::Blend(Paint {
+                                x,
+                                y,
+                                r,
+                                g,
+                                b,
+                                a,
+                            }, blend) => {
+                if x < x_size && y < y_size {
+                    let rgba = [r, g, b, a];
+                    let i: usize = y * x_size + x;
+
+                    self.settle(i);
+                    self.back[i] = blend.blend(rgba, self.back[i]);
+                    self.touch(i..i + 1);
+                }
+            },
+            PaintCmd::Batch(batch) => {
+                // the fields are public, so the size isn't necessarily checked by `new`
+                if Some(batch.rgba.len()) != batch.x_size.checked_mul(batch.y_size) {
+                    error!(
+                        "discarding paint batch of {} pixels, which does not match its {}x{} size",
+                        batch.rgba.len(), batch.x_size, batch.y_size,
+                    );
+                    return;
+                }
+
+                // clip the tile to the canvas
+                let x_end = usize::min(batch.x.saturating_add(batch.x_size), x_size);
+                let y_end = usize::min(batch.y.saturating_add(batch.y_size), y_size);
+
+                for y in batch.y..y_end {
+                    for x in batch.x..x_end {
+                        let rgba = batch.rgba[
+                            (y - batch.y) * batch.x_size + (x - batch.x)
+                        ];
+                        let i: usize = y * x_size + x;
+
+                        // accumulated samples are averaged rather than blended
+                        self.settle(i);
+                        let [r, g, b, a] = rgba;
+                        self.back[i] = match self.accumulate(i, to_f32(Rgba::new(r, g, b, a))) {
+                            Some(average) => to_u8(average).into_array(),
+                            None => batch.blend.blend(rgba, self.back[i]),
+                        };
+                    }
+                }
+                if batch.x < x_end && batch.y < y_end {
+                    self.touch(batch.y * x_size + batch.x..(y_end - 1) * x_size + x_end);
+                }
+            },
+            PaintCmd::Depth(PaintDepth {
+                                x,
+                                y,
+                                rgba,
+                                depth,
+                            }) => {
+                if x < x_size && y < y_size {
+                    let i: usize = y * x_size + x;
+
+                    // depth test
+                    if depth <= self.depth[i] {
+                        self.depth[i] = depth;
+                        self.settle(i);
+                        self.back[i] = rgba;
+                        self.touch(i..i + 1);
+                    }
+                }
+            },
+            PaintCmd::Float(PaintF { x, y, rgba }) => {
+                if x < x_size && y < y_size {
+                    let i: usize = y * x_size + x;
+
+                    // accumulate before tone mapping, so bright samples average correctly
+                    let rgba = self.accumulate(i, rgba).unwrap_or(rgba);
+                    self.set_hdr(i, rgba);
+                    self.touch(i..i + 1);
+                }
+            },
+            PaintCmd::Id(PaintId { x, y, id }) => {
+                if let Some(ref mut ids) = self.ids {
+                    if x < x_size && y < y_size {
+                        ids[y * x_size + x] = id;
+                    }
+                }
+            },
+            PaintCmd::Clear(color) => {
+                let rgba = [color.r, color.g, color.b, color.a];
+                for pixel in &mut self.back {
+                    *pixel = rgba;
+                }
+                self.clear_hdr();
+                self.clear_ids();
+                self.reset_accum();
+                self.touch(0..x_size * y_size);
+            },
+            PaintCmd::FillRect { rect, color } => {
+                let rgba = [color.r, color.g, color.b, color.a];
+
+                // clip the rectangle to the canvas
+                let x_end = usize::min(rect.x.saturating_add(rect.w), x_size);
+                let y_end = usize::min(rect.y.saturating_add(rect.h), y_size);
+
+                for y in rect.y..y_end {
+                    for x in rect.x..x_end {
+                        self.settle(y * x_size + x);
+                        self.back[y * x_size + x] = rgba;
+                    }
+                }
+                if rect.x < x_end && rect.y < y_end {
+                    self.touch(rect.y * x_size + rect.x..(y_end - 1) * x_size + x_end);
+                }
+            },
+            PaintCmd::ClearDepth => {
+                for depth in &mut self.depth {
+                    *depth = f32::INFINITY;
+                }
+            },
+            PaintCmd::Reset => {
+                self.reset_accum();
+                self.history_reset = true;
+            },
+            PaintCmd::Capture(path) => {
+                self.map_hdr();
+                save_capture(self.to_image(), path);
+            },
+            PaintCmd::EndFrame => {
+                self.map_hdr();
+                if self.history.is_some() {
+                    self.blend_frame();
+                } else if let (Some(front), Some(changed)) =
+                    (&mut self.front, self.changed.take()) {
+
+                    // only copy the pixels painted this frame
+                    front[changed.clone()].copy_from_slice(&self.back[changed.clone()]);
+                    self.dirty = Some(union(self.dirty.take(), changed));
+                }
+                if self.options.clear_each_frame {
+                    for pixel in &mut self.back {
+                        *pixel = [0x00, 0x00, 0x00, 0x00];
+                    }
+                    for depth in &mut self.depth {
+                        *depth = f32::INFINITY;
+                    }
+                    self.clear_hdr();
+                    self.clear_ids();
+                    self.touch(0..x_size * y_size);
+                }
+            },
+        }
+    }
+
+    /// Mark a span of pixel indices in `back` as changed.
+    fn touch(&mut self, span: Range<usize>) {
+        // without double-buffering, every paint is displayed immediately
+        if self.front.is_some() {
+            self.changed = Some(union(self.changed.take(), span));
+        } else {
+            self.dirty = Some(union(self.dirty.take(), span));
+        }
+    }
+
+    /// If accumulating, average a sample into a pixel, returning the average to store.
+    ///
+    /// The average is in the range of the samples, so averages of LDR paints are stored as
+    /// LDR, and only averages of HDR paints are tone mapped.
+    fn accumulate(&mut self, i: usize, sample: Rgba<f32>) -> Option<Rgba<f32>> {
+        let accum = self.accum.as_mut()?;
+        let (ref mut sum, ref mut count) = accum[i];
+        *sum += sample;
+        *count += 1;
+        Some(*sum / *count as f32)
+    }
+
+    /// Set a pixel's HDR color, which is tone mapped when next presented.
+    fn set_hdr(&mut self, i: usize, rgba: Rgba<f32>) {
+        let len = self.back.len();
+        self.hdr.get_or_insert_with(|| vec![None; len])[i] = Some(rgba);
+        self.unmapped = Some(union(self.unmapped.take(), i..i + 1));
+    }
+
+    /// If a pixel has an HDR color, tone map it into `back` and forget it, before it's
+    /// painted over or blended with an LDR color.
+    fn settle(&mut self, i: usize) {
+        if let Some(ref mut hdr) = self.hdr {
+            if let Some(rgba) = hdr[i].take() {
+                self.back[i] = tone_map(self.options.tone_map, rgba);
+            }
+        }
+    }
+
+    /// Tone map the HDR colors painted since this was last called into `back`.
+    fn map_hdr(&mut self) {
+        if let (Some(hdr), Some(span)) = (&self.hdr, self.unmapped.take()) {
+            for (pixel, rgba) in self.back[span.clone()].iter_mut().zip(&hdr[span]) {
+                if let Some(rgba) = *rgba {
+                    *pixel = tone_map(self.options.tone_map, rgba);
+                }
+            }
+        }
+    }
+
+    /// Forget every HDR color, when the whole canvas is painted over.
+    fn clear_hdr(&mut self) {
+        self.hdr = None;
+        self.unmapped = None;
+    }
+
+    /// Change the tone mapping, and re-map every HDR color with it.
+    ///
+    /// If double-buffered, this takes effect with the next completed frame.
+    pub(crate) fn set_tone_map(&mut self, tone_map: ToneMap) {
+        self.options.tone_map = tone_map;
+        if self.hdr.is_some() {
+            let len = self.back.len();
+            self.unmapped = Some(0..len);
+            self.touch(0..len);
+        }
+    }
+
+    /// Reset every object ID to 0, if keeping an ID buffer.
+    fn clear_ids(&mut self) {
+        if let Some(ref mut ids) = self.ids {
+            for id in ids {
+                *id = 0;
+            }
+        }
+    }
+
+    /// Discard all accumulated samples.
+    fn reset_accum(&mut self) {
+        if let Some(ref mut accum) = self.accum {
+            for pixel in accum {
+                *pixel = (Rgba::zero(), 0);
+            }
+        }
+    }
+
+    /// Blend the completed frame in `back` into the moving average, and display it.
+    fn blend_frame(&mut self) {
+        let weight =
+            if self.history_reset { 1.0 }
+            else { self.options.frame_blend.unwrap_or(1.0) };
+        self.history_reset = false;
+        self.changed = None;
+
+        if let (Some(history), Some(front)) = (&mut self.history, &mut self.front) {
+            let pixels = history.iter_mut().zip(front.iter_mut()).zip(&self.back);
+            for ((average, displayed), &[r, g, b, a]) in pixels {
+                let sample = to_f32(Rgba::new(r, g, b, a));
+                *average += (sample - *average) * weight;
+                let out = to_u8(*average);
+                *displayed = [out.r, out.g, out.b, out.a];
+            }
+            // every pixel moves towards the new frame, not just those painted
+            self.dirty = Some(0..front.len());
+        }
+    }
+
+    /// The pixels which should be displayed.
+    ///
+    /// If quantizing, these are only updated by `take_dirty`.
+    pub(crate) fn displayed(&self) -> &[[u8; 4]] {
+        if let Some(ref quantized) = self.quantized {
+            return quantized;
+        }
+        match self.front {
+            Some(ref front) => front,
+            None => &self.back,
+        }
+    }
+
+    /// Copy the displayed pixels into an image, gamma encoding them if they're linear.
+    pub(crate) fn to_image(&self) -> RgbaImage {
+        let mut buf: Vec<u8> = Vec::with_capacity(self.x_size * self.y_size * 4);
+        if self.options.linear {
+            let table = srgb_encode_table();
+            for &[r, g, b, a] in self.displayed() {
+                buf.extend_from_slice(&[
+                    table[r as usize],
+                    table[g as usize],
+                    table[b as usize],
+                    a,
+                ]);
+            }
+        } else {
+            for rgba in self.displayed() {
+                buf.extend_from_slice(rgba);
+            }
+        }
+        RgbaImage::from_raw(self.x_size as u32, self.y_size as u32, buf)
+            .expect("image buffer size mismatch")
+    }
+
+    /// The displayed color of a pixel, or `None` if it's outside of the canvas.
+    pub(crate) fn pixel(&self, x: usize, y: usize) -> Option<Rgba<u8>> {
+        if x < self.x_size && y < self.y_size {
+            let [r, g, b, a] = self.displayed()[y * self.x_size + x];
+            Some(Rgba::new(r, g, b, a))
+        } else {
+            None
+        }
+    }
+
+    /// The object ID of a pixel, or `None` if it's outside of the canvas or there's no ID
+    /// buffer.
+    pub(crate) fn id(&self, x: usize, y: usize) -> Option<u32> {
+        match self.ids {
+            Some(ref ids) if x < self.x_size && y < self.y_size => Some(ids[y * self.x_size + x]),
+            _ => None,
+        }
+    }
+
+    /// If the displayed pixels have changed since this was last called, get the span of
+    /// pixel indices which changed.
+    pub(crate) fn take_dirty(&mut self) -> Option<Range<usize>> {
+        // this is when the pixels are presented, so tone map them now
+        self.map_hdr();
+        let dirty = self.dirty.take()?;
+        if let (Some(quantize), Some(quantized)) =
+            (&self.options.quantize, &mut self.quantized) {
+
+            let pixels = match self.front {
+                Some(ref front) => front,
+                None => &self.back,
+            };
+            quantized.copy_from_slice(pixels);
+            quantize.apply(quantized, self.x_size, self.y_size);
+
+            // dithering spreads changes, so all quantized pixels may have changed
+            return Some(0..quantized.len());
+        }
+        Some(dirty)
+    }
+}
+
+/// Tone map an HDR color to a pixel.
+fn tone_map(tone_map: ToneMap, rgba: Rgba<f32>) -> [u8; 4] {
+    let rgb = tone_map.map(rgba.rgb());
+    let out = to_u8(Rgba::from_translucent(rgb, rgba.a));
+    [out.r, out.g, out.b, out.a]
+}
+
+/// Smallest span containing a span and an optional other span.
+fn union(a: Option<Range<usize>>, b: Range<usize>) -> Range<usize> {
+    match a {
+        Some(a) => usize::min(a.start, b.start)..usize::max(a.end, b.end),
+        None => b,
+    }
+}
+
+/// Save an image to a file in a background thread, logging any failure.
+pub(crate) fn save_capture(image: RgbaImage, path: PathBuf) {
+    thread::spawn(move || match image.save(&path) {
+        Ok(()) => info!("saved capture to {:?}", path),
+        Err(e) => error!("failed to save capture to {:?}: {}", path, e),
+    });
+}
+
+/// Push a paint to the queue, discarding it if its coordinates are negative.
+pub(crate) fn push_paint(queue: &SegQueue<PaintCmd>, xy: Vec2<i32>, color: Rgba<u8>) {
+    if let Some(paint) = Paint::at(xy, color) {
+        queue.push(paint.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target() -> PaintTarget {
+        PaintTarget::new(4, 4, TargetOptions {
+            double_buffered: false,
+            clear_each_frame: false,
+            linear: false,
+            tone_map: ToneMap::default(),
+            accumulate: false,
+            frame_blend: None,
+            quantize: None,
+            id_buffer: false,
+        })
+    }
+
+    #[test]
+    fn mismatched_batch_is_discarded() {
+        let mut target = target();
+        let mut batch = PaintBatch::new(0, 0, 2, 2, vec![[0xFF; 4]; 4]);
+        batch.y_size = 3;
+        target.apply(PaintCmd::Batch(batch));
+        assert!(target.displayed().iter().all(|&pixel| pixel == [0x00; 4]));
+        assert_eq!(target.take_dirty(), None);
+    }
+
+    #[test]
+    fn batch_far_off_canvas_is_clipped() {
+        let mut target = target();
+        let batch = PaintBatch::new(usize::MAX - 1, usize::MAX - 1, 2, 2, vec![[0xFF; 4]; 4]);
+        target.apply(PaintCmd::Batch(batch));
+        assert!(target.displayed().iter().all(|&pixel| pixel == [0x00; 4]));
+        assert_eq!(target.take_dirty(), None);
+    }
+}
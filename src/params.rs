@@ -0,0 +1,224 @@
+use crate::{
+    input::{InputEvent, Key},
+    paint::PaintCmd,
+    text::draw_text,
+};
+
+use crossbeam::queue::SegQueue;
+use vek::*;
+
+/// Current value of a parameter.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ParamValue {
+    Float(f32),
+    Bool(bool),
+    /// Index of the selected option.
+    Choice(usize),
+}
+
+/// Range and options of a parameter.
+#[derive(Clone, Debug, PartialEq)]
+enum ParamKind {
+    Float {
+        min: f32,
+        max: f32,
+        step: f32,
+    },
+    Bool,
+    Choice(Vec<String>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Param {
+    name: String,
+    kind: ParamKind,
+    value: ParamValue,
+}
+
+/// Set of named parameters which can be tweaked live with the keyboard, such as constants in
+/// a fragment function, without recompiling.
+///
+/// Number keys 1 through 9 select a parameter, and tab cycles through them. The up and right
+/// arrows increase the selected parameter, and the down and left arrows decrease it, by ten
+/// steps if shift is held. Bools are toggled, and choices cycle through their options.
+///
+/// Parameters are registered with the builder methods, and read by name. Reading a parameter
+/// which wasn't registered, or as the wrong type, panics.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Params {
+    params: Vec<Param>,
+    /// Index of the selected parameter.
+    selected: usize,
+    overlay: bool,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            params: Vec::new(),
+            selected: 0,
+            overlay: true,
+        }
+    }
+}
+
+impl Params {
+    /// Construct an empty set of parameters.
+    pub fn new() -> Self {
+        Params::default()
+    }
+
+    /// Register a float parameter, within `[min, max]`, adjusted by `step`.
+    pub fn float(mut self, name: &str, default: f32, min: f32, max: f32, step: f32) -> Self {
+        self.params.push(Param {
+            name: name.to_owned(),
+            kind: ParamKind::Float { min, max, step },
+            value: ParamValue::Float(default.max(min).min(max)),
+        });
+        self
+    }
+
+    /// Register a bool parameter.
+    pub fn toggle(mut self, name: &str, default: bool) -> Self {
+        self.params.push(Param {
+            name: name.to_owned(),
+            kind: ParamKind::Bool,
+            value: ParamValue::Bool(default),
+        });
+        self
+    }
+
+    /// Register a parameter which selects one of the given options, by index.
+    ///
+    /// Panics if `default` is out of range.
+    pub fn choice(mut self, name: &str, options: &[&str], default: usize) -> Self {
+        assert!(default < options.len(), "default choice out of range");
+        self.params.push(Param {
+            name: name.to_owned(),
+            kind: ParamKind::Choice(options.iter().map(|&option| option.to_owned()).collect()),
+            value: ParamValue::Choice(default),
+        });
+        self
+    }
+
+    /// Whether entry points which display parameters, such as `frag::fragment_params`,
+    /// draw their values over the canvas. Defaults to true.
+    pub fn overlay(mut self, overlay: bool) -> Self {
+        self.overlay = overlay;
+        self
+    }
+
+    /// Whether the parameters' values should be drawn over the canvas.
+    pub fn has_overlay(&self) -> bool {
+        self.overlay
+    }
+
+    /// Observe an input event, returning whether any parameter changed.
+    pub fn input(&mut self, event: &InputEvent) -> bool {
+        let (key, modifiers) = match *event {
+            InputEvent::KeyPress { key: Some(key), modifiers, .. } => (key, modifiers),
+            _ => return false,
+        };
+        if self.params.is_empty() {
+            return false;
+        }
+        let steps = if modifiers.shift { 10 } else { 1 };
+        let number_keys = [
+            Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5,
+            Key::Key6, Key::Key7, Key::Key8, Key::Key9,
+        ];
+
+        match key {
+            Key::Tab => self.selected = (self.selected + 1) % self.params.len(),
+            Key::Up | Key::Right => return self.adjust(steps),
+            Key::Down | Key::Left => return self.adjust(-steps),
+            key => {
+                if let Some(i) = number_keys.iter().position(|&k| k == key) {
+                    if i < self.params.len() {
+                        self.selected = i;
+                    }
+                }
+            },
+        }
+        false
+    }
+
+    /// Adjust the selected parameter by a number of steps, returning whether it changed.
+    fn adjust(&mut self, steps: i32) -> bool {
+        let param = &mut self.params[self.selected];
+        let value = match (&param.kind, param.value) {
+            (&ParamKind::Float { min, max, step }, ParamValue::Float(value)) => {
+                ParamValue::Float((value + step * steps as f32).max(min).min(max))
+            },
+            (&ParamKind::Bool, ParamValue::Bool(value)) => ParamValue::Bool(!value),
+            (ParamKind::Choice(options), ParamValue::Choice(i)) => {
+                let n = options.len() as i32;
+                ParamValue::Choice((i as i32 + steps).rem_euclid(n) as usize)
+            },
+            _ => unreachable!("parameter value doesn't match its kind"),
+        };
+        let changed = value != param.value;
+        param.value = value;
+        changed
+    }
+
+    /// Value of a parameter, or `None` if there's none of that name.
+    pub fn value(&self, name: &str) -> Option<ParamValue> {
+        self.params.iter()
+            .find(|param| param.name == name)
+            .map(|param| param.value)
+    }
+
+    /// Value of a float parameter.
+    pub fn get_f32(&self, name: &str) -> f32 {
+        match self.value(name) {
+            Some(ParamValue::Float(value)) => value,
+            _ => panic!("no float parameter named {:?}", name),
+        }
+    }
+
+    /// Value of a bool parameter.
+    pub fn get_bool(&self, name: &str) -> bool {
+        match self.value(name) {
+            Some(ParamValue::Bool(value)) => value,
+            _ => panic!("no bool parameter named {:?}", name),
+        }
+    }
+
+    /// Index of the selected option of a choice parameter.
+    pub fn get_choice(&self, name: &str) -> usize {
+        match self.value(name) {
+            Some(ParamValue::Choice(i)) => i,
+            _ => panic!("no choice parameter named {:?}", name),
+        }
+    }
+
+    /// A line of text for each parameter, showing its number key and value, with the
+    /// selected parameter marked.
+    pub fn lines(&self) -> Vec<String> {
+        self.params.iter()
+            .enumerate()
+            .map(|(i, param)| {
+                let marker = if i == self.selected { '>' } else { ' ' };
+                let value = match (&param.kind, param.value) {
+                    (_, ParamValue::Float(value)) => format!("{:.3}", value),
+                    (_, ParamValue::Bool(value)) => (if value { "on" } else { "off" }).to_owned(),
+                    (ParamKind::Choice(options), ParamValue::Choice(i)) => options[i].clone(),
+                    (_, ParamValue::Choice(i)) => i.to_string(),
+                };
+                format!("{}{} {}: {}", marker, i + 1, param.name, value)
+            })
+            .collect()
+    }
+
+    /// Draw the parameters' values in the built-in font, with their top-left corner at
+    /// `pos`, outlined in black so they're legible over any image.
+    pub fn draw_overlay(&self, queue: &SegQueue<PaintCmd>, pos: Vec2<i32>, color: Rgba<u8>) {
+        let text = self.lines().join("\n");
+        let shadow = Rgba::new(0x00, 0x00, 0x00, color.a);
+        for &offset in &[Vec2::new(1, 0), Vec2::new(0, 1), Vec2::new(1, 1)] {
+            draw_text(queue, pos + offset, &text, shadow);
+        }
+        draw_text(queue, pos, &text, color);
+    }
+}
@@ -0,0 +1,213 @@
+use crate::{
+    BlendMode,
+    PaintCmd,
+    SegQueue,
+    canvas::Canvas,
+    draw::wu,
+};
+
+use rayon::prelude::*;
+use vek::*;
+
+/// A single particle, in canvas pixel coordinates.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Particle {
+    pub position: Vec2<f32>,
+    /// Velocity, in pixels per second.
+    pub velocity: Vec2<f32>,
+    /// Color added to the canvas at full brightness, with components in `[0, 1]`.
+    pub color: Rgb<f32>,
+    /// Seconds since the particle was spawned.
+    pub age: f32,
+    /// Seconds the particle lives for.
+    pub lifetime: f32,
+}
+
+impl Particle {
+    pub fn new(position: Vec2<f32>, velocity: Vec2<f32>, color: Rgb<f32>, lifetime: f32) -> Self {
+        Particle {
+            position,
+            velocity,
+            color,
+            age: 0.0,
+            lifetime,
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.age < self.lifetime
+    }
+
+    /// Brightness of the particle, fading linearly from 1 when spawned to 0 when it dies.
+    pub fn brightness(&self) -> f32 {
+        if self.lifetime > 0.0 { (1.0 - self.age / self.lifetime).clamp(0.0, 1.0) }
+        else { 0.0 }
+    }
+}
+
+/// How each particle is drawn by `ParticleSystem::splat`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Splat {
+    /// A single pixel.
+    Point,
+    /// A disc with the given radius in pixels, fading smoothly from the center to its edge.
+    Soft {
+        radius: f32,
+    },
+    /// An anti-aliased line trailing behind the particle, as far as it moves in the given
+    /// number of seconds, which suggests motion blur.
+    Streak {
+        length: f32,
+    },
+}
+
+/// Pool of particles, with a parallel update step and additive splatting.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParticleSystem {
+    particles: Vec<Particle>,
+    capacity: usize,
+}
+
+impl ParticleSystem {
+    /// Construct an empty pool, which holds at most `capacity` particles.
+    pub fn new(capacity: usize) -> Self {
+        ParticleSystem {
+            particles: Vec::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Add a particle, unless the pool is full. Returns whether it was added.
+    pub fn spawn(&mut self, particle: Particle) -> bool {
+        if self.particles.len() >= self.capacity {
+            return false;
+        }
+        self.particles.push(particle);
+        true
+    }
+
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    pub fn particles_mut(&mut self) -> &mut [Particle] {
+        &mut self.particles
+    }
+
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    /// Advance every particle by `dt` seconds, in parallel with rayon, and remove the
+    /// particles which die.
+    ///
+    /// The acceleration function gives each particle's acceleration in pixels per second
+    /// squared, such as gravity or attraction to other bodies, and is integrated with
+    /// semi-implicit Euler.
+    pub fn update<A>(&mut self, dt: f32, acceleration: A)
+        where
+            A: Fn(&Particle) -> Vec2<f32> + Sync {
+
+        self.particles.par_iter_mut().for_each(|particle| {
+            particle.velocity += acceleration(particle) * dt;
+            particle.position += particle.velocity * dt;
+            particle.age += dt;
+        });
+        self.particles.retain(Particle::is_alive);
+    }
+
+    /// Accumulate every particle into a canvas of the given size, for additive blending.
+    ///
+    /// Overlapping particles add up, and the sum is clamped. Pixels which no particle
+    /// touches are transparent. Splatting is split across threads with rayon.
+    pub fn splat(&self, x_size: usize, y_size: usize, splat: Splat) -> Canvas {
+        let sums = self.particles.par_iter()
+            .fold(
+                || vec![Rgb::<f32>::zero(); x_size * y_size],
+                |mut sums, particle| {
+                    splat_particle(&mut sums, x_size, y_size, particle, splat);
+                    sums
+                },
+            )
+            .reduce_with(|mut a, b| {
+                for (a, b) in a.iter_mut().zip(b) {
+                    *a += b;
+                }
+                a
+            })
+            .unwrap_or_else(|| vec![Rgb::zero(); x_size * y_size]);
+
+        let mut canvas = Canvas::new(x_size, y_size);
+        for (pixel, sum) in canvas.pixels_mut().iter_mut().zip(sums) {
+            if sum != Rgb::zero() {
+                let rgb = sum.map(|n| (n.clamp(0.0, 1.0) * 255.0).round() as u8);
+                *pixel = [rgb.r, rgb.g, rgb.b, 0xFF];
+            }
+        }
+        canvas
+    }
+
+    /// Splat every particle additively onto the paint queue, as a single batch covering
+    /// the canvas of the given size.
+    ///
+    /// This pushes to the queue directly, so if it's bounded, call
+    /// `DrawContext::wait_for_capacity` first.
+    pub fn draw(&self, queue: &SegQueue<PaintCmd>, x_size: usize, y_size: usize, splat: Splat) {
+        let batch = self.splat(x_size, y_size, splat).to_batch(0, 0);
+        queue.push(batch.with_blend(BlendMode::Additive).into());
+    }
+
+    /// Splat every particle additively onto a canvas.
+    pub fn draw_to_canvas(&self, canvas: &mut Canvas, splat: Splat) {
+        let splatted = self.splat(canvas.x_size(), canvas.y_size(), splat);
+        canvas.blit(&splatted, Vec2::zero(), BlendMode::Additive);
+    }
+}
+
+/// Add a particle's contribution to a buffer of sums.
+fn splat_particle(
+    sums: &mut [Rgb<f32>],
+    x_size: usize,
+    y_size: usize,
+    particle: &Particle,
+    splat: Splat,
+) {
+    let color = particle.color * particle.brightness();
+    let mut add = |xy: Vec2<i32>, weight: f32| {
+        if xy.x >= 0 && xy.y >= 0 && (xy.x as usize) < x_size && (xy.y as usize) < y_size {
+            sums[xy.y as usize * x_size + xy.x as usize] += color * weight;
+        }
+    };
+
+    let p = particle.position;
+    match splat {
+        Splat::Point => add(p.map(|n| n.floor() as i32), 1.0),
+        Splat::Soft { radius } => {
+            let min = (p - radius).map(|n| n.floor() as i32);
+            let max = (p + radius).map(|n| n.ceil() as i32);
+            for y in min.y..=max.y {
+                for x in min.x..=max.x {
+                    // smooth falloff from the pixel center's distance
+                    let center = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                    let t = center.distance_squared(p) / (radius * radius);
+                    if t < 1.0 {
+                        add(Vec2::new(x, y), (1.0 - t) * (1.0 - t));
+                    }
+                }
+            }
+        },
+        Splat::Streak { length } => wu(p - particle.velocity * length, p, &mut add),
+    }
+}
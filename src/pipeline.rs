@@ -0,0 +1,247 @@
+use crate::{
+    PaintDepth,
+    PaintCmd,
+    SegQueue,
+    raster::rasterize_triangle_in,
+};
+
+use vek::*;
+
+/// Value which can be interpolated across a triangle, such as a vertex shader output.
+pub trait Interpolate: Copy {
+    /// Weighted sum of three values, where the weights sum to 1.
+    fn interpolate(a: Self, b: Self, c: Self, weights: Vec3<f32>) -> Self;
+}
+
+impl Interpolate for () {
+    fn interpolate(_: Self, _: Self, _: Self, _: Vec3<f32>) -> Self {}
+}
+
+impl Interpolate for f32 {
+    fn interpolate(a: Self, b: Self, c: Self, w: Vec3<f32>) -> Self {
+        a * w.x + b * w.y + c * w.z
+    }
+}
+
+/// Implement `Interpolate` for vek float vector types.
+macro_rules! interpolate_vec {
+    ($( $vec:ident ),*$(,)?) => {$(
+        impl Interpolate for $vec<f32> {
+            fn interpolate(a: Self, b: Self, c: Self, w: Vec3<f32>) -> Self {
+                a * w.x + b * w.y + c * w.z
+            }
+        }
+    )*}
+}
+
+interpolate_vec!(Vec2, Vec3, Vec4, Rgb, Rgba);
+
+/// Implement `Interpolate` for tuples of interpolatable values.
+macro_rules! interpolate_tuple {
+    ($( ($( $t:ident $i:tt ),*) ),*$(,)?) => {$(
+        impl<$( $t: Interpolate ),*> Interpolate for ($( $t, )*) {
+            fn interpolate(a: Self, b: Self, c: Self, w: Vec3<f32>) -> Self {
+                ($( $t::interpolate(a.$i, b.$i, c.$i, w), )*)
+            }
+        }
+    )*}
+}
+
+interpolate_tuple! {
+    (A 0),
+    (A 0, B 1),
+    (A 0, B 1, C 2),
+    (A 0, B 1, C 2, D 3),
+}
+
+/// Fragment produced by the pipeline.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Fragment {
+    /// Pixel coordinates.
+    pub xy: Vec2<i32>,
+    /// Depth, from 0 at the near plane to 1 at the far plane.
+    pub depth: f32,
+    /// Output of the fragment shader.
+    pub color: Rgba<u8>,
+}
+
+/// Minimal programmable triangle pipeline.
+///
+/// The vertex shader maps each vertex to a clip-space position and some varyings. The
+/// positions are perspective-divided and mapped onto the viewport, the triangles are
+/// rasterized, and the varyings are interpolated perspective-correctly and passed to the
+/// fragment shader to compute each fragment's color.
+///
+/// Clip-space follows OpenGL conventions: after perspective division, x and y range from -1
+/// to 1 with y pointing up, and z ranges from -1 at the near plane to 1 at the far plane.
+pub struct Pipeline<VS, FS> {
+    pub vertex_shader: VS,
+    pub fragment_shader: FS,
+    /// Width of the viewport, in pixels.
+    pub x_size: usize,
+    /// Height of the viewport, in pixels.
+    pub y_size: usize,
+    /// Whether varyings are interpolated perspective-correctly. Defaults to true.
+    ///
+    /// If false, varyings are interpolated affinely in screen space, which is slightly
+    /// cheaper, but warps textures on triangles viewed at an angle, like on early consoles.
+    pub perspective_correct: bool,
+}
+
+impl<VS, FS> Pipeline<VS, FS> {
+    /// Construct a pipeline for a viewport of the given size.
+    pub fn new(
+        x_size: usize,
+        y_size: usize,
+        vertex_shader: VS,
+        fragment_shader: FS,
+    ) -> Self {
+        Pipeline {
+            vertex_shader,
+            fragment_shader,
+            x_size,
+            y_size,
+            perspective_correct: true,
+        }
+    }
+
+    /// Set whether varyings are interpolated perspective-correctly.
+    pub fn with_perspective_correct(mut self, perspective_correct: bool) -> Self {
+        self.perspective_correct = perspective_correct;
+        self
+    }
+
+    /// Map a clip-space position to viewport coordinates, with depth in `[0, 1]`.
+    fn to_viewport(&self, clip: Vec4<f32>) -> Vec3<f32> {
+        let ndc = clip.xyz() / clip.w;
+        Vec3::new(
+            (ndc.x + 1.0) / 2.0 * self.x_size as f32,
+            (1.0 - ndc.y) / 2.0 * self.y_size as f32,
+            (ndc.z + 1.0) / 2.0,
+        )
+    }
+
+    /// Draw indexed triangles, passing each fragment inside the viewport to the callback.
+    ///
+    /// Every three indices form a triangle. Triangles are clipped against the near and far
+    /// planes, so geometry crossing or behind the camera is cut off rather than discarded
+    /// or distorted, and only the parts of triangles within the viewport are rasterized.
+    pub fn draw<V, Var>(
+        &self,
+        vertices: &[V],
+        indices: &[usize],
+        plot: &mut impl FnMut(Fragment),
+    )
+        where
+            VS: Fn(&V) -> (Vec4<f32>, Var),
+            FS: Fn(Var) -> Rgba<u8>,
+            Var: Interpolate {
+
+        let viewport = Aabr {
+            min: Vec2::zero(),
+            max: Vec2::new(self.x_size as i32, self.y_size as i32),
+        };
+
+        // buffers for clipped polygons, reused between triangles
+        let mut polygon: Vec<(Vec4<f32>, Var)> = Vec::new();
+        let mut scratch: Vec<(Vec4<f32>, Var)> = Vec::new();
+
+        for tri in indices.chunks_exact(3) {
+            // vertex stage
+            polygon.clear();
+            for &i in tri {
+                polygon.push((self.vertex_shader)(&vertices[i]));
+            }
+
+            // clip against the near plane (z >= -w) and the far plane (z <= w)
+            clip_polygon(&mut polygon, &mut scratch, |clip| clip.z + clip.w);
+            clip_polygon(&mut polygon, &mut scratch, |clip| clip.w - clip.z);
+
+            // the clipped polygon is convex, so split it into a fan of triangles
+            for i in 1..polygon.len().saturating_sub(1) {
+                let (clip0, var0) = polygon[0];
+                let (clip1, var1) = polygon[i];
+                let (clip2, var2) = polygon[i + 1];
+
+                // perspective divide, viewport transform
+                let screen0 = self.to_viewport(clip0);
+                let screen1 = self.to_viewport(clip1);
+                let screen2 = self.to_viewport(clip2);
+                let inv_w = Vec3::new(1.0 / clip0.w, 1.0 / clip1.w, 1.0 / clip2.w);
+
+                // rasterize
+                let (s0, s1, s2) = (screen0.xy(), screen1.xy(), screen2.xy());
+                rasterize_triangle_in(s0, s1, s2, viewport, &mut |xy, bary| {
+                    // depth is affine in screen space
+                    let depth = screen0.z * bary.x + screen1.z * bary.y + screen2.z * bary.z;
+
+                    // varyings are affine in clip space, so correct for perspective
+                    let weights =
+                        if self.perspective_correct {
+                            let persp = bary * inv_w;
+                            persp / (persp.x + persp.y + persp.z)
+                        } else {
+                            bary
+                        };
+                    let var = Var::interpolate(var0, var1, var2, weights);
+
+                    // fragment stage
+                    let color = (self.fragment_shader)(var);
+                    plot(Fragment { xy, depth, color });
+                });
+            }
+        }
+    }
+
+    /// Draw indexed triangles onto the paint queue as depth-tested paints.
+    ///
+    /// See `draw`.
+    pub fn draw_to_queue<V, Var>(
+        &self,
+        queue: &SegQueue<PaintCmd>,
+        vertices: &[V],
+        indices: &[usize],
+    )
+        where
+            VS: Fn(&V) -> (Vec4<f32>, Var),
+            FS: Fn(Var) -> Rgba<u8>,
+            Var: Interpolate {
+
+        self.draw(vertices, indices, &mut |frag| {
+            queue.push(PaintDepth {
+                x: frag.xy.x as usize,
+                y: frag.xy.y as usize,
+                rgba: [frag.color.r, frag.color.g, frag.color.b, frag.color.a],
+                depth: frag.depth,
+            }.into());
+        });
+    }
+}
+
+/// Clip a convex polygon of clip-space vertices against a plane, with Sutherland-Hodgman.
+///
+/// `dist` is positive on the side of the plane to keep, and the varyings of new vertices
+/// are interpolated linearly in clip space. `scratch` is used as a temporary buffer.
+fn clip_polygon<Var: Interpolate>(
+    polygon: &mut Vec<(Vec4<f32>, Var)>,
+    scratch: &mut Vec<(Vec4<f32>, Var)>,
+    dist: impl Fn(Vec4<f32>) -> f32,
+) {
+    scratch.clear();
+    for (i, &(clip_a, var_a)) in polygon.iter().enumerate() {
+        let (clip_b, var_b) = polygon[(i + 1) % polygon.len()];
+        let da = dist(clip_a);
+        let db = dist(clip_b);
+
+        if da >= 0.0 {
+            scratch.push((clip_a, var_a));
+        }
+        if (da >= 0.0) != (db >= 0.0) {
+            // where the edge crosses the plane
+            let t = da / (da - db);
+            let var = Var::interpolate(var_a, var_b, var_b, Vec3::new(1.0 - t, t, 0.0));
+            scratch.push((Vec4::lerp(clip_a, clip_b, t), var));
+        }
+    }
+    std::mem::swap(polygon, scratch);
+}
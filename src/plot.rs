@@ -0,0 +1,243 @@
+use crate::{
+    PaintCmd,
+    SegQueue,
+    draw::{clip_segment, draw_line, draw_line_aa, fill_circle_aa, fill_polygon},
+    text::{GLYPH_Y_SIZE, draw_text, text_size},
+};
+
+use vek::*;
+
+/// Length in pixels of tick marks, outwards from the axes.
+const TICK_LENGTH: i32 = 4;
+
+/// Gap in pixels between tick marks and their labels.
+const LABEL_GAP: i32 = 2;
+
+/// Mapping from a rectangle of data space to a rectangle of pixels, for drawing charts.
+///
+/// Data y points up, and pixel y points down. Tick labels are drawn outside of the area, so
+/// it should leave a margin for them.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Plot {
+    /// Pixel rectangle of the plotting area.
+    pub area: Rect<i32, i32>,
+    /// Data x values at the left and right edges of the area.
+    pub x_range: (f32, f32),
+    /// Data y values at the bottom and top edges of the area.
+    pub y_range: (f32, f32),
+}
+
+impl Plot {
+    pub fn new(area: Rect<i32, i32>, x_range: (f32, f32), y_range: (f32, f32)) -> Self {
+        Plot {
+            area,
+            x_range,
+            y_range,
+        }
+    }
+
+    /// Construct a plot with ranges fit to the finite points among the given series,
+    /// widened to the nearest tick.
+    pub fn fit<'a>(area: Rect<i32, i32>, series: impl IntoIterator<Item=&'a [Vec2<f32>]>) -> Self {
+        let mut min = Vec2::broadcast(f32::INFINITY);
+        let mut max = Vec2::broadcast(f32::NEG_INFINITY);
+        for &p in series.into_iter().flatten() {
+            if p.x.is_finite() && p.y.is_finite() {
+                min = Vec2::partial_min(min, p);
+                max = Vec2::partial_max(max, p);
+            }
+        }
+        let range = |min: f32, max: f32| {
+            if min > max { (0.0, 1.0) }
+            else if min == max { (min - 1.0, max + 1.0) }
+            else {
+                let step = tick_step(min, max, 5);
+                ((min / step).floor() * step, (max / step).ceil() * step)
+            }
+        };
+        Plot::new(area, range(min.x, max.x), range(min.y, max.y))
+    }
+
+    /// Pixel position of a point in data space.
+    pub fn to_screen(&self, p: Vec2<f32>) -> Vec2<f32> {
+        let tx = (p.x - self.x_range.0) / (self.x_range.1 - self.x_range.0);
+        let ty = (p.y - self.y_range.0) / (self.y_range.1 - self.y_range.0);
+        Vec2::new(
+            self.area.x as f32 + tx * self.area.w as f32,
+            self.area.y as f32 + (1.0 - ty) * self.area.h as f32,
+        )
+    }
+
+    /// Point in data space at a pixel position.
+    pub fn from_screen(&self, p: Vec2<f32>) -> Vec2<f32> {
+        let tx = (p.x - self.area.x as f32) / self.area.w as f32;
+        let ty = 1.0 - (p.y - self.area.y as f32) / self.area.h as f32;
+        Vec2::new(
+            self.x_range.0 + tx * (self.x_range.1 - self.x_range.0),
+            self.y_range.0 + ty * (self.y_range.1 - self.y_range.0),
+        )
+    }
+
+    /// Draw the left and bottom axes, with labeled ticks at round numbers.
+    pub fn draw_axes(&self, queue: &SegQueue<PaintCmd>, color: Rgba<u8>) {
+        let left = self.area.x;
+        let bottom = self.area.y + self.area.h - 1;
+        let right = self.area.x + self.area.w - 1;
+        draw_line(queue, Vec2::new(left, self.area.y), Vec2::new(left, bottom), color);
+        draw_line(queue, Vec2::new(left, bottom), Vec2::new(right, bottom), color);
+
+        let (x_min, x_max) = self.x_range;
+        let x_step = tick_step(x_min, x_max, (self.area.w / 60).max(2) as usize);
+        for x in ticks(x_min, x_max, x_step) {
+            let sx = self.to_screen(Vec2::new(x, 0.0)).x.round() as i32;
+            let sx = sx.min(right);
+            draw_line(queue, Vec2::new(sx, bottom), Vec2::new(sx, bottom + TICK_LENGTH), color);
+
+            let label = tick_label(x, x_step);
+            let size = text_size(&label, 1);
+            let pos = Vec2::new(sx - size.x as i32 / 2, bottom + TICK_LENGTH + LABEL_GAP);
+            draw_text(queue, pos, &label, color);
+        }
+
+        let (y_min, y_max) = self.y_range;
+        let y_step = tick_step(y_min, y_max, (self.area.h / 40).max(2) as usize);
+        for y in ticks(y_min, y_max, y_step) {
+            let sy = self.to_screen(Vec2::new(0.0, y)).y.round() as i32;
+            let sy = sy.min(bottom);
+            draw_line(queue, Vec2::new(left - TICK_LENGTH, sy), Vec2::new(left, sy), color);
+
+            let label = tick_label(y, y_step);
+            let size = text_size(&label, 1);
+            let pos = Vec2::new(
+                left - TICK_LENGTH - LABEL_GAP - size.x as i32,
+                sy - GLYPH_Y_SIZE as i32 / 2,
+            );
+            draw_text(queue, pos, &label, color);
+        }
+    }
+
+    /// Draw a series of points joined by anti-aliased lines, clipped to the area.
+    ///
+    /// Non-finite points break the line.
+    pub fn draw_line_series(
+        &self,
+        queue: &SegQueue<PaintCmd>,
+        points: &[Vec2<f32>],
+        color: Rgba<u8>,
+    ) {
+        let bounds = Aabr {
+            min: Vec2::new(self.area.x as f32, self.area.y as f32),
+            max: Vec2::new(
+                (self.area.x + self.area.w - 1) as f32,
+                (self.area.y + self.area.h - 1) as f32,
+            ),
+        };
+        for pair in points.windows(2) {
+            let a = self.to_screen(pair[0]);
+            let b = self.to_screen(pair[1]);
+            if let Some((a, b)) = clip_segment(a, b, bounds) {
+                draw_line_aa(queue, a, b, color);
+            }
+        }
+    }
+
+    /// Draw a marker at each point, skipping points outside of the area.
+    pub fn draw_scatter(
+        &self,
+        queue: &SegQueue<PaintCmd>,
+        points: &[Vec2<f32>],
+        marker: Marker,
+        radius: f32,
+        color: Rgba<u8>,
+    ) {
+        for &p in points {
+            let c = self.to_screen(p);
+            let inside = c.x >= self.area.x as f32
+                && c.y >= self.area.y as f32
+                && c.x <= (self.area.x + self.area.w) as f32
+                && c.y <= (self.area.y + self.area.h) as f32;
+            if !inside {
+                continue;
+            }
+            marker.draw(queue, c, radius, color);
+        }
+    }
+}
+
+/// Shape drawn at each point of a scatter series.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Marker {
+    /// Filled anti-aliased circle.
+    Circle,
+    /// Filled square.
+    Square,
+    /// Diagonal cross.
+    Cross,
+    /// Upright cross.
+    Plus,
+}
+
+impl Marker {
+    /// Draw the marker centered at a pixel position, extending `radius` pixels out.
+    pub fn draw(self, queue: &SegQueue<PaintCmd>, center: Vec2<f32>, radius: f32, color: Rgba<u8>) {
+        let r = radius;
+        let pixel = |offset: Vec2<f32>| (center + offset).map(|n| n.round() as i32);
+        let line = |a, b| draw_line(queue, pixel(a), pixel(b), color);
+        match self {
+            Marker::Circle => fill_circle_aa(queue, center, r, color),
+            Marker::Square => fill_polygon(queue, &[
+                center + Vec2::new(-r, -r),
+                center + Vec2::new(r, -r),
+                center + Vec2::new(r, r),
+                center + Vec2::new(-r, r),
+            ], color),
+            Marker::Cross => {
+                line(Vec2::new(-r, -r), Vec2::new(r, r));
+                line(Vec2::new(-r, r), Vec2::new(r, -r));
+            },
+            Marker::Plus => {
+                line(Vec2::new(-r, 0.0), Vec2::new(r, 0.0));
+                line(Vec2::new(0.0, -r), Vec2::new(0.0, r));
+            },
+        }
+    }
+}
+
+/// Round step between ticks, of 1, 2, or 5 times a power of ten, giving at most about
+/// `max_ticks` ticks over a range.
+pub fn tick_step(min: f32, max: f32, max_ticks: usize) -> f32 {
+    let span = (max - min).abs();
+    if !span.is_finite() || span <= 0.0 {
+        return 1.0;
+    }
+    let raw = span / max_ticks.max(1) as f32;
+    let magnitude = 10f32.powf(raw.log10().floor());
+    let norm = raw / magnitude;
+    let nice =
+        if norm <= 1.0 { 1.0 }
+        else if norm <= 2.0 { 2.0 }
+        else if norm <= 5.0 { 5.0 }
+        else { 10.0 };
+    nice * magnitude
+}
+
+/// Multiples of `step` within a range.
+pub fn ticks(min: f32, max: f32, step: f32) -> Vec<f32> {
+    let (min, max) = (min.min(max), min.max(max));
+    if !step.is_finite() || step <= 0.0 || !(max - min).is_finite() {
+        return Vec::new();
+    }
+    let first = (min / step).ceil() as i64;
+    let last = (max / step).floor() as i64;
+    (first..=last).map(|i| i as f32 * step).collect()
+}
+
+/// Format a tick value with just enough decimal places to tell ticks apart.
+fn tick_label(value: f32, step: f32) -> String {
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+    // avoid printing "-0"
+    let value =
+        if value.abs() < step * 1e-3 { 0.0 }
+        else { value };
+    format!("{:.*}", decimals, value)
+}
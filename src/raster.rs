@@ -0,0 +1,135 @@
+use crate::{
+    PaintDepth,
+    PaintCmd,
+    SegQueue,
+    paint::push_paint,
+};
+
+use vek::*;
+
+/// Signed area-like edge function, positive when `p` is to one side of the edge `a -> b`.
+fn edge(a: Vec2<f32>, b: Vec2<f32>, p: Vec2<f32>) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+/// Whether the edge `a -> b` is a top or left edge of a positive-area triangle.
+///
+/// Pixels exactly on an edge are only filled for top and left edges, so that triangles
+/// which share an edge never both fill the same pixel.
+fn is_top_left(a: Vec2<f32>, b: Vec2<f32>) -> bool {
+    let d = b - a;
+    (d.y == 0.0 && d.x > 0.0) || d.y < 0.0
+}
+
+/// Walk every pixel whose center lies inside a screen-space triangle.
+///
+/// The callback is passed each pixel's coordinates, along with the barycentric coordinates
+/// of its center with respect to `v0`, `v1`, and `v2`, which sum to 1. The triangle may be
+/// wound either way. Degenerate triangles cover no pixels.
+pub fn rasterize_triangle(
+    v0: Vec2<f32>,
+    v1: Vec2<f32>,
+    v2: Vec2<f32>,
+    callback: &mut impl FnMut(Vec2<i32>, Vec3<f32>),
+) {
+    let bounds = Aabr {
+        min: Vec2::broadcast(i32::MIN),
+        max: Vec2::broadcast(i32::MAX),
+    };
+    rasterize_triangle_in(v0, v1, v2, bounds, callback);
+}
+
+/// Walk every pixel whose center lies inside a screen-space triangle, and within the given
+/// bounds of pixel coordinates, which are inclusive of `min` and exclusive of `max`.
+///
+/// Only the pixels within the bounds are visited, so triangles reaching far off-screen are
+/// as cheap as their visible part. See `rasterize_triangle`.
+pub fn rasterize_triangle_in(
+    v0: Vec2<f32>,
+    v1: Vec2<f32>,
+    v2: Vec2<f32>,
+    bounds: Aabr<i32>,
+    callback: &mut impl FnMut(Vec2<i32>, Vec3<f32>),
+) {
+    // normalize winding so the area is positive
+    let area = edge(v0, v1, v2);
+    let (v1, v2, flipped) =
+        if area < 0.0 { (v2, v1, true) }
+        else { (v1, v2, false) };
+    let area = area.abs();
+    if area == 0.0 {
+        return;
+    }
+
+    let top_left_0 = is_top_left(v1, v2);
+    let top_left_1 = is_top_left(v2, v0);
+    let top_left_2 = is_top_left(v0, v1);
+
+    // pixel bounding box, clipped to the bounds
+    // (float to int casts saturate, so distant vertices can't overflow)
+    let min = Vec2::<f32>::partial_min(Vec2::<f32>::partial_min(v0, v1), v2);
+    let max = Vec2::<f32>::partial_max(Vec2::<f32>::partial_max(v0, v1), v2);
+    let x_min = i32::max(min.x.floor() as i32, bounds.min.x);
+    let y_min = i32::max(min.y.floor() as i32, bounds.min.y);
+    let x_max = i32::min(max.x.ceil() as i32, bounds.max.x);
+    let y_max = i32::min(max.y.ceil() as i32, bounds.max.y);
+
+    for y in y_min..y_max {
+        for x in x_min..x_max {
+            let p = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+
+            let w0 = edge(v1, v2, p);
+            let w1 = edge(v2, v0, p);
+            let w2 = edge(v0, v1, p);
+
+            let inside =
+                (w0 > 0.0 || (w0 == 0.0 && top_left_0))
+                && (w1 > 0.0 || (w1 == 0.0 && top_left_1))
+                && (w2 > 0.0 || (w2 == 0.0 && top_left_2));
+            if !inside {
+                continue;
+            }
+
+            let bary =
+                if flipped { Vec3::new(w0, w2, w1) / area }
+                else { Vec3::new(w0, w1, w2) / area };
+            callback(Vec2::new(x, y), bary);
+        }
+    }
+}
+
+/// Rasterize a solid-color screen-space triangle onto the paint queue.
+///
+/// Pixels with negative coordinates are discarded.
+pub fn draw_triangle(
+    queue: &SegQueue<PaintCmd>,
+    v0: Vec2<f32>,
+    v1: Vec2<f32>,
+    v2: Vec2<f32>,
+    color: Rgba<u8>,
+) {
+    rasterize_triangle(v0, v1, v2, &mut |xy, _| push_paint(queue, xy, color));
+}
+
+/// Rasterize a solid-color screen-space triangle onto the paint queue, with depth testing.
+///
+/// The vertices' z coordinates are interpolated across the triangle as the depth of each
+/// pixel. Pixels with negative coordinates are discarded.
+pub fn draw_triangle_depth(
+    queue: &SegQueue<PaintCmd>,
+    v0: Vec3<f32>,
+    v1: Vec3<f32>,
+    v2: Vec3<f32>,
+    color: Rgba<u8>,
+) {
+    rasterize_triangle(v0.xy(), v1.xy(), v2.xy(), &mut |xy, bary| {
+        if xy.x >= 0 && xy.y >= 0 {
+            queue.push(PaintDepth {
+                x: xy.x as usize,
+                y: xy.y as usize,
+                rgba: [color.r, color.g, color.b, color.a],
+                depth: v0.z * bary.x + v1.z * bary.y + v2.z * bary.z,
+            }.into());
+        }
+    });
+}
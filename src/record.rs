@@ -0,0 +1,200 @@
+use std::{
+    thread,
+    error::Error,
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use crossbeam::channel::{self, Sender, Receiver};
+use image::RgbaImage;
+use gif::SetParameter;
+
+/// File format for recordings.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum RecordFormat {
+    /// A single looping animated GIF.
+    Gif,
+    /// A directory of numbered PNG files.
+    PngSequence,
+}
+
+/// Configuration for recording the canvas over time.
+///
+/// Passed to `WindowBuilder::record`.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Recording {
+    /// File path for a GIF, or directory path for a PNG sequence.
+    pub path: PathBuf,
+    pub format: RecordFormat,
+    /// Capture one out of every this many presented frames, which is positive.
+    every: u32,
+}
+
+impl Recording {
+    /// Record every frame to an animated GIF at the given path.
+    pub fn gif(path: impl Into<PathBuf>) -> Self {
+        Recording {
+            path: path.into(),
+            format: RecordFormat::Gif,
+            every: 1,
+        }
+    }
+
+    /// Record every frame to numbered PNG files in the given directory, which is created if
+    /// it doesn't exist.
+    pub fn png_sequence(dir: impl Into<PathBuf>) -> Self {
+        Recording {
+            path: dir.into(),
+            format: RecordFormat::PngSequence,
+            every: 1,
+        }
+    }
+
+    /// Only capture one out of every `n` presented frames.
+    ///
+    /// Panics if `n` is 0.
+    pub fn every(mut self, n: u32) -> Self {
+        assert!(n > 0, "recording frame interval must be positive");
+        self.every = n;
+        self
+    }
+}
+
+/// Number of captured frames which may wait to be encoded, before further frames are dropped.
+const BUFFERED_FRAMES: usize = 8;
+
+/// A captured frame, sent to the encoding thread.
+struct Frame {
+    image: RgbaImage,
+    /// When the frame was captured.
+    time: Instant,
+    /// Index of the frame among those captured, including any dropped.
+    index: u64,
+}
+
+/// Handle to a background thread which encodes recorded frames.
+///
+/// If encoding falls behind rendering, frames are dropped rather than buffered without
+/// bound, so the GIF shows the previous frame for longer, and the PNG sequence skips
+/// numbers. Dropping the handle waits for the encoding to finish.
+pub(crate) struct Recorder {
+    every: u32,
+    frame_count: u64,
+    send: Option<Sender<Frame>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Recorder {
+    /// Spawn the encoding thread.
+    pub(crate) fn start(recording: Recording) -> Self {
+        let (send, recv) = channel::bounded::<Frame>(BUFFERED_FRAMES);
+        let every = recording.every;
+
+        let thread = thread::spawn(move || {
+            let result = match recording.format {
+                RecordFormat::Gif => encode_gif(&recording.path, recv),
+                RecordFormat::PngSequence => encode_png_sequence(&recording.path, recv),
+            };
+            match result {
+                Ok(()) => info!("saved recording to {:?}", recording.path),
+                Err(e) => error!("failed to save recording to {:?}: {}", recording.path, e),
+            }
+        });
+
+        Recorder {
+            every,
+            frame_count: 0,
+            send: Some(send),
+            thread: Some(thread),
+        }
+    }
+
+    /// Call once per presented frame, with a closure to copy the canvas, which is only
+    /// called if this frame should be captured.
+    pub(crate) fn frame(&mut self, capture: impl FnOnce() -> RgbaImage) {
+        if self.frame_count.is_multiple_of(self.every as u64) {
+            if let Some(ref send) = self.send {
+                // skip copying the canvas if the frame would be dropped anyway
+                let sent = !send.is_full() && send.try_send(Frame {
+                    image: capture(),
+                    time: Instant::now(),
+                    index: self.frame_count / self.every as u64,
+                }).is_ok();
+                if !sent {
+                    trace!("recording encoder is behind, dropping frame");
+                }
+            }
+        }
+        self.frame_count += 1;
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        // close the channel, then wait for the encoder to finish
+        self.send.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Encode frames into a looping animated GIF.
+fn encode_gif(
+    path: &Path,
+    recv: Receiver<Frame>,
+) -> Result<(), Box<dyn Error>> {
+    let mut frames = recv.iter().peekable();
+
+    // size the GIF by the first frame
+    let (x_size, y_size) = match frames.peek() {
+        Some(frame) => frame.image.dimensions(),
+        None => return Ok(()),
+    };
+
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = gif::Encoder::new(file, x_size as u16, y_size as u16, &[])?;
+    encoder.set(gif::Repeat::Infinite)?;
+
+    while let Some(Frame { image, time, .. }) = frames.next() {
+        // frames which don't match the first frame's size (due to resizing) are skipped
+        if image.dimensions() != (x_size, y_size) {
+            continue;
+        }
+
+        // delay until the next frame, in hundredths of a second
+        let delay = match frames.peek() {
+            Some(next) => (next.time - time).as_millis() / 10,
+            None => 0,
+        };
+
+        let mut pixels = image.into_raw();
+        let mut frame = gif::Frame::from_rgba_speed(
+            x_size as u16,
+            y_size as u16,
+            &mut pixels,
+            10,
+        );
+        frame.delay = delay.min(u16::MAX as u128) as u16;
+        encoder.write_frame(&frame)?;
+    }
+
+    Ok(())
+}
+
+/// Encode frames into numbered PNG files in a directory.
+fn encode_png_sequence(
+    dir: &Path,
+    recv: Receiver<Frame>,
+) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(dir)?;
+
+    for Frame { image, index, .. } in recv.iter() {
+        let path = dir.join(format!("frame-{:05}.png", index));
+        image.save(&path)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use rand::{Error, RngCore};
+
+use vek::*;
+
+/// Multiplier of the PCG linear congruential generator.
+const PCG_MULTIPLIER: u64 = 6_364_136_223_846_793_005;
+
+/// Mix the bits of a 64-bit value, with the SplitMix64 finalizer, so nearby inputs give
+/// unrelated outputs.
+pub fn mix64(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Hash a pixel, sample index, and seed into a 64-bit key.
+pub fn pixel_hash(x: u32, y: u32, sample: u32, seed: u64) -> u64 {
+    let key = mix64(seed);
+    let key = mix64(key ^ (x as u64 | (y as u64) << 32));
+    mix64(key ^ sample as u64)
+}
+
+/// Small, fast PCG32 random number generator, with 64 bits of state and a selectable
+/// stream.
+///
+/// This implements `rand::RngCore`, so it can be used with `rand::Rng`'s methods and
+/// distributions. Unlike `rand::thread_rng`, its output depends only on how it was seeded,
+/// so renders using it are reproducible.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Pcg32 {
+    state: u64,
+    /// Increment, which is odd, and selects the stream.
+    inc: u64,
+}
+
+impl Pcg32 {
+    /// Construct a generator from a seed and a stream index. Different streams give
+    /// independent sequences from the same seed.
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Pcg32 {
+            state: 0,
+            inc: (stream << 1) | 1,
+        };
+        rng.step();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.step();
+        rng
+    }
+
+    /// Construct the generator for a sample of a pixel, under a global seed.
+    ///
+    /// Each pixel and sample index gets its own deterministic stream, so a Monte Carlo
+    /// render gives the same result regardless of which thread renders which pixel, or in
+    /// what order, and progressive renders can add samples without repeating earlier ones.
+    pub fn for_pixel(x: u32, y: u32, sample: u32, seed: u64) -> Self {
+        let key = pixel_hash(x, y, sample, seed);
+        Pcg32::new(key, mix64(key))
+    }
+
+    /// Construct the generator for a sample of a pixel, at signed fragment coordinates.
+    ///
+    /// See `for_pixel`.
+    pub fn for_fragment(xy: Vec2<i32>, sample: u32, seed: u64) -> Self {
+        Pcg32::for_pixel(xy.x as u32, xy.y as u32, sample, seed)
+    }
+
+    fn step(&mut self) {
+        self.state = self.state.wrapping_mul(PCG_MULTIPLIER).wrapping_add(self.inc);
+    }
+
+    /// Uniform float in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        // the top 24 bits fill the mantissa exactly
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    /// Uniform point in `[0, 1)` squared.
+    pub fn next_vec2(&mut self) -> Vec2<f32> {
+        let x = self.next_f32();
+        Vec2::new(x, self.next_f32())
+    }
+}
+
+impl RngCore for Pcg32 {
+    fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.step();
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let low = self.next_u32() as u64;
+        low | (self.next_u32() as u64) << 32
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            let bytes = self.next_u32().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
@@ -0,0 +1,301 @@
+use std::f32::consts::PI;
+
+use vek::*;
+
+/// Ray with an origin and a direction.
+///
+/// Directions are not required to be normalized, but hit distances are measured in
+/// multiples of the direction's length.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3<f32>,
+    pub dir: Vec3<f32>,
+}
+
+impl Ray {
+    pub fn new(origin: Vec3<f32>, dir: Vec3<f32>) -> Self {
+        Ray { origin, dir }
+    }
+
+    /// Point at distance `t` along the ray.
+    pub fn at(&self, t: f32) -> Vec3<f32> {
+        self.origin + self.dir * t
+    }
+}
+
+/// Ray intersection with a surface.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Hit {
+    /// Distance along the ray.
+    pub t: f32,
+    /// Normalized surface normal, facing outwards.
+    pub normal: Vec3<f32>,
+    /// Surface coordinates, in `[0, 1]`.
+    pub uv: Vec2<f32>,
+}
+
+/// Shape which can be intersected with rays.
+pub trait Intersect {
+    /// Find the nearest intersection with `ray` with a distance in `[t_min, t_max]`.
+    fn intersect(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Hit>;
+
+    /// Bounding box of the shape.
+    fn bounds(&self) -> Aabb;
+}
+
+/// Axis-aligned bounding box.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3<f32>,
+    pub max: Vec3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3<f32>, max: Vec3<f32>) -> Self {
+        Aabb { min, max }
+    }
+
+    /// Box containing nothing, which is the identity for `union`.
+    pub fn empty() -> Self {
+        Aabb {
+            min: Vec3::broadcast(f32::INFINITY),
+            max: Vec3::broadcast(f32::NEG_INFINITY),
+        }
+    }
+
+    /// Smallest box containing both boxes.
+    pub fn union(self, other: Aabb) -> Self {
+        Aabb {
+            min: Vec3::partial_min(self.min, other.min),
+            max: Vec3::partial_max(self.max, other.max),
+        }
+    }
+
+    /// Smallest box containing this box and a point.
+    pub fn grow(self, point: Vec3<f32>) -> Self {
+        Aabb {
+            min: Vec3::partial_min(self.min, point),
+            max: Vec3::partial_max(self.max, point),
+        }
+    }
+
+    pub fn center(&self) -> Vec3<f32> {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn extent(&self) -> Vec3<f32> {
+        self.max - self.min
+    }
+
+    /// Surface area, or 0 for an empty box.
+    pub fn surface_area(&self) -> f32 {
+        let e = self.extent();
+        if e.x < 0.0 || e.y < 0.0 || e.z < 0.0 {
+            0.0
+        } else {
+            2.0 * (e.x * e.y + e.y * e.z + e.z * e.x)
+        }
+    }
+
+    /// Distances at which `ray` enters and exits the box, clipped to `[t_min, t_max]`.
+    ///
+    /// This is the slab test, which is cheaper than `intersect` when the normal isn't needed.
+    pub fn intersect_range(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<(f32, f32)> {
+        // division by zero gives infinities, which the slab test handles correctly
+        let inv_dir = Vec3::one() / ray.dir;
+        let t0 = (self.min - ray.origin) * inv_dir;
+        let t1 = (self.max - ray.origin) * inv_dir;
+        let near = Vec3::<f32>::partial_min(t0, t1);
+        let far = Vec3::<f32>::partial_max(t0, t1);
+
+        let enter = near.x.max(near.y).max(near.z).max(t_min);
+        let exit = far.x.min(far.y).min(far.z).min(t_max);
+        if enter <= exit {
+            Some((enter, exit))
+        } else {
+            None
+        }
+    }
+}
+
+impl Intersect for Aabb {
+    fn intersect(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Hit> {
+        let (enter, exit) = self.intersect_range(ray, t_min, t_max)?;
+
+        // hit the inside of the box if the ray starts within it
+        let t =
+            if enter > t_min { enter }
+            else if exit < t_max { exit }
+            else { return None };
+
+        // the face hit is the one on the axis where the hit point is closest to the surface
+        let p = ray.at(t);
+        let rel = (p - self.center()) / (self.extent() / 2.0);
+        let axis =
+            if rel.x.abs() >= rel.y.abs() && rel.x.abs() >= rel.z.abs() { 0 }
+            else if rel.y.abs() >= rel.z.abs() { 1 }
+            else { 2 };
+        let mut normal = Vec3::zero();
+        normal[axis] = rel[axis].signum();
+
+        // uv across the face, from the other two axes
+        let local = (p - self.min) / self.extent();
+        let uv = match axis {
+            0 => Vec2::new(local.z, local.y),
+            1 => Vec2::new(local.x, local.z),
+            _ => Vec2::new(local.x, local.y),
+        };
+
+        Some(Hit { t, normal, uv })
+    }
+
+    fn bounds(&self) -> Aabb {
+        *self
+    }
+}
+
+/// Sphere.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sphere {
+    pub center: Vec3<f32>,
+    pub radius: f32,
+}
+
+impl Sphere {
+    pub fn new(center: Vec3<f32>, radius: f32) -> Self {
+        Sphere { center, radius }
+    }
+}
+
+impl Intersect for Sphere {
+    fn intersect(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Hit> {
+        // solve |origin + t * dir - center|^2 = radius^2 for t
+        let oc = ray.origin - self.center;
+        let a = ray.dir.magnitude_squared();
+        let half_b = oc.dot(ray.dir);
+        let c = oc.magnitude_squared() - self.radius * self.radius;
+        let discriminant = half_b * half_b - a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let sqrt_d = discriminant.sqrt();
+
+        // try the nearer root first
+        let mut t = (-half_b - sqrt_d) / a;
+        if t < t_min || t > t_max {
+            t = (-half_b + sqrt_d) / a;
+            if t < t_min || t > t_max {
+                return None;
+            }
+        }
+
+        let normal = (ray.at(t) - self.center) / self.radius;
+
+        // longitude and latitude
+        let uv = Vec2::new(
+            normal.z.atan2(normal.x) / (2.0 * PI) + 0.5,
+            normal.y.clamp(-1.0, 1.0).acos() / PI,
+        );
+
+        Some(Hit { t, normal, uv })
+    }
+
+    fn bounds(&self) -> Aabb {
+        Aabb {
+            min: self.center - Vec3::broadcast(self.radius),
+            max: self.center + Vec3::broadcast(self.radius),
+        }
+    }
+}
+
+/// Triangle.
+///
+/// The normal faces the side from which the vertices appear counter-clockwise. Hits from
+/// either side are reported.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Triangle {
+    pub a: Vec3<f32>,
+    pub b: Vec3<f32>,
+    pub c: Vec3<f32>,
+}
+
+impl Triangle {
+    pub fn new(a: Vec3<f32>, b: Vec3<f32>, c: Vec3<f32>) -> Self {
+        Triangle { a, b, c }
+    }
+
+    /// Normalized geometric normal.
+    pub fn normal(&self) -> Vec3<f32> {
+        (self.b - self.a).cross(self.c - self.a).normalized()
+    }
+}
+
+impl Intersect for Triangle {
+    /// Möller-Trumbore intersection.
+    ///
+    /// The hit's uv is the barycentric weights of `b` and `c`.
+    fn intersect(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Hit> {
+        let edge1 = self.b - self.a;
+        let edge2 = self.c - self.a;
+        let p = ray.dir.cross(edge2);
+        let det = edge1.dot(p);
+
+        // ray parallel to the triangle
+        if det.abs() < f32::EPSILON {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        let s = ray.origin - self.a;
+        let u = s.dot(p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = s.cross(edge1);
+        let v = ray.dir.dot(q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = edge2.dot(q) * inv_det;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        Some(Hit {
+            t,
+            normal: edge1.cross(edge2).normalized(),
+            uv: Vec2::new(u, v),
+        })
+    }
+
+    fn bounds(&self) -> Aabb {
+        Aabb::empty()
+            .grow(self.a)
+            .grow(self.b)
+            .grow(self.c)
+    }
+}
+
+/// Find the nearest intersection of `ray` with any of `shapes`, by testing all of them,
+/// returning the index of the shape hit.
+pub fn closest_hit<T>(
+    shapes: &[T],
+    ray: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Option<(usize, Hit)>
+    where
+        T: Intersect {
+
+    let mut closest = None;
+    let mut t_max = t_max;
+    for (i, shape) in shapes.iter().enumerate() {
+        if let Some(hit) = shape.intersect(ray, t_min, t_max) {
+            t_max = hit.t;
+            closest = Some((i, hit));
+        }
+    }
+    closest
+}
@@ -0,0 +1,270 @@
+use crate::rng::Pcg32;
+
+use rand::{Rng, seq::SliceRandom};
+use vek::*;
+
+/// Bases of the Halton sequence's dimensions, which are the first primes.
+const PRIMES: [u32; 16] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53];
+
+/// Radical inverse of an index in a base: its digits mirrored about the radix point, in
+/// `[0, 1)`.
+///
+/// Panics if `base` is less than 2, which has no digits to mirror.
+pub fn radical_inverse(base: u32, mut index: u32) -> f32 {
+    assert!(base >= 2, "radical inverse base must be at least 2, got {}", base);
+    let inv_base = 1.0 / base as f64;
+    let mut inv = inv_base;
+    let mut value = 0.0;
+    while index > 0 {
+        value += (index % base) as f64 * inv;
+        index /= base;
+        inv *= inv_base;
+    }
+    // round down, so it never reaches 1
+    (value as f32).min(1.0 - f32::EPSILON / 2.0)
+}
+
+/// Component of a point of the Halton sequence, in `[0, 1)`.
+///
+/// Panics if `dimension` is 16 or more. Higher dimensions correlate visibly, so should be
+/// scrambled or padded with other sequences anyway.
+pub fn halton(index: u32, dimension: usize) -> f32 {
+    radical_inverse(PRIMES[dimension], index)
+}
+
+/// Point of the 2D Halton sequence, with bases 2 and 3, in `[0, 1)` squared.
+pub fn halton_2d(index: u32) -> Vec2<f32> {
+    Vec2::new(halton(index, 0), halton(index, 1))
+}
+
+/// Convert 32 fixed-point fractional bits to a float in `[0, 1)`.
+fn fraction(bits: u32) -> f32 {
+    (bits >> 8) as f32 / (1u32 << 24) as f32
+}
+
+/// Point of the 2D Sobol sequence, in `[0, 1)` squared, XOR-scrambled by the given bits.
+///
+/// Each power-of-two prefix of the sequence is stratified in every elementary interval,
+/// which makes it excellent for antialiasing and two-dimensional path tracing decisions.
+/// Scramble with a different value per pixel, such as from `rng::pixel_hash`, to
+/// decorrelate neighboring pixels while keeping the stratification.
+pub fn sobol_2d(index: u32, scramble: Vec2<u32>) -> Vec2<f32> {
+    // the first dimension is the base-2 radical inverse
+    let x = index.reverse_bits();
+
+    // the second dimension's generator matrix follows from the polynomial x + 1
+    let mut y = 0;
+    let mut v = 1 << 31;
+    let mut i = index;
+    while i != 0 {
+        if i & 1 != 0 {
+            y ^= v;
+        }
+        i >>= 1;
+        v ^= v >> 1;
+    }
+
+    Vec2::new(fraction(x ^ scramble.x), fraction(y ^ scramble.y))
+}
+
+/// Offset a point in `[0, 1)` squared by a random shift, wrapping around, which is a
+/// Cranley-Patterson rotation.
+///
+/// Rotating a shared sample pattern by per-pixel offsets, such as from `BlueNoise`, turns
+/// correlated aliasing into noise.
+pub fn rotate(point: Vec2<f32>, offset: Vec2<f32>) -> Vec2<f32> {
+    (point + offset).map(|n| n - n.floor())
+}
+
+/// Jittered grid of `x_strata` by `y_strata` points in `[0, 1)` squared, with one random
+/// point in each cell, in row-major order.
+pub fn stratified(x_strata: usize, y_strata: usize, rng: &mut impl Rng) -> Vec<Vec2<f32>> {
+    let mut points = Vec::with_capacity(x_strata * y_strata);
+    for y in 0..y_strata {
+        for x in 0..x_strata {
+            let jitter = Vec2::new(rng.gen::<f32>(), rng.gen::<f32>());
+            points.push(
+                (Vec2::new(x as f32, y as f32) + jitter)
+                    / Vec2::new(x_strata as f32, y_strata as f32)
+            );
+        }
+    }
+    points
+}
+
+/// `n` points in `[0, 1)`, with one random point in each of `n` equal intervals, shuffled.
+pub fn stratified_1d(n: usize, rng: &mut impl Rng) -> Vec<f32> {
+    let mut points: Vec<f32> = (0..n)
+        .map(|i| (i as f32 + rng.gen::<f32>()) / n as f32)
+        .collect();
+    points.shuffle(rng);
+    points
+}
+
+/// `n` points in `[0, 1)` squared which are stratified in each axis separately, which is
+/// Latin hypercube sampling.
+///
+/// Unlike `stratified`, this works for any number of points.
+pub fn latin_hypercube(n: usize, rng: &mut impl Rng) -> Vec<Vec2<f32>> {
+    let xs = stratified_1d(n, rng);
+    let ys = stratified_1d(n, rng);
+    xs.into_iter().zip(ys).map(|(x, y)| Vec2::new(x, y)).collect()
+}
+
+/// Standard deviation of the energy kernel used to generate blue noise, in pixels.
+const BLUE_NOISE_SIGMA: f32 = 1.5;
+
+/// Tileable blue noise texture, where each value in `[0, 1)` appears once, and similar
+/// values are spread far apart.
+///
+/// Thresholding blue noise gives evenly spaced points without clumps or regular
+/// structure, so using it to offset or dither per-pixel samples gives noise which is
+/// pleasant to look at and easy to filter away. It's generated with Ulichney's
+/// void-and-cluster method, which is quadratic in the number of texels, so sizes around
+/// 64x64 are practical, tiled across the canvas.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlueNoise {
+    size: usize,
+    /// Row-major values.
+    values: Vec<f32>,
+}
+
+impl BlueNoise {
+    /// Generate a square texture of the given size, deterministically from a seed.
+    pub fn generate(size: usize, seed: u64) -> Self {
+        let n = size * size;
+        if n == 0 {
+            return BlueNoise { size, values: Vec::new() };
+        }
+        let mut rng = Pcg32::new(seed, 0);
+
+        // gaussian energy kernel, by toroidal offset
+        let kernel: Vec<f32> = (0..n)
+            .map(|i| {
+                let wrap = |d: usize| usize::min(d, size - d) as f32;
+                let (dx, dy) = (wrap(i % size), wrap(i / size));
+                (-(dx * dx + dy * dy) / (2.0 * BLUE_NOISE_SIGMA * BLUE_NOISE_SIGMA)).exp()
+            })
+            .collect();
+        let mut pattern = VoidCluster {
+            size,
+            kernel,
+            ones: vec![false; n],
+            energy: vec![0.0; n],
+        };
+
+        // start from a sparse random pattern
+        let initial = usize::max(n / 10, 1);
+        while pattern.count() < initial {
+            let i = rng.gen_range(0, n);
+            if !pattern.ones[i] {
+                pattern.toggle(i);
+            }
+        }
+
+        // relax it, moving the tightest cluster into the largest void, until stable
+        for _ in 0..n {
+            let cluster = pattern.tightest_cluster();
+            pattern.toggle(cluster);
+            let void = pattern.largest_void();
+            pattern.toggle(void);
+            if void == cluster {
+                break;
+            }
+        }
+        let relaxed = pattern.ones.clone();
+        let relaxed_energy = pattern.energy.clone();
+
+        // rank the initial points, by repeatedly removing the tightest cluster
+        let mut ranks = vec![0; n];
+        let mut rank = pattern.count();
+        while rank > 0 {
+            rank -= 1;
+            let cluster = pattern.tightest_cluster();
+            pattern.toggle(cluster);
+            ranks[cluster] = rank;
+        }
+
+        // rank the rest, by repeatedly filling the largest void
+        pattern.ones = relaxed;
+        pattern.energy = relaxed_energy;
+        let mut rank = pattern.count();
+        while rank < n {
+            let void = pattern.largest_void();
+            pattern.toggle(void);
+            ranks[void] = rank;
+            rank += 1;
+        }
+
+        let values = ranks.into_iter().map(|rank| rank as f32 / n as f32).collect();
+        BlueNoise { size, values }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Value at a pixel, tiling the texture infinitely.
+    pub fn get(&self, x: i32, y: i32) -> f32 {
+        let size = self.size as i32;
+        if size == 0 {
+            return 0.0;
+        }
+        self.values[(y.rem_euclid(size) * size + x.rem_euclid(size)) as usize]
+    }
+
+    /// Pair of decorrelated values at a pixel, from opposite corners of the texture, for
+    /// offsetting 2D sample patterns with `rotate`.
+    pub fn get_2d(&self, x: i32, y: i32) -> Vec2<f32> {
+        let half = self.size as i32 / 2;
+        Vec2::new(self.get(x, y), self.get(x + half, y + half))
+    }
+}
+
+/// Binary pattern with its energy, for void-and-cluster blue noise generation.
+struct VoidCluster {
+    size: usize,
+    kernel: Vec<f32>,
+    ones: Vec<bool>,
+    /// Sum of the kernel centered on each one, at each texel.
+    energy: Vec<f32>,
+}
+
+impl VoidCluster {
+    fn count(&self) -> usize {
+        self.ones.iter().filter(|&&one| one).count()
+    }
+
+    /// Flip a texel, updating the energy.
+    fn toggle(&mut self, i: usize) {
+        self.ones[i] = !self.ones[i];
+        let sign = if self.ones[i] { 1.0 } else { -1.0 };
+        let size = self.size;
+        let (x0, y0) = (i % size, i / size);
+        for (j, energy) in self.energy.iter_mut().enumerate() {
+            let dx = (j % size + size - x0) % size;
+            let dy = (j / size + size - y0) % size;
+            *energy += sign * self.kernel[dy * size + dx];
+        }
+    }
+
+    /// Highest energy one.
+    fn tightest_cluster(&self) -> usize {
+        self.extreme(true, |a, b| a > b)
+    }
+
+    /// Lowest energy zero.
+    fn largest_void(&self) -> usize {
+        self.extreme(false, |a, b| a < b)
+    }
+
+    fn extreme(&self, one: bool, better: impl Fn(f32, f32) -> bool) -> usize {
+        let mut best: Option<usize> = None;
+        for (i, &energy) in self.energy.iter().enumerate() {
+            if self.ones[i] == one && best.is_none_or(|b| better(energy, self.energy[b])) {
+                best = Some(i);
+            }
+        }
+        best.expect("no texel to choose")
+    }
+}
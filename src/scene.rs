@@ -0,0 +1,360 @@
+use crate::{
+    PaintCmd,
+    SegQueue,
+    camera::Camera,
+    mesh::{self, Mesh},
+    shade::{Light, PbrMaterial},
+    texture::Texture,
+};
+
+use vek::*;
+
+/// Handle to a node in a `Scene`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    /// Position of the node in the scene, for indexing the results of
+    /// `Scene::world_transforms`.
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// Node in a `Scene`, with a transform relative to its parent, and optionally a mesh,
+/// light, or camera attached.
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub name: String,
+    /// Local-to-parent matrix. Defaults to identity.
+    pub transform: Mat4<f32>,
+    /// Index into `Scene::meshes`. Defaults to none.
+    pub mesh: Option<usize>,
+    /// Material of the attached mesh. Defaults to `PbrMaterial::default()`.
+    pub material: PbrMaterial,
+    /// Index into `Scene::textures` of the mesh's base color texture. Defaults to none.
+    pub texture: Option<usize>,
+    /// Light, in the node's local space. Defaults to none.
+    pub light: Option<Light>,
+    /// Camera, in the node's local space. Defaults to none.
+    pub camera: Option<Camera>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+impl Node {
+    /// Node with nothing attached and an identity transform.
+    pub fn new(name: impl Into<String>) -> Self {
+        Node {
+            name: name.into(),
+            transform: Mat4::identity(),
+            mesh: None,
+            material: PbrMaterial::default(),
+            texture: None,
+            light: None,
+            camera: None,
+            parent: None,
+            children: Vec::new(),
+        }
+    }
+
+    pub fn with_transform(mut self, transform: Mat4<f32>) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    pub fn with_mesh(mut self, mesh: usize, material: PbrMaterial) -> Self {
+        self.mesh = Some(mesh);
+        self.material = material;
+        self
+    }
+
+    pub fn with_light(mut self, light: Light) -> Self {
+        self.light = Some(light);
+        self
+    }
+
+    pub fn with_camera(mut self, camera: Camera) -> Self {
+        self.camera = Some(camera);
+        self
+    }
+}
+
+/// Mesh placed in the world by a node.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Instance {
+    pub node: NodeId,
+    /// Index into `Scene::meshes`.
+    pub mesh: usize,
+    /// Model-to-world matrix.
+    pub transform: Mat4<f32>,
+}
+
+/// Hierarchy of nodes, where each node's world transform is its parent's world transform
+/// times its local transform.
+///
+/// Meshes and textures are stored once and referenced by index, so many nodes can share
+/// them. Nodes are never removed, so `NodeId`s stay valid.
+#[derive(Clone, Debug, Default)]
+pub struct Scene {
+    nodes: Vec<Node>,
+    roots: Vec<NodeId>,
+    pub meshes: Vec<Mesh>,
+    pub textures: Vec<Texture>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Scene::default()
+    }
+
+    /// Add a mesh, returning its index, for `Node::with_mesh`.
+    pub fn add_mesh(&mut self, mesh: Mesh) -> usize {
+        self.meshes.push(mesh);
+        self.meshes.len() - 1
+    }
+
+    /// Add a node at the top of the hierarchy.
+    pub fn add(&mut self, node: Node) -> NodeId {
+        self.insert(node, None)
+    }
+
+    /// Add a node as the last child of `parent`.
+    pub fn add_child(&mut self, parent: NodeId, node: Node) -> NodeId {
+        self.insert(node, Some(parent))
+    }
+
+    fn insert(&mut self, mut node: Node, parent: Option<NodeId>) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        node.parent = parent;
+        node.children.clear();
+        self.nodes.push(node);
+        self.siblings_mut(parent).push(id);
+        id
+    }
+
+    /// Children of a node, or the roots.
+    fn siblings_mut(&mut self, parent: Option<NodeId>) -> &mut Vec<NodeId> {
+        match parent {
+            Some(parent) => &mut self.nodes[parent.0].children,
+            None => &mut self.roots,
+        }
+    }
+
+    /// Move a node, with its descendants, to be the last child of `parent`, or a root.
+    ///
+    /// Its local transform is kept, so it moves in the world along with its new parent.
+    /// Panics if `parent` is the node or one of its descendants.
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) {
+        let mut ancestor = parent;
+        while let Some(a) = ancestor {
+            assert!(a != id, "node cannot be parented to itself or its descendant");
+            ancestor = self.nodes[a.0].parent;
+        }
+
+        let old = self.nodes[id.0].parent;
+        self.siblings_mut(old).retain(|&sibling| sibling != id);
+        self.nodes[id.0].parent = parent;
+        self.siblings_mut(parent).push(id);
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.nodes[id.0]
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id.0].parent
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.nodes[id.0].children
+    }
+
+    /// Nodes without a parent.
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    /// All nodes, in the order they were added.
+    pub fn ids(&self) -> impl Iterator<Item=NodeId> {
+        (0..self.nodes.len()).map(NodeId)
+    }
+
+    /// First node with the given name.
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.ids().find(|&id| self.nodes[id.0].name == name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Local-to-world matrix of one node, by walking up its ancestors.
+    pub fn world_transform(&self, id: NodeId) -> Mat4<f32> {
+        let mut transform = self.nodes[id.0].transform;
+        let mut ancestor = self.nodes[id.0].parent;
+        while let Some(a) = ancestor {
+            transform = self.nodes[a.0].transform * transform;
+            ancestor = self.nodes[a.0].parent;
+        }
+        transform
+    }
+
+    /// Local-to-world matrices of all nodes, indexed by `NodeId::index`, propagated down
+    /// the hierarchy once.
+    pub fn world_transforms(&self) -> Vec<Mat4<f32>> {
+        let mut world = vec![Mat4::identity(); self.nodes.len()];
+        let mut stack: Vec<(NodeId, Mat4<f32>)> = self.roots
+            .iter()
+            .map(|&root| (root, Mat4::identity()))
+            .collect();
+        while let Some((id, parent)) = stack.pop() {
+            let node = &self.nodes[id.0];
+            let transform = parent * node.transform;
+            world[id.0] = transform;
+            stack.extend(node.children.iter().map(|&child| (child, transform)));
+        }
+        world
+    }
+
+    /// Every node's mesh, placed in the world.
+    pub fn instances(&self) -> Vec<Instance> {
+        let world = self.world_transforms();
+        self.ids()
+            .filter_map(|id| self.nodes[id.0].mesh.map(|mesh| Instance {
+                node: id,
+                mesh,
+                transform: world[id.0],
+            }))
+            .collect()
+    }
+
+    /// Every node's mesh transformed into world space, such as for building a BVH of the
+    /// scene's triangles with `Mesh::triangles`.
+    pub fn world_meshes(&self) -> Vec<(NodeId, Mesh)> {
+        self.instances()
+            .into_iter()
+            .map(|instance| {
+                (instance.node, self.meshes[instance.mesh].transformed(instance.transform))
+            })
+            .collect()
+    }
+
+    /// Every node's light, transformed into world space, for `shade::blinn_phong`.
+    pub fn lights(&self) -> Vec<Light> {
+        let world = self.world_transforms();
+        self.ids()
+            .filter_map(|id| {
+                let transform = world[id.0];
+                self.nodes[id.0].light.map(|light| match light {
+                    Light::Directional { dir, color } => Light::Directional {
+                        dir: (transform * Vec4::from_direction(dir)).xyz().normalized(),
+                        color,
+                    },
+                    Light::Point { position, color } => Light::Point {
+                        position: (transform * Vec4::from_point(position)).xyz(),
+                        color,
+                    },
+                })
+            })
+            .collect()
+    }
+
+    /// A node's camera, transformed into world space.
+    pub fn camera(&self, id: NodeId) -> Option<Camera> {
+        let camera = self.nodes[id.0].camera?;
+        let transform = self.world_transform(id);
+        let point = |p: Vec3<f32>| (transform * Vec4::from_point(p)).xyz();
+        let up = (transform * Vec4::from_direction(camera.up)).xyz();
+        Some(camera.look_at(point(camera.eye), point(camera.target), up))
+    }
+
+    /// Every node's camera, transformed into world space.
+    pub fn cameras(&self) -> Vec<(NodeId, Camera)> {
+        self.ids()
+            .filter_map(|id| self.camera(id).map(|camera| (id, camera)))
+            .collect()
+    }
+
+    /// Render every node's mesh onto the paint queue through the software pipeline, with
+    /// depth testing.
+    ///
+    /// Meshes are transformed into world space each call, so the fragment function is
+    /// passed the node, and the world-space normal and texture coordinates, as by
+    /// `mesh::render_mesh`.
+    pub fn render(
+        &self,
+        queue: &SegQueue<PaintCmd>,
+        camera: &Camera,
+        x_size: usize,
+        y_size: usize,
+        fragment: impl Fn(&Node, Vec3<f32>, Vec2<f32>) -> Rgba<u8>,
+    ) {
+        let view_projection = camera.view_projection();
+        for (id, mesh) in self.world_meshes() {
+            let node = &self.nodes[id.0];
+            mesh::render_mesh(queue, &mesh, view_projection, x_size, y_size, |normal, uv| {
+                fragment(node, normal, uv)
+            });
+        }
+    }
+}
+
+#[cfg(feature = "import")]
+impl From<crate::gltf::GltfScene> for Scene {
+    /// Convert a loaded glTF scene, keeping its node hierarchy, names, and local transforms.
+    ///
+    /// A node whose mesh has several primitives gets one child node per primitive, since a
+    /// `Node` holds a single mesh and material.
+    fn from(gltf: crate::gltf::GltfScene) -> Self {
+        let mut scene = Scene::new();
+        scene.meshes = gltf.meshes.iter().map(|gltf_mesh| gltf_mesh.mesh.clone()).collect();
+        for &root in &gltf.roots {
+            add_gltf_node(&mut scene, &gltf, root, None);
+        }
+        scene.textures = gltf.textures;
+        scene
+    }
+}
+
+/// Add a glTF node and its descendants to the scene.
+#[cfg(feature = "import")]
+fn add_gltf_node(
+    scene: &mut Scene,
+    gltf: &crate::gltf::GltfScene,
+    index: usize,
+    parent: Option<NodeId>,
+) {
+    let gltf_node = &gltf.nodes[index];
+    let name = gltf_node.name.clone().unwrap_or_default();
+    let primitive_node = |mesh: usize| {
+        let gltf_mesh = &gltf.meshes[mesh];
+        let mut node = Node::new(name.clone()).with_mesh(mesh, gltf_mesh.material);
+        node.texture = gltf_mesh.base_color_texture;
+        node
+    };
+
+    let mut node =
+        if let [mesh] = gltf_node.meshes[..] { primitive_node(mesh) }
+        else { Node::new(name.clone()) };
+    node.transform = gltf_node.transform;
+    node.camera = gltf_node.camera;
+    let id = scene.insert(node, parent);
+
+    if gltf_node.meshes.len() > 1 {
+        for &mesh in &gltf_node.meshes {
+            scene.add_child(id, primitive_node(mesh));
+        }
+    }
+    for &child in &gltf_node.children {
+        add_gltf_node(scene, gltf, child, Some(id));
+    }
+}
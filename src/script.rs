@@ -0,0 +1,620 @@
+use crate::{
+    color::to_u8,
+    frag::{FragOptions, frame_loop},
+    tiles::{TileOrder, TileRenderer},
+};
+
+use std::{
+    fmt,
+    io,
+    fs,
+    error::Error,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use vek::*;
+
+/// Most variables a script may use, including the built-in ones.
+const MAX_VARS: usize = 64;
+
+/// Names of the built-in variables, in slot order.
+const BUILTINS: [&str; 7] = ["x", "y", "px", "py", "w", "h", "t"];
+
+/// Error loading a fragment script.
+#[derive(Debug)]
+pub enum ScriptError {
+    /// Failed to read the file.
+    Io(io::Error),
+    /// The script is malformed.
+    Parse {
+        /// 1-based line number.
+        line: usize,
+        msg: String,
+    },
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScriptError::Io(e) => write!(f, "failed to read script: {}", e),
+            ScriptError::Parse { line, msg } => {
+                write!(f, "script parse error on line {}: {}", line, msg)
+            },
+        }
+    }
+}
+
+impl Error for ScriptError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ScriptError::Io(e) => Some(e),
+            ScriptError::Parse { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for ScriptError {
+    fn from(e: io::Error) -> Self {
+        ScriptError::Io(e)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+    Lt,
+    Gt,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Func {
+    Sin,
+    Cos,
+    Tan,
+    Atan2,
+    Abs,
+    Sqrt,
+    Exp,
+    Log,
+    Floor,
+    Fract,
+    Min,
+    Max,
+    Clamp,
+    Mix,
+    Step,
+    Smoothstep,
+    Length,
+}
+
+impl Func {
+    /// Look up a function and its number of arguments by name.
+    fn by_name(name: &str) -> Option<(Func, usize)> {
+        Some(match name {
+            "sin" => (Func::Sin, 1),
+            "cos" => (Func::Cos, 1),
+            "tan" => (Func::Tan, 1),
+            "atan2" => (Func::Atan2, 2),
+            "abs" => (Func::Abs, 1),
+            "sqrt" => (Func::Sqrt, 1),
+            "exp" => (Func::Exp, 1),
+            "log" => (Func::Log, 1),
+            "floor" => (Func::Floor, 1),
+            "fract" => (Func::Fract, 1),
+            "min" => (Func::Min, 2),
+            "max" => (Func::Max, 2),
+            "clamp" => (Func::Clamp, 3),
+            "mix" => (Func::Mix, 3),
+            "step" => (Func::Step, 2),
+            "smoothstep" => (Func::Smoothstep, 3),
+            "length" => (Func::Length, 2),
+            _ => return None,
+        })
+    }
+
+    fn call(self, args: &[f32]) -> f32 {
+        match self {
+            Func::Sin => args[0].sin(),
+            Func::Cos => args[0].cos(),
+            Func::Tan => args[0].tan(),
+            Func::Atan2 => args[0].atan2(args[1]),
+            Func::Abs => args[0].abs(),
+            Func::Sqrt => args[0].sqrt(),
+            Func::Exp => args[0].exp(),
+            Func::Log => args[0].ln(),
+            Func::Floor => args[0].floor(),
+            Func::Fract => args[0] - args[0].floor(),
+            Func::Min => args[0].min(args[1]),
+            Func::Max => args[0].max(args[1]),
+            Func::Clamp => args[0].max(args[1]).min(args[2]),
+            Func::Mix => args[0] + (args[1] - args[0]) * args[2],
+            Func::Step => if args[1] < args[0] { 0.0 } else { 1.0 },
+            Func::Smoothstep => {
+                let t = ((args[2] - args[0]) / (args[1] - args[0])).clamp(0.0, 1.0);
+                t * t * (3.0 - 2.0 * t)
+            },
+            Func::Length => args[0].hypot(args[1]),
+        }
+    }
+}
+
+/// Parsed expression, with variables resolved to slots.
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Const(f32),
+    Var(usize),
+    Neg(Box<Expr>),
+    Bin(BinOp, Box<Expr>, Box<Expr>),
+    Call(Func, Vec<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, vars: &[f32; MAX_VARS]) -> f32 {
+        match *self {
+            Expr::Const(n) => n,
+            Expr::Var(i) => vars[i],
+            Expr::Neg(ref a) => -a.eval(vars),
+            Expr::Bin(op, ref a, ref b) => {
+                let (a, b) = (a.eval(vars), b.eval(vars));
+                match op {
+                    BinOp::Add => a + b,
+                    BinOp::Sub => a - b,
+                    BinOp::Mul => a * b,
+                    BinOp::Div => a / b,
+                    BinOp::Rem => a.rem_euclid(b),
+                    BinOp::Pow => a.powf(b),
+                    BinOp::Lt => if a < b { 1.0 } else { 0.0 },
+                    BinOp::Gt => if a > b { 1.0 } else { 0.0 },
+                }
+            },
+            Expr::Call(func, ref args) => {
+                let mut values = [0.0; 3];
+                for (value, arg) in values.iter_mut().zip(args) {
+                    *value = arg.eval(vars);
+                }
+                func.call(&values)
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Num(f32),
+    Ident(String),
+    Sym(char),
+}
+
+fn tokenize(line: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut num = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit() || **c == '.') {
+                num.push(c);
+                chars.next();
+            }
+
+            // exponent, such as in `1e-3`, if there are digits after the `e` and its sign
+            let mut rest = chars.clone();
+            if let Some(e) = rest.next().filter(|&c| c == 'e' || c == 'E') {
+                let mut exponent = e.to_string();
+                if let Some(sign) = rest.next_if(|&c| c == '+' || c == '-') {
+                    exponent.push(sign);
+                }
+                if rest.peek().is_some_and(|c| c.is_ascii_digit()) {
+                    while let Some(c) = rest.next_if(|c| c.is_ascii_digit()) {
+                        exponent.push(c);
+                    }
+                    num.push_str(&exponent);
+                    chars = rest;
+                }
+            }
+
+            let n = num.parse().map_err(|_| format!("invalid number {:?}", num))?;
+            tokens.push(Token::Num(n));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut ident = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_alphanumeric() || **c == '_') {
+                ident.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Ident(ident));
+        } else if "+-*/%^<>=(),".contains(c) {
+            tokens.push(Token::Sym(c));
+            chars.next();
+        } else {
+            return Err(format!("unexpected character {:?}", c));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser over the tokens of one line.
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    /// Names of the variables in scope, by slot.
+    vars: &'a [String],
+}
+
+impl<'a> Parser<'a> {
+    fn peek_sym(&self, sym: char) -> bool {
+        self.tokens.get(self.pos) == Some(&Token::Sym(sym))
+    }
+
+    fn expect_sym(&mut self, sym: char) -> Result<(), String> {
+        if self.peek_sym(sym) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("expected {:?}", sym))
+        }
+    }
+
+    // expr := sum (('<' | '>') sum)?
+    fn expr(&mut self) -> Result<Expr, String> {
+        let a = self.sum()?;
+        for &(sym, op) in &[('<', BinOp::Lt), ('>', BinOp::Gt)] {
+            if self.peek_sym(sym) {
+                self.pos += 1;
+                return Ok(Expr::Bin(op, Box::new(a), Box::new(self.sum()?)));
+            }
+        }
+        Ok(a)
+    }
+
+    // sum := product (('+' | '-') product)*
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut a = self.product()?;
+        loop {
+            let op =
+                if self.peek_sym('+') { BinOp::Add }
+                else if self.peek_sym('-') { BinOp::Sub }
+                else { return Ok(a); };
+            self.pos += 1;
+            a = Expr::Bin(op, Box::new(a), Box::new(self.product()?));
+        }
+    }
+
+    // product := unary (('*' | '/' | '%') unary)*
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut a = self.unary()?;
+        loop {
+            let op =
+                if self.peek_sym('*') { BinOp::Mul }
+                else if self.peek_sym('/') { BinOp::Div }
+                else if self.peek_sym('%') { BinOp::Rem }
+                else { return Ok(a); };
+            self.pos += 1;
+            a = Expr::Bin(op, Box::new(a), Box::new(self.unary()?));
+        }
+    }
+
+    // unary := '-' unary | power
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.peek_sym('-') {
+            self.pos += 1;
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.power()
+    }
+
+    // power := atom ('^' unary)?
+    fn power(&mut self) -> Result<Expr, String> {
+        let a = self.atom()?;
+        if self.peek_sym('^') {
+            self.pos += 1;
+            return Ok(Expr::Bin(BinOp::Pow, Box::new(a), Box::new(self.unary()?)));
+        }
+        Ok(a)
+    }
+
+    // atom := number | name | name '(' args ')' | '(' expr ')'
+    fn atom(&mut self) -> Result<Expr, String> {
+        let token = self.tokens.get(self.pos).cloned().ok_or("unexpected end of line")?;
+        self.pos += 1;
+        match token {
+            Token::Num(n) => Ok(Expr::Const(n)),
+            Token::Sym('(') => {
+                let a = self.expr()?;
+                self.expect_sym(')')?;
+                Ok(a)
+            },
+            Token::Ident(ref name) if self.peek_sym('(') => {
+                let (func, arity) = Func::by_name(name)
+                    .ok_or_else(|| format!("unknown function {:?}", name))?;
+                self.pos += 1;
+                let mut args = Vec::new();
+                while !self.peek_sym(')') {
+                    if !args.is_empty() {
+                        self.expect_sym(',')?;
+                    }
+                    args.push(self.expr()?);
+                }
+                self.pos += 1;
+                if args.len() != arity {
+                    let plural = if arity == 1 { "" } else { "s" };
+                    return Err(format!(
+                        "{} takes {} argument{}, not {}",
+                        name, arity, plural, args.len(),
+                    ));
+                }
+                Ok(Expr::Call(func, args))
+            },
+            // bindings shadow the constants, like the built-in variables
+            Token::Ident(ref name) => match self.vars.iter().rposition(|var| var == name) {
+                Some(i) => Ok(Expr::Var(i)),
+                None => match name.as_str() {
+                    "pi" => Ok(Expr::Const(std::f32::consts::PI)),
+                    "tau" => Ok(Expr::Const(2.0 * std::f32::consts::PI)),
+                    _ => Err(format!("unknown variable {:?}", name)),
+                },
+            },
+            Token::Sym(c) => Err(format!("unexpected {:?}", c)),
+        }
+    }
+}
+
+/// Fragment function parsed from a small expression language, for live coding.
+///
+/// A script is a sequence of lines, each either blank, a `#` comment, a binding
+/// `let name = expr`, or an output `r = expr`, `g = expr`, `b = expr`, or `a = expr`.
+/// Outputs are in `[0, 1]`, and default to 0, except alpha which defaults to 1. Later lines
+/// may refer to earlier bindings, which shadow any earlier binding or built-in variable of
+/// the same name, and to the built-in variables:
+///
+/// - `x` and `y`, the pixel center in `[0, 1]`, with y pointing down
+/// - `px` and `py`, the pixel coordinates
+/// - `w` and `h`, the canvas size
+/// - `t`, the animation time in seconds
+/// - `pi` and `tau`
+///
+/// Numbers may have a fraction and an exponent, such as `1.5e-3`. Expressions support `+`,
+/// `-`, `*`, `/`, `%`, `^`, and comparisons `<` and `>` which give 0 or 1, where `^` is
+/// right-associative and binds tighter than negation, so `-2^2` is -4. The functions
+/// `sin`, `cos`, `tan`, `atan2`, `abs`, `sqrt`, `exp`, `log`, `floor`, `fract`, `min`,
+/// `max`, `clamp`, `mix`, `step`, `smoothstep`, and `length` behave as in GLSL, except that
+/// `length` takes two scalars.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Script {
+    /// Bound expressions, which fill the slots after the built-ins.
+    lets: Vec<Expr>,
+    /// Red, green, blue, and alpha outputs.
+    channels: [Expr; 4],
+}
+
+impl Script {
+    /// Parse a script from source.
+    pub fn parse(source: &str) -> Result<Self, ScriptError> {
+        let mut vars: Vec<String> = BUILTINS.iter().map(|&var| var.to_owned()).collect();
+        let mut lets = Vec::new();
+        let mut channels = [Expr::Const(0.0), Expr::Const(0.0), Expr::Const(0.0), Expr::Const(1.0)];
+
+        for (i, line) in source.lines().enumerate() {
+            let err = |msg: String| ScriptError::Parse { line: i + 1, msg };
+            let line = line.split('#').next().unwrap();
+            let tokens = tokenize(line).map_err(err)?;
+
+            // split off the assigned name
+            let (name, rhs, is_let) = match tokens.as_slice() {
+                [] => continue,
+                [Token::Ident(kw), Token::Ident(name), Token::Sym('='), rhs @ ..]
+                    if kw == "let" => (name, rhs, true),
+                [Token::Ident(name), Token::Sym('='), rhs @ ..] => (name, rhs, false),
+                _ => return Err(err("expected `let name = expr` or `channel = expr`".into())),
+            };
+
+            let mut parser = Parser {
+                tokens: rhs,
+                pos: 0,
+                vars: &vars,
+            };
+            let expr = parser.expr().map_err(err)?;
+            if parser.pos < rhs.len() {
+                return Err(err("unexpected tokens after expression".into()));
+            }
+
+            if is_let {
+                if vars.len() >= MAX_VARS {
+                    return Err(err(format!("more than {} variables", MAX_VARS)));
+                }
+                vars.push(name.clone());
+                lets.push(expr);
+            } else {
+                let channel = match name.as_str() {
+                    "r" => 0,
+                    "g" => 1,
+                    "b" => 2,
+                    "a" => 3,
+                    _ => return Err(err(format!("unknown output {:?}", name))),
+                };
+                channels[channel] = expr;
+            }
+        }
+
+        Ok(Script { lets, channels })
+    }
+
+    /// Read and parse a script file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ScriptError> {
+        Script::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Evaluate the script at a pixel, on a canvas of the given size, at the given time.
+    pub fn eval(&self, xy: Vec2<i32>, x_size: usize, y_size: usize, time: f32) -> Rgba<f32> {
+        let mut vars = [0.0; MAX_VARS];
+        let builtins = [
+            (xy.x as f32 + 0.5) / x_size as f32,
+            (xy.y as f32 + 0.5) / y_size as f32,
+            xy.x as f32,
+            xy.y as f32,
+            x_size as f32,
+            y_size as f32,
+            time,
+        ];
+        vars[..BUILTINS.len()].copy_from_slice(&builtins);
+        for (i, expr) in self.lets.iter().enumerate() {
+            vars[BUILTINS.len() + i] = expr.eval(&vars);
+        }
+        let [r, g, b, a] = &self.channels;
+        Rgba::new(r.eval(&vars), g.eval(&vars), b.eval(&vars), a.eval(&vars))
+    }
+}
+
+/// Launch a window which renders a fragment script every frame, at up to the given frames
+/// per second, reloading it whenever the file changes.
+///
+/// See `Script` for the language. If the script fails to load, the error is logged, and the
+/// last script which loaded keeps rendering until the file is fixed. This gives a live
+/// coding workflow: edit the file, save, and see the result.
+///
+/// Space pauses and resumes the animation, and while it's paused, period steps forward by
+/// one frame.
+///
+/// If `fps` isn't positive, or is NaN, frames are rendered as fast as possible.
+///
+/// Every frame is rendered on the same persistent `TileRenderer`.
+pub fn fragment_script(x_size: usize, y_size: usize, fps: f64, path: impl Into<PathBuf>) {
+    let path = path.into();
+    let mut script: Option<Arc<Script>> = None;
+    // modification time of the file when it was last loaded, if it has been
+    let mut loaded: Option<Option<SystemTime>> = None;
+    let renderer = TileRenderer::new(0);
+    let tile_size = FragOptions::default().tile_size;
+
+    frame_loop(
+        x_size,
+        y_size,
+        fps,
+        move |ctx, _, time| {
+            // reload if the file changed
+            let modified = fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+            if loaded != Some(modified) {
+                loaded = Some(modified);
+                match Script::load(&path) {
+                    Ok(loaded) => {
+                        info!("loaded script {:?}", path);
+                        script = Some(Arc::new(loaded));
+                    },
+                    Err(e) => error!("failed to load script {:?}: {}", path, e),
+                }
+            }
+
+            if let Some(ref script) = script {
+                let script = Arc::clone(script);
+                let frame = Arc::new(move |xy: Vec2<i32>| {
+                    to_u8(script.eval(xy, x_size, y_size, time))
+                });
+                renderer.render(x_size, y_size, tile_size, TileOrder::Scanline, ctx, &frame);
+            }
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Red output of a script, at pixel (1, 2) of a 4x8 canvas, at time 0.5.
+    fn red(source: &str) -> f32 {
+        Script::parse(source).unwrap().eval(Vec2::new(1, 2), 4, 8, 0.5).r
+    }
+
+    /// Message and line of a script's parse error.
+    fn parse_error(source: &str) -> (usize, String) {
+        match Script::parse(source) {
+            Err(ScriptError::Parse { line, msg }) => (line, msg),
+            result => panic!("expected parse error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(red("r = 1 + 2 * 3"), 7.0);
+        assert_eq!(red("r = (1 + 2) * 3"), 9.0);
+        assert_eq!(red("r = -2^2"), -4.0);
+        assert_eq!(red("r = (-2)^2"), 4.0);
+        assert_eq!(red("r = 2^3^2"), 512.0);
+        assert_eq!(red("r = 2^-1"), 0.5);
+        assert_eq!(red("r = 8 - 2 - 1"), 5.0);
+        assert_eq!(red("r = 1 + 1 < 3"), 1.0);
+        assert_eq!(red("r = -7 % 3"), 2.0);
+    }
+
+    #[test]
+    fn exponent_literals() {
+        assert_eq!(red("r = 1e3"), 1000.0);
+        assert_eq!(red("r = 2.5E-1"), 0.25);
+        assert_eq!(red("r = 1e+2"), 100.0);
+        assert_eq!(red("r = .5e1"), 5.0);
+        // an `e` without digits isn't an exponent
+        assert_eq!(parse_error("r = 2e").1, "unexpected tokens after expression");
+    }
+
+    #[test]
+    fn builtins() {
+        assert_eq!(red("r = x"), 1.5 / 4.0);
+        assert_eq!(red("r = y"), 2.5 / 8.0);
+        assert_eq!(red("r = px + py * 10"), 21.0);
+        assert_eq!(red("r = w * h"), 32.0);
+        assert_eq!(red("r = t"), 0.5);
+        assert_eq!(red("r = tau / pi"), 2.0);
+    }
+
+    #[test]
+    fn outputs_default_to_opaque_black() {
+        let color = Script::parse("# nothing\n\n").unwrap().eval(Vec2::zero(), 1, 1, 0.0);
+        assert_eq!(color, Rgba::new(0.0, 0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn bindings_shadow_builtins() {
+        assert_eq!(red("let x = x * 4\nr = x"), 1.5);
+        assert_eq!(red("let a = 1\nlet a = a + 1\nr = a"), 2.0);
+        assert_eq!(red("let pi = 3\nr = pi"), 3.0);
+        // a binding doesn't shadow functions
+        assert_eq!(red("let min = 5\nr = min(min, 2)"), 2.0);
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(red("r = clamp(5, 0, 1)"), 1.0);
+        assert_eq!(red("r = mix(2, 4, 0.25)"), 2.5);
+        assert_eq!(red("r = length(3, 4)"), 5.0);
+        assert_eq!(red("r = step(0.5, 0.7)"), 1.0);
+        assert_eq!(red("r = fract(-0.25)"), 0.75);
+    }
+
+    #[test]
+    fn arity_errors() {
+        assert_eq!(parse_error("r = min(1)"), (1, "min takes 2 arguments, not 1".to_owned()));
+        assert_eq!(
+            parse_error("\nr = sin(1, 2)"),
+            (2, "sin takes 1 argument, not 2".to_owned()),
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(parse_error("r = foo(1)").1, "unknown function \"foo\"");
+        assert_eq!(parse_error("r = z").1, "unknown variable \"z\"");
+        assert_eq!(parse_error("q = 1").1, "unknown output \"q\"");
+        assert_eq!(parse_error("r = (1").1, "expected ')'");
+        assert_eq!(parse_error("r = 1 $ 2").1, "unexpected character '$'");
+        assert_eq!(parse_error("r = 1..2").1, "invalid number \"1..2\"");
+        assert_eq!(parse_error("r 1").1, "expected `let name = expr` or `channel = expr`");
+        // bindings are only visible after they're made
+        assert_eq!(parse_error("r = k\nlet k = 1").0, 1);
+    }
+}
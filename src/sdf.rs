@@ -0,0 +1,155 @@
+use crate::rt::{Hit, Ray};
+
+use vek::*;
+
+/// Signed distance from a point to a circle at the origin.
+pub fn circle(p: Vec2<f32>, radius: f32) -> f32 {
+    p.magnitude() - radius
+}
+
+/// Signed distance from a point to an axis-aligned box at the origin, with the given half
+/// extents.
+pub fn box2(p: Vec2<f32>, half_extents: Vec2<f32>) -> f32 {
+    let d = p.map(f32::abs) - half_extents;
+    let outside = d.map(|n| n.max(0.0)).magnitude();
+    let inside = d.x.max(d.y).min(0.0);
+    outside + inside
+}
+
+/// Signed distance from a point to a line segment from `a` to `b`, thickened by `radius`.
+pub fn segment(p: Vec2<f32>, a: Vec2<f32>, b: Vec2<f32>, radius: f32) -> f32 {
+    let pa = p - a;
+    let ba = b - a;
+    let len_sq = ba.magnitude_squared();
+    let h =
+        if len_sq > 0.0 { (pa.dot(ba) / len_sq).clamp(0.0, 1.0) }
+        else { 0.0 };
+    (pa - ba * h).magnitude() - radius
+}
+
+/// Signed distance from a point to a sphere at the origin.
+pub fn sphere(p: Vec3<f32>, radius: f32) -> f32 {
+    p.magnitude() - radius
+}
+
+/// Signed distance from a point to an axis-aligned box at the origin, with the given half
+/// extents.
+pub fn box3(p: Vec3<f32>, half_extents: Vec3<f32>) -> f32 {
+    let d = p.map(f32::abs) - half_extents;
+    let outside = d.map(|n| n.max(0.0)).magnitude();
+    let inside = d.x.max(d.y).max(d.z).min(0.0);
+    outside + inside
+}
+
+/// Signed distance from a point to a torus at the origin, lying in the xz plane.
+///
+/// `major` is the radius of the ring, and `minor` the radius of its tube.
+pub fn torus(p: Vec3<f32>, major: f32, minor: f32) -> f32 {
+    let q = Vec2::new(Vec2::new(p.x, p.z).magnitude() - major, p.y);
+    q.magnitude() - minor
+}
+
+/// Union of two shapes, by their signed distances.
+pub fn union(a: f32, b: f32) -> f32 {
+    a.min(b)
+}
+
+/// Intersection of two shapes, by their signed distances.
+pub fn intersection(a: f32, b: f32) -> f32 {
+    a.max(b)
+}
+
+/// The first shape with the second cut out of it, by their signed distances.
+pub fn subtraction(a: f32, b: f32) -> f32 {
+    a.max(-b)
+}
+
+/// Union of two shapes with a rounded blend between them, with polynomial smooth minimum.
+///
+/// `k` is the size of the blend, and 0 gives a sharp union.
+pub fn smooth_union(a: f32, b: f32, k: f32) -> f32 {
+    if k <= 0.0 {
+        return a.min(b);
+    }
+    let h = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+    b + (a - b) * h - k * h * (1.0 - h)
+}
+
+/// Intersection of two shapes with a rounded blend between them.
+///
+/// `k` is the size of the blend, and 0 gives a sharp intersection.
+pub fn smooth_intersection(a: f32, b: f32, k: f32) -> f32 {
+    -smooth_union(-a, -b, k)
+}
+
+/// The first shape with the second cut out of it, with a rounded blend between them.
+///
+/// `k` is the size of the blend, and 0 gives a sharp subtraction.
+pub fn smooth_subtraction(a: f32, b: f32, k: f32) -> f32 {
+    smooth_intersection(a, -b, k)
+}
+
+/// Estimate the normalized gradient of a 3D signed distance function, which is the outward
+/// surface normal on its surface, with central differences.
+pub fn normal(p: Vec3<f32>, sdf: impl Fn(Vec3<f32>) -> f32) -> Vec3<f32> {
+    const EPS: f32 = 1e-3;
+    let dx = Vec3::new(EPS, 0.0, 0.0);
+    let dy = Vec3::new(0.0, EPS, 0.0);
+    let dz = Vec3::new(0.0, 0.0, EPS);
+    Vec3::new(
+        sdf(p + dx) - sdf(p - dx),
+        sdf(p + dy) - sdf(p - dy),
+        sdf(p + dz) - sdf(p - dz),
+    ).normalized()
+}
+
+/// Configuration of how `sphere_trace` marches along a ray.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TraceOptions {
+    /// Maximum number of steps before giving up. Defaults to 128.
+    pub max_steps: u32,
+    /// Distance from the surface which counts as a hit. Defaults to 0.001.
+    pub epsilon: f32,
+    /// Distance along the ray beyond which to give up. Defaults to 100.
+    pub max_dist: f32,
+}
+
+impl Default for TraceOptions {
+    fn default() -> Self {
+        TraceOptions {
+            max_steps: 128,
+            epsilon: 1e-3,
+            max_dist: 100.0,
+        }
+    }
+}
+
+/// Find where a ray first hits the surface of a 3D signed distance function, by sphere
+/// tracing.
+///
+/// Each step advances by the distance to the nearest surface, so the function must never
+/// overestimate it. The ray's direction should be normalized, and the hit's distance is
+/// along it. Hit UVs are zero.
+pub fn sphere_trace(
+    ray: &Ray,
+    sdf: impl Fn(Vec3<f32>) -> f32,
+    options: &TraceOptions,
+) -> Option<Hit> {
+    let mut t = 0.0;
+    for _ in 0..options.max_steps {
+        let p = ray.at(t);
+        let dist = sdf(p);
+        if dist < options.epsilon {
+            return Some(Hit {
+                t,
+                normal: normal(p, &sdf),
+                uv: Vec2::zero(),
+            });
+        }
+        t += dist;
+        if t > options.max_dist {
+            break;
+        }
+    }
+    None
+}
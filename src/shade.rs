@@ -0,0 +1,494 @@
+use std::f32::consts::PI;
+
+use vek::*;
+
+/// Source of light for `blinn_phong`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Light {
+    /// Light from infinitely far away, arriving from the same direction everywhere, such as
+    /// the sun.
+    Directional {
+        /// Direction from surfaces towards the light.
+        dir: Vec3<f32>,
+        color: Rgb<f32>,
+    },
+    /// Light radiating from a point, falling off with the square of the distance.
+    Point {
+        position: Vec3<f32>,
+        /// Color at a distance of one unit.
+        color: Rgb<f32>,
+    },
+}
+
+impl Light {
+    /// Normalized direction from a point towards the light, and the light's color there.
+    pub fn incident(&self, point: Vec3<f32>) -> (Vec3<f32>, Rgb<f32>) {
+        match *self {
+            Light::Directional { dir, color } => (dir.normalized(), color),
+            Light::Point { position, color } => {
+                let to_light = position - point;
+                let dist_sq = to_light.magnitude_squared();
+                if dist_sq > 0.0 {
+                    (to_light / dist_sq.sqrt(), color / dist_sq)
+                } else {
+                    (Vec3::zero(), Rgb::zero())
+                }
+            },
+        }
+    }
+}
+
+/// Surface reflectance for `blinn_phong`, in linear color.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Material {
+    /// Fraction of ambient light reflected. Defaults to the diffuse color.
+    pub ambient: Rgb<f32>,
+    /// Fraction of light scattered equally in all directions. Defaults to light gray.
+    pub diffuse: Rgb<f32>,
+    /// Fraction of light reflected as highlights. Defaults to a dim white.
+    pub specular: Rgb<f32>,
+    /// Exponent controlling how tight highlights are. Defaults to 32.
+    pub shininess: f32,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            ambient: Rgb::broadcast(0.8),
+            diffuse: Rgb::broadcast(0.8),
+            specular: Rgb::broadcast(0.2),
+            shininess: 32.0,
+        }
+    }
+}
+
+/// Evaluate Blinn-Phong lighting at a point on a surface.
+///
+/// `normal` is the surface normal, such as one interpolated by `pipeline::Pipeline` or from
+/// a ray tracing `Hit`, and `view_dir` points from the surface towards the eye. Neither
+/// needs to be normalized. `ambient` is the color of light arriving from everywhere.
+///
+/// The specular lobe is scaled by `(shininess + 8) / 8`, so highlights keep roughly the same
+/// energy as they tighten. The result is linear and unclamped, so it can be tone mapped, or
+/// gamma encoded with the `color` module.
+pub fn blinn_phong(
+    material: &Material,
+    lights: &[Light],
+    ambient: Rgb<f32>,
+    point: Vec3<f32>,
+    normal: Vec3<f32>,
+    view_dir: Vec3<f32>,
+) -> Rgb<f32> {
+    let normal = normal.normalized();
+    let view_dir = view_dir.normalized();
+    let spec_norm = (material.shininess + 8.0) / 8.0;
+
+    let mut out = material.ambient * ambient;
+    for light in lights {
+        let (light_dir, color) = light.incident(point);
+        let n_dot_l = normal.dot(light_dir);
+        if n_dot_l <= 0.0 {
+            continue;
+        }
+
+        // the half vector between the light and the eye
+        let half = (light_dir + view_dir).normalized();
+        let n_dot_h = normal.dot(half).max(0.0);
+        let specular = material.specular * spec_norm * n_dot_h.powf(material.shininess);
+
+        out += (material.diffuse + specular) * color * n_dot_l;
+    }
+    out
+}
+
+/// Physically based surface reflectance, in the metallic-roughness model, for path tracers.
+///
+/// The BRDF is a Lambertian diffuse lobe plus a GGX microfacet specular lobe, with
+/// height-correlated Smith shadowing and Schlick's Fresnel approximation. The diffuse lobe
+/// is weighted by the light which Fresnel transmits in both directions, so that a surface
+/// never reflects more light than arrives.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PbrMaterial {
+    /// Diffuse color of dielectrics, or specular color of metals, in linear color. Defaults
+    /// to light gray.
+    pub base_color: Rgb<f32>,
+    /// Whether the surface is a metal, from 0 to 1. Defaults to 0.
+    pub metallic: f32,
+    /// Perceptual roughness, from 0 mirror-like to 1 fully rough. Defaults to 0.5.
+    pub roughness: f32,
+    /// Light emitted by the surface, in linear color. Defaults to black.
+    pub emission: Rgb<f32>,
+}
+
+impl Default for PbrMaterial {
+    fn default() -> Self {
+        PbrMaterial {
+            base_color: Rgb::broadcast(0.8),
+            metallic: 0.0,
+            roughness: 0.5,
+            emission: Rgb::zero(),
+        }
+    }
+}
+
+/// Direction sampled from a BRDF.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BrdfSample {
+    /// Normalized direction from the surface towards where light arrives from.
+    pub dir: Vec3<f32>,
+    /// Probability density of choosing `dir`, with respect to solid angle.
+    pub pdf: f32,
+    /// BRDF times cosine divided by the PDF, by which to multiply the light arriving from
+    /// `dir`.
+    pub weight: Rgb<f32>,
+}
+
+impl PbrMaterial {
+    /// GGX alpha, clamped away from zero so that smooth surfaces stay numerically stable.
+    fn alpha(&self) -> f32 {
+        let roughness = self.roughness.clamp(0.03, 1.0);
+        roughness * roughness
+    }
+
+    /// Reflectance at normal incidence.
+    fn f0(&self) -> Rgb<f32> {
+        Rgb::lerp(Rgb::broadcast(0.04), self.base_color, self.metallic)
+    }
+
+    /// Probability of sampling the specular lobe rather than the diffuse one.
+    fn specular_chance(&self) -> f32 {
+        let metallic = self.metallic.clamp(0.0, 1.0);
+        0.5 + 0.5 * metallic
+    }
+
+    /// BRDF times the cosine of the incoming angle, for light arriving from `light_dir`
+    /// and leaving towards `view_dir`, both pointing away from the surface.
+    ///
+    /// None of the vectors need to be normalized. Light from below the surface gives black.
+    pub fn eval(
+        &self,
+        normal: Vec3<f32>,
+        view_dir: Vec3<f32>,
+        light_dir: Vec3<f32>,
+    ) -> Rgb<f32> {
+        let normal = normal.normalized();
+        let view_dir = view_dir.normalized();
+        let light_dir = light_dir.normalized();
+        let n_dot_v = normal.dot(view_dir);
+        let n_dot_l = normal.dot(light_dir);
+        if n_dot_v <= 0.0 || n_dot_l <= 0.0 {
+            return Rgb::zero();
+        }
+
+        let half = (view_dir + light_dir).normalized();
+        let alpha = self.alpha();
+        let f0 = self.f0();
+        let fresnel = fresnel_schlick(f0, view_dir.dot(half).max(0.0));
+        let specular = fresnel
+            * ggx_distribution(normal.dot(half).max(0.0), alpha)
+            * smith_visibility(n_dot_v, n_dot_l, alpha);
+        let diffuse = self.base_color
+            * (Rgb::one() - fresnel_schlick(f0, n_dot_v))
+            * (Rgb::one() - fresnel_schlick(f0, n_dot_l))
+            * (1.0 - self.metallic) / PI;
+        (diffuse + specular) * n_dot_l
+    }
+
+    /// Probability density with which `sample` chooses `light_dir`, with respect to solid
+    /// angle.
+    pub fn pdf(&self, normal: Vec3<f32>, view_dir: Vec3<f32>, light_dir: Vec3<f32>) -> f32 {
+        let chance = self.specular_chance();
+        chance * ggx_pdf(normal, view_dir, light_dir, self.alpha())
+            + (1.0 - chance) * lambert_pdf(normal, light_dir)
+    }
+
+    /// Choose a direction for light to arrive from, towards `view_dir`, in proportion to
+    /// roughly how much it contributes.
+    ///
+    /// `lobe` in `[0, 1)` chooses between the diffuse and specular lobes, and `u` in
+    /// `[0, 1)` squared chooses a direction within the lobe, such as from the `sampling`
+    /// module. The PDF and weight account for both lobes, so the result can be combined
+    /// with light sampling by multiple importance sampling. Returns `None` if the sampled
+    /// direction is below the surface, which should be treated as a path absorbed.
+    pub fn sample(
+        &self,
+        normal: Vec3<f32>,
+        view_dir: Vec3<f32>,
+        lobe: f32,
+        u: Vec2<f32>,
+    ) -> Option<BrdfSample> {
+        let normal = normal.normalized();
+        let view_dir = view_dir.normalized();
+        let dir =
+            if lobe < self.specular_chance() { sample_ggx(normal, view_dir, self.alpha(), u)?.0 }
+            else { sample_lambert(normal, u).0 };
+
+        let pdf = self.pdf(normal, view_dir, dir);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(BrdfSample {
+            dir,
+            pdf,
+            weight: self.eval(normal, view_dir, dir) / pdf,
+        })
+    }
+}
+
+/// Schlick's approximation of Fresnel reflectance, given the reflectance at normal incidence
+/// and the cosine of the angle between the view direction and the half vector.
+pub fn fresnel_schlick(f0: Rgb<f32>, cos_theta: f32) -> Rgb<f32> {
+    let m = (1.0 - cos_theta).clamp(0.0, 1.0);
+    let m5 = m * m * m * m * m;
+    f0 + (Rgb::one() - f0) * m5
+}
+
+/// GGX (Trowbridge-Reitz) normal distribution, given the cosine between the normal and the
+/// half vector, and alpha, which is the square of perceptual roughness.
+pub fn ggx_distribution(n_dot_h: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    a2 / (PI * d * d)
+}
+
+/// Height-correlated Smith shadowing-masking for GGX, divided by the `4 n.v n.l`
+/// denominator of the microfacet BRDF.
+pub fn smith_visibility(n_dot_v: f32, n_dot_l: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let v = n_dot_l * (n_dot_v * n_dot_v * (1.0 - a2) + a2).sqrt();
+    let l = n_dot_v * (n_dot_l * n_dot_l * (1.0 - a2) + a2).sqrt();
+    0.5 / (v + l)
+}
+
+/// Transform a direction from a frame where z is up to one around a normalized normal.
+fn to_world(local: Vec3<f32>, normal: Vec3<f32>) -> Vec3<f32> {
+    let helper =
+        if normal.x.abs() < 0.9 { Vec3::unit_x() }
+        else { Vec3::unit_y() };
+    let tangent = normal.cross(helper).normalized();
+    let bitangent = normal.cross(tangent);
+    tangent * local.x + bitangent * local.y + normal * local.z
+}
+
+/// Cosine-weighted direction in the hemisphere around a normalized normal, from `u` in
+/// `[0, 1)` squared, and its PDF, which matches a Lambertian BRDF exactly.
+pub fn sample_lambert(normal: Vec3<f32>, u: Vec2<f32>) -> (Vec3<f32>, f32) {
+    let r = u.x.sqrt();
+    let phi = 2.0 * PI * u.y;
+    let cos_theta = (1.0 - u.x).max(0.0).sqrt();
+    let dir = to_world(Vec3::new(r * phi.cos(), r * phi.sin(), cos_theta), normal);
+    (dir, cos_theta / PI)
+}
+
+/// PDF with which `sample_lambert` chooses a direction.
+pub fn lambert_pdf(normal: Vec3<f32>, dir: Vec3<f32>) -> f32 {
+    normal.normalized().dot(dir.normalized()).max(0.0) / PI
+}
+
+/// Direction reflected off a microfacet normal sampled from the GGX distribution around a
+/// normalized normal, from `u` in `[0, 1)` squared, and its PDF.
+///
+/// `view_dir` is normalized, pointing away from the surface, and `alpha` is the square of
+/// perceptual roughness. Returns `None` if the reflection is below the surface.
+pub fn sample_ggx(
+    normal: Vec3<f32>,
+    view_dir: Vec3<f32>,
+    alpha: f32,
+    u: Vec2<f32>,
+) -> Option<(Vec3<f32>, f32)> {
+    // microfacet normal, distributed by D(h) (n.h)
+    let a2 = alpha * alpha;
+    let cos_theta = ((1.0 - u.x) / (1.0 + (a2 - 1.0) * u.x)).max(0.0).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.y;
+    let half = to_world(Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta), normal);
+
+    let v_dot_h = view_dir.dot(half);
+    if v_dot_h <= 0.0 {
+        return None;
+    }
+    let dir = half * (2.0 * v_dot_h) - view_dir;
+    if normal.dot(dir) <= 0.0 {
+        return None;
+    }
+    Some((dir, ggx_distribution(cos_theta, alpha) * cos_theta / (4.0 * v_dot_h)))
+}
+
+/// PDF with which `sample_ggx` chooses a reflected direction.
+pub fn ggx_pdf(normal: Vec3<f32>, view_dir: Vec3<f32>, dir: Vec3<f32>, alpha: f32) -> f32 {
+    let normal = normal.normalized();
+    let view_dir = view_dir.normalized();
+    let dir = dir.normalized();
+    if normal.dot(dir) <= 0.0 {
+        return 0.0;
+    }
+    let half = (view_dir + dir).normalized();
+    let n_dot_h = normal.dot(half);
+    let v_dot_h = view_dir.dot(half);
+    if n_dot_h <= 0.0 || v_dot_h <= 0.0 {
+        return 0.0;
+    }
+    ggx_distribution(n_dot_h, alpha) * n_dot_h / (4.0 * v_dot_h)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Materials spanning smooth to rough dielectrics and metals, in white.
+    fn materials() -> Vec<PbrMaterial> {
+        let mut materials = Vec::new();
+        for &metallic in &[0.0, 0.5, 1.0] {
+            for &roughness in &[0.05, 0.3, 0.6, 1.0] {
+                materials.push(PbrMaterial {
+                    base_color: Rgb::one(),
+                    metallic,
+                    roughness,
+                    emission: Rgb::zero(),
+                });
+            }
+        }
+        materials
+    }
+
+    /// Unit view directions around the z axis, from straight on to grazing.
+    fn views() -> Vec<Vec3<f32>> {
+        [1.0f32, 0.7, 0.3, 0.05].iter()
+            .map(|&z| Vec3::new((1.0 - z * z).sqrt(), 0.0, z))
+            .collect()
+    }
+
+    /// Stratified points in `[0, 1)` squared.
+    fn grid(n: usize) -> impl Iterator<Item = Vec2<f32>> {
+        (0..n * n).map(move |i| Vec2::new(
+            ((i % n) as f32 + 0.5) / n as f32,
+            ((i / n) as f32 + 0.5) / n as f32,
+        ))
+    }
+
+    /// Integrate a function of direction over the hemisphere around z, by the midpoint rule
+    /// in spherical coordinates.
+    fn integrate_hemisphere(f: impl Fn(Vec3<f32>) -> f32) -> f32 {
+        let (thetas, phis) = (1000, 400);
+        let (d_theta, d_phi) = (PI / 2.0 / thetas as f32, 2.0 * PI / phis as f32);
+        let mut sum = 0.0f64;
+        for i in 0..thetas {
+            let theta = (i as f32 + 0.5) * d_theta;
+            for j in 0..phis {
+                let phi = (j as f32 + 0.5) * d_phi;
+                let dir = Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                );
+                sum += (f(dir) * theta.sin() * d_theta * d_phi) as f64;
+            }
+        }
+        sum as f32
+    }
+
+    #[test]
+    fn sample_pdf_matches_pdf() {
+        let normal = Vec3::unit_z();
+        for material in materials() {
+            for view_dir in views() {
+                for u in grid(24) {
+                    for &lobe in &[0.2, 0.9] {
+                        let sample = match material.sample(normal, view_dir, lobe, u) {
+                            Some(sample) => sample,
+                            None => continue,
+                        };
+                        let pdf = material.pdf(normal, view_dir, sample.dir);
+                        assert!(
+                            (sample.pdf - pdf).abs() <= pdf * 1e-3,
+                            "{:?}: sampled pdf {} but pdf {}", material, sample.pdf, pdf,
+                        );
+                        let weight = material.eval(normal, view_dir, sample.dir) / pdf;
+                        assert!((sample.weight - weight).reduce_partial_max().abs() < 1e-3);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lambert_pdf_integrates_to_one() {
+        let integral = integrate_hemisphere(|dir| lambert_pdf(Vec3::unit_z(), dir));
+        assert!((integral - 1.0).abs() < 1e-3, "integral {}", integral);
+    }
+
+    #[test]
+    fn ggx_pdf_integrates_to_one() {
+        // less the little mass of reflections which fall below the surface
+        let normal = Vec3::unit_z();
+        for &alpha in &[0.09, 0.25] {
+            let integral = integrate_hemisphere(|dir| ggx_pdf(normal, normal, dir, alpha));
+            assert!(integral > 0.93 && integral < 1.005, "alpha {}: {}", alpha, integral);
+        }
+    }
+
+    #[test]
+    fn white_furnace() {
+        // the average weight of samples estimates the reflected fraction of uniform light
+        let normal = Vec3::unit_z();
+        for material in materials() {
+            for view_dir in views() {
+                let mut sum = Rgb::zero();
+                let mut count = 0;
+                for u in grid(128) {
+                    for &lobe in &[0.25, 0.75] {
+                        if let Some(sample) = material.sample(normal, view_dir, lobe, u) {
+                            sum += sample.weight;
+                        }
+                        count += 1;
+                    }
+                }
+                let albedo = sum / count as f32;
+                assert!(
+                    albedo.reduce_partial_max() <= 1.01,
+                    "{:?} viewed from {:?} reflects {:?}", material, view_dir, albedo,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn eval_is_black_below_surface() {
+        let material = PbrMaterial::default();
+        let normal = Vec3::unit_z();
+        let view_dir = Vec3::new(0.0, 0.6, 0.8);
+        assert_eq!(material.eval(normal, view_dir, Vec3::new(0.3, 0.0, -0.5)), Rgb::zero());
+        assert_eq!(material.eval(normal, -view_dir, Vec3::unit_z()), Rgb::zero());
+    }
+
+    #[test]
+    fn sampled_directions_stay_above_surface() {
+        let normals = [
+            Vec3::unit_z(),
+            -Vec3::unit_x(),
+            Vec3::new(0.95, 0.1, -0.3).normalized(),
+        ];
+        for &normal in &normals {
+            // a view direction tilted away from the normal
+            let tangent = to_world(Vec3::unit_x(), normal);
+            for material in materials() {
+                for &z in &[1.0f32, 0.3, 0.05] {
+                    let view_dir = (normal * z + tangent * (1.0 - z * z).sqrt()).normalized();
+                    for u in grid(16) {
+                        let (dir, _) = sample_lambert(normal, u);
+                        assert!(dir.dot(normal) >= 0.0);
+                        assert!((dir.magnitude() - 1.0).abs() < 1e-4);
+
+                        for &lobe in &[0.2, 0.9] {
+                            if let Some(sample) = material.sample(normal, view_dir, lobe, u) {
+                                assert!(sample.dir.dot(normal) > 0.0, "{:?}", sample);
+                                assert!((sample.dir.magnitude() - 1.0).abs() < 1e-4);
+                                assert!(sample.pdf > 0.0);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
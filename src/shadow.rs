@@ -0,0 +1,122 @@
+use crate::{
+    camera::Camera,
+    pipeline::Pipeline,
+};
+
+use vek::*;
+
+/// Depth buffer rendered from a light's point of view, for testing whether points are in
+/// shadow.
+///
+/// Render the shadow casters into it with `render`, then call `visibility` from the main
+/// pass's fragment shader with each fragment's world-space position.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShadowMap {
+    x_size: usize,
+    y_size: usize,
+    /// World-to-clip matrix of the light.
+    light_vp: Mat4<f32>,
+    /// Row-major depths in `[0, 1]`, or 1 where nothing was rendered.
+    depth: Vec<f32>,
+}
+
+impl ShadowMap {
+    /// Construct an empty shadow map of the given resolution, seen through the given
+    /// world-to-clip matrix.
+    ///
+    /// For a directional light, this is usually an orthographic camera looking along the
+    /// light's direction, and for a spot light, a perspective camera.
+    pub fn new(x_size: usize, y_size: usize, light_vp: Mat4<f32>) -> Self {
+        ShadowMap {
+            x_size,
+            y_size,
+            light_vp,
+            depth: vec![1.0; x_size * y_size],
+        }
+    }
+
+    /// Construct an empty shadow map of the given resolution, seen through a camera.
+    pub fn from_camera(x_size: usize, y_size: usize, camera: &Camera) -> Self {
+        ShadowMap::new(x_size, y_size, camera.view_projection())
+    }
+
+    pub fn x_size(&self) -> usize {
+        self.x_size
+    }
+
+    pub fn y_size(&self) -> usize {
+        self.y_size
+    }
+
+    /// Row-major depths in `[0, 1]`, from the light's near to far plane.
+    pub fn depth(&self) -> &[f32] {
+        &self.depth
+    }
+
+    /// Reset every depth to the far plane.
+    pub fn clear(&mut self) {
+        for depth in &mut self.depth {
+            *depth = 1.0;
+        }
+    }
+
+    /// Render indexed triangles into the depth buffer, keeping the nearest depth per texel.
+    ///
+    /// This is a depth-only pass through the software pipeline, with world-space positions.
+    pub fn render(&mut self, positions: &[Vec3<f32>], indices: &[usize]) {
+        let light_vp = self.light_vp;
+        let pipeline = Pipeline::new(
+            self.x_size,
+            self.y_size,
+            |&p: &Vec3<f32>| (light_vp * Vec4::from_point(p), ()),
+            |()| Rgba::zero(),
+        );
+
+        let x_size = self.x_size;
+        let depth = &mut self.depth;
+        pipeline.draw(positions, indices, &mut |frag| {
+            let i = frag.xy.y as usize * x_size + frag.xy.x as usize;
+            if frag.depth < depth[i] {
+                depth[i] = frag.depth;
+            }
+        });
+    }
+
+    /// Fraction of the light reaching a world-space point, from 0 in full shadow to 1 fully
+    /// lit.
+    ///
+    /// The point is in shadow where it's further from the light than the stored depth, by
+    /// more than `bias`, which avoids surfaces shadowing themselves ("shadow acne"). With a
+    /// `pcf_radius` above 0, the test is repeated over a square of texels that many texels
+    /// out, and averaged, for softer edges. Points outside the map are fully lit.
+    pub fn visibility(&self, point: Vec3<f32>, bias: f32, pcf_radius: u32) -> f32 {
+        let clip = self.light_vp * Vec4::from_point(point);
+        if clip.w <= 0.0 {
+            return 1.0;
+        }
+
+        let ndc = clip.xyz() / clip.w;
+        let x = ((ndc.x + 1.0) / 2.0 * self.x_size as f32).floor() as i64;
+        let y = ((1.0 - ndc.y) / 2.0 * self.y_size as f32).floor() as i64;
+        let depth = (ndc.z + 1.0) / 2.0;
+        if x < 0 || y < 0 || x >= self.x_size as i64 || y >= self.y_size as i64 || depth > 1.0 {
+            return 1.0;
+        }
+
+        // percentage-closer filtering, clamping texels to the edges
+        let r = pcf_radius as i64;
+        let mut lit = 0;
+        let mut total = 0;
+        for dy in -r..=r {
+            for dx in -r..=r {
+                let tx = (x + dx).max(0).min(self.x_size as i64 - 1) as usize;
+                let ty = (y + dy).max(0).min(self.y_size as i64 - 1) as usize;
+                if depth - bias <= self.depth[ty * self.x_size + tx] {
+                    lit += 1;
+                }
+                total += 1;
+            }
+        }
+        lit as f32 / total as f32
+    }
+}
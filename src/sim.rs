@@ -0,0 +1,212 @@
+use crate::{
+    PaintBatch,
+    PaintCmd,
+    SegQueue,
+    frag::frame_loop,
+};
+
+use rayon::prelude::*;
+use vek::*;
+
+/// Options for how `run_grid_with` runs and displays a simulation.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GridOptions {
+    /// Maximum number of steps per second, or unlimited if it isn't positive, or is NaN.
+    /// Defaults to 30.
+    pub fps: f64,
+    /// Size in pixels of each cell's square. Defaults to 1.
+    pub scale: usize,
+}
+
+impl Default for GridOptions {
+    fn default() -> Self {
+        GridOptions {
+            fps: 30.0,
+            scale: 1,
+        }
+    }
+}
+
+/// Row-major grid of simulation cells.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grid<C> {
+    width: usize,
+    height: usize,
+    cells: Vec<C>,
+}
+
+impl<C> Grid<C> {
+    /// Construct a grid with each cell initialized by a function of its coordinates.
+    pub fn new(width: usize, height: usize, init: impl Fn(Vec2<i32>) -> C) -> Self {
+        let mut cells = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                cells.push(init(Vec2::new(x as i32, y as i32)));
+            }
+        }
+        Grid {
+            width,
+            height,
+            cells,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Row-major cells.
+    pub fn cells(&self) -> &[C] {
+        &self.cells
+    }
+
+    /// The cell at some coordinates, wrapping around the edges, so that every cell has
+    /// neighbors.
+    pub fn get(&self, xy: Vec2<i32>) -> &C {
+        let x = xy.x.rem_euclid(self.width as i32) as usize;
+        let y = xy.y.rem_euclid(self.height as i32) as usize;
+        &self.cells[y * self.width + x]
+    }
+
+    /// The cell at some coordinates, or `None` outside of the grid.
+    pub fn try_get(&self, xy: Vec2<i32>) -> Option<&C> {
+        let inside = xy.x >= 0
+            && xy.y >= 0
+            && (xy.x as usize) < self.width
+            && (xy.y as usize) < self.height;
+        if inside {
+            Some(&self.cells[xy.y as usize * self.width + xy.x as usize])
+        } else {
+            None
+        }
+    }
+}
+
+/// Launch a window which runs a grid simulation, such as a cellular automaton, stepping it
+/// every frame. See `run_grid_with`.
+pub fn run_grid<C, I, S, P>(
+    width: usize,
+    height: usize,
+    init: I,
+    step: S,
+    cell_to_color: P,
+)
+    where
+        C: Clone + PartialEq + Send + Sync + 'static,
+        I: Fn(Vec2<i32>) -> C,
+        S: Fn(Vec2<i32>, &Grid<C>) -> C + Send + Sync + 'static,
+        P: Fn(&C) -> Rgba<u8> + Send + Sync + 'static {
+
+    // delegate
+    run_grid_with(width, height, GridOptions::default(), init, step, cell_to_color)
+}
+
+/// Launch a window which runs a grid simulation, such as a cellular automaton, stepping it
+/// every frame, with the given options.
+///
+/// Each step computes every cell's next value from the previous grid, with the step
+/// function, into a second buffer, and then the buffers are swapped. Only cells which
+/// changed are repainted. Rows are stepped and painted in parallel, with rayon.
+///
+/// Space pauses and resumes the simulation, and while it's paused, period steps it once.
+pub fn run_grid_with<C, I, S, P>(
+    width: usize,
+    height: usize,
+    options: GridOptions,
+    init: I,
+    step: S,
+    cell_to_color: P,
+)
+    where
+        C: Clone + PartialEq + Send + Sync + 'static,
+        I: Fn(Vec2<i32>) -> C,
+        S: Fn(Vec2<i32>, &Grid<C>) -> C + Send + Sync + 'static,
+        P: Fn(&C) -> Rgba<u8> + Send + Sync + 'static {
+
+    let scale = options.scale.max(1);
+    let mut front = Grid::new(width, height, init);
+    let mut back = front.clone();
+    let mut first = true;
+
+    frame_loop(
+        width * scale,
+        height * scale,
+        options.fps,
+        move |ctx, _, _| {
+            if first {
+                // paint everything, comparing against nothing
+                first = false;
+                for y in 0..height {
+                    ctx.wait_for_capacity();
+                    paint_row(&ctx.paint, &front, None, y, scale, &cell_to_color);
+                }
+                return;
+            }
+
+            // step into the back buffer
+            let prev = &front;
+            back.cells.par_chunks_mut(width.max(1))
+                .enumerate()
+                .for_each(|(y, row)| for (x, cell) in row.iter_mut().enumerate() {
+                    *cell = step(Vec2::new(x as i32, y as i32), prev);
+                });
+            std::mem::swap(&mut front, &mut back);
+
+            // repaint changed cells
+            let (next, prev) = (&front, &back);
+            (0..height).into_par_iter().for_each(|y| {
+                ctx.wait_for_capacity();
+                paint_row(&ctx.paint, next, Some(prev), y, scale, &cell_to_color);
+            });
+        },
+    );
+}
+
+/// Paint the runs of cells in a row which differ from the previous grid, as one batch each.
+fn paint_row<C: PartialEq>(
+    queue: &SegQueue<PaintCmd>,
+    grid: &Grid<C>,
+    prev: Option<&Grid<C>>,
+    y: usize,
+    scale: usize,
+    cell_to_color: &impl Fn(&C) -> Rgba<u8>,
+) {
+    let row = &grid.cells[y * grid.width..(y + 1) * grid.width];
+    let prev_row = prev.map(|prev| &prev.cells[y * grid.width..(y + 1) * grid.width]);
+    let changed = |x: usize| prev_row.map(|prev_row| prev_row[x] != row[x]).unwrap_or(true);
+
+    let mut x = 0;
+    while x < grid.width {
+        if !changed(x) {
+            x += 1;
+            continue;
+        }
+        let start = x;
+        while x < grid.width && changed(x) {
+            x += 1;
+        }
+
+        let colors: Vec<[u8; 4]> = row[start..x].iter()
+            .map(|cell| {
+                let color = cell_to_color(cell);
+                [color.r, color.g, color.b, color.a]
+            })
+            .collect();
+        let mut rgba = Vec::with_capacity(colors.len() * scale * scale);
+        for _ in 0..scale {
+            for &color in &colors {
+                rgba.extend(std::iter::repeat_n(color, scale));
+            }
+        }
+        let batch = PaintBatch::new(
+            start * scale, y * scale,
+            (x - start) * scale, scale,
+            rgba,
+        );
+        queue.push(batch.into());
+    }
+}
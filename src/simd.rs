@@ -0,0 +1,130 @@
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// Number of lanes in an `F32x8`.
+pub const LANES: usize = 8;
+
+/// Eight `f32`s operated on together, lane by lane.
+///
+/// Operations are written as plain loops over a fixed-size array, which the compiler turns
+/// into SIMD instructions where the target supports them. Build with
+/// `-C target-cpu=native` to use the widest instructions available.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[repr(C, align(32))]
+pub struct F32x8(pub [f32; LANES]);
+
+impl F32x8 {
+    /// Every lane set to the same value.
+    pub fn splat(n: f32) -> Self {
+        F32x8([n; LANES])
+    }
+
+    /// Lanes set to `start`, `start + 1`, and so on.
+    pub fn ramp(start: f32) -> Self {
+        let mut lanes = [0.0; LANES];
+        for (i, lane) in lanes.iter_mut().enumerate() {
+            *lane = start + i as f32;
+        }
+        F32x8(lanes)
+    }
+
+    /// Apply a function to each lane.
+    #[inline]
+    pub fn map(self, f: impl Fn(f32) -> f32) -> Self {
+        let mut out = self.0;
+        for lane in &mut out {
+            *lane = f(*lane);
+        }
+        F32x8(out)
+    }
+
+    /// Apply a function to each pair of lanes.
+    #[inline]
+    pub fn zip_map(self, rhs: Self, f: impl Fn(f32, f32) -> f32) -> Self {
+        let mut out = self.0;
+        for (lane, &r) in out.iter_mut().zip(&rhs.0) {
+            *lane = f(*lane, r);
+        }
+        F32x8(out)
+    }
+
+    pub fn sqrt(self) -> Self {
+        self.map(f32::sqrt)
+    }
+
+    pub fn abs(self) -> Self {
+        self.map(f32::abs)
+    }
+
+    pub fn floor(self) -> Self {
+        self.map(f32::floor)
+    }
+
+    pub fn min(self, rhs: Self) -> Self {
+        self.zip_map(rhs, f32::min)
+    }
+
+    pub fn max(self, rhs: Self) -> Self {
+        self.zip_map(rhs, f32::max)
+    }
+
+    /// Clamp each lane to `[min, max]`.
+    pub fn clamp(self, min: f32, max: f32) -> Self {
+        self.map(|n| n.max(min).min(max))
+    }
+
+    /// Per lane, `a` where `mask` is positive, and `b` otherwise.
+    ///
+    /// Comparisons can be expressed as masks by subtraction, such as `select(x - y, a, b)`
+    /// for `x > y`.
+    pub fn select(mask: Self, a: Self, b: Self) -> Self {
+        let mut out = b.0;
+        for ((lane, &m), &a) in out.iter_mut().zip(&mask.0).zip(&a.0) {
+            if m > 0.0 {
+                *lane = a;
+            }
+        }
+        F32x8(out)
+    }
+}
+
+macro_rules! lane_op {
+    ($trait:ident, $method:ident, $op:tt) => {
+        impl $trait for F32x8 {
+            type Output = F32x8;
+
+            #[inline]
+            fn $method(self, rhs: F32x8) -> F32x8 {
+                self.zip_map(rhs, |a, b| a $op b)
+            }
+        }
+
+        impl $trait<f32> for F32x8 {
+            type Output = F32x8;
+
+            #[inline]
+            fn $method(self, rhs: f32) -> F32x8 {
+                self.map(|a| a $op rhs)
+            }
+        }
+    };
+}
+
+lane_op!(Add, add, +);
+lane_op!(Sub, sub, -);
+lane_op!(Mul, mul, *);
+lane_op!(Div, div, /);
+
+impl Neg for F32x8 {
+    type Output = F32x8;
+
+    #[inline]
+    fn neg(self) -> F32x8 {
+        self.map(|a| -a)
+    }
+}
+
+impl From<f32> for F32x8 {
+    fn from(n: f32) -> Self {
+        F32x8::splat(n)
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{
+    DrawContext,
+    PaintCmd,
+    PresentSignal,
+    window::PaintPusher,
+};
+
+use std::{
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::sink::Sink;
+
+/// Adapter which implements `futures::Sink` over the paint queue, so that renderers driven
+/// by streams can forward paints to the window.
+///
+/// If the window was built with a `paint_capacity`, the sink isn't ready while the queue is
+/// full, so streams are backpressured like `DrawContext::push`. The sink never fails.
+#[derive(Clone, Debug)]
+pub struct PaintSink {
+    pusher: PaintPusher,
+}
+
+impl PaintSink {
+    /// Construct a sink over the paint queue of a drawing thread's context.
+    pub fn new(ctx: &DrawContext) -> Self {
+        PaintSink { pusher: ctx.pusher() }
+    }
+}
+
+impl<T> Sink<T> for PaintSink
+    where
+        T: Into<PaintCmd> {
+
+    type Error = Infallible;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Infallible>> {
+        self.pusher.poll_capacity(cx).map(Ok)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Infallible> {
+        self.pusher.push_now(item);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl PresentSignal {
+    /// Future which resolves when the next frame is presented, to the new frame count, or to
+    /// `None` if the window closes first.
+    pub fn next(&self) -> NextFrame {
+        NextFrame {
+            signal: self.clone(),
+            start: self.count(),
+        }
+    }
+}
+
+/// Future returned by `PresentSignal::next`.
+#[derive(Debug)]
+pub struct NextFrame {
+    signal: PresentSignal,
+    /// Frame count when the future was created.
+    start: u64,
+}
+
+impl Future for NextFrame {
+    type Output = Option<u64>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<u64>> {
+        let mut state = self.signal.inner.state.lock().unwrap();
+        if state.count != self.start {
+            Poll::Ready(Some(state.count))
+        } else if state.closed {
+            Poll::Ready(None)
+        } else {
+            state.wakers.push(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use crate::text::{rasterize_text, text_size};
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use vek::*;
+
+/// How often the window refreshes its statistics.
+const PERIOD: Duration = Duration::from_millis(500);
+
+/// Snapshot of the window's rendering statistics.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Stats {
+    /// Frames presented per second.
+    pub fps: f32,
+    /// Paint instructions applied per second.
+    pub paints_per_sec: f32,
+    /// Number of paint instructions waiting in the queue when it was last drained.
+    pub queue_depth: usize,
+}
+
+/// Shared handle to the window's rendering statistics, which it refreshes twice a second.
+#[derive(Clone, Debug, Default)]
+pub struct RenderStats {
+    stats: Arc<Mutex<Stats>>,
+}
+
+impl RenderStats {
+    /// Get the most recent statistics.
+    pub fn get(&self) -> Stats {
+        *self.stats.lock().unwrap()
+    }
+
+    pub fn fps(&self) -> f32 {
+        self.get().fps
+    }
+
+    pub fn paints_per_sec(&self) -> f32 {
+        self.get().paints_per_sec
+    }
+
+    pub fn queue_depth(&self) -> usize {
+        self.get().queue_depth
+    }
+}
+
+/// Window-side counter which feeds a `RenderStats`.
+pub(crate) struct StatsTracker {
+    stats: RenderStats,
+    period_start: Instant,
+    frames: u32,
+    paints: u64,
+    queue_depth: usize,
+}
+
+impl StatsTracker {
+    pub(crate) fn new(stats: RenderStats) -> Self {
+        StatsTracker {
+            stats,
+            period_start: Instant::now(),
+            frames: 0,
+            paints: 0,
+            queue_depth: 0,
+        }
+    }
+
+    /// Count a presented frame.
+    pub(crate) fn frame(&mut self) {
+        self.frames += 1;
+    }
+
+    /// Count paint instructions applied.
+    pub(crate) fn paints(&mut self, paints: u64) {
+        self.paints += paints;
+    }
+
+    /// Record the queue depth before draining.
+    pub(crate) fn queue_depth(&mut self, queue_depth: usize) {
+        self.queue_depth = queue_depth;
+    }
+
+    /// Publish the statistics if the current period is over, returning whether they were.
+    pub(crate) fn tick(&mut self) -> bool {
+        let elapsed = self.period_start.elapsed();
+        if elapsed < PERIOD {
+            return false;
+        }
+
+        let secs = elapsed.as_secs_f32();
+        *self.stats.stats.lock().unwrap() = Stats {
+            fps: self.frames as f32 / secs,
+            paints_per_sec: self.paints as f32 / secs,
+            queue_depth: self.queue_depth,
+        };
+        self.period_start = Instant::now();
+        self.frames = 0;
+        self.paints = 0;
+        true
+    }
+
+    /// The most recently published statistics.
+    pub(crate) fn get(&self) -> Stats {
+        self.stats.get()
+    }
+}
+
+/// Copy canvas pixels, with the statistics drawn over the top-left corner.
+pub(crate) fn draw_overlay(
+    pixels: &[[u8; 4]],
+    x_size: usize,
+    y_size: usize,
+    stats: &Stats,
+) -> Vec<[u8; 4]> {
+    let mut pixels = pixels.to_vec();
+    let text = format!(
+        "fps: {:.1}\npaints/s: {:.0}\nqueue: {}",
+        stats.fps, stats.paints_per_sec, stats.queue_depth,
+    );
+
+    // darken a backdrop so the text is legible over anything
+    let pad = 2;
+    let size = text_size(&text, 1) + Vec2::broadcast(pad * 2);
+    for y in 0..usize::min(size.y, y_size) {
+        for x in 0..usize::min(size.x, x_size) {
+            let [r, g, b, _] = pixels[y * x_size + x];
+            pixels[y * x_size + x] = [r / 4, g / 4, b / 4, 0xFF];
+        }
+    }
+
+    rasterize_text(Vec2::broadcast(pad as i32), &text, 1, &mut |xy| {
+        if xy.x >= 0 && xy.y >= 0 && (xy.x as usize) < x_size && (xy.y as usize) < y_size {
+            pixels[xy.y as usize * x_size + xy.x as usize] = [0xFF; 4];
+        }
+    });
+    pixels
+}
@@ -0,0 +1,349 @@
+use crate::frag::fragment_to_image;
+
+use std::{
+    env,
+    fmt,
+    io,
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use image::{ImageError, RgbaImage};
+use vek::*;
+
+/// Environment variable which, when set, makes golden-image checks overwrite their
+/// reference images instead of comparing against them.
+pub const UPDATE_VAR: &str = "CPURENDER_UPDATE_GOLDEN";
+
+/// How much an image may differ from its reference and still match.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Tolerance {
+    /// Largest difference allowed in each channel of a pixel. Defaults to 0.
+    ///
+    /// Use `Rgba::broadcast` to allow the same difference in every channel.
+    pub channel: Rgba<u8>,
+    /// Number of pixels allowed to exceed the channel tolerance. Defaults to 0.
+    pub pixels: usize,
+}
+
+/// Result of comparing two images of the same size.
+#[derive(Clone, Debug)]
+pub struct Comparison {
+    /// Number of pixels with a channel differing by more than its tolerance.
+    pub mismatched: usize,
+    /// Largest difference in any channel of any pixel.
+    pub max_diff: u8,
+    /// Image highlighting the mismatched pixels in red, over a dimmed copy of the
+    /// reference.
+    pub diff: RgbaImage,
+}
+
+impl Comparison {
+    /// Whether the images match within the tolerance.
+    pub fn passed(&self, tolerance: Tolerance) -> bool {
+        self.mismatched <= tolerance.pixels
+    }
+}
+
+/// Compare an image to a reference image of the same size, pixel by pixel.
+///
+/// Panics if the images differ in size.
+pub fn compare(actual: &RgbaImage, expected: &RgbaImage, tolerance: Tolerance) -> Comparison {
+    assert_eq!(
+        actual.dimensions(), expected.dimensions(),
+        "compared images differ in size",
+    );
+
+    let mut diff = RgbaImage::new(expected.width(), expected.height());
+    let mut mismatched = 0;
+    let mut max_diff = 0;
+    for ((a, e), d) in actual.pixels().zip(expected.pixels()).zip(diff.pixels_mut()) {
+        let pixel_diff = Rgba::<u8>::from(a.0)
+            .zip(Rgba::from(e.0))
+            .map(|(a, e)| a.abs_diff(e));
+        max_diff = max_diff.max(pixel_diff.reduce_max());
+
+        let exceeds = pixel_diff.zip(tolerance.channel).map(|(diff, tol)| diff > tol);
+        d.0 =
+            if exceeds.reduce_or() {
+                mismatched += 1;
+                [0xFF, 0x00, 0x00, 0xFF]
+            } else {
+                // dimmed luminance of the reference
+                let rgb = Rgb::new(e.0[0], e.0[1], e.0[2]).map(|n| n as f32);
+                let luma = (rgb.r * 0.2126 + rgb.g * 0.7152 + rgb.b * 0.0722) / 4.0;
+                [luma as u8, luma as u8, luma as u8, 0xFF]
+            };
+    }
+
+    Comparison {
+        mismatched,
+        max_diff,
+        diff,
+    }
+}
+
+/// Failure of a golden-image check.
+#[derive(Debug)]
+pub enum GoldenError {
+    /// Failed to read the reference image.
+    Read(ImageError),
+    /// Failed to write the reference, actual, or diff image.
+    Write(io::Error),
+    /// The image differs in size from the reference.
+    Size {
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+    /// The image differs from the reference by more than the tolerance.
+    Mismatch {
+        mismatched: usize,
+        max_diff: u8,
+        /// Where the diff image was saved.
+        diff_path: PathBuf,
+    },
+}
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GoldenError::Read(e) => write!(f, "failed to read reference image: {}", e),
+            GoldenError::Write(e) => write!(f, "failed to write image: {}", e),
+            GoldenError::Size { expected, actual } => write!(
+                f, "image is {}x{}, but reference is {}x{}",
+                actual.0, actual.1, expected.0, expected.1,
+            ),
+            GoldenError::Mismatch { mismatched, max_diff, diff_path } => write!(
+                f, "{} pixels differ from reference, by up to {}, see {}",
+                mismatched, max_diff, diff_path.display(),
+            ),
+        }
+    }
+}
+
+impl Error for GoldenError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GoldenError::Read(e) => Some(e),
+            GoldenError::Write(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Check an image against a reference PNG.
+///
+/// On a mismatch, the image and a diff are saved beside the reference, with the extensions
+/// `.actual.png` and `.diff.png`. If the `CPURENDER_UPDATE_GOLDEN` environment variable is
+/// set, the reference is overwritten with the image instead.
+pub fn check_golden(
+    image: &RgbaImage,
+    reference: impl AsRef<Path>,
+    tolerance: Tolerance,
+) -> Result<(), GoldenError> {
+    let reference = reference.as_ref();
+
+    if env::var_os(UPDATE_VAR).is_some() {
+        info!("updating reference image {}", reference.display());
+        return image.save(reference).map_err(GoldenError::Write);
+    }
+
+    let expected = image::open(reference).map_err(GoldenError::Read)?.to_rgba();
+    if expected.dimensions() != image.dimensions() {
+        return Err(GoldenError::Size {
+            expected: expected.dimensions(),
+            actual: image.dimensions(),
+        });
+    }
+
+    let comparison = compare(image, &expected, tolerance);
+    if comparison.passed(tolerance) {
+        return Ok(());
+    }
+
+    let actual_path = reference.with_extension("actual.png");
+    let diff_path = reference.with_extension("diff.png");
+    image.save(&actual_path).map_err(GoldenError::Write)?;
+    comparison.diff.save(&diff_path).map_err(GoldenError::Write)?;
+    Err(GoldenError::Mismatch {
+        mismatched: comparison.mismatched,
+        max_diff: comparison.max_diff,
+        diff_path,
+    })
+}
+
+/// Render a fragment function headlessly, and assert that it matches a reference PNG.
+///
+/// This is meant for regression tests of renderers. See `check_golden`.
+pub fn assert_golden<F>(
+    x_size: usize,
+    y_size: usize,
+    reference: impl AsRef<Path>,
+    tolerance: Tolerance,
+    fragment: F,
+)
+    where
+        F: Fn(Vec2<i32>) -> Rgba<u8> + Sync {
+
+    let reference = reference.as_ref();
+    let image = fragment_to_image(x_size, y_size, fragment);
+    if let Err(e) = check_golden(&image, reference, tolerance) {
+        panic!("golden image check against {} failed: {}", reference.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{fs, process};
+
+    use image::Rgba as Pixel;
+
+    fn solid(color: [u8; 4]) -> RgbaImage {
+        RgbaImage::from_pixel(4, 3, Pixel(color))
+    }
+
+    /// Empty scratch directory for golden images, unique to the test.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir()
+            .join(format!("cpurender-testing-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn identical_images_match() {
+        let image = solid([10, 20, 30, 255]);
+        let comparison = compare(&image, &image, Tolerance::default());
+        assert_eq!(comparison.mismatched, 0);
+        assert_eq!(comparison.max_diff, 0);
+        assert!(comparison.passed(Tolerance::default()));
+    }
+
+    #[test]
+    fn tolerance_is_per_channel() {
+        let expected = solid([100, 100, 100, 255]);
+        let actual = solid([100, 100, 100, 250]);
+
+        let alpha = Tolerance {
+            channel: Rgba::new(0, 0, 0, 5),
+            pixels: 0,
+        };
+        let comparison = compare(&actual, &expected, alpha);
+        assert_eq!(comparison.mismatched, 0);
+        assert_eq!(comparison.max_diff, 5);
+
+        let red = Tolerance {
+            channel: Rgba::new(5, 0, 0, 0),
+            pixels: 0,
+        };
+        let comparison = compare(&actual, &expected, red);
+        assert_eq!(comparison.mismatched, 12);
+        assert!(!comparison.passed(red));
+    }
+
+    #[test]
+    fn pixel_tolerance_allows_mismatches() {
+        let expected = solid([0, 0, 0, 255]);
+        let mut actual = expected.clone();
+        actual.put_pixel(1, 1, Pixel([255, 0, 0, 255]));
+
+        let tolerance = Tolerance {
+            channel: Rgba::zero(),
+            pixels: 1,
+        };
+        let comparison = compare(&actual, &expected, tolerance);
+        assert_eq!(comparison.mismatched, 1);
+        assert_eq!(comparison.max_diff, 255);
+        assert!(comparison.passed(tolerance));
+        assert!(!comparison.passed(Tolerance::default()));
+    }
+
+    #[test]
+    fn diff_highlights_mismatches() {
+        let expected = solid([200, 200, 200, 255]);
+        let mut actual = expected.clone();
+        actual.put_pixel(2, 0, Pixel([0, 0, 0, 255]));
+
+        let diff = compare(&actual, &expected, Tolerance::default()).diff;
+        assert_eq!(diff.dimensions(), expected.dimensions());
+        for (x, y, pixel) in diff.enumerate_pixels() {
+            if (x, y) == (2, 0) {
+                assert_eq!(pixel.0, [0xFF, 0x00, 0x00, 0xFF]);
+            } else {
+                // dimmed grey
+                assert_eq!(pixel.0, [50, 50, 50, 0xFF]);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn compare_panics_on_size_mismatch() {
+        compare(&RgbaImage::new(2, 2), &RgbaImage::new(3, 2), Tolerance::default());
+    }
+
+    #[test]
+    fn golden_passes_against_itself() {
+        let dir = scratch_dir("passes");
+        let reference = dir.join("ref.png");
+        let image = solid([1, 2, 3, 255]);
+        image.save(&reference).unwrap();
+
+        check_golden(&image, &reference, Tolerance::default()).unwrap();
+        assert!(!dir.join("ref.actual.png").exists());
+        assert!(!dir.join("ref.diff.png").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn golden_mismatch_saves_actual_and_diff() {
+        let dir = scratch_dir("mismatch");
+        let reference = dir.join("ref.png");
+        solid([0, 0, 0, 255]).save(&reference).unwrap();
+
+        let image = solid([9, 0, 0, 255]);
+        match check_golden(&image, &reference, Tolerance::default()) {
+            Err(GoldenError::Mismatch { mismatched, max_diff, diff_path }) => {
+                assert_eq!(mismatched, 12);
+                assert_eq!(max_diff, 9);
+                assert_eq!(diff_path, dir.join("ref.diff.png"));
+            },
+            result => panic!("expected mismatch, got {:?}", result),
+        }
+        let actual = image::open(dir.join("ref.actual.png")).unwrap().to_rgba();
+        assert_eq!(actual.into_raw(), image.clone().into_raw());
+        assert!(dir.join("ref.diff.png").exists());
+
+        // within tolerance
+        let tolerance = Tolerance {
+            channel: Rgba::broadcast(9),
+            pixels: 0,
+        };
+        check_golden(&image, &reference, tolerance).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn golden_reports_size_and_missing_reference() {
+        let dir = scratch_dir("errors");
+        let reference = dir.join("ref.png");
+
+        match check_golden(&solid([0; 4]), &reference, Tolerance::default()) {
+            Err(GoldenError::Read(_)) => (),
+            result => panic!("expected read error, got {:?}", result),
+        }
+
+        RgbaImage::new(2, 2).save(&reference).unwrap();
+        match check_golden(&solid([0; 4]), &reference, Tolerance::default()) {
+            Err(GoldenError::Size { expected, actual }) => {
+                assert_eq!(expected, (2, 2));
+                assert_eq!(actual, (4, 3));
+            },
+            result => panic!("expected size error, got {:?}", result),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,327 @@
+use std::path::Path;
+
+use vek::*;
+use image::{RgbaImage, ImageError, ImageResult};
+
+/// How a texture is filtered when sampled.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Filter {
+    /// Use the nearest texel.
+    Nearest,
+    /// Linearly interpolate between the four nearest texels.
+    Bilinear,
+}
+
+/// How texture coordinates outside of `[0, 1]` are handled.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Wrap {
+    /// Tile the texture.
+    Repeat,
+    /// Tile the texture, mirroring every other tile.
+    MirroredRepeat,
+    /// Extend the texture's edge texels.
+    Clamp,
+}
+
+impl Wrap {
+    /// Map a texel index into `[0, size)`.
+    fn apply(self, i: i32, size: i32) -> i32 {
+        match self {
+            Wrap::Repeat => i.rem_euclid(size),
+            Wrap::MirroredRepeat => {
+                let i = i.rem_euclid(2 * size);
+                if i >= size { 2 * size - 1 - i } else { i }
+            },
+            Wrap::Clamp => i.max(0).min(size - 1),
+        }
+    }
+}
+
+/// Image which can be sampled by UV coordinates, like a GPU texture.
+///
+/// UV `(0, 0)` is the top-left corner of the image, and `(1, 1)` the bottom-right. Samples
+/// are RGBA colors with components in `[0, 1]`.
+#[derive(Clone, Debug)]
+pub struct Texture {
+    /// Mipmap levels, from full resolution downwards.
+    levels: Vec<RgbaImage>,
+    pub filter: Filter,
+    pub wrap: Wrap,
+}
+
+impl Texture {
+    /// Wrap an image, with bilinear filtering and repeat wrapping.
+    ///
+    /// Panics if the image is empty.
+    pub fn new(image: RgbaImage) -> Self {
+        assert!(
+            image.width() > 0 && image.height() > 0,
+            "texture must not be empty",
+        );
+        Texture {
+            levels: vec![image],
+            filter: Filter::Bilinear,
+            wrap: Wrap::Repeat,
+        }
+    }
+
+    /// Load an image file as a texture.
+    ///
+    /// An empty image is an `ImageError::DimensionError`.
+    pub fn open(path: impl AsRef<Path>) -> ImageResult<Self> {
+        let image = image::open(path)?.to_rgba();
+        if image.width() == 0 || image.height() == 0 {
+            return Err(ImageError::DimensionError);
+        }
+        Ok(Texture::new(image))
+    }
+
+    /// Set the filter mode.
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Set the wrap mode.
+    pub fn with_wrap(mut self, wrap: Wrap) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    /// Generate mipmap levels, each half the size of the previous, down to 1x1.
+    pub fn with_mipmaps(mut self) -> Self {
+        self.levels.truncate(1);
+        while let Some(level) = downsample(self.levels.last().unwrap()) {
+            self.levels.push(level);
+        }
+        self
+    }
+
+    /// The full-resolution image.
+    pub fn image(&self) -> &RgbaImage {
+        &self.levels[0]
+    }
+
+    /// Number of mipmap levels, including the full-resolution image.
+    pub fn num_levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Size of a mipmap level, in texels.
+    pub fn size(&self, level: usize) -> Vec2<u32> {
+        let (w, h) = self.levels[level].dimensions();
+        Vec2::new(w, h)
+    }
+
+    /// Read a texel of a mipmap level, applying the wrap mode to its coordinates.
+    pub fn texel(&self, xy: Vec2<i32>, level: usize) -> Rgba<f32> {
+        let image = &self.levels[level];
+        let (w, h) = image.dimensions();
+        let x = self.wrap.apply(xy.x, w as i32) as usize;
+        let y = self.wrap.apply(xy.y, h as i32) as usize;
+
+        let raw: &[u8] = image;
+        let i = (y * w as usize + x) * 4;
+        Rgba::new(
+            raw[i] as f32,
+            raw[i + 1] as f32,
+            raw[i + 2] as f32,
+            raw[i + 3] as f32,
+        ) / 255.0
+    }
+
+    /// Sample the full-resolution image.
+    pub fn sample(&self, uv: Vec2<f32>) -> Rgba<f32> {
+        self.sample_level(uv, 0)
+    }
+
+    /// Sample a single mipmap level.
+    pub fn sample_level(&self, uv: Vec2<f32>, level: usize) -> Rgba<f32> {
+        let size: Vec2<f32> = self.size(level).numcast().unwrap();
+        let xy = uv * size;
+
+        match self.filter {
+            Filter::Nearest => self.texel(xy.map(|n| n.floor() as i32), level),
+            Filter::Bilinear => {
+                // offset so that texel centers are at integer coordinates
+                let xy = xy - 0.5;
+                let base = xy.map(|n| n.floor());
+                let t = xy - base;
+                let base = base.map(|n| n as i32);
+
+                let c00 = self.texel(base, level);
+                let c10 = self.texel(base + Vec2::new(1, 0), level);
+                let c01 = self.texel(base + Vec2::new(0, 1), level);
+                let c11 = self.texel(base + Vec2::new(1, 1), level);
+
+                Rgba::lerp(
+                    Rgba::lerp(c00, c10, t.x),
+                    Rgba::lerp(c01, c11, t.x),
+                    t.y,
+                )
+            },
+        }
+    }
+
+    /// Sample at a fractional level of detail, interpolating between mipmap levels.
+    ///
+    /// Level of detail 0 is the full-resolution image, and each increment halves the
+    /// resolution. Without mipmaps, this is the same as `sample`.
+    pub fn sample_lod(&self, uv: Vec2<f32>, lod: f32) -> Rgba<f32> {
+        let max_level = self.levels.len() - 1;
+        let lod = lod.max(0.0).min(max_level as f32);
+
+        let lower = lod.floor() as usize;
+        let upper = usize::min(lower + 1, max_level);
+        if lower == upper {
+            return self.sample_level(uv, lower);
+        }
+
+        Rgba::lerp(
+            self.sample_level(uv, lower),
+            self.sample_level(uv, upper),
+            lod - lower as f32,
+        )
+    }
+
+    /// Sample with the level of detail derived from the screen-space derivatives of the UV
+    /// coordinates, as a GPU does.
+    ///
+    /// `duv_dx` and `duv_dy` are how much the UV coordinates change from one pixel to the
+    /// next, horizontally and vertically.
+    pub fn sample_grad(
+        &self,
+        uv: Vec2<f32>,
+        duv_dx: Vec2<f32>,
+        duv_dy: Vec2<f32>,
+    ) -> Rgba<f32> {
+        let size: Vec2<f32> = self.size(0).numcast().unwrap();
+        let footprint = f32::max(
+            (duv_dx * size).magnitude(),
+            (duv_dy * size).magnitude(),
+        );
+        self.sample_lod(uv, footprint.max(1.0).log2())
+    }
+}
+
+/// Environment map in the equirectangular (latitude-longitude) projection, which can be
+/// sampled by direction, for backgrounds and ambient lighting.
+///
+/// Directions are in world space with y up. The center of the image faces negative z, the
+/// left and right edges face positive z, and the top and bottom edges face straight up and
+/// down.
+#[derive(Clone, Debug)]
+pub struct EnvMap {
+    texture: Texture,
+}
+
+impl EnvMap {
+    /// Wrap an equirectangular image, with bilinear filtering.
+    ///
+    /// Panics if the image is empty.
+    pub fn from_image(image: RgbaImage) -> Self {
+        EnvMap {
+            texture: Texture::new(image)
+                .with_wrap(Wrap::Repeat)
+                .with_mipmaps(),
+        }
+    }
+
+    /// Load an equirectangular image file as an environment map.
+    ///
+    /// An empty image is an `ImageError::DimensionError`, like `Texture::open`.
+    pub fn open(path: impl AsRef<Path>) -> ImageResult<Self> {
+        Ok(EnvMap {
+            texture: Texture::open(path)?
+                .with_wrap(Wrap::Repeat)
+                .with_mipmaps(),
+        })
+    }
+
+    /// Set the filter mode.
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.texture.filter = filter;
+        self
+    }
+
+    /// The underlying texture, with mipmaps.
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    /// UV coordinates of the texel seen in a direction, which needn't be normalized.
+    pub fn dir_to_uv(dir: Vec3<f32>) -> Vec2<f32> {
+        let dir = dir.normalized();
+        let tau = 2.0 * std::f32::consts::PI;
+        Vec2::new(
+            0.5 + dir.x.atan2(-dir.z) / tau,
+            dir.y.clamp(-1.0, 1.0).acos() / std::f32::consts::PI,
+        )
+    }
+
+    /// Sample the color seen in a direction.
+    pub fn sample(&self, dir: Vec3<f32>) -> Rgba<f32> {
+        self.sample_lod(dir, 0.0)
+    }
+
+    /// Sample the color seen in a direction, blurred to a level of detail, like
+    /// `Texture::sample_lod`.
+    pub fn sample_lod(&self, dir: Vec3<f32>, lod: f32) -> Rgba<f32> {
+        let uv = EnvMap::dir_to_uv(dir);
+
+        // keep filtering from reaching across the poles, which wrapping would
+        let level = lod.max(0.0).round() as usize;
+        let level = level.min(self.texture.num_levels() - 1);
+        let half_texel = 0.5 / self.texture.size(level).y as f32;
+        let v = uv.y.max(half_texel).min(1.0 - half_texel);
+
+        self.texture.sample_lod(Vec2::new(uv.x, v), lod)
+    }
+
+    /// Approximate the ambient light arriving at a surface with the given normal, from a
+    /// heavily blurred sample of the environment.
+    ///
+    /// This is a cheap stand-in for image-based diffuse lighting.
+    pub fn ambient(&self, normal: Vec3<f32>) -> Rgb<f32> {
+        // a level roughly 4 texels tall
+        let lod = (self.texture.num_levels() as f32 - 3.0).max(0.0);
+        self.sample_lod(normal, lod).rgb()
+    }
+}
+
+/// Halve the size of an image, rounding up, with a box filter, or return `None` if it is
+/// already 1x1.
+fn downsample(image: &RgbaImage) -> Option<RgbaImage> {
+    let (w, h) = image.dimensions();
+    if w == 1 && h == 1 {
+        return None;
+    }
+
+    let raw: &[u8] = image;
+    // round up, so odd edges are kept, averaged over the part of the footprint which fits
+    let new_w = w.div_ceil(2);
+    let new_h = h.div_ceil(2);
+    let mut buf: Vec<u8> = Vec::with_capacity((new_w * new_h * 4) as usize);
+
+    for y in 0..new_h {
+        for x in 0..new_w {
+            // average the up to 2x2 source texels
+            let mut sum = [0u32; 4];
+            let mut count = 0;
+            for sy in y * 2..u32::min(y * 2 + 2, h) {
+                for sx in x * 2..u32::min(x * 2 + 2, w) {
+                    let i = ((sy * w + sx) * 4) as usize;
+                    for c in 0..4 {
+                        sum[c] += raw[i + c] as u32;
+                    }
+                    count += 1;
+                }
+            }
+            for channel in &sum {
+                buf.push(((channel + count / 2) / count) as u8);
+            }
+        }
+    }
+
+    RgbaImage::from_raw(new_w, new_h, buf)
+}
@@ -0,0 +1,330 @@
+use crate::{
+    DrawContext,
+    PaintBatch,
+};
+
+use std::{
+    iter,
+    any::Any,
+    cmp::Ordering as CmpOrdering,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        Arc,
+        Condvar,
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crossbeam::{
+    deque::{Injector, Stealer, Worker},
+    sync::WaitGroup,
+};
+use rand::{
+    SeedableRng,
+    rngs::StdRng,
+    seq::SliceRandom,
+};
+use vek::*;
+
+/// How long idle workers sleep before checking for work again, in case a wakeup was missed.
+const IDLE_POLL: Duration = Duration::from_millis(50);
+
+/// Order in which a `TileRenderer` starts rendering tiles.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum TileOrder {
+    /// Row by row, from the top-left.
+    #[default]
+    Scanline,
+    /// Nearest to the center of the canvas first.
+    CenterOut,
+    /// Nearest to a canvas position first.
+    Near(Vec2<f32>),
+    /// Nearest to the mouse cursor first, where the caller tracks the cursor, and otherwise
+    /// the same as `CenterOut`.
+    NearCursor,
+    /// Along a square spiral out from the center, ring by ring.
+    Spiral,
+    /// Along a Hilbert curve, which keeps consecutive tiles adjacent, so the rendered area
+    /// grows in compact patches.
+    Hilbert,
+    /// Shuffled, with the given seed, which shows the whole image at once as it fills in.
+    Random {
+        seed: u64,
+    },
+}
+
+impl TileOrder {
+    /// Sort tiles of a canvas of the given size into this order.
+    ///
+    /// Tiles should be in scanline order to begin with, which breaks ties.
+    pub fn sort(self, tiles: &mut [Rect<usize, usize>], x_size: usize, y_size: usize) {
+        let center = Vec2::new(x_size as f32, y_size as f32) / 2.0;
+        // tiles' positions in the grid of tiles
+        let step = tiles.iter().map(|tile| tile.w.max(tile.h)).max().unwrap_or(1).max(1);
+        let cell = |tile: &Rect<usize, usize>| Vec2::new(tile.x / step, tile.y / step);
+
+        let focus = match self {
+            TileOrder::Scanline => return,
+            TileOrder::CenterOut | TileOrder::NearCursor => center,
+            TileOrder::Near(pos) => pos,
+            TileOrder::Spiral => {
+                let center = center.map(|n| (n / step as f32) as i64);
+                tiles.sort_by_key(|tile| {
+                    let d = cell(tile).map(|n| n as i64) - center;
+                    // ring, then angle around it, starting at the top-left corner
+                    let ring = d.x.abs().max(d.y.abs());
+                    let angle = (d.y as f32).atan2(d.x as f32) + std::f32::consts::PI * 0.75;
+                    let angle = angle.rem_euclid(std::f32::consts::PI * 2.0);
+                    (ring, (angle * 1e4) as i64)
+                });
+                return;
+            },
+            TileOrder::Hilbert => {
+                let cells = tiles.iter()
+                    .map(&cell)
+                    .fold(Vec2::<usize>::zero(), |a, c| Vec2::new(a.x.max(c.x), a.y.max(c.y)));
+                let n = (cells.x.max(cells.y) + 1).next_power_of_two();
+                tiles.sort_by_key(|tile| hilbert_index(n, cell(tile)));
+                return;
+            },
+            TileOrder::Random { seed } => {
+                tiles.shuffle(&mut StdRng::seed_from_u64(seed));
+                return;
+            },
+        };
+        let dist = |tile: &Rect<usize, usize>| {
+            let tile_center = Vec2::new(
+                tile.x as f32 + tile.w as f32 / 2.0,
+                tile.y as f32 + tile.h as f32 / 2.0,
+            );
+            tile_center.distance_squared(focus)
+        };
+        tiles.sort_by(|a, b| dist(a).partial_cmp(&dist(b)).unwrap_or(CmpOrdering::Equal));
+    }
+}
+
+/// Distance along a Hilbert curve filling an `n` by `n` grid, where `n` is a power of two,
+/// of a cell of the grid.
+fn hilbert_index(n: usize, cell: Vec2<usize>) -> usize {
+    let (mut x, mut y) = (cell.x, cell.y);
+    let mut d = 0;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = (x & s > 0) as usize;
+        let ry = (y & s > 0) as usize;
+        d += s * s * ((3 * rx) ^ ry);
+
+        // rotate the quadrant so the curve connects
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - (x & (s - 1));
+                y = s - 1 - (y & (s - 1));
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    d
+}
+
+/// Payload of a panic, as caught by `panic::catch_unwind`.
+type Panic = Box<dyn Any + Send>;
+
+/// Tile rendering task, with where to report a panic.
+struct Job {
+    task: Box<dyn FnOnce() + Send>,
+    /// First panic of any tile of the render, which the caller resumes.
+    panicked: Arc<Mutex<Option<Panic>>>,
+}
+
+impl Job {
+    /// Run the task, catching a panic so the worker survives it, and reporting it to the
+    /// caller if it's the render's first.
+    fn run(self) {
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(self.task)) {
+            self.panicked.lock().unwrap().get_or_insert(payload);
+        }
+    }
+}
+
+/// State shared between a `TileRenderer` and its workers.
+struct Shared {
+    injector: Injector<Job>,
+    stealers: Vec<Stealer<Job>>,
+    /// Held while checking for work before sleeping, and while waking workers.
+    lock: Mutex<()>,
+    wake: Condvar,
+    stop: AtomicBool,
+}
+
+/// Renderer with persistent worker threads, which take tiles from a shared queue in
+/// priority order, and steal from each other when they run out.
+///
+/// Unlike a one-shot parallel iterator, the threads live as long as the renderer, so
+/// rendering frame after frame doesn't pay to start them up, and tiles can be prioritized,
+/// so the interesting part of the canvas is rendered first.
+pub struct TileRenderer {
+    shared: Arc<Shared>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl TileRenderer {
+    /// Start a renderer with the given number of worker threads, or one per CPU if 0.
+    pub fn new(threads: usize) -> Self {
+        let threads =
+            if threads == 0 { rayon::current_num_threads() }
+            else { threads };
+
+        let workers: Vec<Worker<Job>> = (0..threads).map(|_| Worker::new_fifo()).collect();
+        let shared = Arc::new(Shared {
+            injector: Injector::new(),
+            stealers: workers.iter().map(Worker::stealer).collect(),
+            lock: Mutex::new(()),
+            wake: Condvar::new(),
+            stop: AtomicBool::new(false),
+        });
+
+        let threads = workers.into_iter()
+            .enumerate()
+            .map(|(i, local)| {
+                let shared = Arc::clone(&shared);
+                thread::Builder::new()
+                    .name(format!("cpurender tile worker {}", i))
+                    .spawn(move || work(&shared, &local))
+                    .expect("failed to spawn tile worker thread")
+            })
+            .collect();
+
+        TileRenderer {
+            shared,
+            threads,
+        }
+    }
+
+    pub fn num_threads(&self) -> usize {
+        self.threads.len()
+    }
+
+    /// Render every fragment of the canvas once, in square tiles of the given size, started
+    /// in the given order, painting each tile as a batch. Blocks until every tile is done,
+    /// and every clone of `fragment` given to the workers has been dropped.
+    ///
+    /// Remaining tiles are skipped if the window closes. If the fragment function panics,
+    /// remaining tiles are skipped, and the panic is resumed on the calling thread.
+    pub fn render<F>(
+        &self,
+        x_size: usize,
+        y_size: usize,
+        tile_size: usize,
+        order: TileOrder,
+        ctx: &DrawContext,
+        fragment: &Arc<F>,
+    )
+        where
+            F: Fn(Vec2<i32>) -> Rgba<u8> + Send + Sync + 'static {
+
+        let tile_size = tile_size.max(1);
+        let mut tiles: Vec<Rect<usize, usize>> = Vec::new();
+        for y in (0..y_size).step_by(tile_size) {
+            for x in (0..x_size).step_by(tile_size) {
+                let w = usize::min(tile_size, x_size - x);
+                let h = usize::min(tile_size, y_size - y);
+                tiles.push(Rect::new(x, y, w, h));
+            }
+        }
+        order.sort(&mut tiles, x_size, y_size);
+
+        let done = WaitGroup::new();
+        let panicked = Arc::new(Mutex::new(None));
+        for tile in tiles {
+            let done = done.clone();
+            let fragment = Arc::clone(fragment);
+            let paint = ctx.pusher();
+            let shutdown = ctx.shutdown.clone();
+            let skip = Arc::clone(&panicked);
+            let task = Box::new(move || {
+                // signal completion even if this tile is skipped or panics, but only after
+                // dropping the fragment function, which is declared later
+                let _done = done;
+                let fragment = fragment;
+                if shutdown.is_shutdown() || skip.lock().unwrap().is_some() {
+                    return;
+                }
+
+                let mut rgba: Vec<[u8; 4]> = Vec::with_capacity(tile.w * tile.h);
+                for y in tile.y..tile.y + tile.h {
+                    for x in tile.x..tile.x + tile.w {
+                        let color = fragment(Vec2::new(x as i32, y as i32));
+                        rgba.push([color.r, color.g, color.b, color.a]);
+                    }
+                }
+                paint.push(PaintBatch::new(tile.x, tile.y, tile.w, tile.h, rgba));
+            });
+            self.shared.injector.push(Job {
+                task,
+                panicked: Arc::clone(&panicked),
+            });
+        }
+
+        // wake the workers, while holding the lock so that none miss it
+        {
+            let _guard = self.shared.lock.lock().unwrap();
+            self.shared.wake.notify_all();
+        }
+        done.wait();
+
+        let payload = panicked.lock().unwrap().take();
+        if let Some(payload) = payload {
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+impl Drop for TileRenderer {
+    fn drop(&mut self) {
+        {
+            let _guard = self.shared.lock.lock().unwrap();
+            self.shared.stop.store(true, Ordering::SeqCst);
+            self.shared.wake.notify_all();
+        }
+        for thread in self.threads.drain(..) {
+            if thread.join().is_err() {
+                error!("tile worker thread panicked");
+            }
+        }
+    }
+}
+
+/// Run jobs on a worker thread until the renderer stops.
+fn work(shared: &Shared, local: &Worker<Job>) {
+    loop {
+        if let Some(job) = find_job(shared, local) {
+            job.run();
+            continue;
+        }
+
+        // sleep until there's more work
+        let guard = shared.lock.lock().unwrap();
+        if shared.stop.load(Ordering::SeqCst) {
+            return;
+        }
+        if shared.injector.is_empty() {
+            let _ = shared.wake.wait_timeout(guard, IDLE_POLL);
+        }
+    }
+}
+
+/// Take a job from the local queue, or else the shared queue, or else another worker.
+fn find_job(shared: &Shared, local: &Worker<Job>) -> Option<Job> {
+    local.pop().or_else(|| {
+        iter::repeat_with(|| {
+            shared.injector.steal_batch_and_pop(local)
+                .or_else(|| shared.stealers.iter().map(Stealer::steal).collect())
+        })
+            .find(|steal| !steal.is_retry())
+            .and_then(|steal| steal.success())
+    })
+}
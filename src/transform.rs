@@ -0,0 +1,168 @@
+use vek::*;
+
+/// 2D affine transform, which maps a point by scaling and adding its axes, then offsetting
+/// by an origin.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform2 {
+    /// Image of the x axis.
+    pub x_axis: Vec2<f32>,
+    /// Image of the y axis.
+    pub y_axis: Vec2<f32>,
+    /// Image of the origin.
+    pub origin: Vec2<f32>,
+}
+
+impl Default for Transform2 {
+    fn default() -> Self {
+        Transform2::identity()
+    }
+}
+
+impl Transform2 {
+    /// Transform which maps every point to itself.
+    pub fn identity() -> Self {
+        Transform2 {
+            x_axis: Vec2::new(1.0, 0.0),
+            y_axis: Vec2::new(0.0, 1.0),
+            origin: Vec2::zero(),
+        }
+    }
+
+    pub fn translation(offset: Vec2<f32>) -> Self {
+        Transform2 {
+            origin: offset,
+            ..Transform2::identity()
+        }
+    }
+
+    /// Rotation about the origin by an angle in radians, from the positive x axis towards
+    /// the positive y axis.
+    pub fn rotation(angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Transform2 {
+            x_axis: Vec2::new(cos, sin),
+            y_axis: Vec2::new(-sin, cos),
+            origin: Vec2::zero(),
+        }
+    }
+
+    /// Scaling about the origin.
+    pub fn scaling(scale: Vec2<f32>) -> Self {
+        Transform2 {
+            x_axis: Vec2::new(scale.x, 0.0),
+            y_axis: Vec2::new(0.0, scale.y),
+            origin: Vec2::zero(),
+        }
+    }
+
+    /// Transform which applies this one, then `next`.
+    pub fn then(self, next: Transform2) -> Self {
+        Transform2 {
+            x_axis: next.apply_vector(self.x_axis),
+            y_axis: next.apply_vector(self.y_axis),
+            origin: next.apply(self.origin),
+        }
+    }
+
+    /// Transform a point.
+    pub fn apply(&self, point: Vec2<f32>) -> Vec2<f32> {
+        self.apply_vector(point) + self.origin
+    }
+
+    /// Transform a direction, which ignores translation.
+    pub fn apply_vector(&self, vector: Vec2<f32>) -> Vec2<f32> {
+        self.x_axis * vector.x + self.y_axis * vector.y
+    }
+
+    /// Transform which undoes this one, or `None` if this one collapses space onto a line
+    /// or point.
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.x_axis.x * self.y_axis.y - self.y_axis.x * self.x_axis.y;
+        if det.abs() < f32::EPSILON {
+            return None;
+        }
+
+        let x_axis = Vec2::new(self.y_axis.y, -self.x_axis.y) / det;
+        let y_axis = Vec2::new(-self.y_axis.x, self.x_axis.x) / det;
+        let inv = Transform2 {
+            x_axis,
+            y_axis,
+            origin: Vec2::zero(),
+        };
+        Some(Transform2 {
+            origin: -inv.apply_vector(self.origin),
+            ..inv
+        })
+    }
+}
+
+/// Stack of transforms, for composing 2D scenes hierarchically, like the transform state
+/// of canvas APIs.
+///
+/// Translations, rotations, and scalings are applied in the current local space, so they
+/// affect everything drawn afterwards, and `push` and `pop` save and restore the current
+/// transform. The drawing functions suffixed `_transformed` in the `draw` module take the
+/// `current` transform.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransformStack {
+    /// Saved transforms, and the current transform at the top.
+    stack: Vec<Transform2>,
+}
+
+impl Default for TransformStack {
+    fn default() -> Self {
+        TransformStack::new()
+    }
+}
+
+impl TransformStack {
+    /// Construct a stack with just the identity transform.
+    pub fn new() -> Self {
+        TransformStack {
+            stack: vec![Transform2::identity()],
+        }
+    }
+
+    /// The current transform, from local space to canvas space.
+    pub fn current(&self) -> Transform2 {
+        *self.stack.last().unwrap()
+    }
+
+    /// Save the current transform, to be restored by `pop`.
+    pub fn push(&mut self) {
+        let current = self.current();
+        self.stack.push(current);
+    }
+
+    /// Restore the transform saved by the most recent `push`.
+    ///
+    /// Panics if there is no matching `push`.
+    pub fn pop(&mut self) {
+        assert!(self.stack.len() > 1, "transform stack popped without a matching push");
+        self.stack.pop();
+    }
+
+    /// Apply a transform in the current local space.
+    pub fn transform(&mut self, transform: Transform2) {
+        let top = self.stack.last_mut().unwrap();
+        *top = transform.then(*top);
+    }
+
+    pub fn translate(&mut self, offset: Vec2<f32>) {
+        self.transform(Transform2::translation(offset));
+    }
+
+    /// Rotate by an angle in radians.
+    pub fn rotate(&mut self, angle: f32) {
+        self.transform(Transform2::rotation(angle));
+    }
+
+    pub fn scale(&mut self, scale: Vec2<f32>) {
+        self.transform(Transform2::scaling(scale));
+    }
+
+    /// Transform a point from local space to canvas space.
+    pub fn apply(&self, point: Vec2<f32>) -> Vec2<f32> {
+        self.current().apply(point)
+    }
+}
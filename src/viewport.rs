@@ -0,0 +1,196 @@
+use crate::{
+    PaintCmd,
+    SegQueue,
+    paint::{Paint, PaintBatch, PaintDepth, PaintF, PaintId},
+};
+
+use vek::*;
+
+/// Sub-rectangle of the canvas with its own coordinate space and paint queue, for rendering
+/// several views into one window, such as top, front, and perspective views, or an A/B
+/// comparison.
+///
+/// Anything which paints to a queue, such as the `draw` module, can paint to the viewport's
+/// `queue` in local coordinates, with the origin at the viewport's top-left corner.
+/// `flush` then moves the paints into the window's queue, offset into the viewport's
+/// rectangle, and clipped to it.
+///
+/// Commands without coordinates, such as `ClearDepth` and `EndFrame`, are passed through
+/// to the whole window, so frames should be ended on the window's queue, after flushing
+/// every viewport. `Clear` only fills the viewport, and doesn't reset the ID buffer.
+///
+/// This is unrelated to `frag::Viewport`, which is a region of a 2D world.
+#[derive(Debug)]
+pub struct Viewport {
+    rect: Rect<usize, usize>,
+    queue: SegQueue<PaintCmd>,
+}
+
+impl Viewport {
+    /// Construct a viewport over a rectangle of the canvas.
+    pub fn new(rect: Rect<usize, usize>) -> Self {
+        Viewport {
+            rect,
+            queue: SegQueue::new(),
+        }
+    }
+
+    /// Rectangle of the canvas which the viewport covers.
+    pub fn rect(&self) -> Rect<usize, usize> {
+        self.rect
+    }
+
+    pub fn x_size(&self) -> usize {
+        self.rect.w
+    }
+
+    pub fn y_size(&self) -> usize {
+        self.rect.h
+    }
+
+    /// Queue of paint instructions in the viewport's local coordinates.
+    pub fn queue(&self) -> &SegQueue<PaintCmd> {
+        &self.queue
+    }
+
+    /// Push a paint instruction in the viewport's local coordinates.
+    pub fn push(&self, cmd: impl Into<PaintCmd>) {
+        self.queue.push(cmd.into());
+    }
+
+    /// Whether a canvas position, such as the mouse cursor, is within the viewport.
+    pub fn contains(&self, pos: Vec2<f32>) -> bool {
+        pos.x >= self.rect.x as f32
+            && pos.y >= self.rect.y as f32
+            && pos.x < (self.rect.x + self.rect.w) as f32
+            && pos.y < (self.rect.y + self.rect.h) as f32
+    }
+
+    /// Convert a canvas position, such as the mouse cursor, to local coordinates.
+    pub fn to_local(&self, pos: Vec2<f32>) -> Vec2<f32> {
+        pos - Vec2::new(self.rect.x as f32, self.rect.y as f32)
+    }
+
+    /// Move the paint instructions queued so far into another queue, such as
+    /// `DrawContext::paint`, offset into the viewport's rectangle and clipped to it.
+    pub fn flush(&self, target: &SegQueue<PaintCmd>) {
+        while let Ok(cmd) = self.queue.pop() {
+            if let Some(cmd) = self.translate(cmd) {
+                target.push(cmd);
+            }
+        }
+    }
+
+    /// Offset a pixel into the canvas, or `None` if it's outside the viewport.
+    fn pixel(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        if x < self.rect.w && y < self.rect.h {
+            Some((self.rect.x + x, self.rect.y + y))
+        } else {
+            None
+        }
+    }
+
+    /// Clip a local rectangle to the viewport, and offset it into the canvas, or `None` if
+    /// nothing of it remains.
+    fn clip(&self, rect: Rect<usize, usize>) -> Option<Rect<usize, usize>> {
+        let x_end = usize::min(rect.x + rect.w, self.rect.w);
+        let y_end = usize::min(rect.y + rect.h, self.rect.h);
+        if rect.x < x_end && rect.y < y_end {
+            Some(Rect::new(
+                self.rect.x + rect.x,
+                self.rect.y + rect.y,
+                x_end - rect.x,
+                y_end - rect.y,
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Convert a paint instruction from local coordinates to canvas coordinates, or `None`
+    /// if it falls entirely outside the viewport.
+    fn translate(&self, cmd: PaintCmd) -> Option<PaintCmd> {
+        Some(match cmd {
+            PaintCmd::Paint(paint) => {
+                let (x, y) = self.pixel(paint.x, paint.y)?;
+                PaintCmd::Paint(Paint { x, y, ..paint })
+            },
+            PaintCmd::Blend(paint, blend) => {
+                let (x, y) = self.pixel(paint.x, paint.y)?;
+                PaintCmd::Blend(Paint { x, y, ..paint }, blend)
+            },
+            PaintCmd::Depth(paint) => {
+                let (x, y) = self.pixel(paint.x, paint.y)?;
+                PaintCmd::Depth(PaintDepth { x, y, ..paint })
+            },
+            PaintCmd::Float(paint) => {
+                let (x, y) = self.pixel(paint.x, paint.y)?;
+                PaintCmd::Float(PaintF { x, y, ..paint })
+            },
+            PaintCmd::Id(paint) => {
+                let (x, y) = self.pixel(paint.x, paint.y)?;
+                PaintCmd::Id(PaintId { x, y, ..paint })
+            },
+            PaintCmd::Batch(batch) => {
+                let local = Rect::new(batch.x, batch.y, batch.x_size, batch.y_size);
+                let clipped = self.clip(local)?;
+                if clipped.w == batch.x_size && clipped.h == batch.y_size {
+                    PaintCmd::Batch(PaintBatch { x: clipped.x, y: clipped.y, ..batch })
+                } else {
+                    // copy out the rows which remain
+                    let rgba: Vec<[u8; 4]> = batch.rgba
+                        .chunks(batch.x_size)
+                        .take(clipped.h)
+                        .flat_map(|row| row[..clipped.w].iter().copied())
+                        .collect();
+                    PaintCmd::Batch(
+                        PaintBatch::new(clipped.x, clipped.y, clipped.w, clipped.h, rgba)
+                            .with_blend(batch.blend)
+                    )
+                }
+            },
+            PaintCmd::Clear(color) => PaintCmd::FillRect {
+                rect: self.rect,
+                color,
+            },
+            PaintCmd::FillRect { rect, color } => PaintCmd::FillRect {
+                rect: self.clip(rect)?,
+                color,
+            },
+            cmd @ PaintCmd::ClearDepth
+            | cmd @ PaintCmd::Reset
+            | cmd @ PaintCmd::Capture(_)
+            | cmd @ PaintCmd::EndFrame => cmd,
+        })
+    }
+}
+
+/// Split a canvas into a grid of equally sized viewports, in row-major order.
+///
+/// Any remainder pixels go to the last column and row, so the viewports cover the whole
+/// canvas.
+pub fn grid(x_size: usize, y_size: usize, columns: usize, rows: usize) -> Vec<Viewport> {
+    let columns = columns.max(1);
+    let rows = rows.max(1);
+    let (cell_x, cell_y) = (x_size / columns, y_size / rows);
+    let mut viewports = Vec::with_capacity(columns * rows);
+    for row in 0..rows {
+        for column in 0..columns {
+            let (x, y) = (column * cell_x, row * cell_y);
+            let w = if column + 1 == columns { x_size - x } else { cell_x };
+            let h = if row + 1 == rows { y_size - y } else { cell_y };
+            viewports.push(Viewport::new(Rect::new(x, y, w, h)));
+        }
+    }
+    viewports
+}
+
+/// Split a canvas into a row of equally wide viewports, side by side.
+pub fn columns(x_size: usize, y_size: usize, n: usize) -> Vec<Viewport> {
+    grid(x_size, y_size, n, 1)
+}
+
+/// Split a canvas into a column of equally tall viewports, stacked top to bottom.
+pub fn rows(x_size: usize, y_size: usize, n: usize) -> Vec<Viewport> {
+    grid(x_size, y_size, 1, n)
+}
@@ -0,0 +1,127 @@
+use crate::{
+    color::Colormap,
+    texture::Filter,
+};
+
+use image::RgbaImage;
+use vek::*;
+
+/// How `heatmap` maps data values to the `[0, 1]` range of a colormap.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Normalization {
+    /// Stretch the smallest finite value to 0 and the largest to 1.
+    #[default]
+    Auto,
+    /// Map `min` to 0 and `max` to 1, clamping values outside of them.
+    Fixed {
+        min: f32,
+        max: f32,
+    },
+    /// Map 0 to the middle of the colormap, and the largest finite magnitude to the ends,
+    /// which suits diverging colormaps.
+    Symmetric,
+}
+
+impl Normalization {
+    /// The data values which map to 0 and 1.
+    ///
+    /// Non-finite values are ignored when autoscaling, and data with no finite values maps
+    /// from `[0, 1]`.
+    pub fn range(self, data: &[f32]) -> (f32, f32) {
+        let finite = || data.iter().cloned().filter(|n| n.is_finite());
+        match self {
+            Normalization::Fixed { min, max } => (min, max),
+            Normalization::Auto => {
+                let min = finite().fold(f32::INFINITY, f32::min);
+                let max = finite().fold(f32::NEG_INFINITY, f32::max);
+                if min <= max { (min, max) }
+                else { (0.0, 1.0) }
+            },
+            Normalization::Symmetric => {
+                let max = finite().fold(0.0f32, |a, n| a.max(n.abs()));
+                if max > 0.0 { (-max, max) }
+                else { (-1.0, 1.0) }
+            },
+        }
+    }
+}
+
+/// Color a row-major grid of `width` by `height` data values, one pixel per cell.
+///
+/// NaN cells are transparent. Draw the result with `draw::blit_image`, or see
+/// `heatmap_scaled` for smooth upscaling.
+pub fn heatmap(
+    data: &[f32],
+    width: usize,
+    height: usize,
+    colormap: &Colormap,
+    normalization: Normalization,
+) -> RgbaImage {
+    heatmap_scaled(
+        data, width, height, colormap, normalization,
+        Vec2::new(width, height), Filter::Nearest,
+    )
+}
+
+/// Color a row-major grid of `width` by `height` data values, scaled to an image of the
+/// given size.
+///
+/// With `Filter::Bilinear`, the data is interpolated before being colored, so the colors
+/// follow the colormap rather than blending straight between cells. NaN cells are
+/// transparent, along with the pixels interpolated from them.
+///
+/// Panics if the data doesn't match the grid size.
+pub fn heatmap_scaled(
+    data: &[f32],
+    width: usize,
+    height: usize,
+    colormap: &Colormap,
+    normalization: Normalization,
+    size: Vec2<usize>,
+    filter: Filter,
+) -> RgbaImage {
+    assert_eq!(
+        data.len(), width * height,
+        "heatmap data length does not match its size",
+    );
+
+    let mut image = RgbaImage::new(size.x as u32, size.y as u32);
+    if width == 0 || height == 0 {
+        return image;
+    }
+
+    let (min, max) = normalization.range(data);
+    let span =
+        if max != min { max - min }
+        else { 1.0 };
+    let cell = |x: i64, y: i64| {
+        let x = x.max(0).min(width as i64 - 1) as usize;
+        let y = y.max(0).min(height as i64 - 1) as usize;
+        data[y * width + x]
+    };
+
+    let scale = Vec2::new(width as f32 / size.x as f32, height as f32 / size.y as f32);
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        // position in cells
+        let src = (Vec2::new(x as f32, y as f32) + 0.5) * scale;
+        let value = match filter {
+            Filter::Nearest => cell(src.x.floor() as i64, src.y.floor() as i64),
+            Filter::Bilinear => {
+                // offset so that cell centers are at integer coordinates
+                let src = src - 0.5;
+                let base = src.map(|n| n.floor());
+                let t = src - base;
+                let (bx, by) = (base.x as i64, base.y as i64);
+                let top = cell(bx, by) * (1.0 - t.x) + cell(bx + 1, by) * t.x;
+                let bottom = cell(bx, by + 1) * (1.0 - t.x) + cell(bx + 1, by + 1) * t.x;
+                top * (1.0 - t.y) + bottom * t.y
+            },
+        };
+
+        let color =
+            if value.is_nan() { Rgba::zero() }
+            else { colormap.sample((value - min) / span) };
+        pixel.0 = [color.r, color.g, color.b, color.a];
+    }
+    image
+}
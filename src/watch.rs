@@ -0,0 +1,108 @@
+use std::{
+    fs,
+    thread::{self, JoinHandle},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crossbeam::channel::{self, Sender, Receiver, RecvTimeoutError};
+
+/// Default time between checks of watched files.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Handle to a background thread watching files for changes.
+///
+/// Dropping it stops watching, and waits for the thread to return.
+#[derive(Debug)]
+pub struct Watcher {
+    /// Dropped to stop the thread.
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.stop = None;
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                error!("file watch callback panicked");
+            }
+        }
+    }
+}
+
+/// Last modification time of a file, or `None` if it can't be read.
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Call the given callback, on a background thread, with the path of each watched file
+/// whenever it changes on disk, such as textures, meshes, or configuration files which are
+/// edited while a renderer runs.
+///
+/// Files are polled every `DEFAULT_INTERVAL` for changes to their modification time, so this
+/// works on every platform, and also detects files being created or deleted. Edits made in
+/// quick succession may be reported once.
+pub fn on_change<I, F>(paths: I, callback: F) -> Watcher
+    where
+        I: IntoIterator,
+        I::Item: Into<PathBuf>,
+        F: FnMut(&Path) + Send + 'static {
+
+    on_change_every(paths, DEFAULT_INTERVAL, callback)
+}
+
+/// Call the given callback with the path of each watched file whenever it changes, polling
+/// at the given interval.
+///
+/// See `on_change`.
+pub fn on_change_every<I, F>(paths: I, interval: Duration, mut callback: F) -> Watcher
+    where
+        I: IntoIterator,
+        I::Item: Into<PathBuf>,
+        F: FnMut(&Path) + Send + 'static {
+
+    let mut files: Vec<(PathBuf, Option<SystemTime>)> = paths.into_iter()
+        .map(|path| {
+            let path = path.into();
+            let time = modified(&path);
+            (path, time)
+        })
+        .collect();
+    let (stop_send, stop_recv) = channel::bounded::<()>(0);
+
+    let thread = thread::spawn(move || {
+        while let Err(RecvTimeoutError::Timeout) = stop_recv.recv_timeout(interval) {
+            for (path, time) in &mut files {
+                let now = modified(path);
+                if now != *time {
+                    *time = now;
+                    trace!("watched file {:?} changed", path);
+                    callback(path);
+                }
+            }
+        }
+    });
+
+    Watcher {
+        stop: Some(stop_send),
+        thread: Some(thread),
+    }
+}
+
+/// Watch files for changes, sending the path of each file which changes to the returned
+/// channel, such as for the drawing thread to check each frame and reload assets.
+///
+/// See `on_change`. The channel disconnects when the watcher is dropped.
+pub fn channel<I>(paths: I) -> (Watcher, Receiver<PathBuf>)
+    where
+        I: IntoIterator,
+        I::Item: Into<PathBuf> {
+
+    let (send, recv) = channel::unbounded();
+    let watcher = on_change(paths, move |path| {
+        // it's fine if the receiver was dropped
+        let _ = send.send(path.to_owned());
+    });
+    (watcher, recv)
+}
@@ -1,303 +1,1493 @@
+use std::{
+    thread::{self, JoinHandle},
+    fmt,
+    error::Error,
+    sync::{
+        Arc,
+        Mutex,
+        Condvar,
+        atomic::{AtomicBool, Ordering},
+    },
+    task::Waker,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
 
-use std::thread;
-use std::sync::Arc;
+use crossbeam::{
+    queue::SegQueue,
+    channel::{self, Sender, Receiver},
+};
 
-use crossbeam::queue::SegQueue;
+use image::RgbaImage;
+use vek::*;
 
-#[allow(unused_imports)]
+use crate::{
+    input::{InputEvent, MouseButton, View},
+    paint::{PaintCmd, PaintTarget},
+    record::Recording,
+    color::{Quantize, ToneMap},
+    stats::{RenderStats, Stats, draw_overlay},
+    histogram::{Histogram, draw_histogram},
+    texture::Filter,
+};
+
+#[cfg(feature = "gl")]
 use glium::{
-    glutin,
-    glutin::dpi,
-    glutin::{Event, WindowEvent, DeviceEvent, KeyboardInput, VirtualKeyCode, ModifiersState},
-    texture::{UnsignedTexture2d, buffer_texture::{BufferTexture, BufferTextureType}},
-    draw_parameters::DrawParameters,
-    Surface,
-    Display,
-    VertexBuffer,
-    program::{Program, ProgramCreationInput},
-    index::{self, IndexBuffer},
-    backend::Facade,
+    DrawError,
+    SwapBuffersError,
+    vertex,
+    program::ProgramCreationError,
+    index,
+    texture::buffer_texture::CreationError as BufferTextureCreationError,
+    backend::glutin::DisplayCreationError,
 };
 
-/// OS-specific (conditional compilation) window configuration.
-trait WindowBuilderOsSpecific: Sized {
-    fn os_specific_window_configure(self) -> Self;
+#[cfg(not(any(feature = "gl", feature = "soft")))]
+compile_error!("a window backend must be enabled, with either the `gl` or `soft` feature");
+
+/// Window backend using glium and OpenGL.
+#[cfg(feature = "gl")]
+mod gl;
+
+/// Window backend using winit and softbuffer, without a GPU context.
+#[cfg(feature = "soft")]
+mod soft;
+
+/// Handles given to the drawing thread for communicating with the window.
+pub struct DrawContext {
+    /// Queue of paint instructions to the window.
+    pub paint: Arc<SegQueue<PaintCmd>>,
+    /// User input events from the window.
+    pub input: Receiver<InputEvent>,
+    /// Rendering statistics, refreshed by the window.
+    pub stats: RenderStats,
+    /// Signalled when the window closes.
+    ///
+    /// The window waits for the drawing thread to return before it does, so long-running
+    /// drawing code should check this and stop.
+    pub shutdown: ShutdownToken,
+    /// Signalled each time the window presents a frame.
+    pub presented: PresentSignal,
+    /// Number of paint instructions the queue holds before `push` blocks, if bounded.
+    pub paint_capacity: Option<usize>,
+    /// Requests for the window to read back the canvas, with where to send it.
+    readback: Sender<Sender<RgbaImage>>,
+    /// Control messages to the window.
+    control: Sender<WindowControl>,
+    /// Signalled each time the window drains the paint queue.
+    drained: DrainSignal,
 }
 
-#[cfg(target_os = "macos")]
-impl WindowBuilderOsSpecific for glutin::WindowBuilder {
-    fn os_specific_window_configure(self) -> Self {
-        use glium::backend::glutin::glutin::os::macos::WindowBuilderExt;
+impl DrawContext {
+    /// Push a paint instruction to the window.
+    ///
+    /// If the window was built with a `paint_capacity`, this first blocks until the queue
+    /// has room, so fast producers can't grow it without bound.
+    pub fn push(&self, cmd: impl Into<PaintCmd>) {
+        self.wait_for_capacity();
+        self.paint.push(cmd.into());
+    }
 
-        self
-            .with_movable_by_window_background(true)
+    /// Read back the pixels currently displayed, or `None` if the window has closed.
+    ///
+    /// This blocks until the window next applies the paint queue, so the image includes
+    /// everything pushed before the call. Like screenshots, the image is gamma encoded if
+    /// the window is `linear`.
+    pub fn read_canvas(&self) -> Option<RgbaImage> {
+        let (send, recv) = channel::bounded(1);
+        self.readback.send(send).ok()?;
+        recv.recv().ok()
+    }
+
+    /// Copy the pixels currently displayed to the system clipboard, returning false if the
+    /// window has closed.
+    ///
+    /// Like `read_canvas`, this blocks until the window next applies the paint queue. The
+    /// copy itself happens in the background.
+    #[cfg(feature = "clipboard")]
+    pub fn copy_canvas(&self) -> bool {
+        match self.read_canvas() {
+            Some(image) => {
+                crate::clipboard::copy_image(image);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Send a control message to the window, such as to change its title or the cursor.
+    ///
+    /// The window applies control messages each frame, and ignores them once it has
+    /// closed.
+    pub fn control(&self, control: WindowControl) {
+        // it's fine if the window has closed
+        let _ = self.control.send(control);
+    }
+
+    /// Block until the paint queue is below its capacity, or the window closes. Returns
+    /// immediately if the queue is unbounded.
+    ///
+    /// This is for drawing code which pushes to `paint` directly, such as through the
+    /// `draw` module, to apply backpressure between batches of instructions.
+    pub fn wait_for_capacity(&self) {
+        wait_for_capacity(&self.paint, self.paint_capacity, &self.drained);
+    }
+
+    /// Handle for pushing paint instructions like `push`, from tasks which can't borrow the
+    /// context.
+    pub(crate) fn pusher(&self) -> PaintPusher {
+        PaintPusher {
+            paint: Arc::clone(&self.paint),
+            capacity: self.paint_capacity,
+            drained: self.drained.clone(),
+        }
     }
 }
 
-#[cfg(not(target_os = "macos"))]
-impl WindowBuilderOsSpecific for glutin::WindowBuilder {
-    fn os_specific_window_configure(self) -> Self {
-        self
+/// Owned handle for pushing paint instructions to the window, which blocks while the queue
+/// is full, like `DrawContext::push`.
+#[derive(Clone, Debug)]
+pub(crate) struct PaintPusher {
+    paint: Arc<SegQueue<PaintCmd>>,
+    capacity: Option<usize>,
+    drained: DrainSignal,
+}
+
+impl PaintPusher {
+    /// Push a paint instruction to the window, once the queue has room.
+    pub(crate) fn push(&self, cmd: impl Into<PaintCmd>) {
+        wait_for_capacity(&self.paint, self.capacity, &self.drained);
+        self.paint.push(cmd.into());
+    }
+
+    /// Return `Pending` while the queue is full, waking the task once it has room, like
+    /// `push` blocks.
+    #[cfg(feature = "async")]
+    pub(crate) fn poll_capacity(&self, cx: &mut Context) -> Poll<()> {
+        match self.capacity {
+            Some(capacity) => self.drained.poll_while(cx, || self.paint.len() >= capacity),
+            None => Poll::Ready(()),
+        }
+    }
+
+    /// Push a paint instruction to the window without waiting for room, such as after
+    /// `poll_capacity`.
+    #[cfg(feature = "async")]
+    pub(crate) fn push_now(&self, cmd: impl Into<PaintCmd>) {
+        self.paint.push(cmd.into());
+    }
+}
+
+/// Block until the paint queue is below its capacity, if any, or the window closes.
+fn wait_for_capacity(
+    paint: &SegQueue<PaintCmd>,
+    capacity: Option<usize>,
+    drained: &DrainSignal,
+) {
+    if let Some(capacity) = capacity {
+        // the window drains the queue often, even when nothing is presented
+        drained.wait_while(|| paint.len() >= capacity);
+    }
+}
+
+/// Signal which the window raises each time it drains the paint queue, which the drawing
+/// thread waits on when the queue is full.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DrainSignal {
+    state: Arc<Mutex<DrainState>>,
+    condvar: Arc<Condvar>,
+}
+
+#[derive(Debug, Default)]
+struct DrainState {
+    /// Whether the window has closed, so the queue will no longer be drained.
+    closed: bool,
+    /// Tasks waiting for the queue to be drained.
+    wakers: Vec<Waker>,
+}
+
+impl DrainSignal {
+    /// Block while `condition` holds, rechecking it each time the queue is drained, until
+    /// the window closes.
+    fn wait_while(&self, condition: impl Fn() -> bool) {
+        let mut state = self.state.lock().unwrap();
+        // the window locks before signalling, so checking under the lock can't miss a drain
+        while !state.closed && condition() {
+            state = self.condvar.wait(state).unwrap();
+        }
+    }
+
+    /// Return `Pending` while `condition` holds, waking the task when the queue is next
+    /// drained, until the window closes.
+    #[cfg(feature = "async")]
+    fn poll_while(&self, cx: &mut Context, condition: impl Fn() -> bool) -> Poll<()> {
+        let mut state = self.state.lock().unwrap();
+        if !state.closed && condition() {
+            state.wakers.push(cx.waker().clone());
+            Poll::Pending
+        } else {
+            Poll::Ready(())
+        }
+    }
+
+    /// Wake the drawing thread after draining the queue.
+    pub(crate) fn signal(&self) {
+        let mut state = self.state.lock().unwrap();
+        for waker in state.wakers.drain(..) {
+            waker.wake();
+        }
+        self.condvar.notify_all();
+    }
+
+    /// Mark the window as closed, waking the drawing thread.
+    fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        for waker in state.wakers.drain(..) {
+            waker.wake();
+        }
+        self.condvar.notify_all();
+    }
+}
+
+/// Flag which the window sets when it closes, telling the drawing thread to stop.
+#[derive(Clone, Debug, Default)]
+pub struct ShutdownToken {
+    flag: Arc<AtomicBool>,
+}
+
+impl ShutdownToken {
+    /// Whether the window has closed.
+    pub fn is_shutdown(&self) -> bool {
+        self.flag.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn signal(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Counter of frames the window has presented, which can be waited on.
+///
+/// With the `async` feature, it can also be awaited, with `PresentSignal::next`.
+#[derive(Clone, Debug, Default)]
+pub struct PresentSignal {
+    pub(crate) inner: Arc<PresentInner>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct PresentInner {
+    pub(crate) state: Mutex<PresentState>,
+    condvar: Condvar,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct PresentState {
+    /// Number of frames presented.
+    pub(crate) count: u64,
+    /// Whether the window has closed, so no more frames will be presented.
+    pub(crate) closed: bool,
+    /// Tasks waiting for the next frame.
+    pub(crate) wakers: Vec<Waker>,
+}
+
+impl PresentSignal {
+    /// Number of frames presented so far.
+    pub fn count(&self) -> u64 {
+        self.inner.state.lock().unwrap().count
+    }
+
+    /// Block until the next frame is presented, returning the new frame count, or `None` if
+    /// the window closes first.
+    pub fn wait(&self) -> Option<u64> {
+        let mut state = self.inner.state.lock().unwrap();
+        let start = state.count;
+        while state.count == start && !state.closed {
+            state = self.inner.condvar.wait(state).unwrap();
+        }
+        if state.count != start { Some(state.count) } else { None }
+    }
+
+    /// Count a presented frame, waking any waiters.
+    pub(crate) fn signal(&self) {
+        let mut state = self.inner.state.lock().unwrap();
+        state.count += 1;
+        for waker in state.wakers.drain(..) {
+            waker.wake();
+        }
+        self.inner.condvar.notify_all();
+    }
+
+    /// Mark the window as closed, waking any waiters.
+    pub(crate) fn close(&self) {
+        let mut state = self.inner.state.lock().unwrap();
+        state.closed = true;
+        for waker in state.wakers.drain(..) {
+            waker.wake();
+        }
+        self.inner.condvar.notify_all();
+    }
+}
+
+/// Error opening or running a software rendering window.
+#[derive(Debug)]
+pub enum WindowError {
+    /// Failed to create the window and its OpenGL context.
+    #[cfg(feature = "gl")]
+    Context(DisplayCreationError),
+    /// Failed to compile the presentation shader.
+    #[cfg(feature = "gl")]
+    Shader(ProgramCreationError),
+    /// Failed to allocate the vertex buffer.
+    #[cfg(feature = "gl")]
+    VertexBuffer(vertex::BufferCreationError),
+    /// Failed to allocate the index buffer.
+    #[cfg(feature = "gl")]
+    IndexBuffer(index::BufferCreationError),
+    /// Failed to allocate the canvas buffer texture.
+    #[cfg(feature = "gl")]
+    CanvasBuffer(BufferTextureCreationError),
+    /// A draw call failed.
+    #[cfg(feature = "gl")]
+    Draw(DrawError),
+    /// Failed to swap the frame buffers.
+    #[cfg(feature = "gl")]
+    SwapBuffers(SwapBuffersError),
+    /// Failed to create or run the event loop.
+    #[cfg(feature = "soft")]
+    EventLoop(winit::error::EventLoopError),
+    /// Failed to create the window.
+    #[cfg(feature = "soft")]
+    WindowCreation(winit::error::OsError),
+    /// Failed to create, resize, or present the window surface.
+    #[cfg(feature = "soft")]
+    Surface(softbuffer::SoftBufferError),
+}
+
+impl fmt::Display for WindowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            #[cfg(feature = "gl")]
+            WindowError::Context(e) => write!(f, "display creation failure: {}", e),
+            #[cfg(feature = "gl")]
+            WindowError::Shader(e) => write!(f, "failed to create glsl program: {}", e),
+            #[cfg(feature = "gl")]
+            WindowError::VertexBuffer(e) => write!(f, "failed to create vertex buffer: {}", e),
+            #[cfg(feature = "gl")]
+            WindowError::IndexBuffer(e) => write!(f, "failed to create index buffer: {}", e),
+            #[cfg(feature = "gl")]
+            WindowError::CanvasBuffer(e) => write!(f, "error creating buffer texture: {}", e),
+            #[cfg(feature = "gl")]
+            WindowError::Draw(e) => write!(f, "draw call failed: {}", e),
+            #[cfg(feature = "gl")]
+            WindowError::SwapBuffers(e) => write!(f, "failed to swap frame buffers: {}", e),
+            #[cfg(feature = "soft")]
+            WindowError::EventLoop(e) => write!(f, "event loop failure: {}", e),
+            #[cfg(feature = "soft")]
+            WindowError::WindowCreation(e) => write!(f, "window creation failure: {}", e),
+            #[cfg(feature = "soft")]
+            WindowError::Surface(e) => write!(f, "window surface failure: {}", e),
+        }
+    }
+}
+
+impl Error for WindowError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            #[cfg(feature = "gl")]
+            WindowError::Context(e) => Some(e),
+            #[cfg(feature = "gl")]
+            WindowError::Shader(e) => Some(e),
+            #[cfg(feature = "gl")]
+            WindowError::VertexBuffer(e) => Some(e),
+            #[cfg(feature = "gl")]
+            WindowError::IndexBuffer(e) => Some(e),
+            #[cfg(feature = "gl")]
+            WindowError::CanvasBuffer(e) => Some(e),
+            #[cfg(feature = "gl")]
+            WindowError::Draw(e) => Some(e),
+            #[cfg(feature = "gl")]
+            WindowError::SwapBuffers(e) => Some(e),
+            #[cfg(feature = "soft")]
+            WindowError::EventLoop(e) => Some(e),
+            #[cfg(feature = "soft")]
+            WindowError::WindowCreation(e) => Some(e),
+            #[cfg(feature = "soft")]
+            WindowError::Surface(e) => Some(e),
+        }
     }
 }
 
-/// Our vertex type.
-#[derive(Copy, Clone)]
-#[repr(C)]
-struct Vertex { a_pos: [f32; 2] }
+/// Implement `From` for each `WindowError` variant, for use with `?`.
+macro_rules! window_error_from {
+    ($( $variant:ident($err:ty) ),*$(,)?) => {$(
+        impl From<$err> for WindowError {
+            fn from(e: $err) -> Self {
+                WindowError::$variant(e)
+            }
+        }
+    )*}
+}
+
+#[cfg(feature = "gl")]
+window_error_from! {
+    Context(DisplayCreationError),
+    Shader(ProgramCreationError),
+    VertexBuffer(vertex::BufferCreationError),
+    IndexBuffer(index::BufferCreationError),
+    CanvasBuffer(BufferTextureCreationError),
+    Draw(DrawError),
+    SwapBuffers(SwapBuffersError),
+}
+
+#[cfg(feature = "soft")]
+window_error_from! {
+    EventLoop(winit::error::EventLoopError),
+    WindowCreation(winit::error::OsError),
+    Surface(softbuffer::SoftBufferError),
+}
+
+/// Software rendering window.
+///
+/// Windows are configured and opened with `Window::build()`.
+#[derive(Copy, Clone, Debug)]
+pub struct Window;
 
-glium::implement_vertex!(Vertex, a_pos);
+impl Window {
+    /// Begin configuring a window.
+    pub fn build() -> WindowBuilder {
+        WindowBuilder::default()
+    }
+}
+
+/// How the canvas is displayed when the window size differs from it, such as after
+/// resizing or entering fullscreen.
+///
+/// Unless the canvas is resized, it keeps its aspect ratio, and is letterboxed with the
+/// background color. Mouse positions are always reported in canvas pixel coordinates.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Scaling {
+    /// Reallocate the canvas to the window's size, clearing it and sending an
+    /// `InputEvent::Resize` to the drawing thread.
+    Resize,
+    /// Keep the canvas size, and scale it to fit the window.
+    Fit,
+    /// Keep the canvas size, and scale it by the largest whole number of physical pixels
+    /// which fits the window, so every canvas pixel is displayed the same size. This suits
+    /// pixel art and retro renderers, with a small canvas such as 320x240.
+    ///
+    /// If the canvas is larger than the window, it is scaled down to fit instead.
+    Integer,
+}
+
+/// Post-processing effect applied in the presentation shader.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PostEffect {
+    /// Darken towards the corners, with a strength around 1.
+    Vignette(f32),
+    /// Darken between canvas rows, with a strength in `[0, 1]`.
+    Scanlines(f32),
+    /// Bulge the image like a CRT screen, with a curvature around 0.1.
+    ///
+    /// The distortion is applied before any other effect, wherever it's added, so that
+    /// every effect applies to the distorted image.
+    Crt(f32),
+    /// Separate the red and blue channels towards the edges, by an offset in canvas pixels.
+    ChromaticAberration(f32),
+    /// Custom GLSL 4.10 statements.
+    ///
+    /// These may read and modify `vec4 color`, the displayed color, and `vec2 uv`, the
+    /// coordinates within the canvas in `[0, 1]`, and call `vec4 present(vec2 uv)` to
+    /// compute the displayed color at other coordinates. The uniforms `int x_size` and
+    /// `int y_size` hold the canvas size.
+    Glsl(String),
+}
+
+/// Message from the drawing thread telling the window to change its state, sent with
+/// `DrawContext::control`.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum WindowControl {
+    /// Change the window title, such as to display status.
+    SetTitle(String),
+    /// Change the icon of the cursor over the window.
+    SetCursorIcon(CursorIcon),
+    /// Show or hide the cursor over the window.
+    SetCursorVisible(bool),
+    /// Whether to confine the cursor to the window, for FPS-style camera controls.
+    ///
+    /// Where confinement isn't supported, the cursor may be locked in place instead. If
+    /// grabbing fails, a warning is logged.
+    SetCursorGrab(bool),
+    /// Change the tone mapping of HDR paints.
+    ///
+    /// Pixels are kept at full precision, so they're re-mapped without being repainted. If
+    /// double-buffered, this takes effect with the next completed frame.
+    SetToneMap(ToneMap),
+}
+
+/// Icon of the cursor over the window.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum CursorIcon {
+    /// The platform's default arrow.
+    Default,
+    Crosshair,
+    /// Pointing hand, as over links.
+    Hand,
+    /// Text insertion beam.
+    Text,
+    Move,
+    Wait,
+    Help,
+    NotAllowed,
+}
+
+/// Window backend, which presents the canvas.
+///
+/// Each backend is only available if its feature is enabled. Both display the same image,
+/// with canvas row 0 at the top, where mouse coordinates are 0.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Backend {
+    /// OpenGL, with glium, from the `gl` feature.
+    Gl,
+    /// CPU-side surface, with winit and softbuffer, from the `soft` feature.
+    Soft,
+}
+
+impl Backend {
+    /// Whether this backend's feature is enabled.
+    pub fn is_available(self) -> bool {
+        match self {
+            Backend::Gl => cfg!(feature = "gl"),
+            Backend::Soft => cfg!(feature = "soft"),
+        }
+    }
+}
+
+impl Default for Backend {
+    /// `Gl` if it's available, and otherwise `Soft`.
+    fn default() -> Self {
+        if Backend::Gl.is_available() { Backend::Gl }
+        else { Backend::Soft }
+    }
+}
+
+/// Builder for configuring and opening a window.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WindowBuilder {
+    backend: Backend,
+    x_size: usize,
+    y_size: usize,
+    title: String,
+    vsync: bool,
+    max_fps: Option<f64>,
+    resizable: bool,
+    fullscreen: bool,
+    monitor: Option<usize>,
+    scaling: Scaling,
+    filter: Filter,
+    post_effects: Vec<PostEffect>,
+    picking: bool,
+    navigation: bool,
+    decorations: bool,
+    transparent: bool,
+    background: Rgba<u8>,
+    double_buffered: bool,
+    clear_each_frame: bool,
+    linear: bool,
+    tone_map: ToneMap,
+    accumulate: bool,
+    frame_blend: Option<f32>,
+    quantize: Option<Quantize>,
+    id_buffer: bool,
+    hover_title: bool,
+    paint_capacity: Option<usize>,
+    stats_overlay: bool,
+    histogram_overlay: bool,
+    screenshot_dir: PathBuf,
+    record: Option<Recording>,
+}
 
-/// Simplified macro for creating our vertex array.
-macro_rules! vertex_arr {
-    [$( ($x:expr, $y:expr) ),*$(,)?] => {
-        [$( Vertex { a_pos: [$x as f32, $y as f32] }, )*]
+impl Default for WindowBuilder {
+    fn default() -> Self {
+        WindowBuilder {
+            backend: Backend::default(),
+            x_size: 640,
+            y_size: 480,
+            title: "software rendering".into(),
+            vsync: true,
+            max_fps: None,
+            resizable: false,
+            fullscreen: false,
+            monitor: None,
+            scaling: Scaling::Resize,
+            filter: Filter::Nearest,
+            post_effects: Vec::new(),
+            picking: false,
+            navigation: false,
+            decorations: true,
+            transparent: true,
+            background: Rgba::new(0x80, 0x80, 0x80, 0xFF),
+            double_buffered: false,
+            clear_each_frame: false,
+            linear: false,
+            tone_map: ToneMap::Clamp,
+            accumulate: false,
+            frame_blend: None,
+            quantize: None,
+            id_buffer: false,
+            hover_title: false,
+            paint_capacity: None,
+            stats_overlay: false,
+            histogram_overlay: false,
+            screenshot_dir: PathBuf::from("."),
+            record: None,
+        }
     }
 }
 
-/// Instruction to paint a single pixel.
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
-pub struct Paint {
-    pub x: usize,
-    pub y: usize,
-    pub r: u8,
-    pub g: u8,
-    pub b: u8,
-    pub a: u8,
+impl WindowBuilder {
+    /// Which backend presents the canvas, if both the `gl` and `soft` features are enabled.
+    /// Defaults to `Backend::Gl` if it's available.
+    ///
+    /// If the chosen backend's feature isn't enabled, the other backend is used instead,
+    /// with a warning.
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Initial size of the canvas, in pixels. Defaults to 640x480.
+    pub fn size(mut self, x_size: usize, y_size: usize) -> Self {
+        self.x_size = x_size;
+        self.y_size = y_size;
+        self
+    }
+
+    /// Window title. Defaults to "software rendering".
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    /// Whether to synchronize presentation with the display refresh rate. Defaults to true.
+    ///
+    /// Turning this off, without a `max_fps`, presents as fast as possible, for
+    /// benchmarking. The `soft` backend can't synchronize with the display, so with vsync
+    /// it paces presentation to the monitor's refresh rate instead. The achieved frame rate
+    /// is reported through `DrawContext::stats`.
+    pub fn vsync(mut self, vsync: bool) -> Self {
+        self.vsync = vsync;
+        self
+    }
+
+    /// Limit presentation to a target number of frames per second, by sleeping off the rest
+    /// of each frame. Defaults to no limit beyond vsync.
+    ///
+    /// Panics if `fps` isn't positive, or is so small that a frame's duration overflows.
+    pub fn max_fps(mut self, fps: f64) -> Self {
+        assert!(
+            fps > 0.0 && Duration::try_from_secs_f64(1.0 / fps).is_ok(),
+            "max fps must be positive, with a representable frame time, got {}", fps,
+        );
+        self.max_fps = Some(fps);
+        self
+    }
+
+    /// Whether the user may resize the window. Defaults to false.
+    ///
+    /// Resizing reallocates the canvas, clearing it, and sends an `InputEvent::Resize` to
+    /// the drawing thread.
+    pub fn resizable(mut self, resizable: bool) -> Self {
+        self.resizable = resizable;
+        self
+    }
+
+    /// Whether the window opens in borderless fullscreen. Defaults to false.
+    ///
+    /// Fullscreen can also be toggled at runtime with F11, or cmd+ctrl+f. With the default
+    /// `Scaling::Resize`, entering or leaving fullscreen resizes the canvas.
+    pub fn fullscreen(mut self, fullscreen: bool) -> Self {
+        self.fullscreen = fullscreen;
+        self
+    }
+
+    /// Index of the monitor to go fullscreen on, among those the platform reports. Defaults
+    /// to the primary monitor, which is also used if the index is out of range.
+    pub fn monitor(mut self, monitor: usize) -> Self {
+        self.monitor = Some(monitor);
+        self
+    }
+
+    /// How the canvas is displayed when the window size differs from it. Defaults to
+    /// `Scaling::Resize`.
+    pub fn scaling(mut self, scaling: Scaling) -> Self {
+        self.scaling = scaling;
+        self
+    }
+
+    /// Filter used when the canvas is displayed at a different size than it is, such as on
+    /// high-DPI displays or when scaled to fit. Defaults to `Filter::Nearest`.
+    ///
+    /// Nearest filtering keeps pixels sharp but blocky at non-integer scales, whereas bilinear
+    /// filtering is smooth but blurry.
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Add a post-processing effect, applied when the canvas is displayed. Defaults to none.
+    ///
+    /// Effects are applied in the order they're added, and don't affect screenshots or
+    /// recordings. They're ignored by the `soft` backend.
+    ///
+    /// Panics if the effect's parameter isn't finite.
+    pub fn post_effect(mut self, effect: PostEffect) -> Self {
+        match effect {
+            PostEffect::Vignette(n)
+            | PostEffect::Scanlines(n)
+            | PostEffect::Crt(n)
+            | PostEffect::ChromaticAberration(n) => {
+                assert!(n.is_finite(), "post effect parameter must be finite, got {}", n);
+            },
+            PostEffect::Glsl(_) => (),
+        }
+        self.post_effects.push(effect);
+        self
+    }
+
+    /// Whether left clicks on the canvas send an `InputEvent::Pick` to the drawing thread,
+    /// with the color displayed at the clicked pixel. Defaults to false.
+    ///
+    /// This allows simple object picking, by painting objects in distinct colors, and color
+    /// inspection.
+    pub fn picking(mut self, picking: bool) -> Self {
+        self.picking = picking;
+        self
+    }
+
+    /// Whether the user can zoom the displayed canvas with the scroll wheel, and pan it by
+    /// dragging with the left mouse button. Defaults to false.
+    ///
+    /// Navigation only changes how the canvas is displayed. Mouse positions sent to the
+    /// drawing thread are in canvas coordinates under the view, and each change of view is
+    /// sent as an `InputEvent::Navigate`, so the visible region can be re-rendered at
+    /// higher detail.
+    pub fn navigation(mut self, navigation: bool) -> Self {
+        self.navigation = navigation;
+        self
+    }
+
+    /// Whether the window has a title bar and borders. Defaults to true.
+    pub fn decorations(mut self, decorations: bool) -> Self {
+        self.decorations = decorations;
+        self
+    }
+
+    /// Whether the window background may be transparent. Defaults to true.
+    ///
+    /// Combined with a translucent `background` and no `decorations`, this is useful for
+    /// overlays. The `soft` backend presents opaque pixels regardless.
+    pub fn transparent(mut self, transparent: bool) -> Self {
+        self.transparent = transparent;
+        self
+    }
+
+    /// Color displayed behind the canvas, where it is transparent. Defaults to opaque gray.
+    ///
+    /// The background is sRGB, even if the window is `linear`.
+    pub fn background(mut self, background: Rgba<u8>) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// Whether to only display frames completed with `PaintCmd::EndFrame`. Defaults to false.
+    ///
+    /// This prevents tearing, at the cost of displaying nothing until the first
+    /// `PaintCmd::EndFrame`.
+    pub fn double_buffered(mut self, double_buffered: bool) -> Self {
+        self.double_buffered = double_buffered;
+        self
+    }
+
+    /// Whether to clear the canvas to transparent, and reset the depth buffer, after each
+    /// `PaintCmd::EndFrame`. Defaults to false.
+    ///
+    /// This lets the drawing thread draw each frame from scratch without pushing a
+    /// `PaintCmd::Clear` first. It is usually combined with `double_buffered`, so that the
+    /// cleared canvas isn't displayed.
+    pub fn clear_each_frame(mut self, clear_each_frame: bool) -> Self {
+        self.clear_each_frame = clear_each_frame;
+        self
+    }
+
+    /// Whether painted colors are linear, rather than sRGB. Defaults to false.
+    ///
+    /// Linear colors are gamma encoded to sRGB when displayed, captured, and recorded, so
+    /// lighting math can be done directly on painted values. Note that 8 bits per component
+    /// loses precision in dark linear colors. See the `color` module for conversions.
+    pub fn linear(mut self, linear: bool) -> Self {
+        self.linear = linear;
+        self
+    }
+
+    /// Tone mapping applied to HDR `PaintF`s. Defaults to `ToneMap::Clamp`.
+    pub fn tone_map(mut self, tone_map: ToneMap) -> Self {
+        self.tone_map = tone_map;
+        self
+    }
+
+    /// Whether to average repeated paints to each pixel, rather than overwriting them.
+    /// Defaults to false.
+    ///
+    /// This lets Monte Carlo renderers converge by repeatedly painting noisy samples.
+    /// `Paint`s, `PaintBatch`es, and `PaintF`s are accumulated, with HDR samples averaged
+    /// before tone mapping, and `PaintCmd::Reset` restarts accumulation.
+    pub fn accumulate(mut self, accumulate: bool) -> Self {
+        self.accumulate = accumulate;
+        self
+    }
+
+    /// Blend each completed frame into the displayed pixels by the given weight, as an
+    /// exponential moving average, rather than replacing them. Defaults to not blending.
+    ///
+    /// A weight of 1 displays only the newest frame, and smaller weights keep more of the
+    /// previous frames, giving cheap motion blur and smoothing the noise of stochastic
+    /// renderers. Frames are completed with `PaintCmd::EndFrame`, so this implies
+    /// `double_buffered`, and `PaintCmd::Reset` restarts blending from the next frame.
+    /// Blending is done on painted colors, so it's physically correct if the window is
+    /// `linear`. The weight is clamped to `[0, 1]`.
+    ///
+    /// Panics if `weight` is NaN.
+    pub fn frame_blend(mut self, weight: f32) -> Self {
+        assert!(!weight.is_nan(), "frame blend weight must not be NaN");
+        self.frame_blend = Some(weight.clamp(0.0, 1.0));
+        self
+    }
+
+    /// Quantize displayed pixels to a palette, with dithering. Defaults to not quantizing.
+    ///
+    /// Quantization also applies to screenshots and recordings, such as for exporting to
+    /// indexed formats. The palette is in the same color space as painted colors, so should
+    /// be linear if the window is `linear`.
+    pub fn quantize(mut self, quantize: Quantize) -> Self {
+        self.quantize = Some(quantize);
+        self
+    }
+
+    /// Whether to keep an object ID for each canvas pixel, written with `PaintId`s. Defaults
+    /// to false.
+    ///
+    /// As the cursor moves between pixels with different IDs, the window sends an
+    /// `InputEvent::Hover` to the drawing thread, for identifying objects in debug renders.
+    pub fn id_buffer(mut self, id_buffer: bool) -> Self {
+        self.id_buffer = id_buffer;
+        self
+    }
+
+    /// Whether to show the object ID under the cursor in the title bar, in a window with an
+    /// `id_buffer`. Defaults to false.
+    pub fn hover_title(mut self, hover_title: bool) -> Self {
+        self.hover_title = hover_title;
+        self
+    }
+
+    /// Bound the paint queue to roughly the given number of instructions. Defaults to
+    /// unbounded.
+    ///
+    /// When the queue is full, `DrawContext::push` and `DrawContext::wait_for_capacity`
+    /// block until the window next drains the queue, which it does every frame whether or
+    /// not anything changed. This keeps memory in check when drawing code produces
+    /// instructions faster than they're displayed.
+    pub fn paint_capacity(mut self, capacity: usize) -> Self {
+        self.paint_capacity = Some(capacity);
+        self
+    }
+
+    /// Whether to draw rendering statistics over the top-left corner of the canvas. Defaults
+    /// to false.
+    ///
+    /// The overlay isn't included in screenshots or recordings.
+    pub fn stats_overlay(mut self, stats_overlay: bool) -> Self {
+        self.stats_overlay = stats_overlay;
+        self
+    }
+
+    /// Whether to draw a histogram of the displayed pixels over the bottom-left corner of the
+    /// canvas, refreshed with the rendering statistics. Defaults to false.
+    ///
+    /// The red, green, and blue channels are drawn added together, with luminance as a gray
+    /// outline, for debugging exposure and tone mapping. The overlay isn't included in
+    /// screenshots or recordings. See the `histogram` module to analyze pixels directly.
+    pub fn histogram_overlay(mut self, histogram_overlay: bool) -> Self {
+        self.histogram_overlay = histogram_overlay;
+        self
+    }
+
+    /// Directory in which screenshots taken with cmd+s or ctrl+s are saved. Defaults to the
+    /// working directory.
+    ///
+    /// With the `clipboard` feature, cmd+c or ctrl+c copies the canvas to the clipboard
+    /// instead.
+    pub fn screenshot_dir(mut self, screenshot_dir: impl Into<PathBuf>) -> Self {
+        self.screenshot_dir = screenshot_dir.into();
+        self
+    }
+
+    /// Record the canvas to disk as it is presented. Defaults to not recording.
+    ///
+    /// One frame is captured per frame interval, whether or not the canvas changed, so
+    /// recordings have the same length and timing under either backend.
+    pub fn record(mut self, recording: Recording) -> Self {
+        self.record = Some(recording);
+        self
+    }
+
+    /// Open the window.
+    ///
+    /// This will take over the current thread (which should be the main thread) until the
+    /// window closes, because some platforms require the window to be created in the main
+    /// thread. It will call the provided closure in its own thread, with a context containing
+    /// a queue that can be sent draw instructions and a channel of user input.
+    ///
+    /// When the window closes, it signals the context's `ShutdownToken`, and waits for the
+    /// closure to return.
+    ///
+    /// Panics if the window fails. See `try_spawn` for a fallible version.
+    pub fn spawn(self, draw_thread: impl FnOnce(DrawContext) + Send + 'static) {
+        if let Err(e) = self.try_spawn(draw_thread) {
+            panic!("{}", e);
+        }
+    }
+
+    /// Open the window, returning an error rather than panicking on failure.
+    ///
+    /// The drawing thread is only spawned once the window has been successfully created.
+    pub fn try_spawn(
+        mut self,
+        draw_thread: impl FnOnce(DrawContext) + Send + 'static,
+    ) -> Result<(), WindowError> {
+        if !self.backend.is_available() {
+            warn!("{:?} window backend is not enabled, using the default", self.backend);
+            self.backend = Backend::default();
+        }
+        match self.backend {
+            #[cfg(feature = "gl")]
+            Backend::Gl => gl::run(self, draw_thread),
+            #[cfg(feature = "soft")]
+            Backend::Soft => soft::run(self, draw_thread),
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("no window backend is enabled"),
+        }
+    }
 }
 
-/// Open a software rendering window.
+/// Open a software rendering window of the given size, with default configuration.
 ///
-/// This will take over the current thread (which should be the main thread) until the window
-/// closes, because some platforms require the window to be created in the main thread.
-/// It will call the provided closure in its own thread, with a queue that can be sent
-/// draw instructions.
+/// Shorthand for `Window::build().size(x_size, y_size).spawn(draw_thread)`.
 pub fn open_window(
     x_size: usize,
     y_size: usize,
-    draw_thread: impl FnOnce(Arc<SegQueue<Paint>>) + Send + 'static,
+    draw_thread: impl FnOnce(DrawContext) + Send + 'static,
 ) {
+    Window::build()
+        .size(x_size, y_size)
+        .spawn(draw_thread)
+}
+
+/// Open a software rendering window of the given size, with default configuration,
+/// returning an error rather than panicking on failure.
+///
+/// Shorthand for `Window::build().size(x_size, y_size).try_spawn(draw_thread)`.
+pub fn try_open_window(
+    x_size: usize,
+    y_size: usize,
+    draw_thread: impl FnOnce(DrawContext) + Send + 'static,
+) -> Result<(), WindowError> {
+    Window::build()
+        .size(x_size, y_size)
+        .try_spawn(draw_thread)
+}
+
+/// Window's handles to the drawing thread.
+///
+/// Dropping this signals the drawing thread to shut down, without waiting for it.
+struct DrawThread {
+    /// Queue of paint instructions from the drawing thread.
+    paint_queue: Arc<SegQueue<PaintCmd>>,
+    /// Sender of input events, which is dropped on shutdown.
+    input_send: Option<Sender<InputEvent>>,
+    /// Receiver of canvas readback requests, which is dropped on shutdown.
+    readback_recv: Option<Receiver<Sender<RgbaImage>>>,
+    /// Receiver of control messages.
+    control_recv: Receiver<WindowControl>,
+    stats: RenderStats,
+    shutdown: ShutdownToken,
+    presented: PresentSignal,
+    drained: DrainSignal,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DrawThread {
+    /// Forward an input event to the drawing thread.
+    fn send_input(&self, event: InputEvent) {
+        // it's fine if the drawing thread has stopped listening
+        if let Some(ref input_send) = self.input_send {
+            let _ = input_send.send(event);
+        }
+    }
+
+    /// Answer the drawing thread's pending requests to read back the canvas.
+    fn answer_readbacks(&self, target: &PaintTarget) {
+        if let Some(ref readback_recv) = self.readback_recv {
+            for send in readback_recv.try_iter() {
+                // it's fine if the drawing thread has stopped waiting
+                let _ = send.send(target.to_image());
+            }
+        }
+    }
+
+    /// Take the drawing thread's pending control messages.
+    fn take_controls(&self) -> Vec<WindowControl> {
+        self.control_recv.try_iter().collect()
+    }
+
+    /// Signal the drawing thread to shut down, and wait for it to return.
+    fn join(mut self) {
+        self.shutdown.signal();
+        self.presented.close();
+        self.drained.close();
+
+        // disconnect the input and readback channels, to wake the drawing thread if it's
+        // blocked on them
+        self.input_send = None;
+        self.readback_recv = None;
+
+        if let Some(thread) = self.thread.take() {
+            trace!("waiting for drawing thread to return");
+            if thread.join().is_err() {
+                error!("drawing thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for DrawThread {
+    fn drop(&mut self) {
+        self.shutdown.signal();
+        self.presented.close();
+        self.drained.close();
+    }
+}
+
+/// Spawn the drawing thread.
+fn spawn_draw_thread(
+    paint_capacity: Option<usize>,
+    draw_thread: impl FnOnce(DrawContext) + Send + 'static,
+) -> DrawThread {
     // reference-counted queue for painting
     let paint_queue_0 = Arc::new(SegQueue::new());
     let paint_queue_1 = paint_queue_0.clone();
 
+    // channel for forwarding input
+    let (input_send, input_recv) = channel::unbounded();
+
+    // channel for canvas readback requests
+    let (readback_send, readback_recv) = channel::unbounded();
+
+    // channel for control messages
+    let (control_send, control_recv) = channel::unbounded();
+
     // spawn the drawing code in its own thread
     // (capture one of the queues for painting)
-    thread::spawn(move || draw_thread(paint_queue_1));
-
-    // create context
-    let mut events_loop: glutin::EventsLoop = glutin::EventsLoop::new();
-    let display: Display = {
-        let wb = glutin::WindowBuilder::new()
-            .with_dimensions(dpi::LogicalSize::new(x_size as _, y_size as _))
-            .with_decorations(true)
-            .with_transparency(true)
-            .with_resizable(false)
-            .os_specific_window_configure()
-            .with_title("software rendering");
-        let cb = glutin::ContextBuilder::new()
-            .with_vsync(true);
-        Display::new(wb, cb, &events_loop)
-            .expect("display creation failure")
+    let stats = RenderStats::default();
+    let shutdown = ShutdownToken::default();
+    let presented = PresentSignal::default();
+    let drained = DrainSignal::default();
+    let ctx = DrawContext {
+        paint: paint_queue_1,
+        input: input_recv,
+        stats: stats.clone(),
+        shutdown: shutdown.clone(),
+        presented: presented.clone(),
+        paint_capacity,
+        readback: readback_send,
+        control: control_send,
+        drained: drained.clone(),
     };
+    let thread = thread::spawn(move || draw_thread(ctx));
 
-    debug!("supported GLSL versions: {:?}", display.get_context().get_supported_glsl_version());
+    DrawThread {
+        paint_queue: paint_queue_0,
+        input_send: Some(input_send),
+        readback_recv: Some(readback_recv),
+        control_recv,
+        stats,
+        shutdown,
+        presented,
+        drained,
+        thread: Some(thread),
+    }
+}
 
-    // geometry to cover entire screen
-    let vertex_buf: VertexBuffer<Vertex> = VertexBuffer::new(
-        &display,
-        &vertex_arr![
-            (0, 0),
-            (0, 1),
-            (1, 1),
-            (1, 0),
-        ],
-    ).expect("failed to create vertex buffer");
+/// Placement of a canvas scaled to fit within a window, with its aspect ratio preserved,
+/// for `Scaling::Fit` and `Scaling::Integer`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Fit {
+    /// Position of the canvas' top-left corner within the window.
+    offset: Vec2<f64>,
+    /// Size of a canvas pixel within the window.
+    scale: f64,
+}
 
-    let index_buf: IndexBuffer<u8> = IndexBuffer::new(
-        &display,
-        index::PrimitiveType::TriangleStrip,
-        &[1, 2, 0, 3],
-    ).expect("failed to create index buffer");
+impl Fit {
+    /// Center a canvas within a window, as large as fits.
+    ///
+    /// `pixel_ratio` is the number of physical pixels per unit of window size, which integer
+    /// scales are a whole number of.
+    fn new(
+        canvas_size: Vec2<usize>,
+        window_size: Vec2<f64>,
+        scaling: Scaling,
+        pixel_ratio: f64,
+    ) -> Self {
+        let canvas_size = canvas_size.map(|n| n as f64);
+        let mut scale = f64::min(window_size.x / canvas_size.x, window_size.y / canvas_size.y);
+        if scaling == Scaling::Integer && scale * pixel_ratio >= 1.0 {
+            scale = (scale * pixel_ratio).floor() / pixel_ratio;
+        }
+        Fit {
+            offset: (window_size - canvas_size * scale) / 2.0,
+            scale,
+        }
+    }
 
-    // glsl program
-    let program: Program = Program::from_source(
-        &display,
-        r###"
+    /// Convert window coordinates to canvas coordinates.
+    fn to_canvas(self, pos: Vec2<f64>) -> Vec2<f64> {
+        (pos - self.offset) / self.scale
+    }
 
-#version 410
+    /// Convert the mouse position of an input event to canvas coordinates.
+    fn input_to_canvas(&self, event: InputEvent) -> InputEvent {
+        match event {
+            InputEvent::MouseMove { pos } => InputEvent::MouseMove {
+                pos: self.to_canvas(pos.map(|n| n as f64)).map(|n| n as f32),
+            },
+            event => event,
+        }
+    }
+}
 
-in vec2 a_pos;
+/// Range of zoom which navigation allows.
+const MIN_ZOOM: f32 = 0.1;
+const MAX_ZOOM: f32 = 10_000.0;
 
-out vec2 v_pos;
-out vec2 v_tex;
+/// Factor to zoom by per line scrolled.
+const ZOOM_PER_LINE: f32 = 1.1;
 
-void main() {
-    v_pos = (a_pos - vec2(0.5)) * 2.0;
-    v_tex = a_pos;
-    gl_Position = vec4(v_pos, 0.5, 1.0);
+/// Tracks the cursor to zoom and pan the view of the canvas, for windows with navigation.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct Navigator {
+    pub(crate) view: View,
+    /// Last cursor position, in displayed coordinates.
+    cursor: Vec2<f32>,
+    /// Whether the left mouse button is held, panning the view.
+    dragging: bool,
 }
 
-        "###,
-        r###"
-
-#version 410
+impl Navigator {
+    /// Observe an input event, with its mouse position in displayed coordinates, returning
+    /// the new view if it changed.
+    pub(crate) fn input(&mut self, event: &InputEvent) -> Option<View> {
+        match *event {
+            InputEvent::MouseMove { pos } => {
+                let delta = pos - self.cursor;
+                self.cursor = pos;
+                if !self.dragging {
+                    return None;
+                }
+                self.view.offset -= delta / self.view.zoom;
+            },
+            InputEvent::MousePress { button: MouseButton::Left, .. } => {
+                self.dragging = true;
+                return None;
+            },
+            InputEvent::MouseRelease { button: MouseButton::Left, .. } => {
+                self.dragging = false;
+                return None;
+            },
+            InputEvent::Scroll { delta } => {
+                // zoom about the cursor, keeping the canvas point under it in place
+                let anchor = self.view.to_canvas(self.cursor);
+                self.view.zoom = (self.view.zoom * ZOOM_PER_LINE.powf(delta.y))
+                    .clamp(MIN_ZOOM, MAX_ZOOM);
+                self.view.offset = anchor - self.cursor / self.view.zoom;
+            },
+            _ => return None,
+        }
+        Some(self.view)
+    }
 
-uniform int x_size;
-uniform int y_size;
-uniform usamplerBuffer canvas_buf;
+    /// Convert the mouse position of an input event from displayed to canvas coordinates.
+    fn input_to_canvas(&self, event: InputEvent) -> InputEvent {
+        match event {
+            InputEvent::MouseMove { pos } => InputEvent::MouseMove {
+                pos: self.view.to_canvas(pos),
+            },
+            event => event,
+        }
+    }
+}
 
-in vec2 v_pos;
-in vec2 v_tex;
+/// Tracks the cursor to produce `InputEvent::Pick`s.
+#[derive(Copy, Clone, Debug, Default)]
+struct Picker {
+    /// Last cursor position, in canvas coordinates.
+    cursor: Option<Vec2<f32>>,
+}
 
-out vec4 f_col;
+impl Picker {
+    /// Observe an input event, returning a pick event to follow it if it's a left click on
+    /// the canvas.
+    fn input(&mut self, event: &InputEvent, target: &PaintTarget) -> Option<InputEvent> {
+        match *event {
+            InputEvent::MouseMove { pos } => {
+                self.cursor = Some(pos);
+                None
+            },
+            InputEvent::MousePress { button: MouseButton::Left, .. } => {
+                let pos = self.cursor?;
+                if pos.x < 0.0 || pos.y < 0.0 {
+                    return None;
+                }
+                let (x, y) = (pos.x as usize, pos.y as usize);
+                target.pixel(x, y).map(|rgba| InputEvent::Pick { x, y, rgba })
+            },
+            _ => None,
+        }
+    }
+}
 
-void main() {
-    // background
-    f_col = vec4(0.5);
+/// Tracks the cursor to produce `InputEvent::Hover`s.
+#[derive(Copy, Clone, Debug, Default)]
+struct Hoverer {
+    /// Canvas pixel under the cursor, if it's over the canvas.
+    cursor: Option<(usize, usize)>,
+    /// Object ID and pixel last reported, if any.
+    last: Option<(u32, usize, usize)>,
+}
 
-    // compute our canvas integer coordinates
-    uvec2 tex_xy = uvec2(v_tex * vec2(uvec2(x_size, y_size)));
-    int index = int(tex_xy.y * x_size + tex_xy.x);
+impl Hoverer {
+    /// Observe an input event, returning a hover event to follow it if it moves the cursor
+    /// onto a pixel with a different ID, or off the canvas.
+    fn input(&mut self, event: &InputEvent, target: &PaintTarget) -> Option<InputEvent> {
+        match *event {
+            InputEvent::MouseMove { pos } => {
+                self.cursor =
+                    if pos.x < 0.0 || pos.y < 0.0 { None }
+                    else { Some((pos.x as usize, pos.y as usize)) };
+                self.refresh(target)
+            },
+            _ => None,
+        }
+    }
 
-    // retrieve the painted pixel
-    uvec4 painted_256 = texelFetch(canvas_buf, index);
-    vec4 painted = vec4(painted_256) / 256.0;
+    /// Observe the cursor leaving the window, returning a hover event if it was over an
+    /// object.
+    fn leave(&mut self, target: &PaintTarget) -> Option<InputEvent> {
+        self.cursor = None;
+        self.refresh(target)
+    }
 
-    // mix it in, by its alpha
-    f_col = mix(f_col, painted, painted.a);
+    /// Check the ID under the cursor, returning a hover event if it's changed, such as
+    /// because the ID buffer was painted.
+    ///
+    /// Off the canvas, the ID is 0, reported at the last pixel the cursor was over.
+    fn refresh(&mut self, target: &PaintTarget) -> Option<InputEvent> {
+        let hit = self.cursor.and_then(|(x, y)| target.id(x, y).map(|id| (id, x, y)));
+        let (id, x, y) = match (hit, self.last) {
+            (Some(hit), _) => hit,
+            // off the canvas, which is only reported if it was on an object
+            (None, Some((_, x, y))) => (0, x, y),
+            (None, None) => return None,
+        };
+        if self.last.map(|(last, _, _)| last) == Some(id) {
+            return None;
+        }
+        self.last = Some((id, x, y));
+        Some(InputEvent::Hover { x, y, id })
+    }
 }
 
-        "###,
-        None,
-    ).expect("failed to create glsl program");
+/// Window title showing the object ID under the cursor.
+fn title_with_id(title: &str, id: u32) -> String {
+    if id == 0 { title.to_owned() }
+    else { format!("{} - id {}", title, id) }
+}
 
-    // buffer to store the pixels
-    // memory-mapped between CPU and GPU
-    let mut canvas_buf_tex: BufferTexture<[u8; 4]> = {
-        let num_zeroes: usize = x_size * y_size;
-        let mut zeroes: Vec<[u8; 4]> = Vec::with_capacity(num_zeroes);
-        for _ in 0..num_zeroes {
-            zeroes.push([0x00, 0x00, 0x00, 0x00]);
+/// Forward a hover event, if any, to the drawing thread, first showing its object ID in the
+/// window title with `set_title` if `hover_title` is enabled.
+fn send_hover(
+    draw: &DrawThread,
+    hover: Option<InputEvent>,
+    hover_title: bool,
+    title: &str,
+    set_title: impl FnOnce(&str),
+) {
+    if let Some(hover) = hover {
+        if let InputEvent::Hover { id, .. } = hover {
+            if hover_title {
+                set_title(&title_with_id(title, id));
+            }
         }
+        draw.send_input(hover);
+    }
+}
 
-        BufferTexture::dynamic(
-            &display,
-            &zeroes,
-            BufferTextureType::Unsigned,
-        ).expect("error creating buffer texture")
-    };
+/// Overlays drawn over the displayed canvas, but not screenshots or recordings.
+#[derive(Clone, Debug)]
+struct Overlays {
+    stats: bool,
+    histogram: bool,
+    /// Most recent histogram, if the histogram overlay is enabled and has been refreshed.
+    latest: Option<Histogram>,
+}
 
-    // window loop
-    let mut open = true;
-    while open {
-        // render
-        {
-            let uniforms = glium::uniform! {
-                x_size: x_size as i32,
-                y_size: y_size as i32,
-                canvas_buf: &canvas_buf_tex
-            };
-
-            let draw_params = DrawParameters::default();
-
-            let mut frame = display.draw();
-            frame.clear_color_and_depth(
-                (1.0, 1.0, 1.0, 0.0),
-                1.0,
-            );
-            frame.draw(
-                &vertex_buf,
-                &index_buf,
-                &program,
-                &uniforms,
-                &draw_params,
-            ).expect("draw call failed");
-            frame.finish()
-                .expect("failed to swap frame buffers");
-        }
-
-        // apply instructions from the paint queue
-        if !paint_queue_0.is_empty() {
-            let mut canvas_mmap = canvas_buf_tex.map_write();
-
-            while let Ok(Paint {
-                             x,
-                             y,
-                             r,
-                             g,
-                             b,
-                             a,
-                         }) = paint_queue_0.pop() {
-
-                let rgba = [r, g, b, a];
-                let i: usize = y * x_size + x;
-
-                canvas_mmap.set(i, rgba);
+impl Overlays {
+    fn new(stats: bool, histogram: bool) -> Self {
+        Overlays {
+            stats,
+            histogram,
+            latest: None,
+        }
+    }
 
-            }
+    /// Whether any overlay is enabled.
+    fn enabled(&self) -> bool {
+        self.stats || self.histogram
+    }
+
+    /// Recompute the histogram of the displayed pixels, if it's enabled.
+    fn refresh(&mut self, pixels: &[[u8; 4]]) {
+        if self.histogram {
+            self.latest = Some(Histogram::from_pixels(pixels));
         }
+    }
 
-        // poll
-        events_loop.poll_events(|event| {
-            match event {
-
-                Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
-                    // window "X'd out"
-                    open = false;
-                },
-
-                Event::DeviceEvent { event: DeviceEvent::Key(
-                    KeyboardInput {
-                        virtual_keycode: Some(VirtualKeyCode::W),
-                        modifiers: ModifiersState { logo: true, .. },
-                        ..
-                    }
-                ), .. } => {
-                    // cmd+w
-                    open = false;
-                },
-
-                Event::WindowEvent { event: WindowEvent::KeyboardInput {
-                    input: KeyboardInput {
-                        virtual_keycode: Some(VirtualKeyCode::W),
-                        modifiers: ModifiersState { logo: true, .. },
-                        ..
-                    },
-                    ..
-                }, .. } => {
-                    // cmd+w
-                    open = false;
-                }
+    /// Copy canvas pixels, with the enabled overlays drawn over them.
+    fn draw(
+        &self,
+        pixels: &[[u8; 4]],
+        x_size: usize,
+        y_size: usize,
+        stats: &Stats,
+    ) -> Vec<[u8; 4]> {
+        let mut pixels =
+            if self.stats { draw_overlay(pixels, x_size, y_size, stats) }
+            else { pixels.to_vec() };
+        if let Some(ref histogram) = self.latest {
+            draw_histogram(&mut pixels, x_size, y_size, histogram);
+        }
+        pixels
+    }
+}
 
-                Event::DeviceEvent { event: DeviceEvent::Key(
-                    KeyboardInput {
-                        virtual_keycode: Some(VirtualKeyCode::W),
-                        modifiers: ModifiersState { ctrl: true, .. },
-                        ..
-                    }
-                ), .. } => {
-                    // ctrl+w
-                    open = false;
-                },
-
-                Event::WindowEvent { event: WindowEvent::KeyboardInput {
-                    input: KeyboardInput {
-                        virtual_keycode: Some(VirtualKeyCode::W),
-                        modifiers: ModifiersState { ctrl: true, .. },
-                        ..
-                    },
-                    ..
-                }, .. } => {
-                    // ctrl+w
-                    open = false;
-                }
+/// Timestamped path for a new screenshot.
+fn screenshot_path(screenshot_dir: &Path) -> PathBuf {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|t| t.as_millis())
+        .unwrap_or(0);
+    screenshot_dir.join(format!("screenshot-{}.png", millis))
+}
 
-                _ => ()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        input::Modifiers,
+        paint::{Paint, PaintId, TargetOptions},
+    };
 
-            }
+    /// 4x4 canvas, with a red pixel in the top row, and an ID in the bottom row.
+    fn target() -> PaintTarget {
+        let mut target = PaintTarget::new(4, 4, TargetOptions {
+            double_buffered: false,
+            clear_each_frame: false,
+            linear: false,
+            tone_map: ToneMap::default(),
+            accumulate: false,
+            frame_blend: None,
+            quantize: None,
+            id_buffer: true,
         });
+        target.apply(PaintCmd::Paint(Paint { x: 1, y: 0, r: 0xFF, g: 0, b: 0, a: 0xFF }));
+        target.apply(PaintCmd::Id(PaintId { x: 2, y: 3, id: 7 }));
+        target
+    }
+
+    /// Mouse motion to a window position, mapped to the canvas as the backends do.
+    fn move_to(fit: Fit, navigator: &mut Navigator, x: f32, y: f32) -> InputEvent {
+        let input = fit.input_to_canvas(InputEvent::MouseMove { pos: Vec2::new(x, y) });
+        navigator.input(&input);
+        navigator.input_to_canvas(input)
+    }
+
+    fn press() -> InputEvent {
+        InputEvent::MousePress { button: MouseButton::Left, modifiers: Modifiers::default() }
     }
 
-    trace!("closing window");
-}
\ No newline at end of file
+    #[test]
+    fn picks_from_the_top_row() {
+        let target = target();
+        // canvas scaled by 10, letterboxed 20 from the top of the window
+        let fit = Fit::new(Vec2::new(4, 4), Vec2::new(40.0, 80.0), Scaling::Fit, 1.0);
+        let mut navigator = Navigator::default();
+        let mut picker = Picker::default();
+
+        picker.input(&move_to(fit, &mut navigator, 15.0, 25.0), &target);
+        assert_eq!(
+            picker.input(&press(), &target),
+            Some(InputEvent::Pick { x: 1, y: 0, rgba: Rgba::new(0xFF, 0, 0, 0xFF) }),
+        );
+
+        picker.input(&move_to(fit, &mut navigator, 15.0, 15.0), &target);
+        assert_eq!(picker.input(&press(), &target), None);
+    }
+
+    #[test]
+    fn hovers_the_pixel_under_the_cursor() {
+        let target = target();
+        let fit = Fit::new(Vec2::new(4, 4), Vec2::new(40.0, 40.0), Scaling::Fit, 1.0);
+        let mut navigator = Navigator::default();
+        let mut hoverer = Hoverer::default();
+
+        let input = move_to(fit, &mut navigator, 25.0, 35.0);
+        assert_eq!(hoverer.input(&input, &target), Some(InputEvent::Hover { x: 2, y: 3, id: 7 }));
+        let input = move_to(fit, &mut navigator, 25.0, 5.0);
+        assert_eq!(hoverer.input(&input, &target), Some(InputEvent::Hover { x: 2, y: 0, id: 0 }));
+        assert_eq!(hoverer.leave(&target), None);
+    }
+
+    #[test]
+    fn zooms_about_the_cursor() {
+        let mut navigator = Navigator::default();
+        let cursor = Vec2::new(30.0, 10.0);
+        navigator.input(&InputEvent::MouseMove { pos: cursor });
+        let before = navigator.view.to_canvas(cursor);
+        navigator.input(&InputEvent::Scroll { delta: Vec2::new(0.0, 3.0) });
+        assert!(navigator.view.zoom > 1.0);
+        assert!((navigator.view.to_canvas(cursor) - before).magnitude() < 1e-4);
+    }
+
+    #[test]
+    fn drags_the_canvas_with_the_cursor() {
+        let mut navigator = Navigator::default();
+        navigator.input(&InputEvent::MouseMove { pos: Vec2::new(10.0, 10.0) });
+        let grabbed = navigator.view.to_canvas(Vec2::new(10.0, 10.0));
+        navigator.input(&press());
+        navigator.input(&InputEvent::MouseMove { pos: Vec2::new(14.0, 17.0) });
+        assert_eq!(navigator.view.to_canvas(Vec2::new(14.0, 17.0)), grabbed);
+    }
+}
@@ -0,0 +1,877 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use vek::*;
+
+use crate::{
+    input::{InputEvent, Key, MouseButton, Modifiers, PIXELS_PER_LINE},
+    paint::{PaintTarget, TargetOptions, save_capture},
+    record::Recorder,
+    stats::StatsTracker,
+    color::to_f32,
+    texture::Filter,
+};
+
+use super::{
+    DrawContext,
+    CursorIcon,
+    Fit,
+    Navigator,
+    Picker,
+    Hoverer,
+    Overlays,
+    PostEffect,
+    Scaling,
+    WindowBuilder,
+    WindowControl,
+    WindowError,
+    spawn_draw_thread,
+    screenshot_path,
+    send_hover,
+};
+
+#[allow(unused_imports)]
+use glium::{
+    glutin,
+    glutin::dpi,
+    glutin::{
+        Event,
+        WindowEvent,
+        DeviceEvent,
+        KeyboardInput,
+        ElementState,
+        MouseScrollDelta,
+        VirtualKeyCode,
+        ModifiersState,
+    },
+    texture::{
+        UnsignedTexture2d,
+        buffer_texture::{
+            BufferTexture,
+            BufferTextureType,
+            CreationError as BufferTextureCreationError,
+        },
+    },
+    draw_parameters::DrawParameters,
+    Surface,
+    Display,
+    vertex::VertexBuffer,
+    program::{Program, ProgramCreationInput},
+    index::{self, IndexBuffer},
+    backend::Facade,
+};
+
+/// OS-specific (conditional compilation) window configuration.
+trait WindowBuilderOsSpecific: Sized {
+    fn os_specific_window_configure(self) -> Self;
+}
+
+#[cfg(target_os = "macos")]
+impl WindowBuilderOsSpecific for glutin::WindowBuilder {
+    fn os_specific_window_configure(self) -> Self {
+        use glium::backend::glutin::glutin::os::macos::WindowBuilderExt;
+
+        self
+            .with_movable_by_window_background(true)
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+impl WindowBuilderOsSpecific for glutin::WindowBuilder {
+    fn os_specific_window_configure(self) -> Self {
+        self
+    }
+}
+
+/// Our vertex type.
+#[derive(Copy, Clone)]
+#[repr(C)]
+struct Vertex { a_pos: [f32; 2] }
+
+glium::implement_vertex!(Vertex, a_pos);
+
+/// Simplified macro for creating our vertex array.
+macro_rules! vertex_arr {
+    [$( ($x:expr, $y:expr) ),*$(,)?] => {
+        [$( Vertex { a_pos: [$x as f32, $y as f32] }, )*]
+    }
+}
+
+
+/// Run the window loop.
+pub(super) fn run(
+    config: WindowBuilder,
+    draw_thread: impl FnOnce(DrawContext) + Send + 'static,
+) -> Result<(), WindowError> {
+    let WindowBuilder {
+        backend: _,
+        mut x_size,
+        mut y_size,
+        mut title,
+        vsync,
+        max_fps,
+        resizable,
+        fullscreen,
+        monitor,
+        scaling,
+        filter,
+        post_effects,
+        picking,
+        navigation,
+        decorations,
+        transparent,
+        background,
+        double_buffered,
+        clear_each_frame,
+        linear,
+        tone_map,
+        accumulate,
+        frame_blend,
+        quantize,
+        id_buffer,
+        hover_title,
+        paint_capacity,
+        stats_overlay,
+        histogram_overlay,
+        screenshot_dir,
+        record,
+    } = config;
+
+    // create context
+    let mut events_loop: glutin::EventsLoop = glutin::EventsLoop::new();
+    let display: Display = {
+        let wb = glutin::WindowBuilder::new()
+            .with_dimensions(dpi::LogicalSize::new(x_size as _, y_size as _))
+            .with_fullscreen(
+                if fullscreen { Some(select_monitor(&events_loop, monitor)) }
+                else { None }
+            )
+            .with_decorations(decorations)
+            .with_transparency(transparent)
+            .with_resizable(resizable)
+            .os_specific_window_configure()
+            .with_title(title.clone());
+        let cb = glutin::ContextBuilder::new()
+            .with_vsync(vsync);
+        Display::new(wb, cb, &events_loop)?
+    };
+
+    debug!("supported GLSL versions: {:?}", display.get_context().get_supported_glsl_version());
+
+    // geometry to cover entire screen
+    let vertex_buf: VertexBuffer<Vertex> = VertexBuffer::new(
+        &display,
+        &vertex_arr![
+            (0, 0),
+            (0, 1),
+            (1, 1),
+            (1, 0),
+        ],
+    )?;
+
+    let index_buf: IndexBuffer<u8> = IndexBuffer::new(
+        &display,
+        index::PrimitiveType::TriangleStrip,
+        &[1, 2, 0, 3],
+    )?;
+
+    // glsl program
+    let program: Program = Program::from_source(
+        &display,
+        r###"
+
+#version 410
+
+in vec2 a_pos;
+
+out vec2 v_pos;
+out vec2 v_tex;
+
+void main() {
+    v_pos = (a_pos - vec2(0.5)) * 2.0;
+    // canvas row 0 is at the top, like window coordinates
+    v_tex = vec2(a_pos.x, 1.0 - a_pos.y);
+    gl_Position = vec4(v_pos, 0.5, 1.0);
+}
+
+        "###,
+        &r###"
+
+#version 410
+
+uniform int x_size;
+uniform int y_size;
+uniform bool linear;
+uniform bool bilinear;
+uniform vec4 background;
+uniform vec2 view_offset;
+uniform float view_zoom;
+uniform usamplerBuffer canvas_buf;
+
+in vec2 v_pos;
+in vec2 v_tex;
+
+out vec4 f_col;
+
+// retrieve a painted pixel, clamping to the canvas edges
+vec4 fetch(ivec2 xy) {
+    xy = clamp(xy, ivec2(0), ivec2(x_size - 1, y_size - 1));
+    uvec4 painted_256 = texelFetch(canvas_buf, xy.y * x_size + xy.x);
+    return vec4(painted_256) / 256.0;
+}
+
+// compute the displayed color at texture coordinates
+vec4 present(vec2 uv) {
+    // apply the navigation view
+    uv = view_offset / vec2(x_size, y_size) + uv / view_zoom;
+
+    // background outside of the canvas
+    if (any(lessThan(uv, vec2(0.0))) || any(greaterThan(uv, vec2(1.0)))) {
+        return background;
+    }
+
+    // compute our canvas coordinates
+    vec2 pos = uv * vec2(x_size, y_size);
+
+    vec4 painted;
+    if (bilinear) {
+        // offset so that pixel centers are at integer coordinates
+        pos -= vec2(0.5);
+        ivec2 base = ivec2(floor(pos));
+        vec2 t = pos - floor(pos);
+        painted = mix(
+            mix(fetch(base), fetch(base + ivec2(1, 0)), t.x),
+            mix(fetch(base + ivec2(0, 1)), fetch(base + ivec2(1, 1)), t.x),
+            t.y
+        );
+    } else {
+        painted = fetch(ivec2(pos));
+    }
+
+    // gamma encode linear colors to sRGB
+    if (linear) {
+        vec3 lo = painted.rgb * 12.92;
+        vec3 hi = 1.055 * pow(painted.rgb, vec3(1.0 / 2.4)) - 0.055;
+        painted.rgb = mix(lo, hi, step(0.0031308, painted.rgb));
+    }
+
+    // mix it over the background, by its alpha
+    return mix(background, painted, painted.a);
+}
+
+void main() {
+    vec2 uv = v_tex;
+
+    // WARP
+
+    vec4 color = present(uv);
+
+    // POST_PROCESS
+
+    f_col = color;
+}
+
+        "###
+            .replace("// WARP", &warp_glsl(&post_effects))
+            .replace("// POST_PROCESS", &post_process_glsl(&post_effects)),
+        None,
+    )?;
+
+    let background = to_f32(background);
+
+    // buffer to store the pixels
+    let mut canvas_buf_tex = create_canvas(&display, x_size, y_size)?;
+
+    // CPU-side pixels, which are uploaded when changed
+    let mut target_options = TargetOptions {
+        double_buffered,
+        clear_each_frame,
+        linear,
+        tone_map,
+        accumulate,
+        frame_blend,
+        quantize,
+        id_buffer,
+    };
+    let mut target = PaintTarget::new(x_size, y_size, target_options.clone());
+
+    // encoder thread for recording
+    let mut recorder = record.map(Recorder::start);
+
+    // spawn the drawing code in its own thread
+    let draw = spawn_draw_thread(paint_capacity, draw_thread);
+    let mut stats = StatsTracker::new(draw.stats.clone());
+    let mut overlays = Overlays::new(stats_overlay, histogram_overlay);
+
+    // logical window size, for scaling the canvas to fit
+    let mut window_size = Vec2::new(x_size as f64, y_size as f64);
+    let mut fullscreen = fullscreen;
+
+    // cursor tracking for picking and navigation
+    let mut picker = Picker::default();
+    let mut hoverer = Hoverer::default();
+    let mut navigator = Navigator::default();
+
+    // shows the ID under the cursor in the title, if enabled
+    let set_title = |title: &str| display.gl_window().window().set_title(title);
+
+    // device events arrive regardless of focus, so only forward raw motion while focused
+    let mut focused = true;
+
+    // minimum time per frame, if limited
+    let frame_time = max_fps.map(|fps| Duration::from_secs_f64(1.0 / fps));
+
+    // window loop
+    let mut open = true;
+    while open {
+        let frame_start = Instant::now();
+
+        // render
+        {
+            let uniforms = glium::uniform! {
+                x_size: x_size as i32,
+                y_size: y_size as i32,
+                linear: linear,
+                bilinear: filter == Filter::Bilinear,
+                background: background.into_array(),
+                view_offset: navigator.view.offset.into_array(),
+                view_zoom: navigator.view.zoom,
+                canvas_buf: &canvas_buf_tex
+            };
+
+            let mut frame = display.draw();
+
+            // letterbox the canvas if it's scaled
+            let mut draw_params = DrawParameters::default();
+            if scaling != Scaling::Resize {
+                let (width, height) = frame.get_dimensions();
+                let canvas_size = Vec2::new(x_size, y_size);
+                let frame_size = Vec2::new(width as f64, height as f64);
+                let fit = Fit::new(canvas_size, frame_size, scaling, 1.0);
+                draw_params.viewport = Some(glium::Rect {
+                    left: fit.offset.x.round() as u32,
+                    bottom: fit.offset.y.round() as u32,
+                    width: (x_size as f64 * fit.scale).round() as u32,
+                    height: (y_size as f64 * fit.scale).round() as u32,
+                });
+            }
+
+            frame.clear_color_and_depth(
+                (background.r, background.g, background.b, background.a),
+                1.0,
+            );
+            // frame must be finished even if the draw call fails
+            let drawn = frame.draw(
+                &vertex_buf,
+                &index_buf,
+                &program,
+                &uniforms,
+                &draw_params,
+            );
+            frame.finish()?;
+            drawn?;
+            stats.frame();
+            draw.presented.signal();
+        }
+
+        // record the presented frame
+        if let Some(ref mut recorder) = recorder {
+            recorder.frame(|| target.to_image());
+        }
+
+        // apply instructions from the paint queue
+        stats.queue_depth(draw.paint_queue.len());
+        let mut paints = 0;
+        while let Ok(cmd) = draw.paint_queue.pop() {
+            target.apply(cmd);
+            paints += 1;
+        }
+        stats.paints(paints);
+        draw.drained.signal();
+
+        // the ID under the cursor may have been painted over, even if it didn't move
+        send_hover(&draw, hoverer.refresh(&target), hover_title, &title, &set_title);
+        let refreshed = stats.tick();
+        if refreshed {
+            overlays.refresh(target.displayed());
+        }
+
+        let dirty = target.take_dirty();
+        draw.answer_readbacks(&target);
+
+        // apply control messages from the drawing thread
+        for control in draw.take_controls() {
+            match control {
+                // the target is recreated on resize, so keep its options up to date too
+                WindowControl::SetToneMap(tone_map) => {
+                    target_options.tone_map = tone_map;
+                    target.set_tone_map(tone_map);
+                },
+                control => apply_control(display.gl_window().window(), &mut title, control),
+            }
+        }
+
+        // upload changed pixels, with the overlays if enabled
+        if overlays.enabled() {
+            if dirty.is_some() || refreshed {
+                let pixels = overlays.draw(target.displayed(), x_size, y_size, &stats.get());
+                canvas_buf_tex.write(&pixels);
+            }
+        } else if let Some(span) = dirty {
+            // only upload the span which changed
+            canvas_buf_tex.slice(span.clone())
+                .expect("dirty span out of canvas bounds")
+                .write(&target.displayed()[span]);
+        }
+
+        // poll
+        let mut resized = None;
+        let mut screenshot = false;
+        let mut toggle_fullscreen = false;
+        let hidpi = display.gl_window().window().get_hidpi_factor();
+        let fit = Fit::new(Vec2::new(x_size, y_size), window_size, scaling, hidpi);
+        events_loop.poll_events(|event| {
+            // forward input to the drawing thread
+            if let Event::WindowEvent { ref event, .. } = event {
+                if let Some(input) = convert_window_event(event) {
+                    let input =
+                        if scaling != Scaling::Resize { fit.input_to_canvas(input) }
+                        else { input };
+                    let navigated =
+                        if navigation { navigator.input(&input) }
+                        else { None };
+                    let input =
+                        if navigation { navigator.input_to_canvas(input) }
+                        else { input };
+                    let pick = picker.input(&input, &target);
+                    let hover = hoverer.input(&input, &target);
+                    draw.send_input(input);
+                    if let Some(pick) = pick.filter(|_| picking) {
+                        draw.send_input(pick);
+                    }
+                    send_hover(&draw, hover, hover_title, &title, &set_title);
+                    if let Some(view) = navigated {
+                        draw.send_input(InputEvent::Navigate { view });
+                    }
+                }
+                if let WindowEvent::CursorLeft { .. } = *event {
+                    send_hover(&draw, hoverer.leave(&target), hover_title, &title, &set_title);
+                }
+            }
+
+            match event {
+
+                Event::WindowEvent { event: WindowEvent::Resized(size), .. } => {
+                    resized = Some(size);
+                },
+
+                Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
+                    // window "X'd out"
+                    open = false;
+                },
+
+                Event::WindowEvent { event: WindowEvent::Focused(now_focused), .. } => {
+                    focused = now_focused;
+                },
+
+                Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta }, .. } if focused => {
+                    draw.send_input(InputEvent::MouseMotion {
+                        delta: Vec2::new(delta.0 as f32, delta.1 as f32),
+                    });
+                },
+
+                Event::DeviceEvent { event: DeviceEvent::Key(
+                    KeyboardInput {
+                        virtual_keycode: Some(VirtualKeyCode::W),
+                        modifiers: ModifiersState { logo: true, .. },
+                        ..
+                    }
+                ), .. } => {
+                    // cmd+w
+                    open = false;
+                },
+
+                Event::WindowEvent { event: WindowEvent::KeyboardInput {
+                    input: KeyboardInput {
+                        virtual_keycode: Some(VirtualKeyCode::W),
+                        modifiers: ModifiersState { logo: true, .. },
+                        ..
+                    },
+                    ..
+                }, .. } => {
+                    // cmd+w
+                    open = false;
+                }
+
+                Event::DeviceEvent { event: DeviceEvent::Key(
+                    KeyboardInput {
+                        virtual_keycode: Some(VirtualKeyCode::W),
+                        modifiers: ModifiersState { ctrl: true, .. },
+                        ..
+                    }
+                ), .. } => {
+                    // ctrl+w
+                    open = false;
+                },
+
+                Event::WindowEvent { event: WindowEvent::KeyboardInput {
+                    input: KeyboardInput {
+                        virtual_keycode: Some(VirtualKeyCode::W),
+                        modifiers: ModifiersState { ctrl: true, .. },
+                        ..
+                    },
+                    ..
+                }, .. } => {
+                    // ctrl+w
+                    open = false;
+                }
+
+                Event::WindowEvent { event: WindowEvent::KeyboardInput {
+                    input: KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::S),
+                        modifiers,
+                        ..
+                    },
+                    ..
+                }, .. } if modifiers.logo || modifiers.ctrl => {
+                    // cmd+s or ctrl+s
+                    screenshot = true;
+                }
+
+                #[cfg(feature = "clipboard")]
+                Event::WindowEvent { event: WindowEvent::KeyboardInput {
+                    input: KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::C),
+                        modifiers,
+                        ..
+                    },
+                    ..
+                }, .. } if modifiers.logo || modifiers.ctrl => {
+                    // cmd+c or ctrl+c
+                    crate::clipboard::copy_image(target.to_image());
+                }
+
+                Event::WindowEvent { event: WindowEvent::KeyboardInput {
+                    input: KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::F11),
+                        ..
+                    },
+                    ..
+                }, .. } => {
+                    // F11
+                    toggle_fullscreen = true;
+                }
+
+                Event::WindowEvent { event: WindowEvent::KeyboardInput {
+                    input: KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::F),
+                        modifiers,
+                        ..
+                    },
+                    ..
+                }, .. } if modifiers.logo && modifiers.ctrl => {
+                    // cmd+ctrl+f
+                    toggle_fullscreen = true;
+                }
+
+                _ => ()
+
+            }
+        });
+
+        // save a screenshot if requested
+        if screenshot {
+            let path = screenshot_path(&screenshot_dir);
+            save_capture(target.to_image(), path);
+        }
+
+        // enter or leave fullscreen if requested
+        if toggle_fullscreen {
+            fullscreen = !fullscreen;
+            trace!("setting fullscreen to {}", fullscreen);
+            let monitor =
+                if fullscreen { Some(select_monitor(&events_loop, monitor)) }
+                else { None };
+            display.gl_window().window().set_fullscreen(monitor);
+        }
+
+        if let Some(size) = resized {
+            window_size = Vec2::new(size.width, size.height);
+        }
+
+        // reallocate the canvas if the window was resized
+        // (ignoring zero sizes, such as from minimizing)
+        if let Some(size) = resized.filter(|_| scaling == Scaling::Resize) {
+            let new_x_size = size.width.round() as usize;
+            let new_y_size = size.height.round() as usize;
+
+            if (new_x_size, new_y_size) != (x_size, y_size)
+                && new_x_size > 0 && new_y_size > 0 {
+
+                trace!("resizing canvas to {}x{}", new_x_size, new_y_size);
+
+                x_size = new_x_size;
+                y_size = new_y_size;
+                canvas_buf_tex = create_canvas(&display, x_size, y_size)?;
+                target = PaintTarget::new(x_size, y_size, target_options.clone());
+
+                draw.send_input(InputEvent::Resize { x_size, y_size });
+            }
+        }
+
+        // sleep off the rest of the frame, if limited
+        if let Some(frame_time) = frame_time {
+            let elapsed = frame_start.elapsed();
+            if elapsed < frame_time {
+                thread::sleep(frame_time - elapsed);
+            }
+        }
+    }
+
+    trace!("closing window");
+    draw.join();
+    Ok(())
+}
+
+/// GLSL statements applying effects which distort `uv`, before the canvas is first
+/// sampled, so that every other effect samples the distorted image.
+fn warp_glsl(effects: &[PostEffect]) -> String {
+    let mut glsl = String::new();
+    for effect in effects {
+        if let PostEffect::Crt(curvature) = *effect {
+            glsl.push_str(&format!(
+                "{{\n\
+                vec2 d = uv - vec2(0.5);\n\
+                uv = vec2(0.5) + d * (1.0 + {:?} * dot(d, d));\n\
+                }}\n",
+                curvature,
+            ));
+        }
+    }
+    glsl
+}
+
+/// GLSL statements applying post-processing effects, in order, to `color`, which was
+/// presented at `uv`.
+fn post_process_glsl(effects: &[PostEffect]) -> String {
+    let mut glsl = String::new();
+    for effect in effects {
+        let block = match *effect {
+            PostEffect::Vignette(strength) => format!(
+                "vec2 d = uv - vec2(0.5);\n\
+                color.rgb *= clamp(1.0 - {:?} * 2.0 * dot(d, d), 0.0, 1.0);",
+                strength,
+            ),
+            PostEffect::Scanlines(strength) => format!(
+                "float row = fract(uv.y * float(y_size));\n\
+                color.rgb *= mix(1.0, sin(3.14159265 * row), {:?});",
+                strength,
+            ),
+            // applied by `warp_glsl`
+            PostEffect::Crt(_) => continue,
+            PostEffect::ChromaticAberration(offset) => format!(
+                "vec2 d = (uv - vec2(0.5)) * 2.0 * {:?} / vec2(x_size, y_size);\n\
+                color.r = present(uv + d).r;\n\
+                color.b = present(uv - d).b;",
+                offset,
+            ),
+            PostEffect::Glsl(ref source) => source.clone(),
+        };
+
+        // scope each effect's locals
+        glsl.push_str("{\n");
+        glsl.push_str(&block);
+        glsl.push_str("\n}\n");
+    }
+    glsl
+}
+
+/// Monitor at the given index, or the primary monitor.
+fn select_monitor(events_loop: &glutin::EventsLoop, monitor: Option<usize>) -> glutin::MonitorId {
+    monitor
+        .and_then(|i| events_loop.get_available_monitors().nth(i))
+        .unwrap_or_else(|| events_loop.get_primary_monitor())
+}
+
+/// Allocate a zeroed canvas buffer texture.
+fn create_canvas(
+    display: &Display,
+    x_size: usize,
+    y_size: usize,
+) -> Result<BufferTexture<[u8; 4]>, BufferTextureCreationError> {
+    let num_zeroes: usize = x_size * y_size;
+    let mut zeroes: Vec<[u8; 4]> = Vec::with_capacity(num_zeroes);
+    for _ in 0..num_zeroes {
+        zeroes.push([0x00, 0x00, 0x00, 0x00]);
+    }
+
+    BufferTexture::dynamic(
+        display,
+        &zeroes,
+        BufferTextureType::Unsigned,
+    )
+}
+
+/// Convert from a glutin window event, if it is an input event.
+fn convert_window_event(event: &WindowEvent) -> Option<InputEvent> {
+    match *event {
+        WindowEvent::KeyboardInput {
+            input: KeyboardInput {
+                state,
+                scancode,
+                virtual_keycode,
+                modifiers,
+            },
+            ..
+        } => {
+            let key = virtual_keycode.and_then(convert_key);
+            let modifiers = convert_modifiers(modifiers);
+            Some(match state {
+                ElementState::Pressed => InputEvent::KeyPress { key, scancode, modifiers },
+                ElementState::Released => InputEvent::KeyRelease { key, scancode, modifiers },
+            })
+        },
+
+        WindowEvent::CursorMoved { position, .. } => Some(InputEvent::MouseMove {
+            pos: Vec2::new(position.x as f32, position.y as f32),
+        }),
+
+        WindowEvent::MouseInput { state, button, modifiers, .. } => {
+            let button = convert_button(button);
+            let modifiers = convert_modifiers(modifiers);
+            Some(match state {
+                ElementState::Pressed => InputEvent::MousePress { button, modifiers },
+                ElementState::Released => InputEvent::MouseRelease { button, modifiers },
+            })
+        },
+
+        WindowEvent::MouseWheel { delta, .. } => Some(InputEvent::Scroll {
+            delta: match delta {
+                MouseScrollDelta::LineDelta(x, y) => Vec2::new(x, y),
+                MouseScrollDelta::PixelDelta(pos) => Vec2::new(
+                    pos.x as f32 / PIXELS_PER_LINE,
+                    pos.y as f32 / PIXELS_PER_LINE,
+                ),
+            },
+        }),
+
+        WindowEvent::DroppedFile(ref path) => Some(InputEvent::DroppedFile {
+            path: path.clone(),
+        }),
+
+        _ => None,
+    }
+}
+
+fn convert_modifiers(modifiers: ModifiersState) -> Modifiers {
+    Modifiers {
+        shift: modifiers.shift,
+        ctrl: modifiers.ctrl,
+        alt: modifiers.alt,
+        logo: modifiers.logo,
+    }
+}
+
+/// Apply a control message from the drawing thread to the window, keeping track of its
+/// title.
+fn apply_control(window: &glutin::Window, title: &mut String, control: WindowControl) {
+    match control {
+        WindowControl::SetTitle(new_title) => {
+            window.set_title(&new_title);
+            *title = new_title;
+        },
+        WindowControl::SetCursorIcon(icon) => window.set_cursor(convert_cursor_icon(icon)),
+        WindowControl::SetCursorVisible(visible) => window.hide_cursor(!visible),
+        WindowControl::SetCursorGrab(grab) => {
+            if let Err(e) = window.grab_cursor(grab) {
+                warn!("failed to set cursor grab: {}", e);
+            }
+        },
+        // applied to the paint target by the window loop
+        WindowControl::SetToneMap(_) => (),
+    }
+}
+
+fn convert_cursor_icon(icon: CursorIcon) -> glutin::MouseCursor {
+    match icon {
+        CursorIcon::Default => glutin::MouseCursor::Default,
+        CursorIcon::Crosshair => glutin::MouseCursor::Crosshair,
+        CursorIcon::Hand => glutin::MouseCursor::Hand,
+        CursorIcon::Text => glutin::MouseCursor::Text,
+        CursorIcon::Move => glutin::MouseCursor::Move,
+        CursorIcon::Wait => glutin::MouseCursor::Wait,
+        CursorIcon::Help => glutin::MouseCursor::Help,
+        CursorIcon::NotAllowed => glutin::MouseCursor::NotAllowed,
+    }
+}
+
+fn convert_button(button: glutin::MouseButton) -> MouseButton {
+    match button {
+        glutin::MouseButton::Left => MouseButton::Left,
+        glutin::MouseButton::Right => MouseButton::Right,
+        glutin::MouseButton::Middle => MouseButton::Middle,
+        glutin::MouseButton::Other(n) => MouseButton::Other(n as u16),
+    }
+}
+
+fn convert_key(key: VirtualKeyCode) -> Option<Key> {
+    use VirtualKeyCode as V;
+
+    Some(match key {
+        V::A => Key::A, V::B => Key::B, V::C => Key::C, V::D => Key::D,
+        V::E => Key::E, V::F => Key::F, V::G => Key::G, V::H => Key::H,
+        V::I => Key::I, V::J => Key::J, V::K => Key::K, V::L => Key::L,
+        V::M => Key::M, V::N => Key::N, V::O => Key::O, V::P => Key::P,
+        V::Q => Key::Q, V::R => Key::R, V::S => Key::S, V::T => Key::T,
+        V::U => Key::U, V::V => Key::V, V::W => Key::W, V::X => Key::X,
+        V::Y => Key::Y, V::Z => Key::Z,
+        V::Key0 => Key::Key0, V::Key1 => Key::Key1, V::Key2 => Key::Key2,
+        V::Key3 => Key::Key3, V::Key4 => Key::Key4, V::Key5 => Key::Key5,
+        V::Key6 => Key::Key6, V::Key7 => Key::Key7, V::Key8 => Key::Key8,
+        V::Key9 => Key::Key9,
+        V::F1 => Key::F1, V::F2 => Key::F2, V::F3 => Key::F3, V::F4 => Key::F4,
+        V::F5 => Key::F5, V::F6 => Key::F6, V::F7 => Key::F7, V::F8 => Key::F8,
+        V::F9 => Key::F9, V::F10 => Key::F10, V::F11 => Key::F11, V::F12 => Key::F12,
+        V::Escape => Key::Escape,
+        V::Space => Key::Space,
+        V::Return => Key::Enter,
+        V::Tab => Key::Tab,
+        V::Back => Key::Backspace,
+        V::Delete => Key::Delete,
+        V::Insert => Key::Insert,
+        V::Home => Key::Home,
+        V::End => Key::End,
+        V::PageUp => Key::PageUp,
+        V::PageDown => Key::PageDown,
+        V::Left => Key::Left,
+        V::Right => Key::Right,
+        V::Up => Key::Up,
+        V::Down => Key::Down,
+        V::LShift => Key::LShift,
+        V::RShift => Key::RShift,
+        V::LControl => Key::LControl,
+        V::RControl => Key::RControl,
+        V::LAlt => Key::LAlt,
+        V::RAlt => Key::RAlt,
+        V::LWin => Key::LLogo,
+        V::RWin => Key::RLogo,
+        V::Minus => Key::Minus,
+        V::Equals => Key::Equals,
+        V::Comma => Key::Comma,
+        V::Period => Key::Period,
+        V::Slash => Key::Slash,
+        V::Backslash => Key::Backslash,
+        V::Semicolon => Key::Semicolon,
+        V::Apostrophe => Key::Apostrophe,
+        V::LBracket => Key::LBracket,
+        V::RBracket => Key::RBracket,
+        V::Grave => Key::Grave,
+        _ => return None,
+    })
+}
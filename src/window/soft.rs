@@ -0,0 +1,672 @@
+use std::{
+    rc::Rc,
+    num::NonZeroU32,
+    time::{Duration, Instant},
+};
+
+use vek::*;
+
+use crate::{
+    input::{InputEvent, Key, MouseButton, Modifiers, View, PIXELS_PER_LINE},
+    color::srgb_encode_table,
+    paint::{PaintTarget, TargetOptions, save_capture},
+    record::Recorder,
+    stats::StatsTracker,
+    texture::Filter,
+};
+
+use super::{
+    DrawContext,
+    CursorIcon,
+    Fit,
+    Navigator,
+    Picker,
+    Hoverer,
+    Overlays,
+    Scaling,
+    WindowBuilder,
+    WindowControl,
+    WindowError,
+    spawn_draw_thread,
+    screenshot_path,
+    send_hover,
+};
+
+use winit::{
+    dpi::{LogicalSize, PhysicalSize},
+    event::{
+        Event,
+        WindowEvent,
+        DeviceEvent,
+        ElementState,
+        KeyEvent,
+        MouseScrollDelta,
+    },
+    event_loop::{EventLoop, EventLoopWindowTarget, ControlFlow},
+    keyboard::{KeyCode, PhysicalKey, ModifiersState},
+    monitor::MonitorHandle,
+    window::{Fullscreen, CursorGrabMode},
+};
+
+/// Time between polls of the paint queue, with vsync, if the monitor's refresh rate is
+/// unknown.
+///
+/// Softbuffer can't synchronize with the display, so vsync is approximated by pacing
+/// presentation to the refresh rate.
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Run the window loop.
+pub(super) fn run(
+    config: WindowBuilder,
+    draw_thread: impl FnOnce(DrawContext) + Send + 'static,
+) -> Result<(), WindowError> {
+    let WindowBuilder {
+        backend: _,
+        mut x_size,
+        mut y_size,
+        mut title,
+        vsync,
+        max_fps,
+        resizable,
+        fullscreen,
+        monitor,
+        scaling,
+        filter,
+        post_effects,
+        picking,
+        navigation,
+        decorations,
+        transparent,
+        background,
+        double_buffered,
+        clear_each_frame,
+        linear,
+        tone_map,
+        accumulate,
+        frame_blend,
+        quantize,
+        id_buffer,
+        hover_title,
+        paint_capacity,
+        stats_overlay,
+        histogram_overlay,
+        screenshot_dir,
+        record,
+    } = config;
+
+    if !post_effects.is_empty() {
+        warn!("post-processing effects are not supported by the soft backend, and are ignored");
+    }
+
+    // create window and surface
+    let event_loop = EventLoop::new()?;
+    let window = Rc::new(winit::window::WindowBuilder::new()
+        .with_inner_size(LogicalSize::new(x_size as f64, y_size as f64))
+        .with_decorations(decorations)
+        .with_transparent(transparent)
+        .with_resizable(resizable)
+        .with_fullscreen(
+            if fullscreen { Some(Fullscreen::Borderless(select_monitor(&event_loop, monitor))) }
+            else { None }
+        )
+        .with_title(title.clone())
+        .build(&event_loop)?);
+    let context = softbuffer::Context::new(window.clone())?;
+    let mut surface = softbuffer::Surface::new(&context, window.clone())?;
+
+    // CPU-side pixels, which are blitted to the surface when changed
+    let mut target_options = TargetOptions {
+        double_buffered,
+        clear_each_frame,
+        linear,
+        tone_map,
+        accumulate,
+        frame_blend,
+        quantize,
+        id_buffer,
+    };
+    let mut target = PaintTarget::new(x_size, y_size, target_options.clone());
+
+    // gamma encoding for linear colors
+    let encode = if linear { Some(srgb_encode_table()) } else { None };
+
+    // encoder thread for recording
+    let mut recorder = record.map(Recorder::start);
+    let mut next_record = Instant::now();
+
+    // spawn the drawing code in its own thread
+    let draw = spawn_draw_thread(paint_capacity, draw_thread);
+    let mut stats = StatsTracker::new(draw.stats.clone());
+    let mut overlays = Overlays::new(stats_overlay, histogram_overlay);
+
+    // cursor tracking for picking and navigation
+    let mut picker = Picker::default();
+    let mut hoverer = Hoverer::default();
+    let mut navigator = Navigator::default();
+
+    // shows the ID under the cursor in the title, if enabled
+    let set_title = |title: &str| window.set_title(title);
+
+    // time between frames, which is zero when uncapped
+    let frame_interval = match max_fps {
+        Some(fps) => Duration::from_secs_f64(1.0 / fps),
+        None if !vsync => Duration::from_secs(0),
+        None => window
+            .current_monitor()
+            .and_then(|monitor| monitor.refresh_rate_millihertz())
+            .filter(|&millihertz| millihertz > 0)
+            .map(|millihertz| Duration::from_secs_f64(1000.0 / millihertz as f64))
+            .unwrap_or(FRAME_INTERVAL),
+    };
+
+    // window loop
+    // (errors can't be returned from the event handler, so they're stashed here)
+    let mut failure: Option<WindowError> = None;
+    let mut modifiers = Modifiers::default();
+    event_loop.run(|event, elwt| {
+        match event {
+
+            Event::AboutToWait => {
+                // apply instructions from the paint queue
+                stats.queue_depth(draw.paint_queue.len());
+                let mut paints = 0;
+                while let Ok(cmd) = draw.paint_queue.pop() {
+                    target.apply(cmd);
+                    paints += 1;
+                }
+                stats.paints(paints);
+                draw.drained.signal();
+
+                // the ID under the cursor may have been painted over, even if it didn't move
+                send_hover(&draw, hoverer.refresh(&target), hover_title, &title, &set_title);
+                let refreshed = stats.tick();
+                if refreshed {
+                    overlays.refresh(target.displayed());
+                }
+
+                if target.take_dirty().is_some() || (overlays.enabled() && refreshed) {
+                    window.request_redraw();
+                }
+                draw.answer_readbacks(&target);
+
+                // record once per frame interval, like the gl backend, rather than only when
+                // redrawing, so unchanged frames are recorded too
+                if let Some(ref mut recorder) = recorder {
+                    let now = Instant::now();
+                    if now >= next_record {
+                        recorder.frame(|| target.to_image());
+                        next_record = now + frame_interval;
+                    }
+                }
+
+                // apply control messages from the drawing thread
+                for control in draw.take_controls() {
+                    match control {
+                        // the target is recreated on resize, so keep its options up to date too
+                        WindowControl::SetToneMap(tone_map) => {
+                            target_options.tone_map = tone_map;
+                            target.set_tone_map(tone_map);
+                        },
+                        control => apply_control(&window, &mut title, control),
+                    }
+                }
+
+                elwt.set_control_flow(
+                    if frame_interval > Duration::from_secs(0) {
+                        ControlFlow::WaitUntil(Instant::now() + frame_interval)
+                    } else {
+                        ControlFlow::Poll
+                    }
+                );
+            },
+
+            Event::WindowEvent { event, .. } => {
+                let scale = window.scale_factor();
+
+                // forward input to the drawing thread
+                if let Some(input) = convert_window_event(&event, modifiers, scale) {
+                    let input =
+                        if scaling != Scaling::Resize {
+                            let size = window.inner_size().to_logical::<f64>(scale);
+                            let window_size = Vec2::new(size.width, size.height);
+                            Fit::new(Vec2::new(x_size, y_size), window_size, scaling, scale)
+                                .input_to_canvas(input)
+                        } else {
+                            input
+                        };
+                    let navigated =
+                        if navigation { navigator.input(&input) }
+                        else { None };
+                    let input =
+                        if navigation { navigator.input_to_canvas(input) }
+                        else { input };
+                    let pick = picker.input(&input, &target);
+                    let hover = hoverer.input(&input, &target);
+                    draw.send_input(input);
+                    if let Some(pick) = pick.filter(|_| picking) {
+                        draw.send_input(pick);
+                    }
+                    send_hover(&draw, hover, hover_title, &title, &set_title);
+                    if let Some(view) = navigated {
+                        draw.send_input(InputEvent::Navigate { view });
+                        window.request_redraw();
+                    }
+                }
+                if let WindowEvent::CursorLeft { .. } = event {
+                    send_hover(&draw, hoverer.leave(&target), hover_title, &title, &set_title);
+                }
+
+                match event {
+
+                    WindowEvent::RedrawRequested => {
+                        // draw the overlays if enabled
+                        let overlaid;
+                        let pixels =
+                            if overlays.enabled() {
+                                overlaid =
+                                    overlays.draw(target.displayed(), x_size, y_size, &stats.get());
+                                &overlaid[..]
+                            } else {
+                                target.displayed()
+                            };
+
+                        let size = window.inner_size();
+                        let canvas_size = (x_size, y_size);
+                        let style = PresentStyle {
+                            scaling,
+                            view: navigator.view,
+                            filter,
+                            background,
+                            encode: encode.as_ref(),
+                        };
+                        let presented = present(&mut surface, size, pixels, canvas_size, &style);
+                        if let Err(e) = presented {
+                            failure = Some(e);
+                            elwt.exit();
+                            return;
+                        }
+                        stats.frame();
+                        draw.presented.signal();
+                    },
+
+                    WindowEvent::ModifiersChanged(new) => {
+                        modifiers = convert_modifiers(new.state());
+                    },
+
+                    WindowEvent::CloseRequested => {
+                        // window "X'd out"
+                        elwt.exit();
+                    },
+
+                    WindowEvent::KeyboardInput {
+                        event: KeyEvent {
+                            state: ElementState::Pressed,
+                            physical_key: PhysicalKey::Code(KeyCode::F11),
+                            ..
+                        },
+                        ..
+                    } => {
+                        // F11
+                        toggle_fullscreen(&window, elwt, monitor);
+                    },
+
+                    WindowEvent::KeyboardInput {
+                        event: KeyEvent {
+                            state: ElementState::Pressed,
+                            physical_key: PhysicalKey::Code(code),
+                            ..
+                        },
+                        ..
+                    } if modifiers.logo || modifiers.ctrl => match code {
+                        // cmd+ctrl+f
+                        KeyCode::KeyF if modifiers.logo && modifiers.ctrl => {
+                            toggle_fullscreen(&window, elwt, monitor);
+                        },
+                        // cmd+w or ctrl+w
+                        KeyCode::KeyW => elwt.exit(),
+                        // cmd+s or ctrl+s
+                        KeyCode::KeyS => {
+                            save_capture(target.to_image(), screenshot_path(&screenshot_dir));
+                        },
+                        // cmd+c or ctrl+c
+                        #[cfg(feature = "clipboard")]
+                        KeyCode::KeyC => crate::clipboard::copy_image(target.to_image()),
+                        _ => (),
+                    },
+
+                    WindowEvent::Resized(size) => {
+                        // reallocate the canvas
+                        // (ignoring zero sizes, such as from minimizing)
+                        let size = size.to_logical::<f64>(scale);
+                        let new_x_size = size.width.round() as usize;
+                        let new_y_size = size.height.round() as usize;
+
+                        if (new_x_size, new_y_size) != (x_size, y_size)
+                            && new_x_size > 0 && new_y_size > 0
+                            && scaling == Scaling::Resize {
+
+                            trace!("resizing canvas to {}x{}", new_x_size, new_y_size);
+
+                            x_size = new_x_size;
+                            y_size = new_y_size;
+                            target = PaintTarget::new(x_size, y_size, target_options.clone());
+
+                            draw.send_input(InputEvent::Resize { x_size, y_size });
+                        }
+                        window.request_redraw();
+                    },
+
+                    _ => (),
+
+                }
+            },
+
+            // winit only reports device events while the window is focused, by default
+            Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta }, .. } => {
+                draw.send_input(InputEvent::MouseMotion {
+                    delta: Vec2::new(delta.0 as f32, delta.1 as f32),
+                });
+            },
+
+            _ => (),
+
+        }
+    })?;
+
+    trace!("closing window");
+    draw.join();
+    match failure {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Monitor at the given index, or the primary monitor.
+fn select_monitor(
+    elwt: &EventLoopWindowTarget<()>,
+    monitor: Option<usize>,
+) -> Option<MonitorHandle> {
+    monitor
+        .and_then(|i| elwt.available_monitors().nth(i))
+        .or_else(|| elwt.primary_monitor())
+}
+
+/// Enter or leave borderless fullscreen.
+fn toggle_fullscreen(
+    window: &winit::window::Window,
+    elwt: &EventLoopWindowTarget<()>,
+    monitor: Option<usize>,
+) {
+    if window.fullscreen().is_some() {
+        trace!("leaving fullscreen");
+        window.set_fullscreen(None);
+    } else {
+        trace!("entering fullscreen");
+        window.set_fullscreen(Some(Fullscreen::Borderless(select_monitor(elwt, monitor))));
+    }
+}
+
+/// How `present` displays the canvas on the surface.
+struct PresentStyle<'a> {
+    /// Unless this is `Scaling::Resize`, the canvas is letterboxed to preserve its aspect
+    /// ratio, and otherwise it's stretched over the whole surface.
+    scaling: Scaling,
+    /// Navigation view, applied within the scaling.
+    view: View,
+    filter: Filter,
+    background: Rgba<u8>,
+    /// Gamma encoding table, if pixels are linear.
+    encode: Option<&'a [u8; 256]>,
+}
+
+/// Scale canvas pixels to the window surface and present them over the background color.
+fn present(
+    surface: &mut softbuffer::Surface<Rc<winit::window::Window>, Rc<winit::window::Window>>,
+    size: PhysicalSize<u32>,
+    pixels: &[[u8; 4]],
+    (x_size, y_size): (usize, usize),
+    style: &PresentStyle,
+) -> Result<(), WindowError> {
+    let PresentStyle { scaling, view, filter, background, encode } = *style;
+    let (width, height) = match (NonZeroU32::new(size.width), NonZeroU32::new(size.height)) {
+        (Some(width), Some(height)) => (width, height),
+        // minimized
+        _ => return Ok(()),
+    };
+    surface.resize(width, height)?;
+
+    let mut buffer = surface.buffer_mut()?;
+    let surf_x_size = width.get() as usize;
+    let surf_y_size = height.get() as usize;
+    let surf_size = Vec2::new(surf_x_size as f64, surf_y_size as f64);
+    let fit = Fit::new(Vec2::new(x_size, y_size), surf_size, scaling, 1.0);
+    let stretch = Vec2::new(x_size as f64, y_size as f64) / surf_size;
+    for surf_y in 0..surf_y_size {
+        for surf_x in 0..surf_x_size {
+            // canvas pixel under the surface pixel's center
+            let surf_pos = Vec2::new(surf_x as f64 + 0.5, surf_y as f64 + 0.5);
+            let pos =
+                if scaling != Scaling::Resize { fit.to_canvas(surf_pos) }
+                else { surf_pos * stretch };
+            let pos = view.to_canvas(pos.map(|n| n as f32)).map(|n| n as f64);
+            let in_canvas = pos.x >= 0.0 && pos.y >= 0.0
+                && (pos.x as usize) < x_size && (pos.y as usize) < y_size;
+            let [mut r, mut g, mut b, a] =
+                if !in_canvas { [0x00, 0x00, 0x00, 0x00] }
+                else if filter == Filter::Bilinear { bilinear(pixels, x_size, y_size, pos) }
+                else { pixels[pos.y as usize * x_size + pos.x as usize] };
+            if let Some(table) = encode {
+                r = table[r as usize];
+                g = table[g as usize];
+                b = table[b as usize];
+            }
+
+            // mix it over the background, by its alpha
+            let mix = |bg: u8, c: u8| (bg as u32 * (0xFF - a as u32) + c as u32 * a as u32) / 0xFF;
+            buffer[surf_y * surf_x_size + surf_x] =
+                (mix(background.r, r) << 16) | (mix(background.g, g) << 8) | mix(background.b, b);
+        }
+    }
+    buffer.present()?;
+    Ok(())
+}
+
+/// Bilinearly interpolate canvas pixels at a position, clamping to the canvas edges.
+fn bilinear(pixels: &[[u8; 4]], x_size: usize, y_size: usize, pos: Vec2<f64>) -> [u8; 4] {
+    // offset so that pixel centers are at integer coordinates
+    let pos = pos - 0.5;
+    let base = pos.map(|n| n.floor());
+    let t = (pos - base).map(|n| n as f32);
+
+    let fetch = |dx: i64, dy: i64| {
+        let x = (base.x as i64 + dx).max(0).min(x_size as i64 - 1) as usize;
+        let y = (base.y as i64 + dy).max(0).min(y_size as i64 - 1) as usize;
+        let [r, g, b, a] = pixels[y * x_size + x];
+        Rgba::new(r, g, b, a).map(|n| n as f32)
+    };
+    let c = Rgba::lerp(
+        Rgba::lerp(fetch(0, 0), fetch(1, 0), t.x),
+        Rgba::lerp(fetch(0, 1), fetch(1, 1), t.x),
+        t.y,
+    );
+    let c = c.map(|n| n.round() as u8);
+    [c.r, c.g, c.b, c.a]
+}
+
+/// Convert from a winit window event, if it is an input event.
+fn convert_window_event(
+    event: &WindowEvent,
+    modifiers: Modifiers,
+    scale: f64,
+) -> Option<InputEvent> {
+    match *event {
+        WindowEvent::KeyboardInput {
+            event: KeyEvent { state, physical_key, .. },
+            ..
+        } => {
+            let key = match physical_key {
+                PhysicalKey::Code(code) => convert_key(code),
+                PhysicalKey::Unidentified(_) => None,
+            };
+            // winit doesn't portably expose scancodes
+            let scancode = 0;
+            Some(match state {
+                ElementState::Pressed => InputEvent::KeyPress { key, scancode, modifiers },
+                ElementState::Released => InputEvent::KeyRelease { key, scancode, modifiers },
+            })
+        },
+
+        WindowEvent::CursorMoved { position, .. } => {
+            let position = position.to_logical::<f32>(scale);
+            Some(InputEvent::MouseMove {
+                pos: Vec2::new(position.x, position.y),
+            })
+        },
+
+        WindowEvent::MouseInput { state, button, .. } => {
+            let button = convert_button(button);
+            Some(match state {
+                ElementState::Pressed => InputEvent::MousePress { button, modifiers },
+                ElementState::Released => InputEvent::MouseRelease { button, modifiers },
+            })
+        },
+
+        WindowEvent::MouseWheel { delta, .. } => Some(InputEvent::Scroll {
+            delta: match delta {
+                MouseScrollDelta::LineDelta(x, y) => Vec2::new(x, y),
+                MouseScrollDelta::PixelDelta(pos) => {
+                    let pos = pos.to_logical::<f32>(scale);
+                    Vec2::new(pos.x / PIXELS_PER_LINE, pos.y / PIXELS_PER_LINE)
+                },
+            },
+        }),
+
+        WindowEvent::DroppedFile(ref path) => Some(InputEvent::DroppedFile {
+            path: path.clone(),
+        }),
+
+        _ => None,
+    }
+}
+
+fn convert_modifiers(state: ModifiersState) -> Modifiers {
+    Modifiers {
+        shift: state.shift_key(),
+        ctrl: state.control_key(),
+        alt: state.alt_key(),
+        logo: state.super_key(),
+    }
+}
+
+/// Apply a control message from the drawing thread to the window, keeping track of its
+/// title.
+fn apply_control(window: &winit::window::Window, title: &mut String, control: WindowControl) {
+    match control {
+        WindowControl::SetTitle(new_title) => {
+            window.set_title(&new_title);
+            *title = new_title;
+        },
+        WindowControl::SetCursorIcon(icon) => window.set_cursor_icon(convert_cursor_icon(icon)),
+        WindowControl::SetCursorVisible(visible) => window.set_cursor_visible(visible),
+        WindowControl::SetCursorGrab(grab) => {
+            // not every platform supports both grab modes
+            let grabbed =
+                if grab {
+                    window.set_cursor_grab(CursorGrabMode::Confined)
+                        .or_else(|_| window.set_cursor_grab(CursorGrabMode::Locked))
+                } else {
+                    window.set_cursor_grab(CursorGrabMode::None)
+                };
+            if let Err(e) = grabbed {
+                warn!("failed to set cursor grab: {}", e);
+            }
+        },
+        // applied to the paint target by the window loop
+        WindowControl::SetToneMap(_) => (),
+    }
+}
+
+fn convert_cursor_icon(icon: CursorIcon) -> winit::window::CursorIcon {
+    use winit::window::CursorIcon as C;
+
+    match icon {
+        CursorIcon::Default => C::Default,
+        CursorIcon::Crosshair => C::Crosshair,
+        CursorIcon::Hand => C::Pointer,
+        CursorIcon::Text => C::Text,
+        CursorIcon::Move => C::Move,
+        CursorIcon::Wait => C::Wait,
+        CursorIcon::Help => C::Help,
+        CursorIcon::NotAllowed => C::NotAllowed,
+    }
+}
+
+fn convert_button(button: winit::event::MouseButton) -> MouseButton {
+    use winit::event::MouseButton as B;
+
+    match button {
+        B::Left => MouseButton::Left,
+        B::Right => MouseButton::Right,
+        B::Middle => MouseButton::Middle,
+        // X11 numbering for the side buttons
+        B::Back => MouseButton::Other(8),
+        B::Forward => MouseButton::Other(9),
+        B::Other(n) => MouseButton::Other(n),
+    }
+}
+
+fn convert_key(code: KeyCode) -> Option<Key> {
+    use KeyCode as C;
+
+    Some(match code {
+        C::KeyA => Key::A, C::KeyB => Key::B, C::KeyC => Key::C, C::KeyD => Key::D,
+        C::KeyE => Key::E, C::KeyF => Key::F, C::KeyG => Key::G, C::KeyH => Key::H,
+        C::KeyI => Key::I, C::KeyJ => Key::J, C::KeyK => Key::K, C::KeyL => Key::L,
+        C::KeyM => Key::M, C::KeyN => Key::N, C::KeyO => Key::O, C::KeyP => Key::P,
+        C::KeyQ => Key::Q, C::KeyR => Key::R, C::KeyS => Key::S, C::KeyT => Key::T,
+        C::KeyU => Key::U, C::KeyV => Key::V, C::KeyW => Key::W, C::KeyX => Key::X,
+        C::KeyY => Key::Y, C::KeyZ => Key::Z,
+        C::Digit0 => Key::Key0, C::Digit1 => Key::Key1, C::Digit2 => Key::Key2,
+        C::Digit3 => Key::Key3, C::Digit4 => Key::Key4, C::Digit5 => Key::Key5,
+        C::Digit6 => Key::Key6, C::Digit7 => Key::Key7, C::Digit8 => Key::Key8,
+        C::Digit9 => Key::Key9,
+        C::F1 => Key::F1, C::F2 => Key::F2, C::F3 => Key::F3, C::F4 => Key::F4,
+        C::F5 => Key::F5, C::F6 => Key::F6, C::F7 => Key::F7, C::F8 => Key::F8,
+        C::F9 => Key::F9, C::F10 => Key::F10, C::F11 => Key::F11, C::F12 => Key::F12,
+        C::Escape => Key::Escape,
+        C::Space => Key::Space,
+        C::Enter => Key::Enter,
+        C::Tab => Key::Tab,
+        C::Backspace => Key::Backspace,
+        C::Delete => Key::Delete,
+        C::Insert => Key::Insert,
+        C::Home => Key::Home,
+        C::End => Key::End,
+        C::PageUp => Key::PageUp,
+        C::PageDown => Key::PageDown,
+        C::ArrowLeft => Key::Left,
+        C::ArrowRight => Key::Right,
+        C::ArrowUp => Key::Up,
+        C::ArrowDown => Key::Down,
+        C::ShiftLeft => Key::LShift,
+        C::ShiftRight => Key::RShift,
+        C::ControlLeft => Key::LControl,
+        C::ControlRight => Key::RControl,
+        C::AltLeft => Key::LAlt,
+        C::AltRight => Key::RAlt,
+        C::SuperLeft => Key::LLogo,
+        C::SuperRight => Key::RLogo,
+        C::Minus => Key::Minus,
+        C::Equal => Key::Equals,
+        C::Comma => Key::Comma,
+        C::Period => Key::Period,
+        C::Slash => Key::Slash,
+        C::Backslash => Key::Backslash,
+        C::Semicolon => Key::Semicolon,
+        C::Quote => Key::Apostrophe,
+        C::BracketLeft => Key::LBracket,
+        C::BracketRight => Key::RBracket,
+        C::Backquote => Key::Grave,
+        _ => return None,
+    })
+}